@@ -0,0 +1,154 @@
+use std::io::Write;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Chunking, OpenMode, OpenOptions, RepoConfig};
+use acid_store::store::MemoryConfig;
+use once_cell::sync::Lazy;
+
+/// The criterion sample size to use.
+const SAMPLE_SIZE: usize = 50;
+
+/// The criterion measurement time.
+const MEASUREMENT_TIME: Duration = Duration::from_secs(30);
+
+/// The number of random edits to make to the data when measuring deduplication.
+const EDIT_COUNT: usize = 16;
+
+/// The size of the data to chunk.
+static OBJECT_SIZE: Lazy<u64> = Lazy::new(|| bytesize::mib(16u64));
+
+/// The chunking methods to compare and their descriptions.
+static CHUNKING_METHODS: Lazy<Vec<(Chunking, &'static str)>> = Lazy::new(|| {
+    vec![
+        (Chunking::FIXED, "Chunking::Fixed"),
+        (Chunking::ZPAQ, "Chunking::Zpaq"),
+        (Chunking::FASTCDC, "Chunking::FastCdc"),
+    ]
+});
+
+/// Return a buffer containing `size` random bytes for testing purposes.
+fn random_bytes(size: usize) -> Vec<u8> {
+    let mut rng = SmallRng::from_entropy();
+    let mut buffer = vec![0u8; size];
+    rng.fill_bytes(&mut buffer);
+    buffer
+}
+
+/// Return a copy of `data` with small random insertions and deletions.
+fn edit_bytes(data: &[u8]) -> Vec<u8> {
+    let mut rng = SmallRng::from_entropy();
+    let mut edited = data.to_vec();
+    for _ in 0..EDIT_COUNT {
+        let offset = rng.gen_range(0..edited.len() - 64);
+        let length = rng.gen_range(1..64);
+        if rng.gen_bool(0.5) {
+            edited.splice(offset..offset, random_bytes(length));
+        } else {
+            edited.drain(offset..offset + length);
+        }
+    }
+    edited
+}
+
+fn open_repo(chunking: &Chunking) -> acid_store::Result<KeyRepo<String>> {
+    let mut config = RepoConfig::default();
+    config.chunking = chunking.clone();
+
+    OpenOptions::new()
+        .config(config)
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())
+}
+
+/// Write `data` to a new object with the given `key` in `repo`.
+fn write_object(repo: &mut KeyRepo<String>, key: &str, data: &[u8]) {
+    let mut object = repo.insert(String::from(key));
+    object.write_all(data).unwrap();
+    object.commit().unwrap();
+}
+
+pub fn chunk_data(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("Chunk data");
+
+    group.throughput(Throughput::Bytes(*OBJECT_SIZE));
+    group.sample_size(SAMPLE_SIZE);
+    group.measurement_time(MEASUREMENT_TIME);
+
+    for (chunking, description) in &*CHUNKING_METHODS {
+        group.bench_with_input(
+            format!(
+                "{}, {}",
+                bytesize::to_string(*OBJECT_SIZE, true),
+                description
+            ),
+            chunking,
+            |bencher, chunking| {
+                bencher.iter_batched(
+                    || {
+                        (
+                            open_repo(chunking).unwrap(),
+                            random_bytes(*OBJECT_SIZE as usize),
+                        )
+                    },
+                    |(mut repo, data)| write_object(&mut repo, "test", &data),
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+}
+
+pub fn deduplicate_edited_data(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("Deduplicate edited data");
+
+    group.throughput(Throughput::Bytes(*OBJECT_SIZE));
+    group.sample_size(SAMPLE_SIZE);
+    group.measurement_time(MEASUREMENT_TIME);
+
+    for (chunking, description) in &*CHUNKING_METHODS {
+        // Criterion can only measure time, so report the deduplication ratio separately.
+        let mut repo = open_repo(chunking).unwrap();
+        let original = random_bytes(*OBJECT_SIZE as usize);
+        let edited = edit_bytes(&original);
+        write_object(&mut repo, "original", &original);
+        write_object(&mut repo, "edited", &edited);
+        let stats = repo.stats();
+        println!(
+            "{}: deduplication ratio of {:.3} with {} edits",
+            description,
+            stats.apparent_size() as f64 / stats.repo_size() as f64,
+            EDIT_COUNT
+        );
+
+        group.bench_with_input(
+            format!(
+                "{}, {} edits, {}",
+                bytesize::to_string(*OBJECT_SIZE, true),
+                EDIT_COUNT,
+                description
+            ),
+            chunking,
+            |bencher, chunking| {
+                bencher.iter_batched(
+                    || {
+                        let mut repo = open_repo(chunking).unwrap();
+                        let original = random_bytes(*OBJECT_SIZE as usize);
+                        let edited = edit_bytes(&original);
+                        write_object(&mut repo, "original", &original);
+                        (repo, edited)
+                    },
+                    |(mut repo, edited)| write_object(&mut repo, "edited", &edited),
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+}
+
+criterion_group!(chunking, chunk_data, deduplicate_edited_data);
+criterion_main!(chunking);
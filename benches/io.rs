@@ -21,6 +21,9 @@ const SAMPLE_SIZE: usize = 100;
 /// The criterion measurement time.
 const MEASUREMENT_TIME: Duration = Duration::from_secs(30);
 
+/// The number of concurrent writes to use when benchmarking concurrent writes.
+const WRITE_CONCURRENCY: usize = 4;
+
 /// The size of the data to read and write to objects.
 static OBJECT_SIZE: Lazy<u64> = Lazy::new(|| bytesize::mib(1u64));
 
@@ -32,10 +35,13 @@ fn random_bytes(size: usize) -> Vec<u8> {
     buffer
 }
 
-fn open_repo(config: &RepoConfig) -> acid_store::Result<KeyRepo<String>> {
+fn open_repo(config: &RepoConfig, write_concurrency: usize) -> acid_store::Result<KeyRepo<String>> {
     let mut options = OpenOptions::new();
 
-    options.config(config.clone()).mode(OpenMode::CreateNew);
+    options
+        .config(config.clone())
+        .mode(OpenMode::CreateNew)
+        .write_concurrency(write_concurrency);
 
     if config.encryption != Encryption::None {
         options.password(b"Password");
@@ -120,7 +126,46 @@ pub fn write_object(criterion: &mut Criterion) {
             |bencher, config| {
                 bencher.iter_batched(
                     || {
-                        let mut repo = open_repo(config).unwrap();
+                        let mut repo = open_repo(config, 1).unwrap();
+                        repo.insert(String::from(TEST_KEY));
+                        (repo, random_bytes(*OBJECT_SIZE as usize))
+                    },
+                    |(repo, data)| {
+                        let mut object = repo.object(TEST_KEY).unwrap();
+                        object.write_all(data.as_slice()).unwrap();
+                        object.commit().unwrap();
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+}
+
+pub fn write_object_concurrently(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("Write to an object concurrently");
+
+    group.throughput(Throughput::Bytes(*OBJECT_SIZE));
+    group.sample_size(SAMPLE_SIZE);
+    group.measurement_time(MEASUREMENT_TIME);
+
+    for TestSpec {
+        config,
+        description,
+    } in &*TEST_SPECS
+    {
+        group.bench_with_input(
+            format!(
+                "{}, {} writes, {}",
+                bytesize::to_string(*OBJECT_SIZE, true),
+                WRITE_CONCURRENCY,
+                description
+            ),
+            &config,
+            |bencher, config| {
+                bencher.iter_batched(
+                    || {
+                        let mut repo = open_repo(config, WRITE_CONCURRENCY).unwrap();
                         repo.insert(String::from(TEST_KEY));
                         (repo, random_bytes(*OBJECT_SIZE as usize))
                     },
@@ -159,7 +204,7 @@ pub fn read_object(criterion: &mut Criterion) {
                 bencher.iter_batched(
                     || {
                         // Write data to the object.
-                        let mut repo = open_repo(config).unwrap();
+                        let mut repo = open_repo(config, 1).unwrap();
                         let mut object = repo.insert(String::from(TEST_KEY));
                         let data = random_bytes(*OBJECT_SIZE as usize);
                         object.write_all(data.as_slice()).unwrap();
@@ -181,5 +226,10 @@ pub fn read_object(criterion: &mut Criterion) {
     }
 }
 
-criterion_group!(throughput, read_object, write_object);
+criterion_group!(
+    throughput,
+    read_object,
+    write_object,
+    write_object_concurrently
+);
 criterion_main!(throughput);
@@ -43,6 +43,10 @@ pub enum Error {
     #[error("This repository is an unsupported format.")]
     UnsupportedRepo,
 
+    /// The repository configuration is invalid.
+    #[error("The repository configuration is invalid.")]
+    InvalidConfig,
+
     /// The given savepoint is invalid.
     #[error("The given savepoint is invalid.")]
     InvalidSavepoint,
@@ -55,6 +59,14 @@ pub enum Error {
     #[error("A transaction is currently in progress for this object.")]
     TransactionInProgress,
 
+    /// This repository is read-only.
+    #[error("This repository is read-only.")]
+    ReadOnly,
+
+    /// Changes conflict with changes which were committed concurrently by another client.
+    #[error("Changes conflict with changes which were committed concurrently by another client.")]
+    Conflict,
+
     /// This file type is not supported.
     #[error("This file type is not supported.")]
     FileType,
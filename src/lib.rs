@@ -20,13 +20,26 @@
 //!
 //! - [`DirectoryStore`] stores data in a directory in the local file system.
 //! - [`SqliteStore`] stores data in a SQLite database.
+//! - [`RedbStore`] stores data in a single file using an embedded key-value database.
 //! - [`RedisStore`] stores data on a Redis server.
+//! - [`PostgresStore`] stores data in a PostgreSQL database.
 //! - [`S3Store`] stores data in an Amazon S3 bucket.
 //! - [`SftpStore`] stores data on an SFTP server.
+//! - [`WebDavStore`] stores data on a WebDAV server.
 //! - [`RcloneStore`] stores data in a varity of cloud storage backends using
 //! [rclone].
 //! - [`MemoryStore`] stores data in memory.
 //!
+//! The following data stores wrap another data store to add functionality to it.
+//!
+//! - [`CachingStore`] caches data from a slow data store in the local file system.
+//! - [`RetryStore`] retries operations which fail with transient errors.
+//! - [`RateLimitStore`] limits the bandwidth and request rate of a data store.
+//! - [`InstrumentedStore`] collects metrics about the operations of a data store.
+//! - [`FaultStore`] injects faults into the operations of a data store for testing.
+//! - [`MirrorStore`] mirrors data across several data stores.
+//! - [`ShardedStore`] splits data between several data stores.
+//!
 //! # Examples
 //!
 //! ```
@@ -83,9 +96,12 @@
 //! ---               | ---
 //! `store-directory` | Store data in a directory in the local file system
 //! `store-sqlite`    | Store data in a SQLite database
+//! `store-redb`      | Store data in a [redb] database
 //! `store-redis`     | Store data on a Redis server
+//! `store-postgres`  | Store data in a PostgreSQL database
 //! `store-s3`        | Store data in an Amazon S3 bucket
 //! `store-sftp`      | Store data on an SFTP server
+//! `store-webdav`    | Store data on a WebDAV server
 //! `store-rclone`    | Store data in cloud storage via [rclone]
 //!
 //! These features enable additional functionality.
@@ -94,8 +110,11 @@
 //! ---               | ---
 //! `encryption`      | Encrypt repositories
 //! `compression`     | Compress repositories
+//! `compression-zstd` | Compress repositories with Zstandard
 //! `file-metadata`   | Store file metadata and special file types in [`FileRepo`]
 //! `fuse-mount`      | Mount a [`FileRepo`] as a FUSE file system
+//! `async`           | Use [`AsyncDataStore`] and [`AsyncObject`] with Tokio
+//! `tracing`         | Emit [tracing] spans for repository and data store operations
 //!
 //! These features have native dependencies. This table shows their package names on Ubuntu.
 //!
@@ -105,6 +124,8 @@
 //! `fuse-mount`    | `libfuse3-dev`, `pkg-config` | `fuse3`
 //!
 //! [rclone]: https://rclone.org/
+//! [redb]: https://docs.rs/redb
+//! [tracing]: https://docs.rs/tracing
 //!
 //! [`KeyRepo`]: crate::repo::key
 //! [`FileRepo`]: crate::repo::file
@@ -114,11 +135,23 @@
 //! [`DataStore`]: crate::store::DataStore
 //! [`DirectoryStore`]: crate::store::DirectoryStore
 //! [`SqliteStore`]: crate::store::SqliteStore
+//! [`RedbStore`]: crate::store::RedbStore
 //! [`RedisStore`]: crate::store::RedisStore
+//! [`PostgresStore`]: crate::store::PostgresStore
 //! [`S3Store`]: crate::store::S3Store
 //! [`SftpStore`]: crate::store::SftpStore
+//! [`WebDavStore`]: crate::store::WebDavStore
 //! [`RcloneStore`]: crate::store::RcloneStore
 //! [`MemoryStore`]: crate::store::MemoryStore
+//! [`CachingStore`]: crate::store::CachingStore
+//! [`RetryStore`]: crate::store::RetryStore
+//! [`RateLimitStore`]: crate::store::RateLimitStore
+//! [`InstrumentedStore`]: crate::store::InstrumentedStore
+//! [`FaultStore`]: crate::store::FaultStore
+//! [`MirrorStore`]: crate::store::MirrorStore
+//! [`ShardedStore`]: crate::store::ShardedStore
+//! [`AsyncDataStore`]: crate::store::AsyncDataStore
+//! [`AsyncObject`]: crate::repo::AsyncObject
 
 #![forbid(unsafe_code)]
 
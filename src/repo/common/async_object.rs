@@ -0,0 +1,306 @@
+#![cfg(feature = "async")]
+
+use std::cmp::min;
+use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::panic::resume_unwind;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+use tokio::task::{spawn_blocking, JoinHandle};
+
+use super::object::Object;
+
+/// The maximum number of bytes to read or write in a single blocking operation.
+const MAX_BUFFER_SIZE: usize = 2 * 1024 * 1024;
+
+/// The result of a blocking operation on an object.
+#[derive(Debug)]
+enum Operation {
+    Read(io::Result<usize>),
+    Write(io::Result<usize>),
+    Seek(io::Result<u64>),
+    Rewind(io::Result<u64>),
+    Flush(io::Result<()>),
+    Commit(crate::Result<()>),
+}
+
+/// The state of an `AsyncObject`.
+#[derive(Debug)]
+enum State {
+    /// No operation is in progress.
+    ///
+    /// This contains the object and a buffer to reuse between operations. The object is `None` if
+    /// it was lost because a blocking operation failed to complete.
+    Idle(Option<Box<Object>>, Vec<u8>),
+
+    /// A blocking operation is in progress.
+    Busy(JoinHandle<(Operation, Box<Object>, Vec<u8>)>),
+}
+
+/// An [`Object`] which implements `AsyncRead`, `AsyncWrite`, and `AsyncSeek`.
+///
+/// This wraps an [`Object`] and runs each blocking operation on Tokio's blocking thread pool using
+/// [`tokio::task::spawn_blocking`]. Its methods must be called from within a Tokio runtime. Only
+/// one operation can be in progress at a time.
+///
+/// Like [`Object`], writing to an `AsyncObject` begins a transaction, and changes are not persisted
+/// until [`commit`] is called.
+///
+/// The methods of `AsyncRead`, `AsyncWrite`, and `AsyncSeek` return `io::Result`, but the returned
+/// `io::Error` can be converted `Into` a [`crate::Error`] to be consistent with the rest of the
+/// library.
+///
+/// [`Object`]: crate::repo::Object
+/// [`commit`]: crate::repo::AsyncObject::commit
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub struct AsyncObject {
+    state: State,
+
+    /// Whether a seek has been started with `start_seek` but not yet completed.
+    seeking: bool,
+}
+
+impl From<Object> for AsyncObject {
+    fn from(object: Object) -> Self {
+        Self {
+            state: State::Idle(Some(Box::new(object)), Vec::new()),
+            seeking: false,
+        }
+    }
+}
+
+impl AsyncObject {
+    /// Wrap the given `object`.
+    pub fn new(object: Object) -> Self {
+        Self::from(object)
+    }
+
+    /// Commit changes to this object to the repository.
+    ///
+    /// See [`Object::commit`] for details.
+    ///
+    /// # Errors
+    /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`Object::commit`]: crate::repo::Object::commit
+    pub async fn commit(&mut self) -> crate::Result<()> {
+        self.wait_idle().await?;
+        self.start(|object| Operation::Commit(object.commit()))?;
+        match self.wait_idle().await? {
+            Some(Operation::Commit(result)) => result,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Wait for any operation in progress to complete and return the wrapped object.
+    ///
+    /// # Errors
+    /// - `Error::Io`: A previous operation failed to complete.
+    pub async fn into_inner(mut self) -> crate::Result<Object> {
+        self.wait_idle().await?;
+        match self.state {
+            State::Idle(object, _) => object
+                .map(|object| *object)
+                .ok_or_else(|| lost_object().into()),
+            State::Busy(_) => unreachable!(),
+        }
+    }
+
+    /// Wait for any operation in progress to complete, returning its result.
+    async fn wait_idle(&mut self) -> io::Result<Option<Operation>> {
+        WaitIdle(self).await
+    }
+
+    /// Start a blocking operation on the object.
+    ///
+    /// This must only be called when no operation is in progress.
+    fn start(
+        &mut self,
+        operation: impl FnOnce(&mut Object) -> Operation + Send + 'static,
+    ) -> io::Result<()> {
+        self.start_with_buffer(|object, _| operation(object))
+    }
+
+    /// Start a blocking operation on the object which uses the buffer.
+    ///
+    /// This must only be called when no operation is in progress.
+    fn start_with_buffer(
+        &mut self,
+        operation: impl FnOnce(&mut Object, &mut Vec<u8>) -> Operation + Send + 'static,
+    ) -> io::Result<()> {
+        let (object, buffer) = match &mut self.state {
+            State::Idle(object, buffer) => (object.take(), mem::take(buffer)),
+            State::Busy(_) => panic!("An operation is already in progress."),
+        };
+        let mut object = object.ok_or_else(lost_object)?;
+        let mut buffer = buffer;
+        self.seeking = false;
+        self.state = State::Busy(spawn_blocking(move || {
+            let result = operation(&mut object, &mut buffer);
+            (result, object, buffer)
+        }));
+        Ok(())
+    }
+
+    /// Poll for the completion of the operation in progress, returning its result.
+    ///
+    /// This returns `None` if there is no operation in progress.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<Operation>>> {
+        let handle = match &mut self.state {
+            State::Idle(..) => return Poll::Ready(Ok(None)),
+            State::Busy(handle) => handle,
+        };
+        match Pin::new(handle).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok((operation, object, buffer))) => {
+                self.state = State::Idle(Some(object), buffer);
+                match operation {
+                    // Nothing waits for the result of a rewind, so if it failed, the error is
+                    // returned by whichever operation polls for it next.
+                    Operation::Rewind(Err(error)) => Poll::Ready(Err(error)),
+                    Operation::Rewind(Ok(_)) => Poll::Ready(Ok(None)),
+                    operation => Poll::Ready(Ok(Some(operation))),
+                }
+            }
+            Poll::Ready(Err(error)) => {
+                self.state = State::Idle(None, Vec::new());
+                if error.is_panic() {
+                    resume_unwind(error.into_panic());
+                }
+                Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, error)))
+            }
+        }
+    }
+}
+
+/// A future which waits for any operation in progress on an `AsyncObject` to complete.
+struct WaitIdle<'a>(&'a mut AsyncObject);
+
+impl<'a> Future for WaitIdle<'a> {
+    type Output = io::Result<Option<Operation>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_idle(cx)
+    }
+}
+
+/// Return the error for when the object was lost because a blocking operation failed.
+fn lost_object() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "The object is unavailable because a previous operation failed to complete.",
+    )
+}
+
+impl AsyncRead for AsyncObject {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            match self.poll_idle(cx)? {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Operation::Read(result))) => {
+                    let bytes_read = result?;
+                    let bytes_copied = min(bytes_read, buf.remaining());
+                    if let State::Idle(_, buffer) = &self.state {
+                        buf.put_slice(&buffer[..bytes_copied]);
+                    }
+
+                    // If this read was started with a larger buffer and then abandoned, seek back
+                    // to the first byte which was not returned.
+                    let bytes_left = (bytes_read - bytes_copied) as i64;
+                    if bytes_left > 0 {
+                        self.start(move |object| {
+                            Operation::Rewind(object.seek(SeekFrom::Current(-bytes_left)))
+                        })?;
+                    }
+
+                    return Poll::Ready(Ok(()));
+                }
+                // Either no operation was in progress or a different operation was abandoned.
+                Poll::Ready(_) => {
+                    let size = min(buf.remaining(), MAX_BUFFER_SIZE);
+                    self.start_with_buffer(move |object, buffer| {
+                        buffer.resize(size, 0);
+                        Operation::Read(object.read(buffer))
+                    })?;
+                }
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncObject {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match self.poll_idle(cx)? {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Operation::Write(result))) => return Poll::Ready(result),
+                Poll::Ready(_) => {
+                    let data = buf[..min(buf.len(), MAX_BUFFER_SIZE)].to_vec();
+                    self.start(move |object| Operation::Write(object.write(&data)))?;
+                }
+            }
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            match self.poll_idle(cx)? {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Operation::Flush(result))) => return Poll::Ready(result),
+                Poll::Ready(_) => {
+                    self.start(|object| Operation::Flush(object.flush()))?;
+                }
+            }
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncSeek for AsyncObject {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        if let State::Busy(_) = self.state {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Another operation is in progress.",
+            ));
+        }
+        self.start(move |object| Operation::Seek(object.seek(position)))?;
+        self.seeking = true;
+        Ok(())
+    }
+
+    fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        loop {
+            match self.poll_idle(cx)? {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Operation::Seek(result))) if self.seeking => {
+                    self.seeking = false;
+                    return Poll::Ready(result);
+                }
+                // If no seek was started, this returns the current position.
+                Poll::Ready(_) => {
+                    self.start(|object| Operation::Seek(object.stream_position()))?;
+                    self.seeking = true;
+                }
+            }
+        }
+    }
+}
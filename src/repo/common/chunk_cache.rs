@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::handle::Chunk;
+use crate::store::BlockId;
+
+/// The key of a value in a `ChunkCache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheKey {
+    /// The decoded contents of a chunk.
+    Chunk(Chunk),
+
+    /// The decrypted contents of a pack with the given block ID.
+    Pack(BlockId),
+}
+
+/// An entry in a `ChunkCache`.
+#[derive(Debug)]
+struct CacheEntry {
+    /// The cached data.
+    data: Vec<u8>,
+
+    /// The tick at which this entry was last accessed.
+    last_used: u64,
+}
+
+/// A least-recently-used cache of decoded chunks and packs which is shared between objects.
+#[derive(Debug)]
+pub struct ChunkCache {
+    /// The maximum number of bytes of data to store in the cache.
+    capacity: u64,
+
+    /// The number of bytes of data currently stored in the cache.
+    size: u64,
+
+    /// The entries in the cache.
+    entries: HashMap<CacheKey, CacheEntry>,
+
+    /// The keys of the entries in the cache, ordered by when they were last accessed.
+    recency: BTreeMap<u64, CacheKey>,
+
+    /// A counter which is incremented each time an entry is accessed.
+    tick: u64,
+
+    /// The number of lookups which found the value in the cache.
+    hits: u64,
+
+    /// The number of lookups which did not find the value in the cache.
+    misses: u64,
+}
+
+impl ChunkCache {
+    /// Create a new empty cache which stores up to `capacity` bytes of data.
+    ///
+    /// If `capacity` is `0`, the cache is disabled.
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Return whether the cache is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Return whether the value with the given `key` is in the cache.
+    ///
+    /// Unlike `get`, this does not count as an access.
+    pub fn contains(&self, key: &CacheKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Return a copy of the value with the given `key` if it is in the cache.
+    pub fn get(&mut self, key: &CacheKey) -> Option<Vec<u8>> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                self.recency.remove(&entry.last_used);
+                self.recency.insert(self.tick, *key);
+                entry.last_used = self.tick;
+                self.hits += 1;
+                Some(entry.data.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Insert the given `data` into the cache with the given `key`.
+    ///
+    /// This evicts the least-recently-used entries until the cache is within its capacity. If
+    /// `data` is larger than the capacity of the cache, it is not inserted.
+    pub fn insert(&mut self, key: CacheKey, data: &[u8]) {
+        if data.len() as u64 > self.capacity {
+            return;
+        }
+
+        self.remove(&key);
+
+        while self.size + data.len() as u64 > self.capacity {
+            let (_, oldest_key) = self
+                .recency
+                .pop_first()
+                .expect("The cache is over capacity, but it is empty.");
+            let oldest_entry = self.entries.remove(&oldest_key).unwrap();
+            self.size -= oldest_entry.data.len() as u64;
+        }
+
+        self.tick += 1;
+        self.size += data.len() as u64;
+        self.recency.insert(self.tick, key);
+        self.entries.insert(
+            key,
+            CacheEntry {
+                data: data.to_vec(),
+                last_used: self.tick,
+            },
+        );
+    }
+
+    /// Remove the value with the given `key` from the cache.
+    ///
+    /// This must be called when the data associated with `key` is modified.
+    pub fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+            self.size -= entry.data.len() as u64;
+        }
+    }
+
+    /// Return statistics about the cache.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            size: self.size,
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// Statistics about a repository's chunk cache.
+///
+/// The chunk cache is configured with [`OpenOptions::cache_size`].
+///
+/// [`OpenOptions::cache_size`]: crate::repo::OpenOptions::cache_size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    hits: u64,
+    misses: u64,
+    size: u64,
+    capacity: u64,
+}
+
+impl CacheStats {
+    /// The number of times a chunk or pack was found in the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of times a chunk or pack was not found in the cache and had to be read from the
+    /// data store.
+    ///
+    /// Chunks which are read ahead of time because of [`OpenOptions::read_ahead`] are not counted.
+    ///
+    /// [`OpenOptions::read_ahead`]: crate::repo::OpenOptions::read_ahead
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// The number of bytes of data currently stored in the cache.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The maximum number of bytes of data which can be stored in the cache.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+}
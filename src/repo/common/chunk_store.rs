@@ -1,10 +1,15 @@
 use std::cmp::min;
 use std::collections::HashSet;
 
+use secrecy::ExposeSecret;
 use uuid::Uuid;
 
+use super::chunk_cache::CacheKey;
+use super::compression::{BlockFormat, Compression};
+use super::encryption::{Encryption, EncryptionKey};
+use super::handle::Chunk;
 use super::handle::HandleId;
-use super::handle::{chunk_hash, Chunk};
+use super::metadata::RepoMetadata;
 use super::packing::Packing;
 use super::state::{ChunkInfo, Pack, PackIndex, RepoState};
 use crate::store::{BlockId, BlockKey};
@@ -20,7 +25,11 @@ pub trait EncodeBlock {
 
 impl EncodeBlock for RepoState {
     fn encode_data(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
-        let compressed_data = self.metadata.config.compression.compress(data)?;
+        let compressed_data = self
+            .metadata
+            .config
+            .compression
+            .compress_block(data, self.metadata.block_format())?;
 
         Ok(self
             .metadata
@@ -39,7 +48,46 @@ impl EncodeBlock for RepoState {
         self.metadata
             .config
             .compression
-            .decompress(decrypted_data.as_slice())
+            .decompress_block(decrypted_data.as_slice(), self.metadata.block_format())
+    }
+}
+
+/// The configuration for encoding and decoding blocks outside of the repository state.
+///
+/// This is used by worker threads which encode and decode blocks without holding a lock on the
+/// repository state.
+#[derive(Debug)]
+pub struct BlockCodec {
+    pub compression: Compression,
+    pub format: BlockFormat,
+    pub encryption: Encryption,
+    pub key: EncryptionKey,
+}
+
+impl BlockCodec {
+    /// Return a codec which encodes blocks like a repository with the given `metadata`.
+    pub fn new(metadata: &RepoMetadata, master_key: &EncryptionKey) -> Self {
+        BlockCodec {
+            compression: metadata.config.compression.clone(),
+            format: metadata.block_format(),
+            encryption: metadata.config.encryption.clone(),
+            key: EncryptionKey::new(master_key.expose_secret().clone()),
+        }
+    }
+}
+
+impl EncodeBlock for BlockCodec {
+    fn encode_data(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        let compressed_data = self.compression.compress_block(data, self.format)?;
+        Ok(self
+            .encryption
+            .encrypt(compressed_data.as_slice(), &self.key))
+    }
+
+    fn decode_data(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        let decrypted_data = self.encryption.decrypt(data, &self.key)?;
+        self.compression
+            .decompress_block(decrypted_data.as_slice(), self.format)
     }
 }
 
@@ -64,6 +112,45 @@ pub trait WriteBlock: ReadBlock {
 struct PackingBlockReader<'a> {
     repo_state: &'a RepoState,
     store_state: &'a mut StoreState,
+    use_cache: bool,
+}
+
+impl<'a> PackingBlockReader<'a> {
+    /// Return the decrypted contents of the pack with the given `id`.
+    fn read_pack(&self, id: BlockId) -> crate::Result<Vec<u8>> {
+        let cache_key = CacheKey::Pack(id);
+
+        if self.use_cache {
+            if let Some(pack_buffer) = self.repo_state.chunk_cache.lock().unwrap().get(&cache_key) {
+                return Ok(pack_buffer);
+            }
+        }
+
+        let encoded_pack_buffer = self
+            .repo_state
+            .store
+            .lock()
+            .unwrap()
+            .read_block(BlockKey::Data(id))
+            .map_err(crate::Error::Store)?
+            .ok_or(crate::Error::InvalidData)?;
+        let pack_buffer = self
+            .repo_state
+            .metadata
+            .config
+            .encryption
+            .decrypt(encoded_pack_buffer.as_slice(), &self.repo_state.master_key)?;
+
+        if self.use_cache {
+            self.repo_state
+                .chunk_cache
+                .lock()
+                .unwrap()
+                .insert(cache_key, &pack_buffer);
+        }
+
+        Ok(pack_buffer)
+    }
 }
 
 impl<'a> ReadBlock for PackingBlockReader<'a> {
@@ -86,20 +173,7 @@ impl<'a> ReadBlock for PackingBlockReader<'a> {
 
                 // Read a new pack into the read buffer.
                 _ => {
-                    let encoded_pack_buffer = self
-                        .repo_state
-                        .store
-                        .lock()
-                        .unwrap()
-                        .read_block(BlockKey::Data(pack_index.id))
-                        .map_err(crate::Error::Store)?
-                        .ok_or(crate::Error::InvalidData)?;
-                    let pack_buffer = self
-                        .repo_state
-                        .metadata
-                        .config
-                        .encryption
-                        .decrypt(encoded_pack_buffer.as_slice(), &self.repo_state.master_key)?;
+                    let pack_buffer = self.read_pack(pack_index.id)?;
                     let pack = Pack {
                         id: pack_index.id,
                         buffer: pack_buffer,
@@ -115,11 +189,11 @@ impl<'a> ReadBlock for PackingBlockReader<'a> {
             block_buffer.extend_from_slice(&pack_buffer[start..end]);
         }
 
-        self.repo_state
-            .metadata
+        let metadata = &self.repo_state.metadata;
+        metadata
             .config
             .compression
-            .decompress(block_buffer.as_slice())
+            .decompress_block(block_buffer.as_slice(), metadata.block_format())
     }
 }
 
@@ -134,6 +208,7 @@ impl<'a> ReadBlock for PackingBlockWriter<'a> {
         let mut reader = PackingBlockReader {
             repo_state: self.repo_state,
             store_state: self.store_state,
+            use_cache: true,
         };
         reader.read_block(id)
     }
@@ -152,7 +227,11 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
         // a fixed size, as different data may compress with a different compression ratio. The size
         // of the compressed pack would leak metadata about the contents of the pack, as unlike
         // with encryption, the size of the compressed pack would be based on its contents.
-        let compressed_data = self.repo_state.metadata.config.compression.compress(data)?;
+        let metadata = &self.repo_state.metadata;
+        let compressed_data = metadata
+            .config
+            .compression
+            .compress_block(data, metadata.block_format())?;
 
         // The block's offset from the start of the current pack.
         let mut current_offset = current_pack.buffer.len() as u32;
@@ -225,6 +304,11 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
                     .unwrap()
                     .write_block(BlockKey::Data(current_pack.id), encrypted_pack.as_slice())
                     .map_err(crate::Error::Store)?;
+                self.repo_state
+                    .chunk_cache
+                    .lock()
+                    .unwrap()
+                    .remove(&CacheKey::Pack(current_pack.id));
 
                 // We're starting a new pack, so these need to be reset.
                 current_offset = 0;
@@ -255,6 +339,11 @@ impl<'a> WriteBlock for PackingBlockWriter<'a> {
                     .unwrap()
                     .write_block(BlockKey::Data(current_pack.id), encrypted_pack.as_slice())
                     .map_err(crate::Error::Store)?;
+                self.repo_state
+                    .chunk_cache
+                    .lock()
+                    .unwrap()
+                    .remove(&CacheKey::Pack(current_pack.id));
 
                 // We need to update the pack map in the repository state after all data has been
                 // written to the data store. If this method fails early, we can't have the pack map
@@ -345,6 +434,7 @@ pub trait WriteChunk: ReadChunk {
 pub struct StoreReader<'a> {
     repo_state: &'a RepoState,
     store_state: &'a mut StoreState,
+    use_cache: bool,
 }
 
 impl<'a> StoreReader<'a> {
@@ -353,8 +443,18 @@ impl<'a> StoreReader<'a> {
         StoreReader {
             repo_state,
             store_state,
+            use_cache: true,
         }
     }
+
+    /// Bypass the repository's chunk cache and always read data from the data store.
+    ///
+    /// This is used when verifying data, since the cache may hold data which has since been
+    /// corrupted in the data store.
+    pub fn without_cache(mut self) -> Self {
+        self.use_cache = false;
+        self
+    }
 }
 
 impl<'a> ReadBlock for StoreReader<'a> {
@@ -366,6 +466,7 @@ impl<'a> ReadBlock for StoreReader<'a> {
             Packing::Fixed(_) => Box::new(PackingBlockReader {
                 repo_state: self.repo_state,
                 store_state: self.store_state,
+                use_cache: self.use_cache,
             }),
         };
         read_block.read_block(id)
@@ -374,12 +475,30 @@ impl<'a> ReadBlock for StoreReader<'a> {
 
 impl<'a> ReadChunk for StoreReader<'a> {
     fn read_chunk(&mut self, chunk: Chunk) -> crate::Result<Vec<u8>> {
+        let cache_key = CacheKey::Chunk(chunk);
+
+        if self.use_cache {
+            if let Some(data) = self.repo_state.chunk_cache.lock().unwrap().get(&cache_key) {
+                return Ok(data);
+            }
+        }
+
         let chunk_info = self
             .repo_state
             .chunks
             .get(&chunk)
             .ok_or(crate::Error::InvalidData)?;
-        self.read_block(chunk_info.block_id)
+        let data = self.read_block(chunk_info.block_id)?;
+
+        if self.use_cache {
+            self.repo_state
+                .chunk_cache
+                .lock()
+                .unwrap()
+                .insert(cache_key, &data);
+        }
+
+        Ok(data)
     }
 }
 
@@ -401,10 +520,7 @@ impl<'a> StoreWriter<'a> {
 
 impl<'a> ReadBlock for StoreWriter<'a> {
     fn read_block(&mut self, id: BlockId) -> crate::Result<Vec<u8>> {
-        let mut chunk_reader = StoreReader {
-            repo_state: self.repo_state,
-            store_state: self.store_state,
-        };
+        let mut chunk_reader = StoreReader::new(self.repo_state, self.store_state);
         chunk_reader.read_block(id)
     }
 }
@@ -428,26 +544,15 @@ impl<'a> WriteBlock for StoreWriter<'a> {
 
 impl<'a> ReadChunk for StoreWriter<'a> {
     fn read_chunk(&mut self, chunk: Chunk) -> crate::Result<Vec<u8>> {
-        let mut chunk_reader = StoreReader {
-            repo_state: self.repo_state,
-            store_state: self.store_state,
-        };
+        let mut chunk_reader = StoreReader::new(self.repo_state, self.store_state);
         chunk_reader.read_chunk(chunk)
     }
 }
 
 impl<'a> WriteChunk for StoreWriter<'a> {
     fn write_chunk(&mut self, data: &[u8], id: HandleId) -> crate::Result<Chunk> {
-        assert!(
-            data.len() <= u32::MAX as usize,
-            "Given data exceeds maximum chunk size."
-        );
-
         // Get a checksum of the unencoded data.
-        let chunk = Chunk {
-            hash: chunk_hash(data),
-            size: data.len() as u32,
-        };
+        let chunk = Chunk::new(data);
 
         // Check if the chunk already exists.
         if let Some(chunk_info) = self.repo_state.chunks.get_mut(&chunk) {
@@ -34,6 +34,26 @@ pub enum Chunking {
         /// (2^20 = 1048576).
         bits: u32,
     },
+
+    /// Split data using the FastCDC content-defined chunking algorithm.
+    ///
+    /// Like `Zpaq`, this chunking method provides content-defined deduplication, but it is
+    /// typically much faster. Unlike `Zpaq`, it also bounds the size of each chunk, which avoids
+    /// very small chunks that increase overhead and very large chunks that hurt deduplication.
+    ///
+    /// Chunk sizes are normalized toward `avg`, which is rounded down to a power of two. The last
+    /// chunk of an object may be smaller than `min`. The sizes must be nonzero and satisfy
+    /// `min <= avg <= max`; creating a repository with other sizes returns `Error::InvalidConfig`.
+    FastCdc {
+        /// The minimum chunk size in bytes.
+        min: u32,
+
+        /// The average chunk size in bytes.
+        avg: u32,
+
+        /// The maximum chunk size in bytes.
+        max: u32,
+    },
 }
 
 impl Chunking {
@@ -43,11 +63,31 @@ impl Chunking {
     /// A reasonable default value of `Chunking::Zpaq`.
     pub const ZPAQ: Self = Self::Zpaq { bits: 18 };
 
+    /// A reasonable default value of `Chunking::FastCdc`.
+    pub const FASTCDC: Self = Self::FastCdc {
+        min: 64 * 1024,
+        avg: 256 * 1024,
+        max: 1024 * 1024,
+    };
+
+    /// Return whether the parameters of this chunking method are valid.
+    pub(super) fn is_valid(&self) -> bool {
+        match self {
+            Chunking::Fixed { .. } | Chunking::Zpaq { .. } => true,
+            Chunking::FastCdc { min, avg, max } => 0 < *min && min <= avg && avg <= max,
+        }
+    }
+
     /// Return a chunker for this chunking method.
     pub(super) fn to_chunker(&self) -> Box<dyn ChunkerImpl + Send + Sync> {
         match self {
             Chunking::Fixed { size } => Box::new(FixedChunker::new(*size as usize)),
             Chunking::Zpaq { bits } => Box::new(ZPAQ::new(*bits as usize)),
+            Chunking::FastCdc { min, avg, max } => Box::new(FastCdcChunker::new(
+                *min as usize,
+                *avg as usize,
+                *max as usize,
+            )),
         }
     }
 }
@@ -84,6 +124,117 @@ impl ChunkerImpl for FixedChunker {
     }
 }
 
+/// The number of bytes which contribute to the gear hash in `FastCdcChunker`.
+///
+/// Each byte is shifted out of the 64-bit hash after 64 more bytes are hashed.
+const GEAR_WINDOW_SIZE: usize = 64;
+
+/// The random values which the gear hash in `FastCdcChunker` uses for each byte.
+///
+/// These must never change, because that would change where existing data is split into chunks
+/// and prevent it from being deduplicated against new data.
+static GEAR_TABLE: [u64; 256] = gear_table();
+
+/// Generate the values for `GEAR_TABLE` using SplitMix64 with a fixed seed.
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut index = 0;
+    while index < table.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[index] = value ^ (value >> 31);
+        index += 1;
+    }
+    table
+}
+
+/// Return a mask which selects the `bits` most significant bits of a hash.
+fn hash_mask(bits: u32) -> u64 {
+    match bits {
+        0 => 0,
+        bits => u64::MAX << (u64::BITS - bits.min(u64::BITS)),
+    }
+}
+
+/// A `ChunkerImpl` which chunks data using the FastCDC algorithm.
+///
+/// This uses a gear hash to find content-defined boundaries. Bytes before the minimum chunk size
+/// are skipped without checking for a boundary. Until the average chunk size, a boundary requires
+/// more bits of the hash to be zero than after it, which keeps chunk sizes close to the average.
+/// A boundary is always placed at the maximum chunk size.
+pub struct FastCdcChunker {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+    small_mask: u64,
+    large_mask: u64,
+    hash: u64,
+    bytes_read: usize,
+}
+
+impl FastCdcChunker {
+    /// Return a new instance which chunks data using the given chunk sizes.
+    ///
+    /// If the sizes are not ordered `min_size <= avg_size <= max_size`, they are clamped so they
+    /// are. Repositories can't be created with such sizes, but this keeps repositories which were
+    /// created before they were rejected readable.
+    pub fn new(min_size: usize, avg_size: usize, max_size: usize) -> Self {
+        let max_size = max_size.max(1);
+        let min_size = min_size.min(max_size);
+        let avg_size = avg_size.clamp(min_size.max(1), max_size);
+        let bits = avg_size.ilog2();
+        FastCdcChunker {
+            min_size,
+            avg_size,
+            max_size,
+            small_mask: hash_mask(bits + 2),
+            large_mask: hash_mask(bits.saturating_sub(2)),
+            hash: 0,
+            bytes_read: 0,
+        }
+    }
+}
+
+impl ChunkerImpl for FastCdcChunker {
+    fn find_boundary(&mut self, data: &[u8]) -> Option<usize> {
+        // Start hashing early enough that the hash covers a full window at the minimum size.
+        let hash_start = self.min_size.saturating_sub(GEAR_WINDOW_SIZE);
+
+        for (index, byte) in data.iter().enumerate() {
+            self.bytes_read += 1;
+
+            if self.bytes_read <= hash_start {
+                continue;
+            }
+            self.hash = (self.hash << 1).wrapping_add(GEAR_TABLE[*byte as usize]);
+
+            if self.bytes_read < self.min_size {
+                continue;
+            }
+
+            let mask = if self.bytes_read < self.avg_size {
+                self.small_mask
+            } else {
+                self.large_mask
+            };
+
+            if self.hash & mask == 0 || self.bytes_read >= self.max_size {
+                return Some(index + 1);
+            }
+        }
+
+        None
+    }
+
+    fn reset(&mut self) {
+        self.hash = 0;
+        self.bytes_read = 0;
+    }
+}
+
 /// A chunker which partitions data written to it into chunks.
 pub struct IncrementalChunker {
     chunker: Box<dyn ChunkerImpl + Send + Sync>,
@@ -13,17 +13,34 @@ pub trait Commit {
     /// this repository.
     ///
     /// To reclaim space from deleted objects in the backing data store, you must call [`clean`]
-    /// after changes are committed.
+    /// after changes are committed. Each commit is also added to the repository's history, which
+    /// grows until the repository is cleaned.
     ///
     /// This method commits changes for all instances of the repository.
     ///
+    /// If the repository was opened with [`OpenOptions::optimistic`], another client may have
+    /// committed changes since this repository was opened or last committed. If those changes
+    /// modify different instances or different keys in the current instance than the changes in
+    /// this repository, they are merged automatically before committing. Otherwise, this method
+    /// returns `Error::Conflict`, and you can roll back and try again. Merging changes invalidates
+    /// all [`Object`] and [`ReadOnlyObject`] instances associated with the repository. If the data
+    /// store can't conditionally write blocks atomically, the repository is instead locked
+    /// exclusively while it is committed, so this method returns `Error::Locked` if another client
+    /// which can modify the repository has it open.
+    ///
     /// # Errors
     /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::ReadOnly`: The repository is read-only.
+    /// - `Error::Locked`: Another client which can modify the repository has it open.
+    /// - `Error::Conflict`: Another client committed changes which conflict with these changes.
     /// - `Error::InvalidData`: Ciphertext verification failed.
     /// - `Error::Store`: An error occurred with the data store.
     /// - `Error::Io`: An I/O error occurred.
     ///
     /// [`clean`]: crate::repo::Commit::clean
+    /// [`OpenOptions::optimistic`]: crate::repo::OpenOptions::optimistic
+    /// [`Object`]: crate::repo::Object
+    /// [`ReadOnlyObject`]: crate::repo::ReadOnlyObject
     fn commit(&mut self) -> crate::Result<()>;
 
     /// Roll back all changes made since the last commit.
@@ -55,11 +72,22 @@ pub trait Commit {
     /// When data in a repository is deleted, the space is not reclaimed in the backing data store
     /// until those changes are committed and this method is called.
     ///
+    /// Data which is referenced by a [`Snapshot`] is not removed until that snapshot is removed.
+    ///
+    /// If the repository was opened with [`OpenOptions::optimistic`], it can only be cleaned while
+    /// no other client which can modify it has it open.
+    ///
     /// # Errors
     /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::ReadOnly`: The repository is read-only.
+    /// - `Error::Locked`: Another client which can modify the repository has it open.
+    /// - `Error::Conflict`: Another client committed changes while the repository was cleaned.
     /// - `Error::InvalidData`: Ciphertext verification failed.
     /// - `Error::Store`: An error occurred with the data store.
     /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`Snapshot`]: crate::repo::Snapshot
+    /// [`OpenOptions::optimistic`]: crate::repo::OpenOptions::optimistic
     fn clean(&mut self) -> crate::Result<()>;
 }
 
@@ -1,9 +1,16 @@
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "compression")]
+use lz4::{Decoder as Lz4Decoder, EncoderBuilder as Lz4EncoderBuilder};
+#[cfg(any(feature = "compression", feature = "compression-zstd"))]
+use std::io::{Read, Write};
+#[cfg(feature = "compression-zstd")]
 use {
-    lz4::{Decoder as Lz4Decoder, EncoderBuilder as Lz4EncoderBuilder},
-    std::io::{Read, Write},
+    serde::de::{self, Deserializer, SeqAccess, Visitor},
+    serde::ser::Serializer,
+    std::fmt::{self, Debug, Formatter},
+    std::sync::Arc,
+    zstd::stream::{Decoder as ZstdDecoder, Encoder as ZstdEncoder},
 };
 
 /// A data compression method.
@@ -23,9 +30,145 @@ pub enum Compression {
         /// highest compression ratio.
         level: u32,
     },
+
+    /// Compress data using the Zstandard compression algorithm.
+    ///
+    /// This typically gives much better compression ratios than `Lz4`, especially for text, at the
+    /// cost of some speed.
+    #[cfg(feature = "compression-zstd")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression-zstd")))]
+    Zstd {
+        /// The compression level to use.
+        ///
+        /// This is a number in the range 1-22, where 1 gives the fastest compression and 22 gives
+        /// the highest compression ratio. A value of 0 uses the default level, which is 3.
+        level: i32,
+    },
+
+    /// Compress data using the Zstandard compression algorithm with a dictionary.
+    ///
+    /// A dictionary trained on samples of the data being stored greatly improves compression
+    /// ratios for small chunks of similar data, which otherwise compress poorly. The same
+    /// dictionary is used for every chunk in the repository, and it is stored in the repository
+    /// along with the rest of its configuration.
+    ///
+    /// The dictionary is stored unencrypted, even when the repository is encrypted. Because a
+    /// trained dictionary contains fragments of the samples it was trained on, you should not
+    /// train it on sensitive data.
+    #[cfg(feature = "compression-zstd")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression-zstd")))]
+    ZstdDictionary {
+        /// The compression level to use.
+        ///
+        /// This has the same meaning as for `Zstd`.
+        level: i32,
+
+        /// The dictionary to compress data with.
+        dictionary: ZstdDictionary,
+    },
+}
+
+// The tags which identify how a block was compressed.
+const TAG_NONE: u8 = 0;
+#[cfg(feature = "compression")]
+const TAG_LZ4: u8 = 1;
+#[cfg(feature = "compression-zstd")]
+const TAG_ZSTD: u8 = 2;
+#[cfg(feature = "compression-zstd")]
+const TAG_ZSTD_DICTIONARY: u8 = 3;
+
+/// The format of compressed blocks in a repository.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BlockFormat {
+    /// Blocks are always compressed with the repository's compression method and have no tag.
+    ///
+    /// This is the format of repositories created before blocks were tagged.
+    Untagged,
+
+    /// Each block starts with a tag which identifies how it was compressed.
+    ///
+    /// Blocks which compressing would make smaller by less than `threshold` percent are stored
+    /// uncompressed.
+    Tagged {
+        /// The minimum percentage of space compressing a block must save.
+        threshold: u8,
+    },
 }
 
 impl Compression {
+    /// The tag which identifies blocks compressed with this method.
+    fn tag(&self) -> u8 {
+        match self {
+            Compression::None => TAG_NONE,
+            #[cfg(feature = "compression")]
+            Compression::Lz4 { .. } => TAG_LZ4,
+            #[cfg(feature = "compression-zstd")]
+            Compression::Zstd { .. } => TAG_ZSTD,
+            #[cfg(feature = "compression-zstd")]
+            Compression::ZstdDictionary { .. } => TAG_ZSTD_DICTIONARY,
+        }
+    }
+
+    /// Compress the given `data` into a block with the given `format` and return it.
+    pub(crate) fn compress_block(
+        &self,
+        data: &[u8],
+        format: BlockFormat,
+    ) -> crate::Result<Vec<u8>> {
+        let threshold = match format {
+            BlockFormat::Untagged => return self.compress(data),
+            BlockFormat::Tagged { threshold } => threshold as usize,
+        };
+
+        let mut block = Vec::with_capacity(data.len() + 1);
+        if *self != Compression::None {
+            let compressed_data = self.compress(data)?;
+            let saved = data.len().saturating_sub(compressed_data.len());
+            if compressed_data.len() < data.len() && saved * 100 >= data.len() * threshold {
+                block.push(self.tag());
+                block.extend_from_slice(&compressed_data);
+                return Ok(block);
+            }
+        }
+
+        // Compression didn't save enough space, so store the data uncompressed.
+        block.push(TAG_NONE);
+        block.extend_from_slice(data);
+        Ok(block)
+    }
+
+    /// Decompress the given `block` with the given `format` and return its data.
+    ///
+    /// Blocks in the `BlockFormat::Tagged` format are decompressed with whichever method they
+    /// were compressed with, which isn't necessarily this one. Blocks compressed with a
+    /// dictionary can only be decompressed if this method uses the same dictionary.
+    ///
+    /// # Errors
+    /// - `Error::InvalidData`: The block was compressed with an unknown or unsupported method.
+    pub(crate) fn decompress_block(
+        &self,
+        block: &[u8],
+        format: BlockFormat,
+    ) -> crate::Result<Vec<u8>> {
+        if format == BlockFormat::Untagged {
+            return self.decompress(block);
+        }
+
+        let (tag, data) = block.split_first().ok_or(crate::Error::InvalidData)?;
+        match *tag {
+            TAG_NONE => Ok(data.to_vec()),
+            #[cfg(feature = "compression")]
+            TAG_LZ4 => Compression::Lz4 { level: 0 }.decompress(data),
+            #[cfg(feature = "compression-zstd")]
+            TAG_ZSTD => Compression::Zstd { level: 0 }.decompress(data),
+            #[cfg(feature = "compression-zstd")]
+            TAG_ZSTD_DICTIONARY if matches!(self, Compression::ZstdDictionary { .. }) => {
+                self.decompress(data)
+            }
+            _ => Err(crate::Error::InvalidData),
+        }
+    }
+
     /// Compresses the given `data` and returns it.
     pub(crate) fn compress(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
         match self {
@@ -39,6 +182,17 @@ impl Compression {
                 result?;
                 Ok(output)
             }
+            #[cfg(feature = "compression-zstd")]
+            Compression::Zstd { level } => Ok(zstd::bulk::compress(data, *level)?),
+            #[cfg(feature = "compression-zstd")]
+            Compression::ZstdDictionary { level, dictionary } => {
+                let mut output = Vec::with_capacity(data.len());
+                let mut encoder =
+                    ZstdEncoder::with_dictionary(&mut output, *level, dictionary.as_bytes())?;
+                encoder.write_all(data)?;
+                encoder.finish()?;
+                Ok(output)
+            }
         }
     }
 
@@ -55,6 +209,168 @@ impl Compression {
                 result?;
                 Ok(output)
             }
+            #[cfg(feature = "compression-zstd")]
+            Compression::Zstd { .. } => Ok(zstd::stream::decode_all(data)?),
+            #[cfg(feature = "compression-zstd")]
+            Compression::ZstdDictionary { dictionary, .. } => {
+                let mut output = Vec::with_capacity(data.len());
+                let mut decoder = ZstdDecoder::with_dictionary(data, dictionary.as_bytes())?;
+                decoder.read_to_end(&mut output)?;
+                Ok(output)
+            }
+        }
+    }
+}
+
+/// A dictionary for compressing data with [`Compression::ZstdDictionary`].
+///
+/// This is cheap to clone.
+///
+/// [`Compression::ZstdDictionary`]: crate::repo::Compression::ZstdDictionary
+#[cfg(feature = "compression-zstd")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression-zstd")))]
+#[derive(PartialEq, Eq, Clone)]
+pub struct ZstdDictionary(Arc<Vec<u8>>);
+
+#[cfg(feature = "compression-zstd")]
+impl ZstdDictionary {
+    /// Create a dictionary from its raw `bytes`.
+    ///
+    /// The bytes can be a dictionary trained with the `zstd` command-line tool.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(Arc::new(bytes))
+    }
+
+    /// Train a new dictionary of at most `max_size` bytes from the given `samples`.
+    ///
+    /// The samples should be representative of the chunks which will be stored in the
+    /// repository. A dictionary size of around 100KiB and a few thousand samples is typical.
+    ///
+    /// # Errors
+    /// - `Error::Io`: The dictionary could not be trained from the given samples.
+    pub fn train(samples: &[impl AsRef<[u8]>], max_size: usize) -> crate::Result<Self> {
+        Ok(Self::new(zstd::dict::from_samples(samples, max_size)?))
+    }
+
+    /// The raw bytes of this dictionary.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+#[cfg(feature = "compression-zstd")]
+impl Debug for ZstdDictionary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstdDictionary")
+            .field("size", &self.0.len())
+            .finish()
+    }
+}
+
+// We serialize the dictionary as a byte string rather than a sequence of integers, which would
+// take up to twice as much space.
+#[cfg(feature = "compression-zstd")]
+impl Serialize for ZstdDictionary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+#[cfg(feature = "compression-zstd")]
+impl<'de> Deserialize<'de> for ZstdDictionary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DictionaryVisitor;
+
+        impl<'de> Visitor<'de> for DictionaryVisitor {
+            type Value = ZstdDictionary;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("a byte string")
+            }
+
+            fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+                Ok(ZstdDictionary::new(value.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(ZstdDictionary::new(value))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(ZstdDictionary::new(bytes))
+            }
         }
+
+        deserializer.deserialize_byte_buf(DictionaryVisitor)
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use spectral::prelude::*;
+
+    use super::{BlockFormat, Compression, TAG_LZ4, TAG_NONE};
+
+    const TAGGED: BlockFormat = BlockFormat::Tagged { threshold: 10 };
+
+    #[test]
+    fn compressible_data_is_compressed() {
+        let data = vec![0u8; 1024];
+        let block = Compression::Lz4 { level: 1 }
+            .compress_block(&data, TAGGED)
+            .unwrap();
+
+        assert_that!(block[0]).is_equal_to(TAG_LZ4);
+        assert_that!(block.len()).is_less_than(data.len());
+    }
+
+    #[test]
+    fn incompressible_data_is_stored_raw() {
+        let data = (0..1024u32)
+            .map(|index| (index.wrapping_mul(2654435761) >> 24) as u8)
+            .collect::<Vec<_>>();
+        let block = Compression::Lz4 { level: 1 }
+            .compress_block(&data, TAGGED)
+            .unwrap();
+
+        assert_that!(block[0]).is_equal_to(TAG_NONE);
+        assert_that!(block[1..].to_vec()).is_equal_to(data);
+    }
+
+    #[test]
+    fn data_below_threshold_is_stored_raw() {
+        let data = vec![0u8; 1024];
+        let block = Compression::Lz4 { level: 1 }
+            .compress_block(&data, BlockFormat::Tagged { threshold: 100 })
+            .unwrap();
+
+        assert_that!(block[0]).is_equal_to(TAG_NONE);
+    }
+
+    #[test]
+    fn tagged_blocks_decompress_with_different_method() {
+        let data = vec![0u8; 1024];
+        let block = Compression::Lz4 { level: 1 }
+            .compress_block(&data, TAGGED)
+            .unwrap();
+
+        assert_that!(Compression::None.decompress_block(&block, TAGGED)).is_ok_containing(data);
+    }
+
+    #[test]
+    fn untagged_blocks_are_compressed_without_tag() {
+        let data = vec![0u8; 1024];
+        let compression = Compression::Lz4 { level: 1 };
+        let block = compression
+            .compress_block(&data, BlockFormat::Untagged)
+            .unwrap();
+
+        assert_that!(block).is_equal_to(compression.compress(&data).unwrap());
+        assert_that!(compression.decompress_block(&block, BlockFormat::Untagged))
+            .is_ok_containing(data);
     }
 }
@@ -41,6 +41,27 @@ pub struct RepoConfig {
     ///
     /// The default value is `ResourceLimit::Interactive`.
     pub operations_limit: ResourceLimit,
+
+    /// The minimum percentage of space compressing a block must save for it to be compressed.
+    ///
+    /// Blocks which don't compress well, like already-compressed media, are stored uncompressed
+    /// instead. This avoids spending time decompressing them and keeps them from growing. This is a
+    /// number in the range 0-100; creating a repository with a larger value returns
+    /// `Error::InvalidConfig`.
+    ///
+    /// This has no effect in repositories created before this option existed, where blocks are
+    /// always compressed.
+    ///
+    /// The default value is `10`.
+    // Configs are serialized as arrays, so this must come after the fields which existed before it
+    // for those configs to still be deserialized.
+    #[serde(default = "default_compression_threshold")]
+    pub compression_threshold: u8,
+}
+
+/// The default value of `RepoConfig::compression_threshold`.
+fn default_compression_threshold() -> u8 {
+    10
 }
 
 impl Default for RepoConfig {
@@ -52,6 +73,7 @@ impl Default for RepoConfig {
             encryption: Encryption::None,
             memory_limit: ResourceLimit::Interactive,
             operations_limit: ResourceLimit::Interactive,
+            compression_threshold: default_compression_threshold(),
         }
     }
 }
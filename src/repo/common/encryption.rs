@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "encryption")]
 use {
+    aes_gcm::aead::{Aead, KeyInit},
+    aes_gcm::{Aes256Gcm, Key as AesKey, Nonce as AesNonce},
     rand::rngs::OsRng,
     rand::RngCore,
     sodiumoxide::crypto::aead::xchacha20poly1305_ietf::{
@@ -74,8 +76,25 @@ pub enum Encryption {
     #[cfg(feature = "encryption")]
     #[cfg_attr(docsrs, doc(cfg(feature = "encryption")))]
     XChaCha20Poly1305,
+
+    /// Encrypt data using the AES-256-GCM cipher.
+    ///
+    /// Like with XChaCha20-Poly1305, a random nonce is generated each time data is encrypted.
+    /// Because the nonce is only 96 bits, fewer blocks can safely be encrypted with the same key, so
+    /// XChaCha20-Poly1305 should be preferred unless AES-GCM is required.
+    #[cfg(feature = "encryption")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "encryption")))]
+    Aes256Gcm,
 }
 
+/// The size of the nonce used by the AES-256-GCM cipher.
+#[cfg(feature = "encryption")]
+const AES_NONCE_BYTES: usize = 12;
+
+/// The size of the key used by the AES-256-GCM cipher.
+#[cfg(feature = "encryption")]
+const AES_KEY_BYTES: usize = 32;
+
 impl Encryption {
     /// Encrypt the given `cleartext` with the given `key`.
     #[cfg(feature = "encryption")]
@@ -91,6 +110,17 @@ impl Encryption {
                 output.append(&mut ciphertext);
                 output
             }
+            Encryption::Aes256Gcm => {
+                let mut nonce = [0u8; AES_NONCE_BYTES];
+                OsRng.fill_bytes(&mut nonce);
+                let cipher = Aes256Gcm::new(AesKey::<Aes256Gcm>::from_slice(key.expose_secret()));
+                let mut ciphertext = cipher
+                    .encrypt(AesNonce::from_slice(&nonce), cleartext)
+                    .expect("Failed to encrypt data.");
+                let mut output = nonce.to_vec();
+                output.append(&mut ciphertext);
+                output
+            }
         }
     }
 
@@ -107,11 +137,24 @@ impl Encryption {
         match self {
             Encryption::None => Ok(ciphertext.to_vec()),
             Encryption::XChaCha20Poly1305 => {
+                if ciphertext.len() < NONCEBYTES {
+                    return Err(crate::Error::InvalidData);
+                }
                 let nonce = Nonce::from_slice(&ciphertext[..NONCEBYTES]).unwrap();
                 let chacha_key = ChaChaKey::from_slice(key.expose_secret()).unwrap();
                 open(&ciphertext[NONCEBYTES..], None, &nonce, &chacha_key)
                     .map_err(|_| crate::Error::InvalidData)
             }
+            Encryption::Aes256Gcm => {
+                if ciphertext.len() < AES_NONCE_BYTES {
+                    return Err(crate::Error::InvalidData);
+                }
+                let nonce = AesNonce::from_slice(&ciphertext[..AES_NONCE_BYTES]);
+                let cipher = Aes256Gcm::new(AesKey::<Aes256Gcm>::from_slice(key.expose_secret()));
+                cipher
+                    .decrypt(nonce, &ciphertext[AES_NONCE_BYTES..])
+                    .map_err(|_| crate::Error::InvalidData)
+            }
         }
     }
 
@@ -133,6 +176,8 @@ impl Encryption {
             Encryption::None => 0,
             #[cfg(feature = "encryption")]
             Encryption::XChaCha20Poly1305 => KEYBYTES,
+            #[cfg(feature = "encryption")]
+            Encryption::Aes256Gcm => AES_KEY_BYTES,
         }
     }
 }
@@ -32,6 +32,21 @@ pub struct Chunk {
     pub hash: ChunkHash,
 }
 
+impl Chunk {
+    /// Return the chunk for the given `data`.
+    pub fn new(data: &[u8]) -> Self {
+        assert!(
+            data.len() <= u32::MAX as usize,
+            "Given data exceeds maximum chunk size."
+        );
+
+        Self {
+            hash: chunk_hash(data),
+            size: data.len() as u32,
+        }
+    }
+}
+
 /// A contiguous region in an object.
 ///
 /// An object can be represented as a list of extents. An extent can be either a `Chunk`, which is
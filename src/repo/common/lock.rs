@@ -2,11 +2,20 @@ use std::fmt::Debug;
 use std::hash::Hash;
 use std::sync::{Arc, Weak};
 
-use uuid::Uuid;
+use rmp_serde::{from_read, to_vec};
+use serde::{Deserialize, Serialize};
+use uuid::{uuid, Uuid};
 use weak_table::WeakHashSet;
 
 use super::encryption::{Encryption, EncryptionKey};
-use crate::store::{BlockId, BlockKey, BlockType, DataStore};
+use crate::store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+
+/// The ID of the block which holds the lease on the exclusive lock on a data store which supports
+/// creating blocks atomically.
+///
+/// Every lock is stored in its own block, but a client must also hold this lease to hold an
+/// exclusive lock. The lease contains the ID of the holder's lock.
+const EXCLUSIVE_LEASE_ID: Uuid = uuid!("9c3f7b1e-6a2d-4e58-b0c4-8d1f2a3b4c5d");
 
 /// A lock acquired on a resource.
 ///
@@ -78,7 +87,6 @@ pub trait Unlock {
     ///
     /// # Errors
     /// - `Error::NotLocked`: This repository is not locked.
-    /// - `Error::InvalidData`: Ciphertext verification failed.
     /// - `Error::Store`: An error occurred with the data store.
     ///
     /// [`OpenOptions::locking`]: crate::repo::OpenOptions::locking
@@ -96,60 +104,356 @@ pub trait Unlock {
     /// loss.
     ///
     /// # Errors
+    /// - `Error::NotLocked`: This repository does not hold a lock on the data store.
     /// - `Error::Store`: An error occurred with the data store.
     ///
     /// [`OpenOptions::locking`]: crate::repo::OpenOptions::locking
     fn update_context(&self, context: &[u8]) -> crate::Result<()>;
 }
 
-/// Attempt to acquire a lock on the given `store`.
+/// The kind of lock held on a data store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockKind {
+    /// An exclusive lock held by a repository which can be modified.
+    ///
+    /// Only one exclusive lock can be held on a data store at a time.
+    Exclusive,
+
+    /// A shared lock held by a read-only repository.
+    ///
+    /// Any number of shared locks can be held on a data store alongside any other locks. The
+    /// `header_id` is the ID of the header the read-only repository is reading from, which must
+    /// not be removed while the lock is held.
+    Shared { header_id: BlockId },
+
+    /// A lock held by a repository which uses optimistic concurrency control.
+    ///
+    /// Any number of optimistic locks can be held on a data store at a time, but not alongside an
+    /// exclusive lock.
+    Optimistic,
+}
+
+impl LockKind {
+    /// Return whether a lock of this kind can't be held at the same time as a lock of `other` kind.
+    fn conflicts_with(&self, other: &LockKind) -> bool {
+        !matches!(
+            (self, other),
+            (LockKind::Shared { .. }, _)
+                | (_, LockKind::Shared { .. })
+                | (LockKind::Optimistic, LockKind::Optimistic)
+        )
+    }
+}
+
+/// The contents of a lock block in the data store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockInfo {
+    /// The kind of lock.
+    pub kind: LockKind,
+
+    /// The context value associated with the lock.
+    pub context: Vec<u8>,
+}
+
+impl LockInfo {
+    /// Serialize and encrypt this lock.
+    fn encode(&self, encryption: &Encryption, key: &EncryptionKey) -> Vec<u8> {
+        let serialized_lock = to_vec(self).expect("Could not serialize the lock.");
+        encryption.encrypt(&serialized_lock, key)
+    }
+
+    /// Decrypt and deserialize a lock.
+    ///
+    /// Locks written by older versions of this library contain only the context value. These are
+    /// treated as exclusive locks.
+    ///
+    /// Locks which can't be decrypted were written with a different encryption method or key, which
+    /// happens when a client is interrupted while re-encoding the repository. These are treated as
+    /// exclusive locks with an empty context value.
+    fn decode(
+        encoded_lock: &[u8],
+        encryption: &Encryption,
+        key: &EncryptionKey,
+    ) -> crate::Result<Self> {
+        let serialized_lock = match encryption.decrypt(encoded_lock, key) {
+            Ok(serialized_lock) => serialized_lock,
+            Err(_) => {
+                return Ok(LockInfo {
+                    kind: LockKind::Exclusive,
+                    context: Vec::new(),
+                })
+            }
+        };
+        match from_read(serialized_lock.as_slice()) {
+            Ok(lock) => Ok(lock),
+            Err(_) => Ok(LockInfo {
+                kind: LockKind::Exclusive,
+                context: serialized_lock,
+            }),
+        }
+    }
+}
+
+/// Read the lock with the given `id` from the `store`.
+///
+/// This returns `None` if the lock does not exist.
+///
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+pub fn read_lock(
+    store: &mut impl DataStore,
+    encryption: &Encryption,
+    key: &EncryptionKey,
+    id: BlockId,
+) -> crate::Result<Option<LockInfo>> {
+    match store
+        .read_block(BlockKey::Lock(id))
+        .map_err(crate::Error::Store)?
+    {
+        Some(encoded_lock) => Ok(Some(LockInfo::decode(&encoded_lock, encryption, key)?)),
+        None => Ok(None),
+    }
+}
+
+/// Write the given `lock` to the `store` with the given `id`.
+///
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+pub fn write_lock(
+    store: &mut impl DataStore,
+    encryption: &Encryption,
+    key: &EncryptionKey,
+    id: BlockId,
+    lock: &LockInfo,
+) -> crate::Result<()> {
+    store
+        .write_block(BlockKey::Lock(id), &lock.encode(encryption, key))
+        .map_err(crate::Error::Store)
+}
+
+/// Return the IDs of all the locks in the `store`.
 ///
-/// This uses a two-phase locking algorithm to avoid race conditions.
+/// This does not include the lease on the exclusive lock.
 ///
-/// This returns the `BlockId` of the block containing the lock or `None` if a lock could not be
-/// acquired.
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+pub fn list_locks(store: &mut impl DataStore) -> crate::Result<Vec<BlockId>> {
+    let lease_id = BlockId::from(EXCLUSIVE_LEASE_ID);
+    Ok(store
+        .list_blocks(BlockType::Lock)
+        .map_err(crate::Error::Store)?
+        .into_iter()
+        .filter(|lock_id| *lock_id != lease_id)
+        .collect())
+}
+
+/// Return the IDs and contents of all the locks in the `store` which conflict with a lock of the
+/// given `kind`.
+///
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+fn conflicting_locks(
+    store: &mut impl DataStore,
+    encryption: &Encryption,
+    key: &EncryptionKey,
+    kind: LockKind,
+) -> crate::Result<Vec<(BlockId, LockInfo)>> {
+    let mut conflicting_locks = Vec::new();
+    for lock_id in list_locks(store)? {
+        // A lock may be released between listing it and reading it.
+        if let Some(lock) = read_lock(store, encryption, key, lock_id)? {
+            if lock.kind.conflicts_with(&kind) {
+                conflicting_locks.push((lock_id, lock));
+            }
+        }
+    }
+    Ok(conflicting_locks)
+}
+
+/// Return whether the lock with the given `id` is the only lock in the `store` which conflicts with
+/// a lock of the given `kind`.
+///
+/// If `exclusive_lease` is `true`, the lock with the given `id` holds the lease on the exclusive
+/// lock, so other exclusive locks are ignored. Their holders can't acquire the lease and will
+/// release them.
+///
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+fn is_sole_conflicting_lock(
+    store: &mut impl DataStore,
+    encryption: &Encryption,
+    key: &EncryptionKey,
+    id: BlockId,
+    kind: LockKind,
+    exclusive_lease: bool,
+) -> crate::Result<bool> {
+    Ok(conflicting_locks(store, encryption, key, kind)?
+        .iter()
+        .all(|(lock_id, lock)| {
+            *lock_id == id || (exclusive_lease && lock.kind == LockKind::Exclusive)
+        }))
+}
+
+/// Read the ID of the lock which holds the lease on the exclusive lock in the `store`.
+///
+/// This returns `None` if the lease is not held.
+///
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+fn read_lease(store: &mut impl DataStore) -> crate::Result<Option<BlockId>> {
+    match store
+        .read_block(BlockKey::Lock(EXCLUSIVE_LEASE_ID.into()))
+        .map_err(crate::Error::Store)?
+    {
+        Some(lease) => Ok(Some(
+            Uuid::from_slice(&lease)
+                .map_err(|_| crate::Error::Corrupt)?
+                .into(),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Attempt to acquire the lease on the exclusive lock in the `store` for the lock with the given
+/// `id`.
+///
+/// The lock with the given `id` must already be written to the store. This returns `true` if the
+/// lease was acquired or `false` if it's held by another lock.
+///
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+fn acquire_lease(store: &mut impl DataStore, id: BlockId) -> crate::Result<bool> {
+    let lease_key = BlockKey::Lock(EXCLUSIVE_LEASE_ID.into());
+    let lease = Uuid::from(id).as_bytes().to_vec();
+    if store
+        .create_block_exclusive(lease_key, &lease)
+        .map_err(crate::Error::Store)?
+    {
+        return Ok(true);
+    }
+
+    match read_lease(store)? {
+        Some(holder_id) if holder_id == id => Ok(true),
+        Some(holder_id) => {
+            // Clients write their lock before acquiring the lease, so if the holder's lock doesn't
+            // exist, it was removed by a lock handler before the lease could be released. We take
+            // over the lease, but only if no other client has done so first.
+            if store
+                .read_block(BlockKey::Lock(holder_id))
+                .map_err(crate::Error::Store)?
+                .is_some()
+            {
+                return Ok(false);
+            }
+            store
+                .write_block_if(lease_key, Some(Uuid::from(holder_id).as_bytes()), &lease)
+                .map_err(crate::Error::Store)
+        }
+        // The lease was released since we tried to create it.
+        None => store
+            .create_block_exclusive(lease_key, &lease)
+            .map_err(crate::Error::Store),
+    }
+}
+
+/// Release the lease on the exclusive lock in the `store` if it's held by the lock with the given
+/// `id`.
+///
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+fn release_lease(store: &mut impl DataStore, id: BlockId) -> crate::Result<()> {
+    if read_lease(store)? == Some(id) {
+        store
+            .remove_block(BlockKey::Lock(EXCLUSIVE_LEASE_ID.into()))
+            .map_err(crate::Error::Store)?;
+    }
+    Ok(())
+}
+
+/// Return the IDs of the headers which are being read by holders of shared locks on the `store`.
+///
+/// # Errors
+/// - `Error::Store`: An error occurred with the data store.
+pub fn shared_lock_headers(
+    store: &mut impl DataStore,
+    encryption: &Encryption,
+    key: &EncryptionKey,
+) -> crate::Result<Vec<BlockId>> {
+    let mut header_ids = Vec::new();
+    for lock_id in list_locks(store)? {
+        if let Some(LockInfo {
+            kind: LockKind::Shared { header_id },
+            ..
+        }) = read_lock(store, encryption, key, lock_id)?
+        {
+            header_ids.push(header_id);
+        }
+    }
+    Ok(header_ids)
+}
+
+/// Attempt to acquire a lock of the given `kind` on the given `store`.
+///
+/// This uses a two-phase locking algorithm to avoid race conditions. Existing locks which don't
+/// conflict with a lock of the given `kind` are ignored, except that when acquiring a lock which
+/// isn't shared, the `handler` is also invoked with the context of each existing shared lock, which
+/// is removed if it returns `true`. A client which crashes while holding a shared lock never
+/// releases it, which would keep the header it was reading from being cleaned up.
+///
+/// Each lock is stored in a block with a new random ID. If the store can create blocks atomically,
+/// a client holding an exclusive lock must also hold a lease, which is acquired with
+/// [`DataStore::create_block_exclusive`]. This guarantees that when two clients compete for an
+/// exclusive lock, exactly one of them acquires it. Clients which compete for different kinds of
+/// locks may still both fail to acquire one.
+///
+/// This returns the `BlockId` of the block containing the lock.
 ///
 /// # Errors
 /// - `Error::Locked`: The repository is locked.
-/// - `Error::InvalidData`: Ciphertext verification failed.
 /// - `Error::Store`: An error occurred with the data store.
 /// - `Error::Io`: An I/O error occurred.
 pub fn lock_store<'a>(
     store: &mut impl DataStore,
     encryption: &Encryption,
     key: &EncryptionKey,
+    kind: LockKind,
     context: &'a [u8],
-    handler: impl FnOnce(&[u8]) -> bool + 'a,
+    mut handler: impl FnMut(&[u8]) -> bool + 'a,
 ) -> crate::Result<BlockId> {
+    let exclusive_lease =
+        kind == LockKind::Exclusive && store.conditional_writes() >= ConditionalWrites::Create;
     let current_lock_id = Uuid::new_v4().into();
 
+    // Give the handler a chance to remove shared locks left behind by readers which crashed.
+    if !matches!(kind, LockKind::Shared { .. }) {
+        for lock_id in list_locks(store)? {
+            if let Some(LockInfo {
+                kind: LockKind::Shared { .. },
+                context,
+            }) = read_lock(store, encryption, key, lock_id)?
+            {
+                if handler(context.as_slice()) {
+                    unlock_store(store, lock_id)?;
+                }
+            }
+        }
+    }
+
     // Check for any existing locks on the repository.
-    let existing_locks = store
-        .list_blocks(BlockType::Lock)
-        .map_err(crate::Error::Store)?;
+    let existing_locks = conflicting_locks(store, encryption, key, kind)?;
 
-    match *existing_locks.as_slice() {
+    match existing_locks.as_slice() {
         // There are no exising locks.
         [] => {}
 
         // There is exactly one existing lock.
-        [existing_lock_id] => {
-            let encrypted_existing_lock_context = store
-                .read_block(BlockKey::Lock(existing_lock_id))
-                .map_err(crate::Error::Store)?
-                .ok_or(crate::Error::Locked)?;
-            let existing_lock_context =
-                encryption.decrypt(&encrypted_existing_lock_context, key)?;
-
+        [(existing_lock_id, existing_lock)] => {
             // Invoke the lock handler with the existing lock's lock ID to see if it should be
             // removed.
-            if handler(existing_lock_context.as_slice()) {
-                store
-                    .remove_block(BlockKey::Lock(existing_lock_id))
-                    .map_err(crate::Error::Store)?;
-            } else {
+            if !handler(existing_lock.context.as_slice()) {
                 return Err(crate::Error::Locked);
             }
+            unlock_store(store, *existing_lock_id)?;
         }
 
         // There is more than one existing lock. We do not try to resolve this situation.
@@ -159,20 +463,29 @@ pub fn lock_store<'a>(
     }
 
     // Acquire a lock on the repository.
-    let encrypted_current_lock_context = encryption.encrypt(context, key);
-    store
-        .write_block(
-            BlockKey::Lock(current_lock_id),
-            &encrypted_current_lock_context,
-        )
-        .map_err(crate::Error::Store)?;
+    let current_lock = LockInfo {
+        kind,
+        context: context.to_vec(),
+    };
+    write_lock(store, encryption, key, current_lock_id, &current_lock)?;
 
-    // Check if any new locks have been acquired since we last checked.
-    let existing_locks = store
-        .list_blocks(BlockType::Lock)
-        .map_err(crate::Error::Store)?;
+    // If another client acquired the lease since we last checked, it holds the exclusive lock.
+    if exclusive_lease && !acquire_lease(store, current_lock_id)? {
+        store
+            .remove_block(BlockKey::Lock(current_lock_id))
+            .map_err(crate::Error::Store)?;
+        return Err(crate::Error::Locked);
+    }
 
-    if existing_locks == [current_lock_id] {
+    // Check if any new locks have been acquired since we last checked.
+    if is_sole_conflicting_lock(
+        store,
+        encryption,
+        key,
+        current_lock_id,
+        kind,
+        exclusive_lease,
+    )? {
         // No new locks have been acquired, which means our lock is valid.
         Ok(current_lock_id)
     } else {
@@ -180,22 +493,69 @@ pub fn lock_store<'a>(
         // avoid a race condition. It is possible for two clients to compete for a lock and for
         // neither to acquire one. This locking algorithm does not guarantee that a lock will be
         // granted.
-        store
-            .remove_block(BlockKey::Lock(current_lock_id))
-            .map_err(crate::Error::Store)?;
+        unlock_store(store, current_lock_id)?;
         Err(crate::Error::Locked)
     }
 }
 
+/// Change the lock with the given `id` on the `store` to a lock of the given `kind`.
+///
+/// The context value of the lock is preserved. If another lock is held which conflicts with a lock
+/// of the given `kind`, the lock is left unchanged.
+///
+/// # Errors
+/// - `Error::Locked`: Another lock is held which conflicts with a lock of the given `kind`.
+/// - `Error::NotLocked`: The lock with the given `id` is not held.
+/// - `Error::Store`: An error occurred with the data store.
+pub fn change_lock_kind(
+    store: &mut impl DataStore,
+    encryption: &Encryption,
+    key: &EncryptionKey,
+    id: BlockId,
+    kind: LockKind,
+) -> crate::Result<()> {
+    let exclusive_lease =
+        kind == LockKind::Exclusive && store.conditional_writes() >= ConditionalWrites::Create;
+    let previous_lock = read_lock(store, encryption, key, id)?.ok_or(crate::Error::NotLocked)?;
+    let new_lock = LockInfo {
+        kind,
+        context: previous_lock.context.clone(),
+    };
+    write_lock(store, encryption, key, id, &new_lock)?;
+
+    // Like when acquiring a lock, we check for conflicting locks after writing ours to avoid a
+    // race condition.
+    if exclusive_lease && !acquire_lease(store, id)? {
+        write_lock(store, encryption, key, id, &previous_lock)?;
+        return Err(crate::Error::Locked);
+    }
+    if !is_sole_conflicting_lock(store, encryption, key, id, kind, exclusive_lease)? {
+        if exclusive_lease {
+            release_lease(store, id)?;
+        }
+        write_lock(store, encryption, key, id, &previous_lock)?;
+        return Err(crate::Error::Locked);
+    }
+
+    if kind != LockKind::Exclusive {
+        release_lease(store, id)?;
+    }
+
+    Ok(())
+}
+
 /// Attempt to release a lock on the given `store`.
 ///
-/// This attempts to release the lock with the given lock `id`.
+/// This attempts to release the lock with the given lock `id`, along with the lease on the
+/// exclusive lock if that lock holds it. Other locks are never released, even if the lock with the
+/// given `id` was already removed by a lock handler.
 ///
 /// # Errors
 /// - `Error::Store`: An error occurred with the data store.
 pub fn unlock_store(store: &mut impl DataStore, id: BlockId) -> crate::Result<()> {
+    // The lease is released first so that it never refers to a lock which was released normally.
+    release_lease(store, id)?;
     store
         .remove_block(BlockKey::Lock(id))
-        .map_err(crate::Error::Store)?;
-    Ok(())
+        .map_err(crate::Error::Store)
 }
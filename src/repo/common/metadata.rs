@@ -3,9 +3,12 @@ use std::collections::HashMap;
 use rmp_serde::from_read;
 use serde::{Deserialize, Serialize};
 
+use super::compression::BlockFormat;
 use super::config::RepoConfig;
 use super::encryption::{EncryptionKey, KeySalt};
 use super::handle::{Chunk, HandleIdTable};
+use super::retention::CommitInfo;
+use super::snapshot::Snapshot;
 use super::state::{ChunkInfo, InstanceId, InstanceInfo, PackIndex};
 use crate::store::{BlockId, BlockKey, DataStore, OpenStore};
 
@@ -23,6 +26,33 @@ pub struct Header {
 
     /// The table of object handle IDs.
     pub handle_table: HandleIdTable,
+
+    /// A map of snapshot names to the snapshots themselves.
+    ///
+    /// This defaults to empty so that headers written before snapshots existed can still be read.
+    #[serde(default)]
+    pub snapshots: HashMap<String, Snapshot>,
+
+    /// The history of retained commits, ordered from oldest to newest.
+    ///
+    /// The last commit in this list is the commit which this header belongs to.
+    #[serde(default)]
+    pub commits: Vec<CommitInfo>,
+
+    /// The ID of the header which this header was based on.
+    ///
+    /// This is `None` for the first header in a repository and for headers written before this
+    /// field existed.
+    #[serde(default)]
+    pub parent: Option<BlockId>,
+
+    /// The ID of the block which stores the progress of a re-encode which was interrupted before
+    /// it could be committed.
+    ///
+    /// This is `None` unless a re-encode is in progress. It is kept when the repository is
+    /// committed and discarded when the repository is cleaned.
+    #[serde(default)]
+    pub reencode: Option<BlockId>,
 }
 
 /// Metadata for a repository.
@@ -42,6 +72,12 @@ pub struct RepoMetadata {
 
     /// The ID of the chunk which stores the repository header.
     pub header_id: BlockId,
+
+    /// Whether each block is tagged with the method it was compressed with.
+    ///
+    /// This is `false` for repositories created before blocks were tagged.
+    #[serde(default)]
+    pub tagged_blocks: bool,
 }
 
 impl RepoMetadata {
@@ -67,6 +103,17 @@ impl RepoMetadata {
 }
 
 impl RepoMetadata {
+    /// The format of compressed blocks in this repository.
+    pub fn block_format(&self) -> BlockFormat {
+        if self.tagged_blocks {
+            BlockFormat::Tagged {
+                threshold: self.config.compression_threshold,
+            }
+        } else {
+            BlockFormat::Untagged
+        }
+    }
+
     /// Create a `RepoInfo` using the metadata in this struct.
     pub fn to_info(&self) -> RepoInfo {
         RepoInfo {
@@ -76,6 +123,19 @@ impl RepoMetadata {
     }
 }
 
+/// Read and deserialize the repository metadata from the super block of the `store`.
+///
+/// # Errors
+/// - `Error::Corrupt`: The super block is missing or could not be deserialized.
+/// - `Error::Store`: An error occurred with the data store.
+pub fn read_metadata(store: &mut impl DataStore) -> crate::Result<RepoMetadata> {
+    let serialized_metadata = store
+        .read_block(BlockKey::Super)
+        .map_err(crate::Error::Store)?
+        .ok_or(crate::Error::Corrupt)?;
+    from_read(serialized_metadata.as_slice()).map_err(|_| crate::Error::Corrupt)
+}
+
 /// Return information about the repository in the given `store` without opening it.
 pub fn peek_info_store(store: &mut impl DataStore) -> crate::Result<RepoInfo> {
     // Read and deserialize the metadata.
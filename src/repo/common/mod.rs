@@ -1,6 +1,11 @@
+#[cfg(feature = "async")]
+pub use self::async_object::AsyncObject;
+pub use self::chunk_cache::CacheStats;
 pub use self::chunking::Chunking;
 pub use self::commit::Commit;
 pub use self::compression::Compression;
+#[cfg(feature = "compression-zstd")]
+pub use self::compression::ZstdDictionary;
 pub use self::config::RepoConfig;
 pub use self::encryption::{Encryption, ResourceLimit};
 pub use self::handle::{ContentId, ObjectId, ObjectStats};
@@ -11,10 +16,15 @@ pub use self::object::{Object, ReadOnlyObject};
 pub use self::open_options::{OpenMode, OpenOptions, DEFAULT_INSTANCE};
 pub use self::open_repo::{OpenRepo, SwitchInstance, VersionId};
 pub use self::packing::Packing;
+pub use self::reencode::{ReencodeProgress, ReencodeReport};
 pub use self::repository::KeyRepo;
+pub use self::retention::{CleanReport, CommitId, CommitInfo, RetentionPolicy};
 pub use self::savepoint::{Restore, RestoreSavepoint, Savepoint};
+pub use self::snapshot::Snapshot;
 pub use self::state::InstanceId;
 
+mod async_object;
+mod chunk_cache;
 mod chunk_store;
 mod chunking;
 mod commit;
@@ -30,6 +40,11 @@ mod object_store;
 mod open_options;
 mod open_repo;
 mod packing;
+mod read_pool;
+mod reencode;
 mod repository;
+mod retention;
 mod savepoint;
+mod snapshot;
 mod state;
+mod write_pool;
@@ -197,6 +197,7 @@ impl Object {
     /// # Errors
     /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
     /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::ReadOnly`: The repository is read-only.
     /// - `Error::InvalidData`: Ciphertext verification failed.
     /// - `Error::Store`: An error occurred with the data store.
     /// - `Error::Io`: An I/O error occurred.
@@ -219,6 +220,7 @@ impl Object {
     /// - `Error::Serialize`: The given value could not be serialized.
     /// - `Error::TransactionInProgress`: A transaction is currently in progress for this object.
     /// - `Error::InvalidObject`: The object has been invalidated.
+    /// - `Error::ReadOnly`: The repository is read-only.
     /// - `Error::InvalidData`: Ciphertext verification failed.
     /// - `Error::Store`: An error occurred with the data store.
     /// - `Error::Io`: An I/O error occurred.
@@ -268,6 +270,10 @@ impl Object {
     ///
     /// [`ReadOnlyObject`]: crate::repo::ReadOnlyObject
     /// [`Commit::commit`]: crate::repo::Commit::commit
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "object_commit", level = "debug", skip_all)
+    )]
     pub fn commit(&mut self) -> crate::Result<()> {
         ObjectStore::new(&self.repo_state, &self.handle)?
             .writer_guard(&mut self.object_state)
@@ -282,6 +288,10 @@ impl Object {
 }
 
 impl Read for Object {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "object_read", level = "debug", skip_all, fields(len = buf.len()))
+    )]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         ObjectStore::new(&self.repo_state, &self.handle)?
             .reader_guard(&mut self.object_state)
@@ -300,6 +310,10 @@ impl Seek for Object {
 }
 
 impl Write for Object {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "object_write", level = "debug", skip_all, fields(len = buf.len()))
+    )]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         ObjectStore::new(&self.repo_state, &self.handle)?
             .writer_guard(&mut self.object_state)
@@ -307,6 +321,10 @@ impl Write for Object {
             .write(buf)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "object_flush", level = "debug", skip_all)
+    )]
     fn flush(&mut self) -> io::Result<()> {
         ObjectStore::new(&self.repo_state, &self.handle)?
             .writer_guard(&mut self.object_state)
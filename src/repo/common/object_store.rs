@@ -1,14 +1,18 @@
 use std::cmp::{min, Ordering};
+use std::collections::HashSet;
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 
 use rmp_serde::{from_read, to_vec};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use uuid::Uuid;
 
+use super::chunk_cache::CacheKey;
 use super::chunk_store::{ReadChunk, StoreReader, StoreWriter, WriteChunk};
-use super::handle::{chunk_hash, ContentId, Extent, ObjectHandle, ObjectStats};
-use super::state::{ExtentLocation, ObjectState, RepoState, SeekPosition};
+use super::handle::{chunk_hash, Chunk, ContentId, Extent, ObjectHandle, ObjectStats};
+use super::packing::Packing;
+use super::state::{ChunkInfo, ExtentLocation, ObjectState, RepoState, SeekPosition};
 use crate::repo::ObjectId;
 
 pub struct ObjectStore {
@@ -197,7 +201,7 @@ impl<'a> ObjectReader<'a> {
         let expected_chunks = self.handle.chunks().collect::<Vec<_>>();
 
         for chunk in expected_chunks {
-            match self.store_reader().read_chunk(chunk) {
+            match self.store_reader().without_cache().read_chunk(chunk) {
                 Ok(data) => {
                     if data.len() != chunk.size as usize || chunk_hash(&data) != chunk.hash {
                         return Ok(false);
@@ -256,13 +260,16 @@ impl<'a> ObjectReader<'a> {
             SeekPosition::Extent(location) => location,
         };
 
+        let sequential = self.object_state.prefetcher.advance(current_location.index);
+
         match current_location.extent {
             Extent::Chunk(chunk) => {
                 // If we're reading from a new chunk, read the contents of that chunk into the read
                 // buffer.
                 if Some(chunk) != self.object_state.buffered_chunk {
                     self.object_state.buffered_chunk = Some(chunk);
-                    self.object_state.read_buffer = self.store_reader().read_chunk(chunk)?;
+                    self.object_state.read_buffer =
+                        self.read_chunk_ahead(chunk, current_location.index, sequential)?;
                 }
 
                 let start = current_location.relative_position() as usize;
@@ -279,6 +286,62 @@ impl<'a> ObjectReader<'a> {
         }
     }
 
+    /// Return the contents of the given `chunk` at the extent with the given `index`.
+    ///
+    /// If the repository has a read pool and the object is being read `sequential`ly, this starts
+    /// reading the chunks which follow it in the background.
+    fn read_chunk_ahead(
+        &mut self,
+        chunk: Chunk,
+        index: usize,
+        sequential: bool,
+    ) -> crate::Result<Vec<u8>> {
+        let pool = match (
+            &self.repo_state.read_pool,
+            &self.repo_state.metadata.config.packing,
+        ) {
+            // Packs are cached by the `StoreState`, so packing repositories can't use the read pool.
+            (Some(pool), Packing::None) => pool,
+            _ => return self.store_reader().read_chunk(chunk),
+        };
+
+        if sequential {
+            let chunk_cache = self.repo_state.chunk_cache.lock().unwrap();
+            let next_chunks = self.handle.extents[index + 1..]
+                .iter()
+                .filter_map(|extent| match extent {
+                    Extent::Chunk(next_chunk) if *next_chunk != chunk => Some(*next_chunk),
+                    _ => None,
+                })
+                .take(pool.read_ahead())
+                // There's no need to read ahead chunks which are already cached.
+                .filter(|next_chunk| !chunk_cache.contains(&CacheKey::Chunk(*next_chunk)));
+            for next_chunk in next_chunks {
+                if let Some(chunk_info) = self.repo_state.chunks.get(&next_chunk) {
+                    self.object_state
+                        .prefetcher
+                        .request(pool, next_chunk, chunk_info.block_id);
+                }
+            }
+        } else {
+            // The chunks which were read ahead are unlikely to be read now.
+            self.object_state.prefetcher.reset();
+        }
+
+        match self.object_state.prefetcher.take(&chunk) {
+            Some(result) => {
+                let data = result?;
+                self.repo_state
+                    .chunk_cache
+                    .lock()
+                    .unwrap()
+                    .insert(CacheKey::Chunk(chunk), &data);
+                Ok(data)
+            }
+            None => self.store_reader().read_chunk(chunk),
+        }
+    }
+
     /// Deserialize a value serialized with `ObjectWriter::serialize`.
     pub fn deserialize<T: DeserializeOwned>(&mut self) -> crate::Result<T> {
         self.seek(SeekFrom::Start(0))?;
@@ -434,6 +497,10 @@ impl<'a> ObjectWriter<'a> {
 
     /// Set the length of the object.
     pub fn set_len(&mut self, size: u64) -> crate::Result<()> {
+        if self.repo_state.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+
         // Because this modifies the object, we need to start a new transaction.
         match self.object_state.transaction_lock {
             None => match self.repo_state.transactions.acquire_lock(self.handle.id) {
@@ -457,15 +524,102 @@ impl<'a> ObjectWriter<'a> {
     }
 
     /// Write chunks stored in the chunker to the repository.
+    ///
+    /// If the repository has a write pool, this returns once the chunks have been queued, and
+    /// `finish_writes` must be called before they can be referenced by the object.
     fn write_chunks(&mut self) -> crate::Result<()> {
+        let pool = match (
+            &self.repo_state.write_pool,
+            &self.repo_state.metadata.config.packing,
+        ) {
+            // Packs are written sequentially, so packing repositories can't use the write pool.
+            (Some(pool), Packing::None) => Arc::clone(pool),
+            _ => {
+                for chunk_data in self.object_state.chunker.chunks() {
+                    let handle_id = self.handle.id;
+                    let chunk = self.store_writer().write_chunk(&chunk_data, handle_id)?;
+                    self.object_state.new_chunks.push(chunk);
+                }
+                return Ok(());
+            }
+        };
+
         for chunk_data in self.object_state.chunker.chunks() {
-            let handle_id = self.handle.id;
-            let chunk = self.store_writer().write_chunk(&chunk_data, handle_id)?;
+            // Chunks are hashed here rather than on the write pool so that duplicate chunks are
+            // detected before they're written.
+            let chunk = Chunk::new(&chunk_data);
+
+            if let Some(chunk_info) = self.repo_state.chunks.get_mut(&chunk) {
+                chunk_info.references.insert(self.handle.id);
+            } else if !self.object_state.pending_writes.contains(&chunk) {
+                let block_id = Uuid::new_v4().into();
+                self.object_state
+                    .pending_writes
+                    .start(&pool, block_id, chunk, chunk_data);
+            }
             self.object_state.new_chunks.push(chunk);
+
+            // Limit how many chunks are buffered in memory waiting to be written.
+            while self.object_state.pending_writes.len() > pool.concurrency() * 2 {
+                self.finish_next_write()?;
+            }
         }
+
         Ok(())
     }
 
+    /// Wait for the next chunk being written by the write pool to finish.
+    ///
+    /// This returns `false` if there are no chunks being written.
+    fn finish_next_write(&mut self) -> crate::Result<bool> {
+        let (chunk, block_id, result) = match self.object_state.pending_writes.finish_next() {
+            Some(write) => write,
+            None => return Ok(false),
+        };
+
+        if let Err(error) = result {
+            // The chunk was never written, so the object can't reference it.
+            self.object_state
+                .new_chunks
+                .retain(|new_chunk| *new_chunk != chunk);
+            return Err(error);
+        }
+
+        // Another object may have written the same chunk in the meantime, in which case the block
+        // we wrote is unreferenced and will be removed the next time the repository is cleaned.
+        self.repo_state
+            .chunks
+            .entry(chunk)
+            .or_insert_with(|| ChunkInfo {
+                block_id,
+                references: HashSet::new(),
+            })
+            .references
+            .insert(self.handle.id);
+
+        Ok(true)
+    }
+
+    /// Wait for all the chunks being written by the write pool to finish.
+    ///
+    /// If any of the writes failed, this returns the first error once all the writes have finished.
+    fn finish_writes(&mut self) -> crate::Result<()> {
+        let mut first_error = None;
+        loop {
+            match self.finish_next_write() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
     /// Serialize the given `value` and write it to the object.
     pub fn serialize<T: Serialize>(&mut self, value: &T) -> crate::Result<()> {
         let serialized = to_vec(value).map_err(|_| crate::Error::Serialize)?;
@@ -517,9 +671,11 @@ impl<'a> ObjectWriter<'a> {
             }
         }
 
-        // Write all the remaining data in the chunker to the repository.
+        // Write all the remaining data in the chunker to the repository. We need to wait for all the
+        // chunks to be written before the object can reference them.
         self.object_state.chunker.flush()?;
         self.write_chunks()?;
+        self.finish_writes()?;
 
         // Find the index of the first extent which is being overwritten.
         let start_index = match &self.object_state.start_position {
@@ -564,6 +720,10 @@ impl<'a> ObjectWriter<'a> {
 // the user needs to explicitly call `commit` when they're done writing data.
 impl<'a> Write for ObjectWriter<'a> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.repo_state.read_only {
+            return Err(crate::Error::ReadOnly.into());
+        }
+
         // Attempt to acquire a transaction lock if one has not already been acquired.
         let first_write = match self.object_state.transaction_lock {
             None => match self.repo_state.transactions.acquire_lock(self.handle.id) {
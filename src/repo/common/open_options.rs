@@ -6,19 +6,59 @@ use rmp_serde::{from_read, to_vec};
 use secrecy::ExposeSecret;
 use uuid::{uuid, Uuid};
 
-use crate::store::{BlockKey, DataStore, OpenStore};
+use crate::store::{BlockId, BlockKey, DataStore, InstrumentedStore, OpenStore, StoreMetrics};
 
+use super::chunk_cache::ChunkCache;
+use super::chunk_store::BlockCodec;
 use super::chunking::Chunking;
 use super::compression::Compression;
 use super::config::RepoConfig;
 use super::encryption::{Encryption, EncryptionKey, KeySalt, ResourceLimit};
 use super::handle::HandleIdTable;
-use super::lock::{lock_store, LockTable};
-use super::metadata::{Header, RepoMetadata};
+use super::lock::{lock_store, unlock_store, write_lock, LockInfo, LockKind, LockTable};
+use super::metadata::{read_metadata, Header, RepoMetadata};
 use super::open_repo::OpenRepo;
 use super::packing::Packing;
+use super::read_pool::ReadPool;
 use super::repository::KeyRepo;
 use super::state::{InstanceId, RepoState};
+use super::write_pool::WritePool;
+
+/// Separate connections to the data store for the repository's worker threads.
+struct WorkerStores {
+    /// The connections used by the write pool.
+    write: Vec<Box<dyn DataStore>>,
+
+    /// The connections used by the read pool.
+    read: Vec<Box<dyn DataStore>>,
+}
+
+impl WorkerStores {
+    /// Start the write pool and read pool for a repository with the given `metadata`.
+    ///
+    /// A pool is `None` if there are no connections for it.
+    fn start(
+        self,
+        metadata: &RepoMetadata,
+        master_key: &EncryptionKey,
+    ) -> (Option<Arc<WritePool>>, Option<Arc<ReadPool>>) {
+        if self.write.is_empty() && self.read.is_empty() {
+            return (None, None);
+        }
+        let codec = Arc::new(RwLock::new(BlockCodec::new(metadata, master_key)));
+        let write_pool = if self.write.is_empty() {
+            None
+        } else {
+            Some(Arc::new(WritePool::new(self.write, Arc::clone(&codec))))
+        };
+        let read_pool = if self.read.is_empty() {
+            None
+        } else {
+            Some(Arc::new(ReadPool::new(self.read, codec)))
+        };
+        (write_pool, read_pool)
+    }
+}
 
 /// The default repository instance ID.
 ///
@@ -45,6 +85,19 @@ pub enum OpenMode {
 
     /// Create a new repository, failing if it already exists.
     CreateNew,
+
+    /// Open an existing repository as read-only, failing if it doesn't exist.
+    ///
+    /// A read-only repository does not take the exclusive lock on the data store, so any number of
+    /// read-only repositories can be open alongside one repository which can be modified. Instead,
+    /// it takes a shared lock which records which commit it is reading, and that lock is the only
+    /// thing it ever writes to the data store. Cleaning the repository does not remove data which
+    /// is still needed by a read-only repository holding a shared lock.
+    ///
+    /// A read-only repository continues to read the commit which was most recent when it was opened
+    /// even if the repository is committed by another client. Any attempt to modify it will fail
+    /// with `Error::ReadOnly`.
+    ReadOnly,
 }
 
 type BoxLockHandler<'a> = Box<dyn FnMut(&[u8]) -> bool + 'a>;
@@ -110,6 +163,10 @@ pub struct OpenOptions<'a> {
     instance: InstanceId,
     lock_context: &'a [u8],
     lock_handler: BoxLockHandler<'a>,
+    optimistic: bool,
+    write_concurrency: usize,
+    read_ahead: usize,
+    cache_size: u64,
 }
 
 impl<'a> Default for OpenOptions<'a> {
@@ -128,6 +185,10 @@ impl<'a> OpenOptions<'a> {
             instance: DEFAULT_INSTANCE,
             lock_context: &[],
             lock_handler: Box::new(|_| false),
+            optimistic: false,
+            write_concurrency: 1,
+            read_ahead: 0,
+            cache_size: 0,
         }
     }
 
@@ -181,6 +242,17 @@ impl<'a> OpenOptions<'a> {
         self
     }
 
+    /// Overwrite the compression threshold specified in [`RepoConfig::compression_threshold`].
+    ///
+    /// This is only applicable when creating a new repository. This is ignored when opening an
+    /// existing repository.
+    ///
+    /// [`RepoConfig::compression_threshold`]: crate::repo::RepoConfig::compression_threshold
+    pub fn compression_threshold(&mut self, threshold: u8) -> &mut Self {
+        self.config.compression_threshold = threshold;
+        self
+    }
+
     /// Overwrite the encryption method specified in [`RepoConfig::encryption`].
     ///
     /// This is only applicable when creating a new repository. This is ignored when opening an
@@ -236,6 +308,12 @@ impl<'a> OpenOptions<'a> {
     /// `handler` returns `false`, the existing lock will be respected and opening the repository
     /// will fail. If a lock handler is not specified, an existing lock will always be respected.
     ///
+    /// Read-only repositories hold shared locks, which don't prevent the repository from being
+    /// opened. However, a read-only repository which crashes never releases its lock, which keeps
+    /// the data it was reading from being reclaimed when the repository is cleaned. When opening a
+    /// repository which can be modified, `handler` is also passed the context value of each shared
+    /// lock, and if it returns `true`, that lock is removed.
+    ///
     /// Opening a repository can still fail due to lock conflicts even if `handler` returns `true`
     /// or is never called.
     ///
@@ -267,6 +345,116 @@ impl<'a> OpenOptions<'a> {
         self
     }
 
+    /// Use optimistic concurrency control instead of locking the repository exclusively.
+    ///
+    /// By default, a repository which can be modified holds an exclusive lock on the data store,
+    /// so only one client can write to it at a time. If `optimistic` is `true`, any number of
+    /// clients which also use optimistic concurrency control can open the repository and write to
+    /// it at the same time. A repository can't be opened with optimistic concurrency control while
+    /// another client holds an exclusive lock on it, and vice versa.
+    ///
+    /// When a repository using optimistic concurrency control is committed, the commit fails with
+    /// `Error::Conflict` if another client has committed changes which conflict with it since it
+    /// was opened or last committed. Changes don't conflict if they modify different instances or
+    /// different keys in the current instance, in which case they are merged automatically. See
+    /// [`Commit::commit`] for details.
+    ///
+    /// Detecting conflicting commits depends on the data store being able to conditionally write a
+    /// block atomically, which is the case when [`DataStore::conditional_writes`] returns
+    /// `ConditionalWrites::Full`. See [`DataStore::write_block_if`] for details. With other data
+    /// stores, the repository is temporarily locked exclusively each time it is committed, so
+    /// committing fails with `Error::Locked` while another client which can modify the repository
+    /// has it open.
+    ///
+    /// This is ignored when opening a repository with `OpenMode::ReadOnly`.
+    ///
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    /// [`DataStore::conditional_writes`]: crate::store::DataStore::conditional_writes
+    /// [`DataStore::write_block_if`]: crate::store::DataStore::write_block_if
+    pub fn optimistic(&mut self, optimistic: bool) -> &mut Self {
+        self.optimistic = optimistic;
+        self
+    }
+
+    /// The number of chunks to write to the data store concurrently.
+    ///
+    /// By default, an object's data is compressed, encrypted, and written to the data store one
+    /// chunk at a time on the thread which writes to the object. If this is greater than `1`, the
+    /// repository instead starts this many worker threads which compress, encrypt, and write chunks
+    /// in parallel, each with its own connection to the data store opened from the config passed to
+    /// [`open`]. This can significantly increase throughput on data stores with high latency.
+    ///
+    /// When using concurrent writes, writing to an [`Object`] may return before its data has been
+    /// written to the data store, and an error which occurs while writing that data may be returned
+    /// by a later write or by [`Object::commit`]. [`Object::commit`] waits for all the object's
+    /// data to be written before committing it, so it is just as atomic as it is otherwise.
+    ///
+    /// This is ignored when opening a repository with `OpenMode::ReadOnly` and has no effect if
+    /// packing is enabled.
+    ///
+    /// # Panics
+    /// - `concurrency` is `0`.
+    ///
+    /// [`open`]: crate::repo::OpenOptions::open
+    /// [`Object`]: crate::repo::Object
+    /// [`Object::commit`]: crate::repo::Object::commit
+    pub fn write_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        assert!(concurrency > 0, "The write concurrency must be at least 1.");
+        self.write_concurrency = concurrency;
+        self
+    }
+
+    /// The number of chunks to read ahead when reading an object sequentially.
+    ///
+    /// By default, an object's data is read from the data store, decrypted, and decompressed one
+    /// chunk at a time as it is needed. If this is greater than `0`, the repository starts this
+    /// many worker threads, each with its own connection to the data store opened from the config
+    /// passed to [`open`]. When an [`Object`] or [`ReadOnlyObject`] detects that it is being read
+    /// sequentially, these threads read and decode up to this many of the following chunks in the
+    /// background. This can significantly increase throughput when streaming data from data stores
+    /// with high latency.
+    ///
+    /// Seeking to a position which is not sequential discards any chunks which were read ahead.
+    ///
+    /// This has no effect if packing is enabled. The default value is `0`.
+    ///
+    /// [`open`]: crate::repo::OpenOptions::open
+    /// [`Object`]: crate::repo::Object
+    /// [`ReadOnlyObject`]: crate::repo::ReadOnlyObject
+    pub fn read_ahead(&mut self, chunks: usize) -> &mut Self {
+        self.read_ahead = chunks;
+        self
+    }
+
+    /// The maximum number of bytes of decoded data to cache in memory.
+    ///
+    /// By default, each [`Object`] reads, decrypts, and decompresses the chunks it reads
+    /// independently, so two objects which read the same deduplicated chunk each read it from the
+    /// data store. If this is greater than `0`, the repository keeps a cache of recently read
+    /// chunks, which is shared between all the objects in the repository. If packing is enabled,
+    /// recently read packs are cached as well. Once the cache holds this many bytes, the least
+    /// recently used data is evicted.
+    ///
+    /// You can use [`KeyRepo::cache_stats`] to see how effective the cache is.
+    ///
+    /// The default value is `0`, which disables the cache.
+    ///
+    /// [`Object`]: crate::repo::Object
+    /// [`KeyRepo::cache_stats`]: crate::repo::key::KeyRepo::cache_stats
+    pub fn cache_size(&mut self, bytes: u64) -> &mut Self {
+        self.cache_size = bytes;
+        self
+    }
+
+    /// The kind of lock to acquire when opening a repository which can be modified.
+    fn lock_kind(&self) -> LockKind {
+        if self.optimistic {
+            LockKind::Optimistic
+        } else {
+            LockKind::Exclusive
+        }
+    }
+
     /// Open the instance of the repository with the given `id`.
     ///
     /// Opening a repository without specifying an instance ID will always open the same default
@@ -282,7 +470,11 @@ impl<'a> OpenOptions<'a> {
     }
 
     /// Open the repository, failing if it doesn't exist.
-    fn open_repo<R: OpenRepo>(&mut self, mut store: impl DataStore + 'static) -> crate::Result<R> {
+    fn open_repo<R: OpenRepo>(
+        &mut self,
+        mut store: InstrumentedStore<impl DataStore + 'static>,
+        worker_stores: WorkerStores,
+    ) -> crate::Result<R> {
         // Read the repository version to see if this is a compatible repository.
         let serialized_version = store
             .read_block(BlockKey::Version)
@@ -295,12 +487,7 @@ impl<'a> OpenOptions<'a> {
         }
 
         // Read the repository metadata from the super block.
-        let serialized_metadata = store
-            .read_block(BlockKey::Super)
-            .map_err(crate::Error::Store)?
-            .ok_or(crate::Error::Corrupt)?;
-        let metadata: RepoMetadata =
-            from_read(serialized_metadata.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        let metadata = read_metadata(&mut store)?;
 
         let password = match self.password {
             Some(password) if metadata.config.encryption != Encryption::None => Some(password),
@@ -317,57 +504,64 @@ impl<'a> OpenOptions<'a> {
             None => EncryptionKey::new(Vec::new()),
         };
 
-        // Attempt to acquire a lock on the repository.
-        let lock_id = lock_store(
-            &mut store,
-            &metadata.config.encryption,
-            &master_key,
-            self.lock_context,
-            &mut self.lock_handler,
-        )?;
-
-        // We read the metadata again after acquiring a lock but before getting the header ID to
-        // avoid a race condition. We don't have to worry about decrypting the master encryption key
-        // again because the master encryption key should never change.
-        let serialized_metadata = store
-            .read_block(BlockKey::Super)
-            .map_err(crate::Error::Store)?
-            .ok_or(crate::Error::Corrupt)?;
-        let metadata: RepoMetadata =
-            from_read(serialized_metadata.as_slice()).map_err(|_| crate::Error::Corrupt)?;
-
-        // Read, decrypt, decompress, and deserialize the repository header.
-        let encrypted_header = store
-            .read_block(BlockKey::Header(metadata.header_id))
-            .map_err(crate::Error::Store)?
-            .ok_or(crate::Error::Corrupt)?;
-        let compressed_header = metadata
-            .config
-            .encryption
-            .decrypt(&encrypted_header, &master_key)
-            .map_err(|_| crate::Error::Corrupt)?;
-        let serialized_header = metadata
-            .config
-            .compression
-            .decompress(&compressed_header)
-            .map_err(|_| crate::Error::Corrupt)?;
-        let header = from_read(serialized_header.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        let read_only = self.mode == OpenMode::ReadOnly;
+
+        let (lock_id, metadata, header) = if read_only {
+            self.lock_shared(&mut store, &master_key)?
+        } else {
+            // Attempt to acquire a lock on the repository.
+            let lock_id = lock_store(
+                &mut store,
+                &metadata.config.encryption,
+                &master_key,
+                self.lock_kind(),
+                self.lock_context,
+                &mut self.lock_handler,
+            )?;
+
+            // We read the metadata again after acquiring a lock but before getting the header ID
+            // to avoid a race condition. We don't have to worry about decrypting the master
+            // encryption key again because the master encryption key should never change.
+            let metadata = read_metadata(&mut store)?;
+
+            // Read, decrypt, decompress, and deserialize the repository header.
+            let encrypted_header = store
+                .read_block(BlockKey::Header(metadata.header_id))
+                .map_err(crate::Error::Store)?
+                .ok_or(crate::Error::Corrupt)?;
+            let header = decode_header(&metadata, &master_key, &encrypted_header)?;
+
+            (lock_id, metadata, header)
+        };
 
         let Header {
             chunks,
             packs,
             instances,
             handle_table,
+            snapshots,
+            commits,
+            reencode,
+            ..
         } = header;
 
+        let (write_pool, read_pool) = worker_stores.start(&metadata, &master_key);
+        let store_metrics = store.metrics().clone();
+
         let state = Arc::new(RwLock::new(RepoState {
-            store: Mutex::new(Box::new(store)),
+            store: Arc::new(Mutex::new(Box::new(store))),
             metadata,
             chunks,
             packs,
             transactions: LockTable::new(),
             master_key,
-            lock_id,
+            lock_id: Some(lock_id),
+            read_only,
+            optimistic: self.optimistic && !read_only,
+            write_pool,
+            read_pool,
+            chunk_cache: Arc::new(Mutex::new(ChunkCache::new(self.cache_size))),
+            store_metrics,
         }));
 
         let repo: KeyRepo<R::Key> = KeyRepo {
@@ -376,17 +570,73 @@ impl<'a> OpenOptions<'a> {
             objects: HashMap::new(),
             instances,
             handle_table,
+            snapshots,
+            commits,
+            reencode,
             transaction_id: Arc::new(Uuid::new_v4()),
         };
 
         repo.change_instance(self.instance)
     }
 
+    /// Acquire a shared lock on the repository and read the most recent header.
+    ///
+    /// This returns the ID of the shared lock along with the repository metadata and header.
+    fn lock_shared(
+        &self,
+        store: &mut impl DataStore,
+        master_key: &EncryptionKey,
+    ) -> crate::Result<(BlockId, RepoMetadata, Header)> {
+        let lock_id = Uuid::new_v4().into();
+        let mut previous_header_id = None;
+
+        loop {
+            let metadata = read_metadata(store)?;
+            let lock = LockInfo {
+                kind: LockKind::Shared {
+                    header_id: metadata.header_id,
+                },
+                context: self.lock_context.to_vec(),
+            };
+            write_lock(
+                store,
+                &metadata.config.encryption,
+                master_key,
+                lock_id,
+                &lock,
+            )?;
+
+            let encrypted_header = store
+                .read_block(BlockKey::Header(metadata.header_id))
+                .map_err(crate::Error::Store)?;
+
+            match encrypted_header {
+                Some(encrypted_header) => {
+                    let header = decode_header(&metadata, master_key, &encrypted_header)?;
+                    return Ok((lock_id, metadata, header));
+                }
+                // The header may have been removed by a client cleaning the repository before our
+                // lock was written, in which case a newer header has been written since we read
+                // the metadata. If the header ID hasn't changed, the repository is corrupt.
+                None if previous_header_id == Some(metadata.header_id) => {
+                    unlock_store(store, lock_id)?;
+                    return Err(crate::Error::Corrupt);
+                }
+                None => previous_header_id = Some(metadata.header_id),
+            }
+        }
+    }
+
     /// Create a new repository, failing if one already exists.
     fn create_repo<R: OpenRepo>(
         &mut self,
-        mut store: impl DataStore + 'static,
+        mut store: InstrumentedStore<impl DataStore + 'static>,
+        worker_stores: WorkerStores,
     ) -> crate::Result<R> {
+        if self.config.compression_threshold > 100 || !self.config.chunking.is_valid() {
+            return Err(crate::Error::InvalidConfig);
+        }
+
         let password = match self.password {
             Some(password) if self.config.encryption != Encryption::None => Some(password),
             // Return an error if a password was required but not provided.
@@ -416,6 +666,7 @@ impl<'a> OpenOptions<'a> {
             &mut store,
             &self.config.encryption,
             &master_key,
+            self.lock_kind(),
             self.lock_context,
             &mut self.lock_handler,
         )?;
@@ -448,30 +699,37 @@ impl<'a> OpenOptions<'a> {
             packs: HashMap::new(),
             instances: HashMap::new(),
             handle_table: HandleIdTable::new(),
+            snapshots: HashMap::new(),
+            commits: Vec::new(),
+            parent: None,
+            reencode: None,
+        };
+
+        // Create the repository metadata with the header block references.
+        let metadata = RepoMetadata {
+            id: Uuid::new_v4().into(),
+            config: self.config.clone(),
+            master_key: encrypted_master_key,
+            salt,
+            header_id: Uuid::new_v4().into(),
+            tagged_blocks: true,
         };
 
         // Serialize, encode, and write the header to the data store.
         let serialized_header =
             to_vec(&header).expect("Could not serialize the repository header.");
-        let compressed_header = self.config.compression.compress(&serialized_header)?;
+        let compressed_header = self
+            .config
+            .compression
+            .compress_block(&serialized_header, metadata.block_format())?;
         let encrypted_header = self
             .config
             .encryption
             .encrypt(&compressed_header, &master_key);
-        let header_id = Uuid::new_v4().into();
         store
-            .write_block(BlockKey::Header(header_id), &encrypted_header)
+            .write_block(BlockKey::Header(metadata.header_id), &encrypted_header)
             .map_err(crate::Error::Store)?;
 
-        // Create the repository metadata with the header block references.
-        let metadata = RepoMetadata {
-            id: Uuid::new_v4().into(),
-            config: self.config.clone(),
-            master_key: encrypted_master_key,
-            salt,
-            header_id,
-        };
-
         // Write the repository metadata.
         let serialized_metadata = to_vec(&metadata).expect("Could not serialize metadata.");
         store
@@ -489,16 +747,29 @@ impl<'a> OpenOptions<'a> {
             packs,
             instances,
             handle_table,
+            snapshots,
+            commits,
+            reencode,
+            ..
         } = header;
 
+        let (write_pool, read_pool) = worker_stores.start(&metadata, &master_key);
+        let store_metrics = store.metrics().clone();
+
         let state = Arc::new(RwLock::new(RepoState {
-            store: Mutex::new(Box::new(store)),
+            store: Arc::new(Mutex::new(Box::new(store))),
             metadata,
             chunks,
             packs,
             transactions: LockTable::new(),
             master_key,
-            lock_id,
+            lock_id: Some(lock_id),
+            read_only: false,
+            optimistic: self.optimistic,
+            write_pool,
+            read_pool,
+            chunk_cache: Arc::new(Mutex::new(ChunkCache::new(self.cache_size))),
+            store_metrics,
         }));
 
         let repo: KeyRepo<R::Key> = KeyRepo {
@@ -507,6 +778,9 @@ impl<'a> OpenOptions<'a> {
             objects: HashMap::new(),
             instances,
             handle_table,
+            snapshots,
+            commits,
+            reencode,
             transaction_id: Arc::new(Uuid::new_v4()),
         };
 
@@ -516,14 +790,16 @@ impl<'a> OpenOptions<'a> {
     /// Open or create the repository.
     ///
     /// # Errors
-    /// - `Error::NotFound`: There is no repository in the data store and `OpenMode::Open` was
-    /// specified.
+    /// - `Error::NotFound`: There is no repository in the data store and `OpenMode::Open` or
+    /// `OpenMode::ReadOnly` was specified.
+    /// - `Error::NotFound`: The instance does not exist and `OpenMode::ReadOnly` was specified.
     /// - `Error::AlreadyExists`: A repository already exists in the data store and
     /// `OpenMode::CreateNew` was specified.
     /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
     /// - `Error::Locked`: The repository is locked.
     /// - `Error::Password`: The password provided is invalid.
     /// - `Error::Password`: A password was required but not provided.
+    /// - `Error::InvalidConfig`: A new repository would be created and the config is invalid.
     /// - `Error::Deserialize`: Could not deserialize some data in the repository.
     /// - `Error::UnsupportedRepo`: The repository is an unsupported format. This can happen if the
     /// serialized data format changed or if the data store already contains a different type of
@@ -539,22 +815,43 @@ impl<'a> OpenOptions<'a> {
         R: OpenRepo,
         C: OpenStore,
     {
-        let mut store = config.open()?;
+        // Every connection to the data store collects metrics in the same place.
+        let metrics = StoreMetrics::new();
+        let mut store = InstrumentedStore::new(config.open()?, metrics.clone());
+
+        // Open a separate connection to the data store for each worker in the write pool and the
+        // read pool.
+        let open_stores = |count: usize| {
+            (0..count)
+                .map(|_| {
+                    let store = InstrumentedStore::new(config.open()?, metrics.clone());
+                    Ok(Box::new(store) as Box<dyn DataStore>)
+                })
+                .collect::<crate::Result<Vec<_>>>()
+        };
+        let worker_stores = WorkerStores {
+            write: if self.write_concurrency > 1 && self.mode != OpenMode::ReadOnly {
+                open_stores(self.write_concurrency)?
+            } else {
+                Vec::new()
+            },
+            read: open_stores(self.read_ahead)?,
+        };
 
         match self.mode {
-            OpenMode::Open => self.open_repo(store),
+            OpenMode::Open | OpenMode::ReadOnly => self.open_repo(store, worker_stores),
             OpenMode::Create => {
                 if store
                     .read_block(BlockKey::Version)
                     .map_err(crate::Error::Store)?
                     .is_some()
                 {
-                    self.open_repo(store)
+                    self.open_repo(store, worker_stores)
                 } else {
-                    self.create_repo(store)
+                    self.create_repo(store, worker_stores)
                 }
             }
-            OpenMode::CreateNew => self.create_repo(store),
+            OpenMode::CreateNew => self.create_repo(store, worker_stores),
         }
     }
 }
@@ -570,3 +867,22 @@ impl<'a> Debug for OpenOptions<'a> {
             .finish_non_exhaustive()
     }
 }
+
+/// Decrypt, decompress, and deserialize the given `encrypted_header`.
+fn decode_header(
+    metadata: &RepoMetadata,
+    master_key: &EncryptionKey,
+    encrypted_header: &[u8],
+) -> crate::Result<Header> {
+    let compressed_header = metadata
+        .config
+        .encryption
+        .decrypt(encrypted_header, master_key)
+        .map_err(|_| crate::Error::Corrupt)?;
+    let serialized_header = metadata
+        .config
+        .compression
+        .decompress_block(&compressed_header, metadata.block_format())
+        .map_err(|_| crate::Error::Corrupt)?;
+    from_read(serialized_header.as_slice()).map_err(|_| crate::Error::Corrupt)
+}
@@ -0,0 +1,238 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Debug, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+
+use super::chunk_store::{BlockCodec, EncodeBlock};
+use super::handle::Chunk;
+use crate::store::{BlockId, BlockKey, DataStore};
+
+/// The result of reading a chunk on a `ReadPool`.
+///
+/// This contains the generation of the `Prefetcher` which requested the chunk, the chunk itself,
+/// and the decoded contents of the chunk.
+type ReadResult = (u64, Chunk, crate::Result<Vec<u8>>);
+
+/// A request to read and decode a chunk on a `ReadPool`.
+struct ReadJob {
+    /// The generation of the `Prefetcher` which requested the chunk.
+    generation: u64,
+
+    /// The chunk to read.
+    chunk: Chunk,
+
+    /// The ID of the block which stores the chunk.
+    block_id: BlockId,
+
+    /// The channel to send the result of the read to.
+    results: Sender<ReadResult>,
+}
+
+/// A pool of worker threads which read blocks from a data store and decode them concurrently.
+///
+/// Each worker thread has its own connection to the data store so that several reads can be in
+/// flight at once.
+pub struct ReadPool {
+    /// The channel used to send jobs to the worker threads.
+    ///
+    /// This is `None` once the pool is being shut down.
+    jobs: Option<Mutex<Sender<ReadJob>>>,
+
+    /// The worker threads.
+    workers: Vec<JoinHandle<()>>,
+
+    /// The codec the worker threads use to decode blocks.
+    codec: Arc<RwLock<BlockCodec>>,
+}
+
+impl Debug for ReadPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadPool")
+            .field("workers", &self.workers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReadPool {
+    /// Start a new pool with one worker thread for each of the given `stores`.
+    ///
+    /// Blocks are decoded using the given `codec`.
+    pub fn new(stores: Vec<Box<dyn DataStore>>, codec: Arc<RwLock<BlockCodec>>) -> Self {
+        let (job_sender, job_receiver) = channel::<ReadJob>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = stores
+            .into_iter()
+            .map(|mut store| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let codec = Arc::clone(&codec);
+                thread::spawn(move || loop {
+                    // The lock is released before the job is run so other workers can receive jobs.
+                    let job = match job_receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+                    // Panics are caught so the reader isn't left waiting for a result forever.
+                    let result = catch_unwind(AssertUnwindSafe(|| {
+                        let encoded_data = store
+                            .read_block(BlockKey::Data(job.block_id))
+                            .map_err(crate::Error::Store)?
+                            .ok_or(crate::Error::InvalidData)?;
+                        codec.read().unwrap().decode_data(&encoded_data)
+                    }))
+                    .unwrap_or_else(|_| {
+                        Err(crate::Error::Store(crate::store::Error::msg(
+                            "A worker thread panicked while reading a block.",
+                        )))
+                    });
+                    // The reader may have been dropped, in which case the result is discarded.
+                    job.results.send((job.generation, job.chunk, result)).ok();
+                })
+            })
+            .collect();
+
+        Self {
+            jobs: Some(Mutex::new(job_sender)),
+            workers,
+            codec,
+        }
+    }
+
+    /// The number of chunks to read ahead of the current one.
+    pub fn read_ahead(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Replace the codec used to decode blocks.
+    ///
+    /// Blocks which are already being read may still be decoded with the old codec.
+    pub fn set_codec(&self, codec: BlockCodec) {
+        *self.codec.write().unwrap() = codec;
+    }
+}
+
+impl Drop for ReadPool {
+    fn drop(&mut self) {
+        // Closing the channel causes the workers to exit once they finish any remaining jobs.
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+/// The chunks which an object is reading ahead of its current position on a `ReadPool`.
+#[derive(Debug)]
+pub struct Prefetcher {
+    /// The chunks which have been requested but not yet received.
+    requested: HashSet<Chunk>,
+
+    /// The chunks which have been received but not yet read.
+    ready: HashMap<Chunk, crate::Result<Vec<u8>>>,
+
+    /// A counter which is incremented each time the prefetched chunks are discarded.
+    ///
+    /// This is used to ignore the results of reads which were requested before the prefetched
+    /// chunks were discarded.
+    generation: u64,
+
+    /// The index of the extent which was most recently read from.
+    last_index: Option<usize>,
+
+    /// The channel used by the workers to send back the results of reads.
+    sender: Mutex<Sender<ReadResult>>,
+
+    /// The channel used to receive the results of reads.
+    receiver: Mutex<Receiver<ReadResult>>,
+}
+
+impl Prefetcher {
+    /// Create a new empty `Prefetcher`.
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            requested: HashSet::new(),
+            ready: HashMap::new(),
+            generation: 0,
+            last_index: None,
+            sender: Mutex::new(sender),
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    /// Record that the extent at the given `index` is being read from.
+    ///
+    /// This returns whether the extent is being read sequentially, which is the case if it is the
+    /// first extent or it follows the extent which was previously read from.
+    pub fn advance(&mut self, index: usize) -> bool {
+        let sequential = match self.last_index {
+            None => index == 0,
+            Some(last_index) => index == last_index || index == last_index + 1,
+        };
+        self.last_index = Some(index);
+        sequential
+    }
+
+    /// Return whether the given `chunk` has been requested.
+    pub fn contains(&self, chunk: &Chunk) -> bool {
+        self.requested.contains(chunk) || self.ready.contains_key(chunk)
+    }
+
+    /// Start reading the given `chunk` from the block with the given `block_id`.
+    pub fn request(&mut self, pool: &ReadPool, chunk: Chunk, block_id: BlockId) {
+        if self.contains(&chunk) {
+            return;
+        }
+        let job = ReadJob {
+            generation: self.generation,
+            chunk,
+            block_id,
+            results: self.sender.lock().unwrap().clone(),
+        };
+        pool.jobs
+            .as_ref()
+            .expect("The read pool has been shut down.")
+            .lock()
+            .unwrap()
+            .send(job)
+            .expect("The read pool's workers have exited.");
+        self.requested.insert(chunk);
+    }
+
+    /// Return the contents of the given `chunk` if it has been requested.
+    ///
+    /// If the chunk has been requested but not yet received, this waits for it. This returns
+    /// `None` if the chunk has not been requested.
+    pub fn take(&mut self, chunk: &Chunk) -> Option<crate::Result<Vec<u8>>> {
+        if !self.requested.contains(chunk) {
+            return self.ready.remove(chunk);
+        }
+
+        let receiver = self.receiver.lock().unwrap();
+        loop {
+            let (generation, received_chunk, result) = receiver
+                .recv()
+                .expect("The read pool's workers have exited.");
+
+            // This read was requested before the prefetched chunks were discarded.
+            if generation != self.generation {
+                continue;
+            }
+
+            self.requested.remove(&received_chunk);
+            if received_chunk == *chunk {
+                return Some(result);
+            }
+            self.ready.insert(received_chunk, result);
+        }
+    }
+
+    /// Discard all the chunks which have been requested.
+    pub fn reset(&mut self) {
+        self.generation += 1;
+        self.requested.clear();
+        self.ready.clear();
+    }
+}
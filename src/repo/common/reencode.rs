@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::BlockId;
+
+use super::config::RepoConfig;
+use super::encryption::KeySalt;
+use super::handle::{Chunk, Extent};
+use super::metadata::RepoStats;
+use super::state::PackIndex;
+
+/// The progress of a re-encode which has not been committed yet.
+///
+/// This is stored in its own block, which is referenced by the repository header, so that a
+/// re-encode which was interrupted can be resumed without re-encoding the data which was already
+/// written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReencodeJournal {
+    /// The configuration the repository is being re-encoded with.
+    pub config: RepoConfig,
+
+    /// The new master encryption key encrypted with the user's password.
+    ///
+    /// This is the current master key if the encryption method is not being changed.
+    pub master_key: Vec<u8>,
+
+    /// The salt used to derive a key from the user's password for the new master key.
+    pub salt: KeySalt,
+
+    /// A map of chunks to the IDs of the blocks they have been re-encoded in.
+    pub chunks: HashMap<Chunk, BlockId>,
+
+    /// A map of the IDs of blocks which have been re-encoded to their locations in packs.
+    pub packs: HashMap<BlockId, Vec<PackIndex>>,
+
+    /// A map of the extents of objects which have been re-chunked to their new extents.
+    ///
+    /// This is empty if the chunking method is not being changed.
+    pub extents: HashMap<Vec<Extent>, Vec<Extent>>,
+}
+
+/// The progress of re-encoding a repository.
+///
+/// This is passed to the callback given to [`KeyRepo::reencode`].
+///
+/// [`KeyRepo::reencode`]: crate::repo::key::KeyRepo::reencode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReencodeProgress {
+    pub(super) processed_bytes: u64,
+    pub(super) total_bytes: u64,
+}
+
+impl ReencodeProgress {
+    /// The number of bytes of data which have been re-encoded so far.
+    ///
+    /// This includes data which was re-encoded before the re-encode was interrupted and resumed.
+    pub fn processed_bytes(&self) -> u64 {
+        self.processed_bytes
+    }
+
+    /// The total number of bytes of data which need to be re-encoded.
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+}
+
+/// A report of the changes made by re-encoding a repository.
+///
+/// This is returned by [`KeyRepo::reencode`].
+///
+/// [`KeyRepo::reencode`]: crate::repo::key::KeyRepo::reencode
+#[derive(Debug, Clone)]
+pub struct ReencodeReport {
+    pub(super) before: RepoStats,
+    pub(super) after: RepoStats,
+    pub(super) resumed_bytes: u64,
+}
+
+impl ReencodeReport {
+    /// Statistics about the repository before it was re-encoded.
+    pub fn before(&self) -> &RepoStats {
+        &self.before
+    }
+
+    /// Statistics about the repository after it was re-encoded.
+    pub fn after(&self) -> &RepoStats {
+        &self.after
+    }
+
+    /// The number of bytes of data which were re-encoded by an earlier call which was interrupted.
+    ///
+    /// This data did not need to be re-encoded again.
+    pub fn resumed_bytes(&self) -> u64 {
+        self.resumed_bytes
+    }
+}
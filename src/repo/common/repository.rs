@@ -1,32 +1,46 @@
 use std::borrow::Borrow;
+use std::cmp::max;
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+use std::io::Write;
 use std::mem;
 use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
 use rmp_serde::{from_read, to_vec};
+use rmpv::Value;
 use secrecy::ExposeSecret;
 use static_assertions::assert_impl_all;
 use uuid::{uuid, Uuid};
 
-use crate::store::{BlockKey, BlockType, DataStore};
+use crate::store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore, StoreMetrics};
 
+use super::chunk_cache::CacheStats;
 use super::chunk_store::{
-    EncodeBlock, ReadBlock, ReadChunk, StoreReader, StoreState, StoreWriter, WriteBlock,
+    BlockCodec, EncodeBlock, ReadBlock, ReadChunk, StoreReader, StoreState, StoreWriter, WriteBlock,
 };
+use super::chunking::IncrementalChunker;
 use super::commit::Commit;
+use super::config::RepoConfig;
 use super::encryption::{Encryption, EncryptionKey, KeySalt, ResourceLimit};
-use super::handle::{chunk_hash, HandleIdTable, ObjectHandle};
+use super::handle::{chunk_hash, Chunk, Extent, HandleIdTable, ObjectHandle};
 use super::key::{Key, Keys};
-use super::lock::{unlock_store, Unlock};
-use super::metadata::{Header, RepoInfo, RepoStats};
+use super::lock::{
+    change_lock_kind, list_locks, read_lock, shared_lock_headers, unlock_store, write_lock,
+    LockInfo, LockKind, LockTable, Unlock,
+};
+use super::metadata::{read_metadata, Header, RepoInfo, RepoMetadata, RepoStats};
 use super::object::Object;
 use super::object_store::{ObjectReader, ObjectWriter};
 use super::open_repo::OpenRepo;
 use super::open_repo::VersionId;
 use super::packing::Packing;
+use super::reencode::{ReencodeJournal, ReencodeProgress, ReencodeReport};
+use super::retention::{CleanReport, CommitId, CommitInfo, RetentionPolicy};
 use super::savepoint::{KeyRestore, RestoreSavepoint, Savepoint};
-use super::state::{InstanceId, InstanceInfo, ObjectState, RepoState};
+use super::snapshot::Snapshot;
+use super::state::{ChunkInfo, InstanceId, InstanceInfo, ObjectState, PackIndex, RepoState};
 
 /// An object store which maps keys to seekable binary blobs.
 ///
@@ -51,6 +65,15 @@ pub struct KeyRepo<K: Key> {
     /// storing it.
     pub(super) handle_table: HandleIdTable,
 
+    /// A map of snapshot names to the snapshots themselves.
+    pub(super) snapshots: HashMap<String, Snapshot>,
+
+    /// The history of retained commits, ordered from oldest to newest.
+    pub(super) commits: Vec<CommitInfo>,
+
+    /// The ID of the block which stores the progress of an interrupted re-encode.
+    pub(super) reencode: Option<BlockId>,
+
     /// The unique ID for the current transaction.
     ///
     /// This ID changes each time the repository is opened or committed. It is used to invalidate
@@ -207,9 +230,19 @@ impl<K: Key> KeyRepo<K> {
         true
     }
 
+    /// Return whether this repository is read-only.
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.state.read().unwrap().read_only
+    }
+
     /// Write the map of objects for the current instance to the data store.
+    ///
+    /// If the repository is read-only, the object map can't have changed, so this does nothing.
     pub(super) fn write_object_map(&mut self) -> crate::Result<()> {
         let mut state = self.state.write().unwrap();
+        if state.read_only {
+            return Ok(());
+        }
 
         let handle = &mut self
             .instances
@@ -257,6 +290,11 @@ impl<K: Key> KeyRepo<K> {
     ) -> crate::Result<R> {
         let is_new_instance = !self.instances.contains_key(&instance_id);
 
+        // A read-only repository can't create a new instance.
+        if is_new_instance && self.is_read_only() {
+            return Err(crate::Error::NotFound);
+        }
+
         let new_objects = if is_new_instance {
             // Create the object handle for the object which will store the object map for the new
             // instance.
@@ -302,6 +340,9 @@ impl<K: Key> KeyRepo<K> {
             objects: new_objects,
             instances: self.instances,
             handle_table: self.handle_table,
+            snapshots: self.snapshots,
+            commits: self.commits,
+            reencode: self.reencode,
             transaction_id: self.transaction_id,
         };
 
@@ -313,30 +354,29 @@ impl<K: Key> KeyRepo<K> {
     }
 
     /// Atomically encode and write the given serialized `header` to the data store.
-    fn write_serialized_header(&mut self, serialized_header: &[u8]) -> crate::Result<()> {
+    ///
+    /// The header is written to a new block with the given `header_id`.
+    ///
+    /// # Errors
+    /// - `Error::Conflict`: Another client has written a header since the current one.
+    fn write_serialized_header(
+        &mut self,
+        header_id: BlockId,
+        serialized_header: &[u8],
+    ) -> crate::Result<()> {
         let mut state = self.state.write().unwrap();
-        // Encode the serialized header.
-        let encoded_header = state.encode_data(serialized_header)?;
+        if state.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
 
         // Write the new header to a new block.
-        let header_id = Uuid::new_v4().into();
-        state
-            .store
-            .lock()
-            .unwrap()
-            .write_block(BlockKey::Header(header_id), encoded_header.as_slice())
-            .map_err(crate::Error::Store)?;
-        state.metadata.header_id = header_id;
+        write_header(&state, header_id, serialized_header)?;
 
-        // Atomically write the new repository metadata containing the new header ID.
-        let serialized_metadata =
-            to_vec(&state.metadata).expect("Could not serialize repository metadata.");
-        state
-            .store
-            .lock()
-            .unwrap()
-            .write_block(BlockKey::Super, &serialized_metadata)
-            .map_err(crate::Error::Store)?;
+        let mut new_metadata = state.metadata.clone();
+        new_metadata.header_id = header_id;
+        replace_metadata(&state, &new_metadata)?;
+
+        state.metadata = new_metadata;
         Ok(())
     }
 
@@ -348,6 +388,10 @@ impl<K: Key> KeyRepo<K> {
             packs: state.packs.clone(),
             instances: self.instances.clone(),
             handle_table: self.handle_table.clone(),
+            snapshots: self.snapshots.clone(),
+            commits: self.commits.clone(),
+            parent: Some(state.metadata.header_id),
+            reencode: self.reencode,
         }
     }
 
@@ -364,6 +408,10 @@ impl<K: Key> KeyRepo<K> {
             packs: std::mem::take(&mut state.packs),
             instances: std::mem::take(&mut self.instances),
             handle_table: std::mem::take(&mut self.handle_table),
+            snapshots: std::mem::take(&mut self.snapshots),
+            commits: std::mem::take(&mut self.commits),
+            parent: Some(state.metadata.header_id),
+            reencode: self.reencode,
         };
 
         // Serialize the header so we can write it to the data store.
@@ -376,29 +424,43 @@ impl<K: Key> KeyRepo<K> {
             packs,
             instances,
             handle_table,
+            snapshots,
+            commits,
+            ..
         } = header;
         state.chunks = chunks;
         state.packs = packs;
         self.instances = instances;
         self.handle_table = handle_table;
+        self.snapshots = snapshots;
+        self.commits = commits;
 
         serialized_header
     }
 
     /// Replace the repository header with `header` and return the old one.
+    ///
+    /// The progress of an interrupted re-encode is not part of any transaction, so it is kept.
     fn replace_header(&mut self, header: Header) -> Header {
         let mut state = self.state.write().unwrap();
         let old_chunks = mem::replace(&mut state.chunks, header.chunks);
         let old_packs = mem::replace(&mut state.packs, header.packs);
         let old_instances = mem::replace(&mut self.instances, header.instances);
         let old_handle_table = mem::replace(&mut self.handle_table, header.handle_table);
+        let old_snapshots = mem::replace(&mut self.snapshots, header.snapshots);
+        let old_commits = mem::replace(&mut self.commits, header.commits);
         Header {
             chunks: old_chunks,
             packs: old_packs,
             instances: old_instances,
             handle_table: old_handle_table,
+            snapshots: old_snapshots,
+            commits: old_commits,
+            parent: Some(state.metadata.header_id),
+            reencode: self.reencode,
         }
     }
+
     /// Atomically restore the repository's state from the given `header`.
     ///
     /// This restores the state of the repository using the data in the given `header` and then
@@ -437,6 +499,7 @@ impl<K: Key> KeyRepo<K> {
     /// - `Error::Io`: An I/O error occurred.
     ///
     /// [`Object::verify`]: crate::repo::Object::verify
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub fn verify(&self) -> crate::Result<HashSet<&K>> {
         let state = self.state.read().unwrap();
 
@@ -445,7 +508,7 @@ impl<K: Key> KeyRepo<K> {
 
         // Get the set of hashes of chunks which are corrupt.
         let mut store_state = StoreState::new();
-        let mut store_reader = StoreReader::new(&state, &mut store_state);
+        let mut store_reader = StoreReader::new(&state, &mut store_state).without_cache();
         for chunk in expected_chunks {
             match store_reader.read_chunk(chunk) {
                 Ok(data) => {
@@ -596,208 +659,653 @@ impl<K: Key> KeyRepo<K> {
         }
     }
 
+    /// Return statistics about the repository's chunk cache.
+    ///
+    /// The returned `CacheStats` represents the cache at the time this method was called. The cache
+    /// is shared with any snapshots of this repository, so their reads are included.
+    ///
+    /// The size of the cache is configured with [`OpenOptions::cache_size`].
+    ///
+    /// [`OpenOptions::cache_size`]: crate::repo::OpenOptions::cache_size
+    pub fn cache_stats(&self) -> CacheStats {
+        self.state
+            .read()
+            .unwrap()
+            .chunk_cache
+            .lock()
+            .unwrap()
+            .stats()
+    }
+
+    /// Return the metrics collected for the repository's data store.
+    ///
+    /// Every call the repository makes to its data store is counted, including calls made by the
+    /// worker threads configured with [`OpenOptions::write_concurrency`] and
+    /// [`OpenOptions::read_ahead`]. The returned handle is shared with any snapshots of this
+    /// repository, and it continues to be updated as the repository is used.
+    ///
+    /// [`OpenOptions::write_concurrency`]: crate::repo::OpenOptions::write_concurrency
+    /// [`OpenOptions::read_ahead`]: crate::repo::OpenOptions::read_ahead
+    pub fn store_metrics(&self) -> StoreMetrics {
+        self.state.read().unwrap().store_metrics.clone()
+    }
+
     /// Return information about the repository.
     pub fn info(&self) -> RepoInfo {
         self.state.read().unwrap().metadata.to_info()
     }
-}
 
-impl<K: Key> RestoreSavepoint for KeyRepo<K> {
-    type Restore = KeyRestore<K>;
+    /// Create a new snapshot of the repository with the given `name` and return it.
+    ///
+    /// The snapshot captures the current state of every instance of the repository, including
+    /// changes which have not been committed. The snapshot itself is not persisted to the data store
+    /// until [`Commit::commit`] is called, and it is discarded if changes are rolled back.
+    ///
+    /// Unlike a [`Savepoint`], a snapshot is not invalidated when changes are committed. Data
+    /// referenced by a snapshot is not removed by [`Commit::clean`] until the snapshot is removed
+    /// with [`remove_snapshot`].
+    ///
+    /// # Errors
+    /// - `Error::AlreadyExists`: There is already a snapshot with the given `name`.
+    /// - `Error::ReadOnly`: The repository is read-only.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    /// [`Savepoint`]: crate::repo::Savepoint
+    /// [`remove_snapshot`]: crate::repo::key::KeyRepo::remove_snapshot
+    pub fn create_snapshot(&mut self, name: &str) -> crate::Result<Snapshot> {
+        if self.is_read_only() {
+            return Err(crate::Error::ReadOnly);
+        }
 
-    fn savepoint(&mut self) -> crate::Result<Savepoint> {
+        if self.snapshots.contains_key(name) {
+            return Err(crate::Error::AlreadyExists);
+        }
+
+        // Write the map of objects for the current instance so that it's included in the snapshot.
         self.write_object_map()?;
 
-        Ok(Savepoint {
-            header: Arc::new(self.clone_header()),
-            transaction_id: Arc::downgrade(&self.transaction_id),
-        })
+        // Snapshots don't store other snapshots, the commit history, or the progress of a
+        // re-encode, so we temporarily remove them while we serialize the header.
+        let snapshots = mem::take(&mut self.snapshots);
+        let commits = mem::take(&mut self.commits);
+        let reencode = self.reencode.take();
+        let serialized_header = self.serialize_header();
+        self.snapshots = snapshots;
+        self.commits = commits;
+        self.reencode = reencode;
+
+        let header_id = Uuid::new_v4().into();
+        write_header(&self.state.read().unwrap(), header_id, &serialized_header)?;
+
+        let snapshot = Snapshot {
+            name: name.to_string(),
+            time: SystemTime::now(),
+            header_id,
+        };
+        self.snapshots.insert(name.to_string(), snapshot.clone());
+
+        Ok(snapshot)
     }
 
-    fn start_restore(&mut self, savepoint: &Savepoint) -> crate::Result<Self::Restore> {
-        match savepoint.transaction_id.upgrade() {
-            None => return Err(crate::Error::InvalidSavepoint),
-            Some(transaction_id) if transaction_id != self.transaction_id => {
-                return Err(crate::Error::InvalidSavepoint)
-            }
-            _ => (),
-        }
+    /// Return a list of the snapshots in this repository, ordered by the time they were created.
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        let mut snapshots = self.snapshots.values().cloned().collect::<Vec<_>>();
+        snapshots.sort_by_key(|snapshot| snapshot.time);
+        snapshots
+    }
 
-        let old_header = self.replace_header((*savepoint.header).clone());
+    /// Remove the snapshot with the given `name` from the repository.
+    ///
+    /// This returns `true` if the snapshot was removed or `false` if it didn't exist.
+    ///
+    /// The snapshot is not removed persistently until [`Commit::commit`] is called. The space used
+    /// by data referenced only by this snapshot isn't reclaimed in the backing data store until
+    /// changes are committed and [`Commit::clean`] is called.
+    ///
+    /// [`Commit::commit`]: crate::repo::Commit::commit
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn remove_snapshot(&mut self, name: &str) -> bool {
+        self.snapshots.remove(name).is_some()
+    }
 
-        match self.read_object_map() {
-            Ok(objects) => Ok(KeyRestore {
-                objects,
-                header: self.replace_header(old_header),
-                transaction_id: savepoint.transaction_id.clone(),
-                instance_id: self.instance_id,
-            }),
+    /// Open the snapshot with the given `name` as a read-only repository.
+    ///
+    /// The returned repository contains the current instance of this repository as it was when the
+    /// snapshot was created. Any attempt to modify it will fail with `Error::ReadOnly`. You can use
+    /// [`SwitchInstance::switch_instance`] on this repository to access other instances in the
+    /// snapshot.
+    ///
+    /// The returned repository holds a shared lock on the data store with the same context as this
+    /// repository's lock until it is dropped. While it is open, [`Commit::clean`] won't remove or
+    /// repack the data it references, so it can still be read after this repository is cleaned.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no snapshot with the given `name`.
+    /// - `Error::Corrupt`: The snapshot is corrupt.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`SwitchInstance::switch_instance`]: crate::repo::SwitchInstance::switch_instance
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    pub fn open_snapshot(&self, name: &str) -> crate::Result<Self> {
+        let snapshot = self.snapshots.get(name).ok_or(crate::Error::NotFound)?;
+        self.open_header(snapshot.header_id)
+    }
+
+    /// Return a list of the commits in this repository's history, ordered from oldest to newest.
+    ///
+    /// The last commit in this list is the most recent commit. Old commits are removed from the
+    /// history when the repository is cleaned unless they are retained by the [`RetentionPolicy`]
+    /// passed to [`clean_retaining`].
+    ///
+    /// [`RetentionPolicy`]: crate::repo::RetentionPolicy
+    /// [`clean_retaining`]: crate::repo::key::KeyRepo::clean_retaining
+    pub fn commits(&self) -> Vec<CommitInfo> {
+        self.commits.clone()
+    }
+
+    /// Open the commit with the given `id` as a read-only repository.
+    ///
+    /// The returned repository contains the current instance of this repository as it was when the
+    /// commit was made. See [`open_snapshot`] for details about read-only repositories.
+    ///
+    /// # Errors
+    /// - `Error::NotFound`: There is no commit with the given `id` in the repository's history.
+    /// - `Error::Corrupt`: The commit is corrupt.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`open_snapshot`]: crate::repo::key::KeyRepo::open_snapshot
+    pub fn open_commit(&self, id: CommitId) -> crate::Result<Self> {
+        let commit = self
+            .commits
+            .iter()
+            .find(|commit| commit.id == id)
+            .ok_or(crate::Error::NotFound)?;
+        self.open_header(commit.header_id)
+    }
+
+    /// Open the repository header with the given `header_id` as a read-only repository.
+    ///
+    /// The returned repository holds a shared lock on the header, which is released when it is
+    /// dropped.
+    fn open_header(&self, header_id: BlockId) -> crate::Result<Self> {
+        let lock_id = self.lock_header(header_id)?;
+        let result = read_header(&self.state.read().unwrap(), header_id)
+            .and_then(|header| self.view_header(header_id, header));
+        match result {
+            Ok(repo) => {
+                repo.state.write().unwrap().lock_id = Some(lock_id);
+                Ok(repo)
+            }
             Err(error) => {
-                self.replace_header(old_header);
+                let state = self.state.read().unwrap();
+                unlock_store(&mut *state.store.lock().unwrap(), lock_id).ok();
                 Err(error)
             }
         }
     }
 
-    fn finish_restore(&mut self, restore: Self::Restore) -> bool {
-        match restore.transaction_id.upgrade() {
-            None => return false,
-            Some(transaction_id) if transaction_id != self.transaction_id => return false,
-            _ => (),
-        }
-
-        if restore.instance_id != self.instance_id {
-            return false;
-        }
+    /// Acquire a shared lock on the header with the given `header_id` and return its ID.
+    ///
+    /// The lock has the same context as this repository's lock. The lock is acquired before the
+    /// header is read so that a client cleaning the repository can't remove the header in between.
+    fn lock_header(&self, header_id: BlockId) -> crate::Result<BlockId> {
+        let state = self.state.read().unwrap();
+        let mut store = state.store.lock().unwrap();
+        let context = match state.lock_id {
+            Some(lock_id) => read_lock(
+                &mut *store,
+                &state.metadata.config.encryption,
+                &state.master_key,
+                lock_id,
+            )?
+            .map(|lock| lock.context)
+            .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let lock_id = Uuid::new_v4().into();
+        let lock = LockInfo {
+            kind: LockKind::Shared { header_id },
+            context,
+        };
+        write_lock(
+            &mut *store,
+            &state.metadata.config.encryption,
+            &state.master_key,
+            lock_id,
+            &lock,
+        )?;
+        Ok(lock_id)
+    }
 
-        self.replace_header(restore.header);
-        self.objects = restore.objects;
+    /// Open the given `header` with the given `header_id` as a read-only repository.
+    fn view_header(&self, header_id: BlockId, header: Header) -> crate::Result<Self> {
+        let state = self.state.read().unwrap();
 
-        true
-    }
-}
+        let Header {
+            chunks,
+            mut packs,
+            instances,
+            handle_table,
+            ..
+        } = header;
 
-impl<K: Key> Commit for KeyRepo<K> {
-    fn commit(&mut self) -> crate::Result<()> {
-        // Write the map of objects for the current instance.
-        self.write_object_map()?;
+        // Blocks referenced by the header may have been repacked since the header was written, in
+        // which case their current locations are in the pack map of this repository.
+        for (block_id, index_list) in packs.iter_mut() {
+            if let Some(current_index_list) = state.packs.get(block_id) {
+                *index_list = current_index_list.clone();
+            }
+        }
 
-        // Serialize the header.
-        let serialized_header = self.serialize_header();
+        let mut metadata = state.metadata.clone();
+        metadata.header_id = header_id;
 
-        // Write the serialized header to the data store, atomically completing the commit. If this
-        // completes successfully, changes have been committed and this method MUST return `Ok`.
-        self.write_serialized_header(serialized_header.as_slice())?;
+        let read_only_state = RepoState {
+            store: Arc::clone(&state.store),
+            metadata,
+            chunks,
+            packs,
+            transactions: LockTable::new(),
+            master_key: EncryptionKey::new(state.master_key.expose_secret().clone()),
+            lock_id: None,
+            read_only: true,
+            optimistic: false,
+            write_pool: None,
+            read_pool: state.read_pool.clone(),
+            chunk_cache: Arc::clone(&state.chunk_cache),
+            store_metrics: state.store_metrics.clone(),
+        };
 
-        // Now that the commit has succeeded, we must invalidate all savepoints associated with this
-        // repository.
-        self.transaction_id = Arc::new(Uuid::new_v4());
+        let mut repo = KeyRepo {
+            state: Arc::new(RwLock::new(read_only_state)),
+            instance_id: self.instance_id,
+            objects: HashMap::new(),
+            instances,
+            handle_table,
+            snapshots: HashMap::new(),
+            commits: Vec::new(),
+            reencode: None,
+            transaction_id: Arc::new(Uuid::new_v4()),
+        };
+        repo.objects = repo.read_object_map()?;
 
-        Ok(())
+        Ok(repo)
     }
 
-    fn rollback(&mut self) -> crate::Result<()> {
+    /// Merge the uncommitted changes in this repository with the most recent commit.
+    ///
+    /// This is used when another client has committed changes since this repository was opened or
+    /// last committed. Changes can be merged if they modify different instances or different keys
+    /// in the current instance. Because the object maps of other instances can't be deserialized,
+    /// any change to the object map of another instance is considered to conflict.
+    ///
+    /// If this returns `Ok`, the state of the repository has been replaced with the most recent
+    /// commit plus the uncommitted changes in the current instance. If this returns `Err`, the
+    /// repository is unchanged.
+    ///
+    /// # Errors
+    /// - `Error::Conflict`: The uncommitted changes conflict with the most recent commit.
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    fn rebase(&mut self) -> crate::Result<()> {
         let state = self.state.read().unwrap();
-        // Read the header from the previous commit from the data store.
-        let encoded_header = state
-            .store
-            .lock()
-            .unwrap()
-            .read_block(BlockKey::Header(state.metadata.header_id))
-            .map_err(crate::Error::Store)?
-            .ok_or(crate::Error::Corrupt)?;
-        let serialized_header = state.decode_data(encoded_header.as_slice())?;
-        let header: Header =
-            from_read(serialized_header.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        let base_id = state.metadata.header_id;
+        let their_id = read_metadata(&mut *state.store.lock().unwrap())?.header_id;
+
+        // The header this repository was based on may have been removed if the repository was
+        // cleaned by another client, in which case we can't tell which changes are ours.
+        let base_header = match read_header(&state, base_id) {
+            Err(crate::Error::Corrupt) => return Err(crate::Error::Conflict),
+            result => result?,
+        };
+        let their_header = read_header(&state, their_id)?;
         drop(state);
 
-        // Atomically restore from the deserialized header.
-        self.restore_header(header)
-    }
+        // We can only compare the object maps of other instances by their contents, which differ
+        // whenever an object map is rewritten even if it didn't change.
+        let instance_ids = self
+            .instances
+            .keys()
+            .chain(base_header.instances.keys())
+            .collect::<HashSet<_>>();
+        for instance_id in instance_ids {
+            if *instance_id == self.instance_id {
+                continue;
+            }
+            let our_extents = self
+                .instances
+                .get(instance_id)
+                .map(|info| &info.objects.extents);
+            let base_extents = base_header
+                .instances
+                .get(instance_id)
+                .map(|info| &info.objects.extents);
+            if our_extents != base_extents {
+                return Err(crate::Error::Conflict);
+            }
+        }
 
-    fn clean(&mut self) -> crate::Result<()> {
-        let mut state = self.state.write().unwrap();
+        let version_id = self.instances[&self.instance_id].version_id;
+        if let Some(their_instance) = their_header.instances.get(&self.instance_id) {
+            if their_instance.version_id != version_id {
+                return Err(crate::Error::Conflict);
+            }
+        }
 
-        // Read the header from the previous commit.
-        let encoded_header = state
-            .store
-            .lock()
-            .unwrap()
-            .read_block(BlockKey::Header(state.metadata.header_id))
-            .map_err(crate::Error::Store)?
-            .ok_or(crate::Error::Corrupt)?;
-        let serialized_header = state.decode_data(encoded_header.as_slice())?;
-        let previous_header: Header =
-            from_read(serialized_header.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        // Snapshots which were created or removed by both clients conflict unless they're the same.
+        let their_snapshots = &their_header.snapshots;
+        let base_snapshots = &base_header.snapshots;
+        for (name, snapshot) in &self.snapshots {
+            if base_snapshots.get(name) != Some(snapshot)
+                && their_snapshots.get(name) != base_snapshots.get(name)
+                && their_snapshots.get(name) != Some(snapshot)
+            {
+                return Err(crate::Error::Conflict);
+            }
+        }
+        let removed_snapshots = base_snapshots
+            .keys()
+            .filter(|name| !self.snapshots.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        let added_snapshots = self
+            .snapshots
+            .iter()
+            .filter(|(name, snapshot)| base_snapshots.get(*name) != Some(*snapshot))
+            .map(|(name, snapshot)| (name.clone(), snapshot.clone()))
+            .collect::<Vec<_>>();
 
-        // We need to find the set of blocks which are either currently referenced by the repository
-        // or were referenced after the previous commit. It's important that we don't clean up
-        // blocks which were referenced after the previous commit because that would make it
-        // impossible to roll back changes, and this method may be called before the repository is
-        // committed.
-        let mut referenced_blocks = state
-            .chunks
-            .values()
-            .map(|info| info.block_id)
+        // Find the keys in the current instance which we changed and check that they weren't
+        // changed differently by the other client.
+        let base_objects = self.view_header(base_id, base_header)?.objects;
+        let their_objects = self.view_header(their_id, their_header.clone())?.objects;
+        let extents_of = |objects: &HashMap<K, Arc<RwLock<ObjectHandle>>>, key: &K| {
+            objects
+                .get(key)
+                .map(|handle| handle.read().unwrap().extents.clone())
+        };
+        let keys = self
+            .objects
+            .keys()
+            .chain(base_objects.keys())
             .collect::<HashSet<_>>();
-        let previous_referenced_blocks = previous_header.chunks.values().map(|info| info.block_id);
-        referenced_blocks.extend(previous_referenced_blocks);
+        let mut changes = Vec::new();
+        for key in keys {
+            let our_extents = extents_of(&self.objects, key);
+            let base_extents = extents_of(&base_objects, key);
+            if our_extents == base_extents {
+                continue;
+            }
+            let their_extents = extents_of(&their_objects, key);
+            if their_extents != base_extents && their_extents != our_extents {
+                return Err(crate::Error::Conflict);
+            }
+            changes.push((key.clone(), our_extents));
+        }
 
-        // Remove all blocks from the data store which are unreferenced.
-        match &state.metadata.config.packing {
-            Packing::None => {
-                // When packing is disabled, we can just remove the unreferenced data blocks from
-                // the data store directly.
-                {
-                    let block_ids = state
-                        .store
-                        .lock()
-                        .unwrap()
-                        .list_blocks(BlockType::Data)
-                        .map_err(crate::Error::Store)?;
+        // Keep track of where the chunks we reference are stored before we replace our state.
+        let (our_chunks, our_packs) = {
+            let state = self.state.read().unwrap();
+            let our_chunks = state
+                .chunks
+                .iter()
+                .map(|(chunk, info)| (*chunk, info.block_id))
+                .collect::<HashMap<_, _>>();
+            (our_chunks, state.packs.clone())
+        };
 
-                    let mut store = state.store.lock().unwrap();
-                    for block_id in block_ids {
-                        if !referenced_blocks.contains(&block_id) {
-                            store
-                                .remove_block(BlockKey::Data(block_id))
-                                .map_err(crate::Error::Store)?;
-                        }
-                    }
-                }
+        let their_reencode = their_header.reencode;
+        self.restore_header(their_header)?;
+        self.reencode = their_reencode;
+        self.state.write().unwrap().metadata.header_id = their_id;
 
-                drop(state);
-            }
-            Packing::Fixed(_) => {
-                // When packing is enabled, we need to repack the packs which contain unreferenced
-                // blocks.
+        // If the other client hasn't committed the current instance, we need to create it.
+        if !self.instances.contains_key(&self.instance_id) {
+            let instance_info = InstanceInfo {
+                version_id,
+                objects: ObjectHandle {
+                    id: self.handle_table.next(),
+                    extents: Vec::new(),
+                },
+            };
+            self.instances.insert(self.instance_id, instance_info);
+        }
 
-                // Get an iterator of block IDs and the list of packs they're contained in.
-                let blocks_to_packs = state.packs.iter().chain(previous_header.packs.iter());
-
-                // Get a map of pack IDs to the set of blocks contained in them.
-                let mut packs_to_blocks = HashMap::new();
-                for (block_id, index_list) in blocks_to_packs {
-                    for pack_index in index_list {
-                        packs_to_blocks
-                            .entry(pack_index.id)
-                            .or_insert_with(HashSet::new)
-                            .insert(*block_id);
-                    }
-                }
+        for (key, extents) in changes {
+            self.remove(&key);
+            let extents = match extents {
+                Some(extents) => extents,
+                None => continue,
+            };
 
-                // The list of IDs of packs which contain at least one unreferenced block.
-                let mut packs_to_remove = Vec::new();
+            let handle = ObjectHandle {
+                id: self.handle_table.next(),
+                extents,
+            };
+            let mut state = self.state.write().unwrap();
+            for chunk in handle.chunks() {
+                let block_id = our_chunks[&chunk];
+                let chunk_info = state.chunks.entry(chunk).or_insert_with(|| ChunkInfo {
+                    block_id,
+                    references: HashSet::new(),
+                });
+                chunk_info.references.insert(handle.id);
+                let block_id = chunk_info.block_id;
+                if let Some(index_list) = our_packs.get(&block_id) {
+                    state
+                        .packs
+                        .entry(block_id)
+                        .or_insert_with(|| index_list.clone());
+                }
+            }
+            drop(state);
+            self.objects.insert(key, Arc::new(RwLock::new(handle)));
+        }
 
-                // The list of blocks which need to be repacked. These are referenced blocks which
-                // are contained in packs which contain at least one unreferenced block.
-                let mut blocks_to_repack = Vec::new();
+        for name in removed_snapshots {
+            self.snapshots.remove(&name);
+        }
+        self.snapshots.extend(added_snapshots);
 
-                // Iterate over the IDs of packs which are contained in the data store.
-                let data_blocks = state
-                    .store
-                    .lock()
-                    .unwrap()
-                    .list_blocks(BlockType::Data)
-                    .map_err(crate::Error::Store)?;
-                for pack_id in data_blocks {
-                    match packs_to_blocks.get(&pack_id) {
-                        Some(contained_blocks) => {
-                            let contains_unreferenced_blocks = contained_blocks
-                                .iter()
-                                .any(|block_id| !referenced_blocks.contains(block_id));
-                            if contains_unreferenced_blocks {
-                                let contained_referenced_blocks =
-                                    contained_blocks.intersection(&referenced_blocks).copied();
-                                packs_to_remove.push(pack_id);
-                                blocks_to_repack.extend(contained_referenced_blocks);
-                            }
-                        }
-                        // This pack does not contain any blocks that we know about. We can remove
-                        // it.
-                        None => packs_to_remove.push(pack_id),
-                    }
-                }
+        Ok(())
+    }
+
+    /// Clean up the repository, retaining old commits according to the given `policy`.
+    ///
+    /// This is like [`Commit::clean`], except that commits which are retained by `policy` are kept
+    /// in the repository's history along with all the data they reference. All other commits
+    /// except the most recent one are removed from the history.
+    ///
+    /// Removing commits from the history takes effect immediately and does not require the
+    /// repository to be committed.
+    ///
+    /// # Errors
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::ReadOnly`: The repository is read-only.
+    /// - `Error::Locked`: Another client which can modify the repository has it open.
+    /// - `Error::Conflict`: Another client committed changes while the repository was cleaned.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "clean", skip_all))]
+    pub fn clean_retaining(&mut self, policy: &RetentionPolicy) -> crate::Result<()> {
+        if self.is_read_only() {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        // Other clients using optimistic concurrency control could commit changes which reference
+        // data we're about to remove.
+        self.with_exclusive_lock(|repo| repo.clean_exclusive(policy))
+    }
+
+    /// Call `f` with this repository while no other client can modify it.
+    ///
+    /// If this repository uses optimistic concurrency control, its lock is temporarily upgraded to
+    /// an exclusive lock while `f` is called.
+    fn with_exclusive_lock<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> crate::Result<T>,
+    ) -> crate::Result<T> {
+        let state = self.state.read().unwrap();
+        let lock_id = match (state.optimistic, state.lock_id) {
+            (true, Some(lock_id)) => lock_id,
+            _ => {
+                drop(state);
+                return f(self);
+            }
+        };
+
+        let change_kind = |state: &RepoState, kind| {
+            change_lock_kind(
+                &mut *state.store.lock().unwrap(),
+                &state.metadata.config.encryption,
+                &state.master_key,
+                lock_id,
+                kind,
+            )
+        };
+        change_kind(&state, LockKind::Exclusive)?;
+        drop(state);
+
+        let result = f(self);
+
+        let state = self.state.read().unwrap();
+        let downgrade_result = change_kind(&state, LockKind::Optimistic);
+        result.and_then(|value| downgrade_result.map(|_| value))
+    }
+
+    /// Commit changes which have been made to the repository without upgrading its lock.
+    fn commit_unlocked(&mut self) -> crate::Result<()> {
+        loop {
+            // Write the map of objects for the current instance.
+            self.write_object_map()?;
+
+            // Add this commit to the commit history so that it's included in the header.
+            let header_id = Uuid::new_v4().into();
+            self.commits.push(CommitInfo {
+                id: Uuid::new_v4().into(),
+                time: SystemTime::now(),
+                header_id,
+            });
+
+            // Serialize the header.
+            let serialized_header = self.serialize_header();
+
+            // Write the serialized header to the data store, atomically completing the commit. If
+            // this completes successfully, changes have been committed and this method MUST return
+            // `Ok`.
+            match self.write_serialized_header(header_id, serialized_header.as_slice()) {
+                Ok(()) => break,
+                Err(error) => {
+                    self.commits.pop();
+
+                    // If another client committed changes concurrently, try to merge them with
+                    // ours and commit again.
+                    let optimistic = self.state.read().unwrap().optimistic;
+                    match error {
+                        crate::Error::Conflict if optimistic => self.rebase()?,
+                        error => return Err(error),
+                    }
+                }
+            }
+        }
+
+        // Now that the commit has succeeded, we must invalidate all savepoints associated with this
+        // repository.
+        self.transaction_id = Arc::new(Uuid::new_v4());
+
+        Ok(())
+    }
+
+    /// Clean up the repository assuming no other client can modify it.
+    fn clean_exclusive(&mut self, policy: &RetentionPolicy) -> crate::Result<()> {
+        let mut state = self.state.write().unwrap();
+
+        let CleanPlan {
+            previous_header,
+            retained_headers,
+            mut header_ids,
+            shared_header_ids,
+            referenced_blocks,
+            pinned_blocks,
+            retained_commits,
+            dropped_commits,
+        } = self.plan_clean(&state, policy)?;
+
+        // Remove all blocks from the data store which are unreferenced.
+        match &state.metadata.config.packing {
+            Packing::None => {
+                // When packing is disabled, we can just remove the unreferenced data blocks from
+                // the data store directly.
+                let block_ids = state
+                    .store
+                    .lock()
+                    .unwrap()
+                    .list_blocks(BlockType::Data)
+                    .map_err(crate::Error::Store)?;
+
+                let mut store = state.store.lock().unwrap();
+                for block_id in block_ids {
+                    if !referenced_blocks.contains(&block_id) {
+                        store
+                            .remove_block(BlockKey::Data(block_id))
+                            .map_err(crate::Error::Store)?;
+                    }
+                }
+            }
+            Packing::Fixed(_) => {
+                // When packing is enabled, we need to repack the packs which contain unreferenced
+                // blocks.
+
+                // Blocks which are only referenced by a snapshot or a retained commit may not be in
+                // the current pack map, so we need to know their locations to repack them.
+                for retained_header in &retained_headers {
+                    for (block_id, index_list) in &retained_header.packs {
+                        if referenced_blocks.contains(block_id) {
+                            state
+                                .packs
+                                .entry(*block_id)
+                                .or_insert_with(|| index_list.clone());
+                        }
+                    }
+                }
+
+                let data_blocks = state
+                    .store
+                    .lock()
+                    .unwrap()
+                    .list_blocks(BlockType::Data)
+                    .map_err(crate::Error::Store)?;
+
+                // Get an iterator of block IDs and the list of packs they're contained in.
+                let blocks_to_packs = state
+                    .packs
+                    .iter()
+                    .chain(previous_header.packs.iter())
+                    .chain(
+                        retained_headers
+                            .iter()
+                            .flat_map(|header| header.packs.iter()),
+                    );
+
+                let (packs_to_remove, blocks_to_repack) = packs_to_clean(
+                    data_blocks,
+                    blocks_to_packs,
+                    &referenced_blocks,
+                    &pinned_blocks,
+                );
 
                 // For each block that needs repacking, read it from its current pack and write it
                 // to a new one.
@@ -833,39 +1341,67 @@ impl<K: Key> Commit for KeyRepo<K> {
                 state
                     .packs
                     .retain(|block_id, _| referenced_blocks.contains(block_id));
+            }
+        }
 
-                // Next we need to write the updated pack map to the data store. To do this, we have
-                // to write the entire header. Because this method does not commit any changes, it's
-                // important that we write the previous header, changing only the pack map.
-                {
-                    let mut previous_header = previous_header;
-
-                    // Temporarily move the pack map into the previous header just so that we can
-                    // serialize it. Once we're done, move it back. This avoids needing the clone
-                    // the pack map.
-                    previous_header.packs = std::mem::take(&mut state.packs);
-                    let serialized_header = to_vec(&previous_header)
-                        .expect("Could not serialize the repository header.");
-                    mem::swap(&mut previous_header.packs, &mut state.packs);
-                    drop(previous_header);
-
-                    // Encode the serialized header and write it to the data store.
-                    let encoded_header = state.encode_data(serialized_header.as_slice())?;
-                    drop(state);
-                    self.write_serialized_header(encoded_header.as_slice())?;
+        // Next we need to write the updated pack map and commit history to the data store. To do
+        // this, we have to write the entire header. Because this method does not commit any
+        // changes, it's important that we write the previous header, changing only the pack map
+        // and the commit history. The progress of an interrupted re-encode is also discarded,
+        // because the blocks it wrote are unreferenced and have just been removed. The block which
+        // stores that progress is removed along with the other unreferenced headers below.
+        let old_header_id = state.metadata.header_id;
+        if state.metadata.config.packing != Packing::None
+            || !dropped_commits.is_empty()
+            || previous_header.reencode.is_some()
+        {
+            let mut previous_header = previous_header;
+            previous_header.reencode = None;
+            let new_header_id = Uuid::new_v4().into();
+
+            // Remove dropped commits from the history and point the most recent commit at the
+            // header we're about to write.
+            let update_history = |commits: &mut Vec<CommitInfo>| {
+                commits.retain(|commit| retained_commits.contains(&commit.id));
+                for commit in commits.iter_mut() {
+                    if commit.header_id == old_header_id {
+                        commit.header_id = new_header_id;
+                    }
                 }
-            }
+            };
+            update_history(&mut previous_header.commits);
+
+            // Temporarily move the pack map into the previous header just so that we can
+            // serialize it. Once we're done, move it back. This avoids needing the clone the pack
+            // map.
+            previous_header.packs = mem::take(&mut state.packs);
+            let serialized_header =
+                to_vec(&previous_header).expect("Could not serialize the repository header.");
+            mem::swap(&mut previous_header.packs, &mut state.packs);
+            drop(previous_header);
+
+            // Write the serialized header to the data store.
+            drop(state);
+            self.write_serialized_header(new_header_id, serialized_header.as_slice())?;
+            update_history(&mut self.commits);
+        } else {
+            drop(state);
         }
+        self.reencode = None;
 
         // Remove old unreferenced headers from the data store.
         {
             let state = self.state.read().unwrap();
+            if !shared_header_ids.contains(&old_header_id) {
+                header_ids.remove(&old_header_id);
+            }
+            header_ids.insert(state.metadata.header_id);
             let mut store = state.store.lock().unwrap();
             let unreferenced_headers = store
                 .list_blocks(BlockType::Header)
                 .map_err(crate::Error::Store)?
                 .into_iter()
-                .filter(|&block_id| block_id != state.metadata.header_id);
+                .filter(|block_id| !header_ids.contains(block_id));
             for block_id in unreferenced_headers {
                 store
                     .remove_block(BlockKey::Header(block_id))
@@ -875,48 +1411,1272 @@ impl<K: Key> Commit for KeyRepo<K> {
 
         Ok(())
     }
+
+    /// Return a report of the changes [`clean_retaining`] would make with the given `policy`.
+    ///
+    /// This does not modify the repository or the data store.
+    ///
+    /// # Errors
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`clean_retaining`]: crate::repo::key::KeyRepo::clean_retaining
+    pub fn clean_dry_run(&self, policy: &RetentionPolicy) -> crate::Result<CleanReport> {
+        let state = self.state.read().unwrap();
+
+        let CleanPlan {
+            previous_header,
+            retained_headers,
+            referenced_blocks,
+            pinned_blocks,
+            dropped_commits,
+            ..
+        } = self.plan_clean(&state, policy)?;
+
+        let data_blocks = state
+            .store
+            .lock()
+            .unwrap()
+            .list_blocks(BlockType::Data)
+            .map_err(crate::Error::Store)?;
+
+        let reclaimed_bytes = match &state.metadata.config.packing {
+            Packing::None => {
+                let mut reclaimed_bytes = 0u64;
+                let mut store = state.store.lock().unwrap();
+                for block_id in data_blocks {
+                    if !referenced_blocks.contains(&block_id) {
+                        reclaimed_bytes += store
+                            .block_size(BlockKey::Data(block_id))
+                            .map_err(crate::Error::Store)?
+                            .unwrap_or(0);
+                    }
+                }
+                reclaimed_bytes
+            }
+            Packing::Fixed(pack_size) => {
+                let blocks_to_packs = state
+                    .packs
+                    .iter()
+                    .chain(previous_header.packs.iter())
+                    .chain(
+                        retained_headers
+                            .iter()
+                            .flat_map(|header| header.packs.iter()),
+                    );
+                let (packs_to_remove, blocks_to_repack) = packs_to_clean(
+                    data_blocks,
+                    blocks_to_packs,
+                    &referenced_blocks,
+                    &pinned_blocks,
+                );
+
+                // Blocks which need to be repacked will take up space in new packs.
+                let repacked_bytes: u64 = blocks_to_repack
+                    .iter()
+                    .filter_map(|block_id| {
+                        state.packs.get(block_id).or_else(|| {
+                            retained_headers
+                                .iter()
+                                .find_map(|header| header.packs.get(block_id))
+                        })
+                    })
+                    .flatten()
+                    .map(|pack_index| pack_index.size as u64)
+                    .sum();
+
+                (packs_to_remove.len() as u64 * *pack_size as u64).saturating_sub(repacked_bytes)
+            }
+        };
+
+        Ok(CleanReport {
+            dropped_commits,
+            reclaimed_bytes,
+        })
+    }
+
+    /// Determine which headers and blocks must be kept when cleaning with the given `policy`.
+    fn plan_clean(&self, state: &RepoState, policy: &RetentionPolicy) -> crate::Result<CleanPlan> {
+        // Read the header from the previous commit.
+        let previous_header = read_header(state, state.metadata.header_id)?;
+
+        let retained_commits = policy.retained(&previous_header.commits);
+        let (kept_commits, dropped_commits): (Vec<_>, Vec<_>) = previous_header
+            .commits
+            .iter()
+            .cloned()
+            .partition(|commit| retained_commits.contains(&commit.id));
+
+        // Headers referenced by a snapshot or a retained commit must not be removed. This includes
+        // snapshots which exist now and snapshots which existed after the previous commit, for the
+        // same reason as below.
+        let mut header_ids = HashSet::new();
+        header_ids.insert(state.metadata.header_id);
+        header_ids.extend(
+            self.snapshots
+                .values()
+                .chain(previous_header.snapshots.values())
+                .map(|snapshot| snapshot.header_id),
+        );
+        header_ids.extend(kept_commits.iter().map(|commit| commit.header_id));
+
+        let mut retained_headers = Vec::with_capacity(header_ids.len());
+        for header_id in &header_ids {
+            if *header_id != state.metadata.header_id {
+                retained_headers.push(read_header(state, *header_id)?);
+            }
+        }
+
+        // Headers which are being read by read-only repositories holding a shared lock must not be
+        // removed, and the packs containing the blocks they reference must not be repacked, because
+        // those repositories can't see the updated pack map.
+        let shared_header_ids = shared_lock_headers(
+            &mut *state.store.lock().unwrap(),
+            &state.metadata.config.encryption,
+            &state.master_key,
+        )?
+        .into_iter()
+        .collect::<HashSet<_>>();
+        let mut pinned_blocks = HashSet::new();
+        for header_id in &shared_header_ids {
+            let shared_header = if *header_id == state.metadata.header_id {
+                None
+            } else {
+                match read_header(state, *header_id) {
+                    Ok(header) => Some(header),
+                    // The read-only repository may have failed to open because the header was
+                    // removed before its lock was acquired.
+                    Err(crate::Error::Corrupt) => continue,
+                    Err(error) => return Err(error),
+                }
+            };
+            let header = shared_header.as_ref().unwrap_or(&previous_header);
+            pinned_blocks.extend(header.chunks.values().map(|info| info.block_id));
+            if let Some(header) = shared_header {
+                header_ids.insert(*header_id);
+                retained_headers.push(header);
+            }
+        }
+
+        // We need to find the set of blocks which are either currently referenced by the repository
+        // or were referenced after the previous commit. It's important that we don't clean up
+        // blocks which were referenced after the previous commit because that would make it
+        // impossible to roll back changes, and this method may be called before the repository is
+        // committed. Blocks referenced by a retained header must also be kept.
+        let mut referenced_blocks = state
+            .chunks
+            .values()
+            .map(|info| info.block_id)
+            .collect::<HashSet<_>>();
+        for header in retained_headers.iter().chain([&previous_header]) {
+            referenced_blocks.extend(header.chunks.values().map(|info| info.block_id));
+        }
+
+        referenced_blocks.extend(pinned_blocks.iter().copied());
+
+        // Blocks which are being written by the write pool aren't referenced yet, but they will be
+        // once the objects writing them are committed.
+        if let Some(write_pool) = &state.write_pool {
+            referenced_blocks.extend(write_pool.in_flight_blocks());
+        }
+
+        Ok(CleanPlan {
+            previous_header,
+            retained_headers,
+            header_ids,
+            shared_header_ids,
+            referenced_blocks,
+            pinned_blocks,
+            retained_commits,
+            dropped_commits,
+        })
+    }
+
+    /// Re-encode all the data in the repository using the given `config`.
+    ///
+    /// This rewrites every chunk in the repository using the packing, compression, and encryption
+    /// methods in `config`. If the chunking method in `config` is different from the current one,
+    /// every object in every instance of the repository is also split into new chunks. Snapshots
+    /// and the commit history are re-encoded along with the current state of the repository, so
+    /// they can still be opened afterwards. Uncommitted changes are included in the re-encode.
+    ///
+    /// The re-encoded repository is committed atomically once all the data has been rewritten. If
+    /// this returns `Err`, the repository is unchanged. Progress is periodically saved to the data
+    /// store, so if this method is interrupted, calling it again with the same `config` resumes the
+    /// re-encode instead of starting over. Saved progress is kept when the repository is committed,
+    /// but it is discarded if the repository is cleaned before the re-encode is resumed.
+    ///
+    /// If the encryption method in `config` is different from the current one, the current
+    /// `password` for the repository must be provided, and it becomes the password for the new
+    /// encryption method. Otherwise, the `password` is ignored, and so are the `memory_limit` and
+    /// `operations_limit` in `config`; use [`change_password`] to change those.
+    ///
+    /// The `progress` callback is called each time a chunk of data is re-encoded.
+    ///
+    /// No other client can have the repository open while it is re-encoded, including read-only
+    /// clients. The space used by the old data is not reclaimed in the backing data store until
+    /// [`Commit::clean`] is called.
+    ///
+    /// # Errors
+    /// - `Error::Password`: The encryption method is changed and the password is missing or invalid.
+    /// - `Error::InvalidConfig`: The `config` is invalid.
+    /// - `Error::Locked`: Another client has the repository open.
+    /// - `Error::ReadOnly`: The repository is read-only.
+    /// - `Error::Corrupt`: The repository is corrupt. This is most likely unrecoverable.
+    /// - `Error::Deserialize`: The object map of an instance could not be deserialized.
+    /// - `Error::InvalidData`: Ciphertext verification failed.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    ///
+    /// [`change_password`]: crate::repo::key::KeyRepo::change_password
+    /// [`Commit::clean`]: crate::repo::Commit::clean
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub fn reencode(
+        &mut self,
+        config: RepoConfig,
+        password: Option<&[u8]>,
+        mut progress: impl FnMut(ReencodeProgress),
+    ) -> crate::Result<ReencodeReport> {
+        if self.is_read_only() {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        if config.compression_threshold > 100 || !config.chunking.is_valid() {
+            return Err(crate::Error::InvalidConfig);
+        }
+
+        self.with_exclusive_lock(|repo| repo.reencode_exclusive(config, password, &mut progress))
+    }
+
+    /// Re-encode the repository assuming no other client can modify it.
+    fn reencode_exclusive(
+        &mut self,
+        mut config: RepoConfig,
+        password: Option<&[u8]>,
+        progress: &mut dyn FnMut(ReencodeProgress),
+    ) -> crate::Result<ReencodeReport> {
+        let before = self.stats();
+
+        // Write the map of objects for the current instance so that it's re-encoded along with the
+        // other instances.
+        self.write_object_map()?;
+
+        let state = self.state.read().unwrap();
+        check_sole_lock(&state)?;
+
+        let old_config = &state.metadata.config;
+        let encryption_changed = config.encryption != old_config.encryption;
+        let rechunk = config.chunking != old_config.chunking;
+
+        // The master key is only re-encrypted with the password if the encryption method changes.
+        if !encryption_changed {
+            config.memory_limit = old_config.memory_limit;
+            config.operations_limit = old_config.operations_limit;
+        } else if old_config.encryption != Encryption::None {
+            let password = password.ok_or(crate::Error::Password)?;
+            state.metadata.decrypt_master_key(password)?;
+        }
+
+        // Resume the re-encode which was interrupted if it was using the same config.
+        let saved_journal = match self.reencode {
+            Some(journal_id) => Some(read_journal(&state, journal_id)?),
+            None => None,
+        }
+        .filter(|journal| journal.config == config);
+
+        let (mut journal, master_key) = match saved_journal {
+            Some(journal) => {
+                let master_key = if !encryption_changed {
+                    EncryptionKey::new(state.master_key.expose_secret().clone())
+                } else if config.encryption == Encryption::None {
+                    EncryptionKey::new(Vec::new())
+                } else {
+                    let password = password.ok_or(crate::Error::Password)?;
+                    let new_metadata = RepoMetadata {
+                        config: config.clone(),
+                        master_key: journal.master_key.clone(),
+                        salt: journal.salt.clone(),
+                        ..state.metadata.clone()
+                    };
+                    new_metadata.decrypt_master_key(password)?
+                };
+                (journal, master_key)
+            }
+            None => {
+                let (master_key, encrypted_master_key, salt) = if !encryption_changed {
+                    (
+                        EncryptionKey::new(state.master_key.expose_secret().clone()),
+                        state.metadata.master_key.clone(),
+                        state.metadata.salt.clone(),
+                    )
+                } else if config.encryption == Encryption::None {
+                    (EncryptionKey::new(Vec::new()), Vec::new(), KeySalt::empty())
+                } else {
+                    let password = password.ok_or(crate::Error::Password)?;
+                    let master_key = EncryptionKey::generate(config.encryption.key_size());
+                    let salt = KeySalt::generate();
+                    let user_key = EncryptionKey::derive(
+                        password,
+                        &salt,
+                        config.encryption.key_size(),
+                        config.memory_limit,
+                        config.operations_limit,
+                    );
+                    let encrypted_master_key = config
+                        .encryption
+                        .encrypt(master_key.expose_secret(), &user_key);
+                    (master_key, encrypted_master_key, salt)
+                };
+                let journal = ReencodeJournal {
+                    config: config.clone(),
+                    master_key: encrypted_master_key,
+                    salt,
+                    chunks: HashMap::new(),
+                    packs: HashMap::new(),
+                    extents: HashMap::new(),
+                };
+                (journal, master_key)
+            }
+        };
+
+        // The source state can read every chunk referenced by the current state of the
+        // repository, the commit history, and the snapshots.
+        let mut source_chunks = HashMap::new();
+        let mut source_packs = HashMap::new();
+        let mut map_handles = Vec::new();
+        for header_id in self.retained_header_ids() {
+            let header = read_header(&state, header_id)?;
+            source_chunks.extend(header.chunks);
+            source_packs.extend(header.packs);
+            map_handles.extend(header.instances.into_values().map(|info| info.objects));
+        }
+        source_chunks.extend(state.chunks.clone());
+        // Blocks may have been repacked since older headers were written, in which case their
+        // current locations are in the pack map of this repository.
+        source_packs.extend(state.packs.clone());
+        map_handles.extend(self.instances.values().map(|info| info.objects.clone()));
+        let source = detached_state(
+            &state,
+            state.metadata.clone(),
+            EncryptionKey::new(state.master_key.expose_secret().clone()),
+            source_chunks,
+            source_packs,
+        );
+
+        // The target state writes chunks using the new config.
+        let target_metadata = RepoMetadata {
+            config: config.clone(),
+            master_key: journal.master_key.clone(),
+            salt: journal.salt.clone(),
+            tagged_blocks: true,
+            ..state.metadata.clone()
+        };
+        let target_packs = mem::take(&mut journal.packs);
+        let mut target = detached_state(
+            &state,
+            target_metadata,
+            master_key,
+            HashMap::new(),
+            target_packs,
+        );
+        drop(state);
+
+        let mut source_store_state = StoreState::new();
+        let mut target_store_state = StoreState::new();
+        let mut reader = StoreReader::new(&source, &mut source_store_state).without_cache();
+        let mut current_progress = ReencodeProgress {
+            processed_bytes: 0,
+            total_bytes: 0,
+        };
+        let mut resumed_bytes = 0u64;
+        let mut saved_chunks = journal.chunks.len();
+
+        if rechunk {
+            // Get the distinct lists of extents of the objects in every instance.
+            let mut object_extents = HashSet::new();
+            let mut read_maps = HashSet::new();
+            for map_handle in &map_handles {
+                if read_maps.insert(&map_handle.extents) {
+                    for (_, handle) in read_raw_object_map(&source, map_handle)? {
+                        object_extents.insert(handle.extents);
+                    }
+                }
+            }
+            current_progress.total_bytes = object_extents
+                .iter()
+                .map(|extents| data_size(extents))
+                .sum();
+
+            let mut chunker = IncrementalChunker::new(config.chunking.to_chunker());
+            for extents in object_extents {
+                if journal.extents.contains_key(&extents) {
+                    resumed_bytes += data_size(&extents);
+                    current_progress.processed_bytes += data_size(&extents);
+                    progress(current_progress);
+                    continue;
+                }
+
+                // Holes are preserved, and the data between them is split into new chunks.
+                let mut new_extents = Vec::new();
+                for extent in &extents {
+                    match extent {
+                        Extent::Chunk(chunk) => {
+                            chunker.write_all(&reader.read_chunk(*chunk)?)?;
+                            current_progress.processed_bytes += chunk.size as u64;
+                        }
+                        Extent::Hole { .. } => chunker.flush()?,
+                    }
+                    for data in chunker.chunks() {
+                        let chunk = write_reencoded_chunk(
+                            &mut target,
+                            &mut target_store_state,
+                            &mut journal,
+                            &data,
+                        )?;
+                        new_extents.push(Extent::Chunk(chunk));
+                    }
+                    if let Extent::Hole { .. } = extent {
+                        new_extents.push(*extent);
+                    }
+                    progress(current_progress);
+                }
+                chunker.flush()?;
+                for data in chunker.chunks() {
+                    let chunk = write_reencoded_chunk(
+                        &mut target,
+                        &mut target_store_state,
+                        &mut journal,
+                        &data,
+                    )?;
+                    new_extents.push(Extent::Chunk(chunk));
+                }
+                journal.extents.insert(extents, new_extents);
+
+                if should_save_progress(saved_chunks, journal.chunks.len()) {
+                    self.save_reencode_progress(&journal, &target)?;
+                    saved_chunks = journal.chunks.len();
+                }
+            }
+        } else {
+            current_progress.total_bytes =
+                source.chunks.keys().map(|chunk| chunk.size as u64).sum();
+
+            for chunk in source.chunks.keys() {
+                if journal.chunks.contains_key(chunk) {
+                    resumed_bytes += chunk.size as u64;
+                } else {
+                    let data = reader.read_chunk(*chunk)?;
+                    write_reencoded_chunk(
+                        &mut target,
+                        &mut target_store_state,
+                        &mut journal,
+                        &data,
+                    )?;
+                }
+                current_progress.processed_bytes += chunk.size as u64;
+                progress(current_progress);
+
+                if should_save_progress(saved_chunks, journal.chunks.len()) {
+                    self.save_reencode_progress(&journal, &target)?;
+                    saved_chunks = journal.chunks.len();
+                }
+            }
+        }
+
+        // All the data has been re-encoded, so now we rewrite each header in the commit history and
+        // each snapshot to reference the re-encoded data.
+        target.chunks = journal
+            .chunks
+            .iter()
+            .map(|(chunk, block_id)| {
+                let chunk_info = ChunkInfo {
+                    block_id: *block_id,
+                    references: HashSet::new(),
+                };
+                (*chunk, chunk_info)
+            })
+            .collect();
+        let header_ids = self
+            .retained_header_ids()
+            .into_iter()
+            .map(|header_id| (header_id, Uuid::new_v4().into()))
+            .collect::<HashMap<BlockId, BlockId>>();
+        for (old_header_id, new_header_id) in &header_ids {
+            let header = read_header(&source, *old_header_id)?;
+            let header = reencode_header(header, &source, &mut target, &journal, &header_ids)?;
+            let serialized_header =
+                to_vec(&header).expect("Could not serialize the repository header.");
+            write_header(&target, *new_header_id, &serialized_header)?;
+        }
+
+        // Then we rewrite the current state of the repository as a new commit.
+        let new_header_id = Uuid::new_v4().into();
+        let mut header = reencode_header(
+            self.clone_header(),
+            &source,
+            &mut target,
+            &journal,
+            &header_ids,
+        )?;
+        header.commits.push(CommitInfo {
+            id: Uuid::new_v4().into(),
+            time: SystemTime::now(),
+            header_id: new_header_id,
+        });
+        let serialized_header =
+            to_vec(&header).expect("Could not serialize the repository header.");
+        write_header(&target, new_header_id, &serialized_header)?;
+
+        // Get the new extents of the objects in the current instance before committing, since this
+        // can fail.
+        let mut new_extents = Vec::new();
+        if rechunk {
+            for handle_lock in self.objects.values() {
+                let extents = journal
+                    .extents
+                    .get(&handle_lock.read().unwrap().extents)
+                    .ok_or(crate::Error::Corrupt)?;
+                new_extents.push((Arc::clone(handle_lock), extents.clone()));
+            }
+        }
+
+        // Commit the re-encode by atomically replacing the repository metadata.
+        let mut state = self.state.write().unwrap();
+        let new_metadata = RepoMetadata {
+            header_id: new_header_id,
+            ..target.metadata.clone()
+        };
+        check_sole_lock(&state)?;
+        replace_metadata(&state, &new_metadata)?;
+
+        // The re-encode has been committed, so this method MUST return `Ok` from this point.
+        if let Some(lock_id) = state.lock_id.filter(|_| encryption_changed) {
+            // If re-encrypting our lock fails, other clients will treat it as an exclusive lock
+            // because they can't decrypt it.
+            let mut store = state.store.lock().unwrap();
+            if let Ok(Some(lock)) = read_lock(
+                &mut *store,
+                &state.metadata.config.encryption,
+                &state.master_key,
+                lock_id,
+            ) {
+                write_lock(
+                    &mut *store,
+                    &new_metadata.config.encryption,
+                    &target.master_key,
+                    lock_id,
+                    &lock,
+                )
+                .ok();
+            }
+        }
+
+        for (handle_lock, extents) in new_extents {
+            handle_lock.write().unwrap().extents = extents;
+        }
+
+        // The saved progress is no longer referenced. If removing it fails, it will be removed the
+        // next time the repository is cleaned.
+        if let Some(journal_id) = self.reencode.take() {
+            state
+                .store
+                .lock()
+                .unwrap()
+                .remove_block(BlockKey::Header(journal_id))
+                .ok();
+        }
+
+        let Header {
+            chunks,
+            packs,
+            instances,
+            snapshots,
+            commits,
+            ..
+        } = header;
+        state.metadata = new_metadata;
+        state.master_key = mem::replace(&mut target.master_key, EncryptionKey::new(Vec::new()));
+        state.chunks = chunks;
+        state.packs = packs;
+        if let Some(write_pool) = &state.write_pool {
+            write_pool.set_codec(BlockCodec::new(&state.metadata, &state.master_key));
+        }
+        if let Some(read_pool) = &state.read_pool {
+            read_pool.set_codec(BlockCodec::new(&state.metadata, &state.master_key));
+        }
+        drop(state);
+
+        self.instances = instances;
+        self.snapshots = snapshots;
+        self.commits = commits;
+
+        // Savepoints reference data which has been re-encoded, so they must be invalidated.
+        self.transaction_id = Arc::new(Uuid::new_v4());
+
+        Ok(ReencodeReport {
+            before,
+            after: self.stats(),
+            resumed_bytes,
+        })
+    }
+
+    /// Return the IDs of the headers of the commits and snapshots in this repository.
+    fn retained_header_ids(&self) -> HashSet<BlockId> {
+        self.commits
+            .iter()
+            .map(|commit| commit.header_id)
+            .chain(self.snapshots.values().map(|snapshot| snapshot.header_id))
+            .collect()
+    }
+
+    /// Save the progress of a re-encode to the data store so that it can be resumed.
+    ///
+    /// The progress is written to a new block, and then the header from the previous commit is
+    /// written so that it references that block. Like when cleaning the repository, uncommitted
+    /// changes are not committed.
+    fn save_reencode_progress(
+        &mut self,
+        journal: &ReencodeJournal,
+        target: &RepoState,
+    ) -> crate::Result<()> {
+        // The pack map for the re-encoded blocks is kept in the target state while they're being
+        // written.
+        let serialized_journal = to_vec(&ReencodeJournal {
+            packs: target.packs.clone(),
+            ..journal.clone()
+        })
+        .expect("Could not serialize the re-encode journal.");
+
+        let journal_id = Uuid::new_v4().into();
+        let state = self.state.read().unwrap();
+        write_header(&state, journal_id, &serialized_journal)?;
+        let old_header_id = state.metadata.header_id;
+        let mut previous_header = read_header(&state, old_header_id)?;
+        drop(state);
+
+        let new_header_id = Uuid::new_v4().into();
+        let update_history = |commits: &mut Vec<CommitInfo>| {
+            for commit in commits.iter_mut() {
+                if commit.header_id == old_header_id {
+                    commit.header_id = new_header_id;
+                }
+            }
+        };
+        update_history(&mut previous_header.commits);
+
+        previous_header.reencode = Some(journal_id);
+        let serialized_header =
+            to_vec(&previous_header).expect("Could not serialize the repository header.");
+        drop(previous_header);
+
+        self.write_serialized_header(new_header_id, serialized_header.as_slice())?;
+        update_history(&mut self.commits);
+
+        // The previous progress is no longer referenced. If removing it fails, it will be removed
+        // the next time the repository is cleaned.
+        if let Some(old_journal_id) = self.reencode.replace(journal_id) {
+            let state = self.state.read().unwrap();
+            state
+                .store
+                .lock()
+                .unwrap()
+                .remove_block(BlockKey::Header(old_journal_id))
+                .ok();
+        }
+
+        Ok(())
+    }
+}
+
+/// The headers and blocks which must be kept when cleaning a repository.
+struct CleanPlan {
+    /// The header from the previous commit.
+    previous_header: Header,
+
+    /// The headers of snapshots and retained commits other than the previous commit.
+    retained_headers: Vec<Header>,
+
+    /// The IDs of header blocks which must not be removed.
+    header_ids: HashSet<BlockId>,
+
+    /// The IDs of headers which are being read by read-only repositories.
+    shared_header_ids: HashSet<BlockId>,
+
+    /// The IDs of data blocks which must not be removed.
+    referenced_blocks: HashSet<BlockId>,
+
+    /// The IDs of data blocks which must not be moved to a different pack.
+    pinned_blocks: HashSet<BlockId>,
+
+    /// The IDs of commits which are retained in the commit history.
+    retained_commits: HashSet<CommitId>,
+
+    /// The commits which will be removed from the commit history.
+    dropped_commits: Vec<CommitInfo>,
+}
+
+/// Determine which packs need to be removed when cleaning a repository.
+///
+/// This accepts the IDs of the `data_blocks` in the data store, an iterator of block IDs and the
+/// packs they're contained in, the set of `referenced_blocks`, and the set of `pinned_blocks` whose
+/// packs must not be removed. It returns the list of IDs of packs which need to be removed and the
+/// list of referenced blocks which need to be repacked.
+fn packs_to_clean<'a>(
+    data_blocks: Vec<BlockId>,
+    blocks_to_packs: impl Iterator<Item = (&'a BlockId, &'a Vec<PackIndex>)>,
+    referenced_blocks: &HashSet<BlockId>,
+    pinned_blocks: &HashSet<BlockId>,
+) -> (Vec<BlockId>, Vec<BlockId>) {
+    // Get a map of pack IDs to the set of blocks contained in them.
+    let mut packs_to_blocks = HashMap::new();
+    for (block_id, index_list) in blocks_to_packs {
+        for pack_index in index_list {
+            packs_to_blocks
+                .entry(pack_index.id)
+                .or_insert_with(HashSet::new)
+                .insert(*block_id);
+        }
+    }
+
+    // The list of IDs of packs which contain at least one unreferenced block.
+    let mut packs_to_remove = Vec::new();
+
+    // The list of blocks which need to be repacked. These are referenced blocks which are contained
+    // in packs which contain at least one unreferenced block.
+    let mut blocks_to_repack = HashSet::new();
+
+    // Iterate over the IDs of packs which are contained in the data store.
+    for pack_id in data_blocks {
+        match packs_to_blocks.get(&pack_id) {
+            Some(contained_blocks) => {
+                let contains_unreferenced_blocks = contained_blocks
+                    .iter()
+                    .any(|block_id| !referenced_blocks.contains(block_id));
+                let contains_pinned_blocks = !contained_blocks.is_disjoint(pinned_blocks);
+                if contains_unreferenced_blocks && !contains_pinned_blocks {
+                    let contained_referenced_blocks =
+                        contained_blocks.intersection(referenced_blocks).copied();
+                    packs_to_remove.push(pack_id);
+                    blocks_to_repack.extend(contained_referenced_blocks);
+                }
+            }
+            // This pack does not contain any blocks that we know about. We can remove it.
+            None => packs_to_remove.push(pack_id),
+        }
+    }
+
+    (packs_to_remove, blocks_to_repack.into_iter().collect())
+}
+
+/// Read, decode, and deserialize the header with the given `header_id` from the data store.
+fn read_header(state: &RepoState, header_id: BlockId) -> crate::Result<Header> {
+    let encoded_header = state
+        .store
+        .lock()
+        .unwrap()
+        .read_block(BlockKey::Header(header_id))
+        .map_err(crate::Error::Store)?
+        .ok_or(crate::Error::Corrupt)?;
+    let serialized_header = state.decode_data(encoded_header.as_slice())?;
+    from_read(serialized_header.as_slice()).map_err(|_| crate::Error::Corrupt)
+}
+
+/// Read, decode, and deserialize the re-encode journal with the given `journal_id`.
+fn read_journal(state: &RepoState, journal_id: BlockId) -> crate::Result<ReencodeJournal> {
+    let encoded_journal = state
+        .store
+        .lock()
+        .unwrap()
+        .read_block(BlockKey::Header(journal_id))
+        .map_err(crate::Error::Store)?
+        .ok_or(crate::Error::Corrupt)?;
+    let serialized_journal = state.decode_data(encoded_journal.as_slice())?;
+    from_read(serialized_journal.as_slice()).map_err(|_| crate::Error::Corrupt)
+}
+
+/// Encode and write the given serialized `header` to a new block with the given `header_id`.
+fn write_header(
+    state: &RepoState,
+    header_id: BlockId,
+    serialized_header: &[u8],
+) -> crate::Result<()> {
+    let encoded_header = state.encode_data(serialized_header)?;
+    state
+        .store
+        .lock()
+        .unwrap()
+        .write_block(BlockKey::Header(header_id), encoded_header.as_slice())
+        .map_err(crate::Error::Store)
+}
+
+/// Atomically replace the repository metadata in the super block with `new_metadata`.
+///
+/// The super block is only replaced if no other client has written a new header since the one
+/// referenced by the metadata in `state`. Otherwise, the header referenced by `new_metadata` is
+/// removed.
+///
+/// # Errors
+/// - `Error::Conflict`: Another client has written a header since the current one.
+/// - `Error::Corrupt`: The super block is missing or could not be deserialized.
+/// - `Error::Store`: An error occurred with the data store.
+fn replace_metadata(state: &RepoState, new_metadata: &RepoMetadata) -> crate::Result<()> {
+    let serialized_metadata =
+        to_vec(new_metadata).expect("Could not serialize repository metadata.");
+    let mut expected_metadata =
+        to_vec(&state.metadata).expect("Could not serialize repository metadata.");
+
+    // Atomically write the new repository metadata containing the new header ID, but only if no
+    // other client has written a new header since the current one. This is only atomic if the data
+    // store supports `ConditionalWrites::Full`.
+    let mut store = state.store.lock().unwrap();
+    while !store
+        .write_block_if(
+            BlockKey::Super,
+            Some(&expected_metadata),
+            &serialized_metadata,
+        )
+        .map_err(crate::Error::Store)?
+    {
+        let current_serialized_metadata = store
+            .read_block(BlockKey::Super)
+            .map_err(crate::Error::Store)?
+            .ok_or(crate::Error::Corrupt)?;
+        let current_metadata: RepoMetadata =
+            from_read(current_serialized_metadata.as_slice()).map_err(|_| crate::Error::Corrupt)?;
+        if current_metadata.header_id != state.metadata.header_id {
+            // The header we just wrote will never be referenced, so we remove it. If this fails,
+            // it will be removed the next time the repository is cleaned.
+            store
+                .remove_block(BlockKey::Header(new_metadata.header_id))
+                .ok();
+            return Err(crate::Error::Conflict);
+        }
+
+        // The super block still references the current header, but it was serialized differently,
+        // possibly by a different version of this library.
+        expected_metadata = current_serialized_metadata;
+    }
+
+    Ok(())
+}
+
+/// The minimum number of chunks to re-encode between saving the progress of a re-encode.
+const REENCODE_SAVE_INTERVAL: usize = 1024;
+
+/// Return whether the progress of a re-encode should be saved.
+///
+/// This accepts the number of re-encoded chunks when progress was last saved and the current
+/// number of re-encoded chunks. The interval grows with the number of chunks so that the total
+/// cost of saving progress stays proportional to the size of the repository.
+fn should_save_progress(saved_chunks: usize, current_chunks: usize) -> bool {
+    current_chunks - saved_chunks >= max(REENCODE_SAVE_INTERVAL, current_chunks / 4)
+}
+
+/// Return the number of bytes of data in the given `extents`, not including holes.
+fn data_size(extents: &[Extent]) -> u64 {
+    extents
+        .iter()
+        .filter_map(|extent| match extent {
+            Extent::Chunk(chunk) => Some(chunk.size as u64),
+            Extent::Hole { .. } => None,
+        })
+        .sum()
+}
+
+/// Return an error if any client other than the one with the given `state` has a lock.
+///
+/// # Errors
+/// - `Error::Locked`: Another client has a lock on the repository.
+/// - `Error::Store`: An error occurred with the data store.
+fn check_sole_lock(state: &RepoState) -> crate::Result<()> {
+    let lock_ids = list_locks(&mut *state.store.lock().unwrap())?;
+    if lock_ids
+        .iter()
+        .all(|lock_id| Some(*lock_id) == state.lock_id)
+    {
+        Ok(())
+    } else {
+        Err(crate::Error::Locked)
+    }
+}
+
+/// Return a new `RepoState` which shares the data store of `state` but does not hold a lock.
+///
+/// This is used to read and write blocks with a different `metadata` and `master_key`.
+fn detached_state(
+    state: &RepoState,
+    metadata: RepoMetadata,
+    master_key: EncryptionKey,
+    chunks: HashMap<Chunk, ChunkInfo>,
+    packs: HashMap<BlockId, Vec<PackIndex>>,
+) -> RepoState {
+    RepoState {
+        store: Arc::clone(&state.store),
+        metadata,
+        chunks,
+        packs,
+        transactions: LockTable::new(),
+        master_key,
+        lock_id: None,
+        read_only: false,
+        optimistic: false,
+        write_pool: None,
+        read_pool: None,
+        chunk_cache: Arc::clone(&state.chunk_cache),
+        store_metrics: state.store_metrics.clone(),
+    }
+}
+
+/// Write the given `data` as a re-encoded chunk if it hasn't been already and return it.
+fn write_reencoded_chunk(
+    target: &mut RepoState,
+    store_state: &mut StoreState,
+    journal: &mut ReencodeJournal,
+    data: &[u8],
+) -> crate::Result<Chunk> {
+    let chunk = Chunk::new(data);
+    if let Entry::Vacant(entry) = journal.chunks.entry(chunk) {
+        let block_id = Uuid::new_v4().into();
+        StoreWriter::new(target, store_state).write_block(block_id, data)?;
+        entry.insert(block_id);
+    }
+    Ok(chunk)
+}
+
+/// Read the object map stored in the object with the given `handle`.
+///
+/// Because the type of the keys in the object map isn't known, the keys are returned as MessagePack
+/// values.
+///
+/// # Errors
+/// - `Error::Deserialize`: The object map could not be deserialized.
+/// - `Error::InvalidData`: Ciphertext verification failed.
+/// - `Error::Store`: An error occurred with the data store.
+/// - `Error::Io`: An I/O error occurred.
+fn read_raw_object_map(
+    state: &RepoState,
+    handle: &ObjectHandle,
+) -> crate::Result<Vec<(Value, ObjectHandle)>> {
+    let mut object_state = ObjectState::new(state.metadata.config.chunking.to_chunker());
+    let mut reader = ObjectReader::new(state, &mut object_state, handle);
+    let entries = match reader.deserialize()? {
+        Value::Map(entries) => entries,
+        _ => return Err(crate::Error::Deserialize),
+    };
+    entries
+        .into_iter()
+        .map(|(key, value)| {
+            let serialized_handle = to_vec(&value).map_err(|_| crate::Error::Deserialize)?;
+            let handle =
+                from_read(serialized_handle.as_slice()).map_err(|_| crate::Error::Deserialize)?;
+            Ok((key, handle))
+        })
+        .collect()
+}
+
+/// Write the given object map `entries` to a new object with the given `handle`.
+///
+/// # Errors
+/// - `Error::InvalidData`: Ciphertext verification failed.
+/// - `Error::Store`: An error occurred with the data store.
+/// - `Error::Io`: An I/O error occurred.
+fn write_raw_object_map(
+    state: &mut RepoState,
+    handle: &mut ObjectHandle,
+    entries: Vec<(Value, ObjectHandle)>,
+) -> crate::Result<()> {
+    let entries = entries
+        .into_iter()
+        .map(|(key, handle)| {
+            let serialized_handle =
+                to_vec(&handle).expect("Could not serialize the object handle.");
+            let value = from_read(serialized_handle.as_slice())
+                .expect("Could not deserialize the object handle.");
+            (key, value)
+        })
+        .collect();
+
+    // The old contents of the object can't be read using the new config, so we replace them rather
+    // than overwriting them.
+    handle.extents.clear();
+    let mut object_state = ObjectState::new(state.metadata.config.chunking.to_chunker());
+    let mut writer = ObjectWriter::new(state, &mut object_state, handle);
+    writer.serialize(&Value::Map(entries))
+}
+
+/// Rewrite the given `header` to reference the data re-encoded by the `journal`.
+///
+/// If the chunking method was changed, the object maps of each instance in `header` are read from
+/// the `source` state and rewritten in the `target` state. The IDs of the headers referenced by
+/// `header` are mapped using `header_ids`, and commits and snapshots whose headers are not in
+/// `header_ids` are removed.
+///
+/// # Errors
+/// - `Error::Corrupt`: The header references data which was not re-encoded.
+/// - `Error::Deserialize`: The object map of an instance could not be deserialized.
+/// - `Error::InvalidData`: Ciphertext verification failed.
+/// - `Error::Store`: An error occurred with the data store.
+/// - `Error::Io`: An I/O error occurred.
+fn reencode_header(
+    mut header: Header,
+    source: &RepoState,
+    target: &mut RepoState,
+    journal: &ReencodeJournal,
+    header_ids: &HashMap<BlockId, BlockId>,
+) -> crate::Result<Header> {
+    if journal.config.chunking == source.metadata.config.chunking {
+        header.chunks = header
+            .chunks
+            .into_iter()
+            .map(|(chunk, chunk_info)| {
+                let block_id = *journal.chunks.get(&chunk).ok_or(crate::Error::Corrupt)?;
+                let chunk_info = ChunkInfo {
+                    block_id,
+                    references: chunk_info.references,
+                };
+                Ok((chunk, chunk_info))
+            })
+            .collect::<crate::Result<_>>()?;
+    } else {
+        // Chunks are reference counted by the IDs of the handles which reference them, so we can
+        // rebuild the chunk map from the object handles in each instance.
+        let mut chunks = HashMap::new();
+        for instance_info in header.instances.values_mut() {
+            let mut entries = read_raw_object_map(source, &instance_info.objects)?;
+            for (_, handle) in entries.iter_mut() {
+                handle.extents = journal
+                    .extents
+                    .get(&handle.extents)
+                    .ok_or(crate::Error::Corrupt)?
+                    .clone();
+            }
+            let handles = entries
+                .iter()
+                .map(|(_, handle)| (handle.id, handle.chunks().collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            write_raw_object_map(target, &mut instance_info.objects, entries)?;
+
+            let map_handle = &instance_info.objects;
+            let handles = handles
+                .into_iter()
+                .chain(Some((map_handle.id, map_handle.chunks().collect())));
+            for (handle_id, handle_chunks) in handles {
+                for chunk in handle_chunks {
+                    let block_id = target
+                        .chunks
+                        .get(&chunk)
+                        .ok_or(crate::Error::Corrupt)?
+                        .block_id;
+                    chunks
+                        .entry(chunk)
+                        .or_insert_with(|| ChunkInfo {
+                            block_id,
+                            references: HashSet::new(),
+                        })
+                        .references
+                        .insert(handle_id);
+                }
+            }
+        }
+        header.chunks = chunks;
+    }
+
+    header.packs = header
+        .chunks
+        .values()
+        .filter_map(|chunk_info| {
+            let index_list = target.packs.get(&chunk_info.block_id)?;
+            Some((chunk_info.block_id, index_list.clone()))
+        })
+        .collect();
+    header.snapshots = header
+        .snapshots
+        .into_iter()
+        .filter_map(|(name, snapshot)| {
+            let header_id = *header_ids.get(&snapshot.header_id)?;
+            Some((
+                name,
+                Snapshot {
+                    header_id,
+                    ..snapshot
+                },
+            ))
+        })
+        .collect();
+    header.commits = header
+        .commits
+        .into_iter()
+        .filter_map(|commit| {
+            let header_id = *header_ids.get(&commit.header_id)?;
+            Some(CommitInfo {
+                header_id,
+                ..commit
+            })
+        })
+        .collect();
+    header.parent = header
+        .parent
+        .and_then(|header_id| header_ids.get(&header_id).copied());
+    header.reencode = None;
+
+    Ok(header)
+}
+
+impl<K: Key> RestoreSavepoint for KeyRepo<K> {
+    type Restore = KeyRestore<K>;
+
+    fn savepoint(&mut self) -> crate::Result<Savepoint> {
+        self.write_object_map()?;
+
+        Ok(Savepoint {
+            header: Arc::new(self.clone_header()),
+            transaction_id: Arc::downgrade(&self.transaction_id),
+        })
+    }
+
+    fn start_restore(&mut self, savepoint: &Savepoint) -> crate::Result<Self::Restore> {
+        match savepoint.transaction_id.upgrade() {
+            None => return Err(crate::Error::InvalidSavepoint),
+            Some(transaction_id) if transaction_id != self.transaction_id => {
+                return Err(crate::Error::InvalidSavepoint)
+            }
+            _ => (),
+        }
+
+        let old_header = self.replace_header((*savepoint.header).clone());
+
+        match self.read_object_map() {
+            Ok(objects) => Ok(KeyRestore {
+                objects,
+                header: self.replace_header(old_header),
+                transaction_id: savepoint.transaction_id.clone(),
+                instance_id: self.instance_id,
+            }),
+            Err(error) => {
+                self.replace_header(old_header);
+                Err(error)
+            }
+        }
+    }
+
+    fn finish_restore(&mut self, restore: Self::Restore) -> bool {
+        match restore.transaction_id.upgrade() {
+            None => return false,
+            Some(transaction_id) if transaction_id != self.transaction_id => return false,
+            _ => (),
+        }
+
+        if restore.instance_id != self.instance_id {
+            return false;
+        }
+
+        self.replace_header(restore.header);
+        self.objects = restore.objects;
+
+        true
+    }
+}
+
+impl<K: Key> Commit for KeyRepo<K> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    fn commit(&mut self) -> crate::Result<()> {
+        let state = self.state.read().unwrap();
+        if state.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        // If the data store can't conditionally write the super block atomically, two clients
+        // using optimistic concurrency control could both replace it, and the second would
+        // silently overwrite the first's commit. To prevent this, we hold an exclusive lock while
+        // committing instead.
+        let needs_lock = state.optimistic
+            && state.store.lock().unwrap().conditional_writes() < ConditionalWrites::Full;
+        drop(state);
+        if needs_lock {
+            self.with_exclusive_lock(|repo| repo.commit_unlocked())
+        } else {
+            self.commit_unlocked()
+        }
+    }
+
+    fn rollback(&mut self) -> crate::Result<()> {
+        let state = self.state.read().unwrap();
+        // Read the header from the previous commit from the data store.
+        let header = read_header(&state, state.metadata.header_id)?;
+        drop(state);
+
+        // Atomically restore from the deserialized header.
+        self.restore_header(header)
+    }
+
+    fn clean(&mut self) -> crate::Result<()> {
+        self.clean_retaining(&RetentionPolicy::default())
+    }
 }
 
 impl<K: Key> Unlock for KeyRepo<K> {
     fn unlock(&self) -> crate::Result<()> {
         let state = self.state.read().unwrap();
+        let lock_id = match state.lock_id {
+            Some(lock_id) => lock_id,
+            None => return Ok(()),
+        };
         let mut store = state.store.lock().unwrap();
-        unlock_store(&mut *store, state.lock_id)
+        unlock_store(&mut *store, lock_id)
     }
 
     fn is_locked(&self) -> crate::Result<bool> {
         let state = self.state.read().unwrap();
+        let lock_id = match state.lock_id {
+            Some(lock_id) => lock_id,
+            None => return Ok(false),
+        };
         let mut store = state.store.lock().unwrap();
         store
-            .read_block(BlockKey::Lock(state.lock_id))
+            .read_block(BlockKey::Lock(lock_id))
             .map_err(crate::Error::Store)
             .map(|result| result.is_some())
     }
 
     fn context(&self) -> crate::Result<Vec<u8>> {
         let state = self.state.read().unwrap();
+        let lock_id = state.lock_id.ok_or(crate::Error::NotLocked)?;
         let mut store = state.store.lock().unwrap();
-        let encrypted_context = store
-            .read_block(BlockKey::Lock(state.lock_id))
-            .map_err(crate::Error::Store)?
-            .ok_or(crate::Error::NotLocked)?;
-        state
-            .metadata
-            .config
-            .encryption
-            .decrypt(&encrypted_context, &state.master_key)
+        let lock = read_lock(
+            &mut *store,
+            &state.metadata.config.encryption,
+            &state.master_key,
+            lock_id,
+        )?
+        .ok_or(crate::Error::NotLocked)?;
+        Ok(lock.context)
     }
 
     fn update_context(&self, context: &[u8]) -> crate::Result<()> {
         let state = self.state.read().unwrap();
+        let lock_id = state.lock_id.ok_or(crate::Error::NotLocked)?;
+        let kind = if state.read_only {
+            LockKind::Shared {
+                header_id: state.metadata.header_id,
+            }
+        } else if state.optimistic {
+            LockKind::Optimistic
+        } else {
+            LockKind::Exclusive
+        };
+        let lock = LockInfo {
+            kind,
+            context: context.to_vec(),
+        };
         let mut store = state.store.lock().unwrap();
-        let encrypted_context = state
-            .metadata
-            .config
-            .encryption
-            .encrypt(context, &state.master_key);
-        store
-            .write_block(BlockKey::Lock(state.lock_id), &encrypted_context)
-            .map_err(crate::Error::Store)
+        write_lock(
+            &mut *store,
+            &state.metadata.config.encryption,
+            &state.master_key,
+            lock_id,
+            &lock,
+        )
     }
 }
@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::BlockId;
+
+uuid_type! {
+    /// A UUID which uniquely identifies a commit in a repository.
+    CommitId
+}
+
+/// Information about a commit in a repository.
+///
+/// Each time a repository is committed, a new commit is added to its history. Old commits are
+/// removed from the history when the repository is cleaned unless they are retained by a
+/// [`RetentionPolicy`].
+///
+/// Until then, every commit keeps its repository header in the data store, along with any data it
+/// references, and the whole history is rewritten with each new commit. A repository which is
+/// committed often should be cleaned regularly to keep commits fast and reclaim this space.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitInfo {
+    /// The unique ID of this commit.
+    pub(super) id: CommitId,
+
+    /// The time this commit was made.
+    pub(super) time: SystemTime,
+
+    /// The ID of the block which stores the repository header for this commit.
+    pub(super) header_id: BlockId,
+}
+
+impl CommitInfo {
+    /// The unique ID of this commit.
+    pub fn id(&self) -> CommitId {
+        self.id
+    }
+
+    /// The time this commit was made.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+}
+
+/// A policy for which old commits to retain when cleaning a repository.
+///
+/// The most recent commit is always retained. A commit which is selected by any of the rules in
+/// this policy is retained. For the hourly, daily, and monthly rules, the most recent commit in each
+/// period is retained, starting with the most recent period which contains a commit. Periods are
+/// measured in UTC.
+///
+/// This type implements `Default`, which retains only the most recent commit.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct RetentionPolicy {
+    /// The number of most recent commits to retain.
+    pub last: u32,
+
+    /// The number of hours for which to retain the most recent commit in that hour.
+    pub hourly: u32,
+
+    /// The number of days for which to retain the most recent commit in that day.
+    pub daily: u32,
+
+    /// The number of months for which to retain the most recent commit in that month.
+    pub monthly: u32,
+}
+
+impl RetentionPolicy {
+    /// Return the IDs of the commits in `commits` which are retained by this policy.
+    ///
+    /// The given `commits` must be ordered from oldest to newest.
+    pub(super) fn retained(&self, commits: &[CommitInfo]) -> HashSet<CommitId> {
+        let mut retained = HashSet::new();
+
+        // The most recent commit is always retained.
+        retained.extend(commits.last().map(|commit| commit.id));
+
+        retained.extend(
+            commits
+                .iter()
+                .rev()
+                .take(self.last as usize)
+                .map(|commit| commit.id),
+        );
+
+        let periods: [(u32, PeriodFn); 3] = [
+            (self.hourly, |secs| (secs / 3600) as i64),
+            (self.daily, |secs| (secs / 86400) as i64),
+            (self.monthly, month_of),
+        ];
+
+        for (count, period_of) in periods {
+            let mut last_period = None;
+            let mut periods_retained = 0;
+            for commit in commits.iter().rev() {
+                if periods_retained >= count {
+                    break;
+                }
+                let period = period_of(unix_secs(commit.time));
+                if last_period != Some(period) {
+                    retained.insert(commit.id);
+                    periods_retained += 1;
+                    last_period = Some(period);
+                }
+            }
+        }
+
+        retained
+    }
+}
+
+/// A function which returns the period which a number of seconds since the Unix epoch falls in.
+type PeriodFn = fn(u64) -> i64;
+
+/// Return the number of whole seconds between the Unix epoch and `time`.
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Return the number of calendar months between the Unix epoch and `secs` seconds after it.
+fn month_of(secs: u64) -> i64 {
+    // This converts a number of days since the epoch to a date in the proleptic Gregorian calendar.
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    year * 12 + month - 1
+}
+
+/// A report of the changes which would be made by cleaning a repository.
+///
+/// This is returned by [`KeyRepo::clean_dry_run`].
+///
+/// [`KeyRepo::clean_dry_run`]: crate::repo::key::KeyRepo::clean_dry_run
+#[derive(Debug, Clone)]
+pub struct CleanReport {
+    pub(super) dropped_commits: Vec<CommitInfo>,
+    pub(super) reclaimed_bytes: u64,
+}
+
+impl CleanReport {
+    /// The commits which would be removed from the repository's history.
+    pub fn dropped_commits(&self) -> &[CommitInfo] {
+        &self.dropped_commits
+    }
+
+    /// The number of bytes which would be reclaimed in the backing data store.
+    ///
+    /// If packing is enabled, this is an estimate, as blocks which are still referenced may need to
+    /// be written to new packs.
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.reclaimed_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use maplit::hashset;
+    use spectral::prelude::*;
+    use uuid::Uuid;
+
+    use super::{month_of, CommitInfo, RetentionPolicy};
+
+    /// Return a list of commits made at each of the given number of seconds after the epoch.
+    fn commits_at(secs: &[u64]) -> Vec<CommitInfo> {
+        secs.iter()
+            .map(|secs| CommitInfo {
+                id: Uuid::new_v4().into(),
+                time: UNIX_EPOCH + Duration::from_secs(*secs),
+                header_id: Uuid::new_v4().into(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_policy_retains_most_recent_commit() {
+        let commits = commits_at(&[0, 10, 20]);
+        let retained = RetentionPolicy::default().retained(&commits);
+        assert_that!(retained).is_equal_to(hashset![commits[2].id]);
+    }
+
+    #[test]
+    fn last_policy_retains_most_recent_commits() {
+        let commits = commits_at(&[0, 10, 20, 30]);
+        let policy = RetentionPolicy {
+            last: 2,
+            ..RetentionPolicy::default()
+        };
+        let retained = policy.retained(&commits);
+        assert_that!(retained).is_equal_to(hashset![commits[2].id, commits[3].id]);
+    }
+
+    #[test]
+    fn hourly_policy_retains_most_recent_commit_in_each_hour() {
+        let commits = commits_at(&[0, 1800, 3600, 5400, 7200]);
+        let policy = RetentionPolicy {
+            hourly: 2,
+            ..RetentionPolicy::default()
+        };
+        let retained = policy.retained(&commits);
+        assert_that!(retained).is_equal_to(hashset![commits[3].id, commits[4].id]);
+    }
+
+    #[test]
+    fn daily_policy_skips_days_without_commits() {
+        let commits = commits_at(&[0, 86400 * 5, 86400 * 5 + 10]);
+        let policy = RetentionPolicy {
+            daily: 2,
+            ..RetentionPolicy::default()
+        };
+        let retained = policy.retained(&commits);
+        assert_that!(retained).is_equal_to(hashset![commits[0].id, commits[2].id]);
+    }
+
+    #[test]
+    fn months_are_calendar_months() {
+        // 2021-01-31T00:00:00Z and 2021-02-01T00:00:00Z
+        assert_that!(month_of(1612051200)).is_equal_to(2021 * 12);
+        assert_that!(month_of(1612137600)).is_equal_to(2021 * 12 + 1);
+        // 2020-02-29T00:00:00Z
+        assert_that!(month_of(1582934400)).is_equal_to(2020 * 12 + 1);
+        assert_that!(month_of(0)).is_equal_to(1970 * 12);
+    }
+}
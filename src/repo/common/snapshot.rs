@@ -0,0 +1,42 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::BlockId;
+
+/// A named, persistent snapshot of a repository.
+///
+/// A snapshot captures the state of every instance of the repository when it was created. Unlike a
+/// [`Savepoint`], a snapshot is persisted to the data store when the repository is committed and
+/// survives the repository being closed and reopened. Data referenced by a snapshot is not removed
+/// when the repository is cleaned until the snapshot is removed.
+///
+/// Snapshots are created with [`KeyRepo::create_snapshot`] and can be opened as a read-only
+/// repository with [`KeyRepo::open_snapshot`].
+///
+/// [`Savepoint`]: crate::repo::Savepoint
+/// [`KeyRepo::create_snapshot`]: crate::repo::key::KeyRepo::create_snapshot
+/// [`KeyRepo::open_snapshot`]: crate::repo::key::KeyRepo::open_snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The name of this snapshot.
+    pub(super) name: String,
+
+    /// The time this snapshot was created.
+    pub(super) time: SystemTime,
+
+    /// The ID of the block which stores the repository header for this snapshot.
+    pub(super) header_id: BlockId,
+}
+
+impl Snapshot {
+    /// The name of this snapshot.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The time this snapshot was created.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+}
@@ -1,12 +1,13 @@
 use std::collections::{HashMap, HashSet};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use cdchunking::ChunkerImpl;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::store::{BlockId, DataStore};
+use crate::store::{BlockId, DataStore, StoreMetrics};
 
+use super::chunk_cache::ChunkCache;
 use super::chunk_store::StoreState;
 use super::chunking::IncrementalChunker;
 use super::encryption::EncryptionKey;
@@ -14,6 +15,8 @@ use super::handle::{Chunk, Extent, HandleId, ObjectHandle};
 use super::lock::{unlock_store, Lock, LockTable};
 use super::metadata::RepoMetadata;
 use super::open_repo::VersionId;
+use super::read_pool::{Prefetcher, ReadPool};
+use super::write_pool::{PendingWrites, WritePool};
 
 /// Information about a chunk in a repository.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -94,7 +97,9 @@ pub struct InstanceInfo {
 #[derive(Debug)]
 pub struct RepoState {
     /// The data store which backs this repository.
-    pub store: Mutex<Box<dyn DataStore>>,
+    ///
+    /// This is shared with any read-only views of this repository, such as snapshots.
+    pub store: Arc<Mutex<Box<dyn DataStore>>>,
 
     /// The metadata for the repository.
     pub metadata: RepoMetadata,
@@ -113,15 +118,46 @@ pub struct RepoState {
 
     /// The `BlockId` of the key which stores the lock on the repository.
     ///
-    /// This is used to release the lock when the repository is dropped.
-    pub lock_id: BlockId,
+    /// This is used to release the lock when the repository is dropped. This is `None` if this
+    /// repository does not hold a lock on the data store.
+    pub lock_id: Option<BlockId>,
+
+    /// Whether this repository is read-only.
+    pub read_only: bool,
+
+    /// Whether this repository uses optimistic concurrency control.
+    ///
+    /// If this is `true`, other clients may commit changes to the repository while it is open.
+    pub optimistic: bool,
+
+    /// The pool used to write chunks concurrently.
+    ///
+    /// If this is `None`, chunks are written one at a time.
+    pub write_pool: Option<Arc<WritePool>>,
+
+    /// The pool used to read chunks ahead of the current position in an object.
+    ///
+    /// If this is `None`, chunks are only read once they're needed.
+    pub read_pool: Option<Arc<ReadPool>>,
+
+    /// The cache of decoded chunks and packs.
+    ///
+    /// This is shared with any read-only views of this repository, such as snapshots.
+    pub chunk_cache: Arc<Mutex<ChunkCache>>,
+
+    /// The metrics collected for the data store and the connections used by worker threads.
+    ///
+    /// This is shared with any read-only views of this repository, such as snapshots.
+    pub store_metrics: StoreMetrics,
 }
 
 impl Drop for RepoState {
     fn drop(&mut self) {
         // Attempt to release the lock on the repository. This may fail.
-        let mut store = self.store.lock().unwrap();
-        unlock_store(&mut *store, self.lock_id).ok();
+        if let Some(lock_id) = self.lock_id {
+            let mut store = self.store.lock().unwrap();
+            unlock_store(&mut *store, lock_id).ok();
+        }
     }
 }
 
@@ -192,6 +228,12 @@ pub struct ObjectState {
 
     /// The state for reading and writing blocks to the data store.
     pub store_state: StoreState,
+
+    /// The chunks which are being written by the repository's write pool.
+    pub pending_writes: PendingWrites,
+
+    /// The chunks which are being read ahead by the repository's read pool.
+    pub prefetcher: Prefetcher,
 }
 
 impl ObjectState {
@@ -207,6 +249,8 @@ impl ObjectState {
             hole_buffer: Vec::new(),
             transaction_lock: None,
             store_state: StoreState::new(),
+            pending_writes: PendingWrites::new(),
+            prefetcher: Prefetcher::new(),
         }
     }
 }
@@ -0,0 +1,226 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Debug, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+
+use super::chunk_store::{BlockCodec, EncodeBlock};
+use super::handle::Chunk;
+use crate::store::{BlockId, BlockKey, DataStore};
+
+/// The result of writing a block on a `WritePool`.
+type WriteResult = (BlockId, crate::Result<()>);
+
+/// A request to encode and write a block on a `WritePool`.
+struct WriteJob {
+    /// The ID of the block to write.
+    id: BlockId,
+
+    /// The unencoded data to write.
+    data: Vec<u8>,
+
+    /// The channel to send the result of the write to.
+    results: Sender<WriteResult>,
+}
+
+/// A pool of worker threads which encode blocks and write them to a data store concurrently.
+///
+/// Each worker thread has its own connection to the data store so that several writes can be in
+/// flight at once.
+pub struct WritePool {
+    /// The channel used to send jobs to the worker threads.
+    ///
+    /// This is `None` once the pool is being shut down.
+    jobs: Option<Mutex<Sender<WriteJob>>>,
+
+    /// The worker threads.
+    workers: Vec<JoinHandle<()>>,
+
+    /// The codec the worker threads use to encode blocks.
+    codec: Arc<RwLock<BlockCodec>>,
+
+    /// The IDs of blocks which are being written or whose writes haven't been finished by the
+    /// object which started them.
+    in_flight: Mutex<HashSet<BlockId>>,
+}
+
+impl Debug for WritePool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WritePool")
+            .field("workers", &self.workers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl WritePool {
+    /// Start a new pool with one worker thread for each of the given `stores`.
+    ///
+    /// Blocks are encoded using the given `codec`.
+    pub fn new(stores: Vec<Box<dyn DataStore>>, codec: Arc<RwLock<BlockCodec>>) -> Self {
+        let (job_sender, job_receiver) = channel::<WriteJob>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = stores
+            .into_iter()
+            .map(|mut store| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let codec = Arc::clone(&codec);
+                thread::spawn(move || loop {
+                    // The lock is released before the job is run so other workers can receive jobs.
+                    let job = match job_receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+                    // Panics are caught so the writer isn't left waiting for a result forever.
+                    let result = catch_unwind(AssertUnwindSafe(|| {
+                        let encoded_data = codec.read().unwrap().encode_data(&job.data)?;
+                        store
+                            .write_block(BlockKey::Data(job.id), &encoded_data)
+                            .map_err(crate::Error::Store)
+                    }))
+                    .unwrap_or_else(|_| {
+                        Err(crate::Error::Store(crate::store::Error::msg(
+                            "A worker thread panicked while writing a block.",
+                        )))
+                    });
+                    // The writer may have been dropped, in which case the result is discarded.
+                    job.results.send((job.id, result)).ok();
+                })
+            })
+            .collect();
+
+        Self {
+            jobs: Some(Mutex::new(job_sender)),
+            workers,
+            codec,
+            in_flight: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// The number of writes which can be in progress at once.
+    pub fn concurrency(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Replace the codec used to encode blocks.
+    ///
+    /// Blocks which are already being written may still be encoded with the old codec.
+    pub fn set_codec(&self, codec: BlockCodec) {
+        *self.codec.write().unwrap() = codec;
+    }
+
+    /// Return the IDs of blocks which are being written.
+    ///
+    /// These blocks are not yet referenced by the repository, but they must not be removed when
+    /// cleaning the repository.
+    pub fn in_flight_blocks(&self) -> Vec<BlockId> {
+        self.in_flight.lock().unwrap().iter().copied().collect()
+    }
+}
+
+impl Drop for WritePool {
+    fn drop(&mut self) {
+        // Closing the channel causes the workers to exit once they finish any remaining jobs.
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+/// The chunks which an object is currently writing on a `WritePool`.
+#[derive(Debug)]
+pub struct PendingWrites {
+    /// The chunks which are being written, keyed by the ID of the block they're written to.
+    chunks: HashMap<BlockId, Chunk>,
+
+    /// The pool which is writing the chunks.
+    pool: Option<Arc<WritePool>>,
+
+    /// The channel used by the workers to send back the results of writes.
+    sender: Mutex<Sender<WriteResult>>,
+
+    /// The channel used to receive the results of writes.
+    receiver: Mutex<Receiver<WriteResult>>,
+}
+
+impl PendingWrites {
+    /// Create a new empty `PendingWrites`.
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            chunks: HashMap::new(),
+            pool: None,
+            sender: Mutex::new(sender),
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    /// The number of writes which are in progress.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Return whether the given `chunk` is being written.
+    pub fn contains(&self, chunk: &Chunk) -> bool {
+        self.chunks
+            .values()
+            .any(|pending_chunk| pending_chunk == chunk)
+    }
+
+    /// Start writing the given `chunk` with the given `data` to the block with the given `id`.
+    pub fn start(&mut self, pool: &Arc<WritePool>, id: BlockId, chunk: Chunk, data: Vec<u8>) {
+        pool.in_flight.lock().unwrap().insert(id);
+        let job = WriteJob {
+            id,
+            data,
+            results: self.sender.lock().unwrap().clone(),
+        };
+        pool.jobs
+            .as_ref()
+            .expect("The write pool has been shut down.")
+            .lock()
+            .unwrap()
+            .send(job)
+            .expect("The write pool's workers have exited.");
+        self.chunks.insert(id, chunk);
+        self.pool = Some(Arc::clone(pool));
+    }
+
+    /// Wait for the next write to finish and return its chunk, block ID, and result.
+    ///
+    /// This returns `None` if there are no writes in progress.
+    pub fn finish_next(&mut self) -> Option<(Chunk, BlockId, crate::Result<()>)> {
+        if self.chunks.is_empty() {
+            return None;
+        }
+        let (id, result) = self
+            .receiver
+            .lock()
+            .unwrap()
+            .recv()
+            .expect("The write pool's workers have exited.");
+        let chunk = self
+            .chunks
+            .remove(&id)
+            .expect("Received the result of a write which was not started.");
+        if let Some(pool) = &self.pool {
+            pool.in_flight.lock().unwrap().remove(&id);
+        }
+        Some((chunk, id, result))
+    }
+}
+
+impl Drop for PendingWrites {
+    fn drop(&mut self) {
+        // Any blocks which are still being written will never be referenced, so they no longer
+        // need to be protected from being cleaned.
+        if let Some(pool) = &self.pool {
+            let mut in_flight = pool.in_flight.lock().unwrap();
+            for id in self.chunks.keys() {
+                in_flight.remove(id);
+            }
+        }
+    }
+}
@@ -35,6 +35,19 @@
 //! **Removing an existing lock is potentially dangerous, as concurrent access to a repository can
 //! cause data loss.**
 //!
+//! A repository can also be opened read-only using [`OpenMode::ReadOnly`]. A read-only repository
+//! takes a shared lock instead of an exclusive one, so any number of read-only repositories can be
+//! open at the same time as each other and as one writer. A read-only repository sees the
+//! repository as of the last commit when it was opened, even if the writer commits new changes.
+//! Data which is still being read by a read-only repository is not removed when the repository is
+//! cleaned.
+//!
+//! Multiple clients can also write to a repository at the same time by opening it with
+//! [`OpenOptions::optimistic`]. Instead of locking the repository exclusively, each client commits
+//! a new header based on the one it last saw, and a commit which conflicts with changes committed
+//! by another client in the meantime fails with `Error::Conflict`. Changes which don't conflict are
+//! merged automatically.
+//!
 //! See [`Unlock`] for more information about locking.
 //!
 //! # Atomicity
@@ -53,6 +66,17 @@
 //! atomically undo or redo changes to a repository without rolling back all changes made since the
 //! last commit. See [`RestoreSavepoint`] for more information.
 //!
+//! # Snapshots
+//! Repositories support creating named snapshots of their current state. Unlike savepoints,
+//! snapshots are stored persistently in the data store and survive the repository being closed.
+//! A snapshot can be opened as a read-only repository, and data referenced by a snapshot is not
+//! removed when the repository is cleaned until the snapshot is removed. See [`Snapshot`] for more
+//! information.
+//!
+//! Each commit is also recorded in the repository's commit history. By default, cleaning a
+//! repository removes every commit except the most recent one from its history, but you can keep
+//! older commits according to a [`RetentionPolicy`] and open them as read-only repositories.
+//!
 //! # Encryption
 //! If encryption is enabled, the Argon2id key derivation function is used to derive a key from a
 //! user-supplied password. This key is used to encrypt the repository's randomly generated master
@@ -68,6 +92,12 @@
 //! The information in [`RepoInfo`] is never encrypted, and can be read without decrypting the
 //! repository using [`peek_info`].
 //!
+//! # Re-encoding
+//! The configuration of a repository is chosen when it is created, but an existing repository can
+//! be migrated to a new [`RepoConfig`] with [`KeyRepo::reencode`]. This rewrites all the data in the
+//! repository with the new chunking, packing, compression, and encryption methods and commits the
+//! change atomically. Re-encoding can be resumed if it is interrupted.
+//!
 //! # Instances
 //! A repository can consist of multiple instances, each identified by an [`InstanceId`]. Each
 //! repository instance has completely separate contents, meaning that data in one instance won't
@@ -106,20 +136,30 @@
 //! [`Commit::commit`]: crate::repo::Commit::commit
 //! [`Commit::clean`]: crate::repo::Commit::clean
 //! [`RestoreSavepoint`]: crate::repo::RestoreSavepoint
+//! [`Snapshot`]: crate::repo::Snapshot
+//! [`RetentionPolicy`]: crate::repo::RetentionPolicy
 //! [`Packing`]: crate::repo::Packing
 //! [`RepoInfo`]: crate::repo::RepoInfo
+//! [`RepoConfig`]: crate::repo::RepoConfig
+//! [`KeyRepo::reencode`]: crate::repo::key::KeyRepo::reencode
 //! [`peek_info`]: crate::repo::peek_info
 //! [`InstanceId`]: crate::repo::InstanceId
 //! [`SwitchInstance::switch_instance`]: crate::repo::SwitchInstance::switch_instance
 //! [`FileRepo`]: crate::repo::file::FileRepo
 
 pub use self::common::{
-    peek_info, Chunking, Commit, Compression, ContentId, Encryption, InstanceId, Object, ObjectId,
-    ObjectStats, OpenMode, OpenOptions, OpenRepo, Packing, ReadOnlyObject, RepoConfig, RepoId,
-    RepoInfo, RepoStats, ResourceLimit, Restore, RestoreSavepoint, Savepoint, SwitchInstance,
-    Unlock, VersionId, DEFAULT_INSTANCE,
+    peek_info, CacheStats, Chunking, CleanReport, Commit, CommitId, CommitInfo, Compression,
+    ContentId, Encryption, InstanceId, Object, ObjectId, ObjectStats, OpenMode, OpenOptions,
+    OpenRepo, Packing, ReadOnlyObject, ReencodeProgress, ReencodeReport, RepoConfig, RepoId,
+    RepoInfo, RepoStats, ResourceLimit, Restore, RestoreSavepoint, RetentionPolicy, Savepoint,
+    Snapshot, SwitchInstance, Unlock, VersionId, DEFAULT_INSTANCE,
 };
 
+#[cfg(feature = "async")]
+pub use self::common::AsyncObject;
+#[cfg(feature = "compression-zstd")]
+pub use self::common::ZstdDictionary;
+
 /// An object store which maps keys to seekable binary blobs.
 ///
 /// This module contains the [`KeyRepo`] repository type.
@@ -8,9 +8,11 @@ use uuid::uuid;
 use super::info::{KeyId, KeyIdTable, ObjectKey, RepoKey, RepoState, StateRestore};
 use super::iter::Keys;
 use crate::repo::{
-    key::KeyRepo, Commit, InstanceId, Object, OpenRepo, RepoInfo, RepoStats, ResourceLimit,
-    RestoreSavepoint, Savepoint, Unlock, VersionId,
+    key::KeyRepo, CacheStats, CleanReport, Commit, CommitId, CommitInfo, InstanceId, Object,
+    OpenRepo, RepoInfo, RepoStats, ResourceLimit, RestoreSavepoint, RetentionPolicy, Savepoint,
+    Snapshot, Unlock, VersionId,
 };
+use crate::store::StoreMetrics;
 
 /// A low-level repository type which can be used to implement higher-level repository types
 ///
@@ -88,6 +90,11 @@ where
 
     /// Write the state and ID table to the backing repository.
     fn write_state(&mut self) -> crate::Result<()> {
+        // A read-only repository can't modify its state, so there is nothing to write.
+        if self.repo.is_read_only() {
+            return Ok(());
+        }
+
         // We write to a temporary object before copying to the final destination to make the write
         // atomic.
         let mut object = self.repo.insert(RepoKey::Stage);
@@ -259,10 +266,97 @@ where
         self.repo.stats()
     }
 
+    /// Return statistics about the repository's chunk cache.
+    ///
+    /// See [`KeyRepo::cache_stats`] for details.
+    ///
+    /// [`KeyRepo::cache_stats`]: crate::repo::key::KeyRepo::cache_stats
+    pub fn cache_stats(&self) -> CacheStats {
+        self.repo.cache_stats()
+    }
+
+    /// Return the metrics collected for the repository's data store.
+    ///
+    /// See [`KeyRepo::store_metrics`] for details.
+    ///
+    /// [`KeyRepo::store_metrics`]: crate::repo::key::KeyRepo::store_metrics
+    pub fn store_metrics(&self) -> StoreMetrics {
+        self.repo.store_metrics()
+    }
+
     /// Return information about the repository.
     pub fn info(&self) -> RepoInfo {
         self.repo.info()
     }
+
+    /// Create a new snapshot of the repository with the given `name` and return it.
+    ///
+    /// See [`KeyRepo::create_snapshot`] for details.
+    ///
+    /// [`KeyRepo::create_snapshot`]: crate::repo::key::KeyRepo::create_snapshot
+    pub fn create_snapshot(&mut self, name: &str) -> crate::Result<Snapshot> {
+        self.write_state()?;
+        self.repo.create_snapshot(name)
+    }
+
+    /// Return a list of the snapshots in this repository, ordered by the time they were created.
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        self.repo.snapshots()
+    }
+
+    /// Remove the snapshot with the given `name` from the repository.
+    ///
+    /// See [`KeyRepo::remove_snapshot`] for details.
+    ///
+    /// [`KeyRepo::remove_snapshot`]: crate::repo::key::KeyRepo::remove_snapshot
+    pub fn remove_snapshot(&mut self, name: &str) -> bool {
+        self.repo.remove_snapshot(name)
+    }
+
+    /// Open the snapshot with the given `name` as a read-only repository.
+    ///
+    /// See [`KeyRepo::open_snapshot`] for details.
+    ///
+    /// [`KeyRepo::open_snapshot`]: crate::repo::key::KeyRepo::open_snapshot
+    pub fn open_snapshot(&self, name: &str) -> crate::Result<Self> {
+        Self::open_repo(self.repo.open_snapshot(name)?)
+    }
+
+    /// Clean up the repository, retaining old commits according to the given `policy`.
+    ///
+    /// See [`KeyRepo::clean_retaining`] for details.
+    ///
+    /// [`KeyRepo::clean_retaining`]: crate::repo::key::KeyRepo::clean_retaining
+    pub fn clean_retaining(&mut self, policy: &RetentionPolicy) -> crate::Result<()> {
+        self.repo.clean_retaining(policy)
+    }
+
+    /// Return a report of the changes `clean_retaining` would make with the given `policy`.
+    ///
+    /// See [`KeyRepo::clean_dry_run`] for details.
+    ///
+    /// [`KeyRepo::clean_dry_run`]: crate::repo::key::KeyRepo::clean_dry_run
+    pub fn clean_dry_run(&self, policy: &RetentionPolicy) -> crate::Result<CleanReport> {
+        self.repo.clean_dry_run(policy)
+    }
+
+    /// Return a list of the commits in this repository's history, ordered from oldest to newest.
+    ///
+    /// See [`KeyRepo::commits`] for details.
+    ///
+    /// [`KeyRepo::commits`]: crate::repo::key::KeyRepo::commits
+    pub fn commits(&self) -> Vec<CommitInfo> {
+        self.repo.commits()
+    }
+
+    /// Open the commit with the given `id` as a read-only repository.
+    ///
+    /// See [`KeyRepo::open_commit`] for details.
+    ///
+    /// [`KeyRepo::open_commit`]: crate::repo::key::KeyRepo::open_commit
+    pub fn open_commit(&self, id: CommitId) -> crate::Result<Self> {
+        Self::open_repo(self.repo.open_commit(id)?)
+    }
 }
 
 impl<State> Commit for StateRepo<State>
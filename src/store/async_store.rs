@@ -0,0 +1,427 @@
+#![cfg(feature = "async")]
+
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Condvar, Mutex};
+
+use async_trait::async_trait;
+use static_assertions::assert_obj_safe;
+use tokio::runtime::Handle;
+use tokio::task::spawn_blocking;
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::OpenStore;
+
+/// A persistent store for blocks of data which performs I/O asynchronously.
+///
+/// This is an asynchronous counterpart to [`DataStore`], and its methods have the same semantics
+/// as the corresponding methods of [`DataStore`].
+///
+/// Repositories can only be backed by a [`DataStore`]. You can use [`BlockingStore`] to use an
+/// `AsyncDataStore` as a [`DataStore`] and [`SpawnBlockingStore`] to use a [`DataStore`] as an
+/// `AsyncDataStore`.
+///
+/// [`DataStore`]: crate::store::DataStore
+/// [`BlockingStore`]: crate::store::BlockingStore
+/// [`SpawnBlockingStore`]: crate::store::SpawnBlockingStore
+#[async_trait]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub trait AsyncDataStore: Send {
+    /// Write the given `data` as a new block with the given `key`.
+    ///
+    /// See [`DataStore::write_block`] for details.
+    ///
+    /// [`DataStore::write_block`]: crate::store::DataStore::write_block
+    async fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()>;
+
+    /// Return the bytes of the block with the given `key`.
+    ///
+    /// See [`DataStore::read_block`] for details.
+    ///
+    /// [`DataStore::read_block`]: crate::store::DataStore::read_block
+    async fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>>;
+
+    /// Remove the block with the given `key` from the store.
+    ///
+    /// See [`DataStore::remove_block`] for details.
+    ///
+    /// [`DataStore::remove_block`]: crate::store::DataStore::remove_block
+    async fn remove_block(&mut self, key: BlockKey) -> super::Result<()>;
+
+    /// Return a list of IDs of blocks of the given `kind` in the store.
+    ///
+    /// See [`DataStore::list_blocks`] for details.
+    ///
+    /// [`DataStore::list_blocks`]: crate::store::DataStore::list_blocks
+    async fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>>;
+
+    /// Return the size of the block with the given `key` in bytes.
+    ///
+    /// See [`DataStore::block_size`] for details.
+    ///
+    /// [`DataStore::block_size`]: crate::store::DataStore::block_size
+    async fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        Ok(self.read_block(key).await?.map(|data| data.len() as u64))
+    }
+
+    /// Write the given `data` to the block with the given `key` only if its current contents are
+    /// `expected`.
+    ///
+    /// See [`DataStore::write_block_if`] for details.
+    ///
+    /// [`DataStore::write_block_if`]: crate::store::DataStore::write_block_if
+    async fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        if self.read_block(key).await?.as_deref() != expected {
+            return Ok(false);
+        }
+        self.write_block(key, data).await?;
+        Ok(true)
+    }
+
+    /// Write the given `data` as a new block with the given `key` only if no such block exists.
+    ///
+    /// See [`DataStore::create_block_exclusive`] for details.
+    ///
+    /// [`DataStore::create_block_exclusive`]: crate::store::DataStore::create_block_exclusive
+    async fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.write_block_if(key, None, data).await
+    }
+
+    /// Return which conditional writes this data store can perform atomically.
+    ///
+    /// See [`DataStore::conditional_writes`] for details.
+    ///
+    /// [`DataStore::conditional_writes`]: crate::store::DataStore::conditional_writes
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::None
+    }
+}
+
+assert_obj_safe!(AsyncDataStore);
+
+#[async_trait]
+impl AsyncDataStore for Box<dyn AsyncDataStore> {
+    async fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.as_mut().write_block(key, data).await
+    }
+
+    async fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.as_mut().read_block(key).await
+    }
+
+    async fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.as_mut().remove_block(key).await
+    }
+
+    async fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.as_mut().list_blocks(kind).await
+    }
+
+    async fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.as_mut().block_size(key).await
+    }
+
+    async fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        self.as_mut().write_block_if(key, expected, data).await
+    }
+
+    async fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.as_mut().create_block_exclusive(key, data).await
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.as_ref().conditional_writes()
+    }
+}
+
+impl Debug for dyn AsyncDataStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("AsyncDataStore")
+    }
+}
+
+/// A `DataStore` which wraps an [`AsyncDataStore`].
+///
+/// This blocks the current thread on each operation of the wrapped store using a Tokio runtime.
+/// Because of this, its methods panic if they are called from within an asynchronous context. To
+/// use a repository backed by this store from asynchronous code, call its methods from within
+/// [`tokio::task::spawn_blocking`].
+///
+/// This type implements [`OpenStore`] if the wrapped store implements `Clone`, so it can be used
+/// with [`OpenOptions`] to open repositories.
+///
+/// [`AsyncDataStore`]: crate::store::AsyncDataStore
+/// [`OpenStore`]: crate::store::OpenStore
+/// [`OpenOptions`]: crate::repo::OpenOptions
+#[derive(Debug, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub struct BlockingStore<S> {
+    store: S,
+    handle: Handle,
+}
+
+impl<S: AsyncDataStore> BlockingStore<S> {
+    /// Wrap the given `store`, running its operations on the runtime with the given `handle`.
+    pub fn new(store: S, handle: Handle) -> Self {
+        Self { store, handle }
+    }
+
+    /// Consume this value, returning the wrapped store.
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+}
+
+impl<S: AsyncDataStore> DataStore for BlockingStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.handle.block_on(self.store.write_block(key, data))
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.handle.block_on(self.store.read_block(key))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.handle.block_on(self.store.remove_block(key))
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.handle.block_on(self.store.list_blocks(kind))
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.handle.block_on(self.store.block_size(key))
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        self.handle
+            .block_on(self.store.write_block_if(key, expected, data))
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.handle
+            .block_on(self.store.create_block_exclusive(key, data))
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.store.conditional_writes()
+    }
+}
+
+impl<S: AsyncDataStore + Clone + 'static> OpenStore for BlockingStore<S> {
+    type Store = Self;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(self.clone())
+    }
+}
+
+/// A function which opens a new data store.
+type OpenFn<S> = Box<dyn Fn() -> crate::Result<S> + Send + Sync>;
+
+/// The data stores used by a `SpawnBlockingStore` and its clones.
+struct StorePool<S> {
+    /// The stores which are not currently performing an operation.
+    idle: Mutex<Vec<S>>,
+
+    /// A condition variable which is notified when a store becomes idle.
+    available: Condvar,
+
+    /// A function which opens a new store when every store is busy.
+    ///
+    /// If this is `None`, operations wait until a store becomes idle.
+    open: Option<OpenFn<S>>,
+
+    /// Which conditional writes the stores can perform atomically.
+    conditional_writes: ConditionalWrites,
+}
+
+impl<S> StorePool<S> {
+    /// Take an idle store from the pool, opening a new one or waiting if there are none.
+    fn take(&self) -> super::Result<S> {
+        let mut idle = self.idle.lock().unwrap();
+        loop {
+            if let Some(store) = idle.pop() {
+                return Ok(store);
+            }
+            if let Some(open) = &self.open {
+                drop(idle);
+                return open().map_err(|error| match error {
+                    crate::Error::Store(error) => error,
+                    error => super::Error::new(error),
+                });
+            }
+            idle = self.available.wait(idle).unwrap();
+        }
+    }
+
+    /// Return a store which was taken from the pool.
+    fn give(&self, store: S) {
+        self.idle.lock().unwrap().push(store);
+        self.available.notify_one();
+    }
+}
+
+/// A store taken from a `StorePool` which is returned to it when this value is dropped.
+///
+/// This ensures the store is returned even if an operation panics.
+struct PooledStore<'a, S> {
+    pool: &'a StorePool<S>,
+    store: Option<S>,
+}
+
+impl<'a, S> Drop for PooledStore<'a, S> {
+    fn drop(&mut self) {
+        if let Some(store) = self.store.take() {
+            self.pool.give(store);
+        }
+    }
+}
+
+/// An [`AsyncDataStore`] which wraps a `DataStore`.
+///
+/// This runs each operation of the wrapped store on Tokio's blocking thread pool using
+/// [`tokio::task::spawn_blocking`]. Its methods must be called from within a Tokio runtime.
+///
+/// Clones of this value share the wrapped store. If it was created with [`new`], there is only
+/// one wrapped store, so operations performed by different clones at the same time run one after
+/// another. If it was created with [`from_config`], a new store is opened from the config
+/// whenever an operation is performed while every other store is busy, so operations can run
+/// concurrently.
+///
+/// [`AsyncDataStore`]: crate::store::AsyncDataStore
+/// [`new`]: crate::store::SpawnBlockingStore::new
+/// [`from_config`]: crate::store::SpawnBlockingStore::from_config
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub struct SpawnBlockingStore<S> {
+    pool: Arc<StorePool<S>>,
+}
+
+impl<S: DataStore + 'static> SpawnBlockingStore<S> {
+    /// Wrap the given `store`.
+    pub fn new(store: S) -> Self {
+        Self {
+            pool: Arc::new(StorePool {
+                conditional_writes: store.conditional_writes(),
+                idle: Mutex::new(vec![store]),
+                available: Condvar::new(),
+                open: None,
+            }),
+        }
+    }
+
+    /// Wrap stores opened from the given `config`.
+    ///
+    /// This opens one store immediately and opens more as they are needed to perform operations
+    /// concurrently.
+    ///
+    /// # Errors
+    /// - `Error::UnsupportedStore`: The data store is an unsupported format.
+    /// - `Error::Store`: An error occurred with the data store.
+    /// - `Error::Io`: An I/O error occurred.
+    pub fn from_config<C>(config: C) -> crate::Result<Self>
+    where
+        C: OpenStore<Store = S> + Send + Sync + 'static,
+    {
+        let store = config.open()?;
+        Ok(Self {
+            pool: Arc::new(StorePool {
+                conditional_writes: store.conditional_writes(),
+                idle: Mutex::new(vec![store]),
+                available: Condvar::new(),
+                open: Some(Box::new(move || config.open())),
+            }),
+        })
+    }
+
+    /// Run the given function with a wrapped store on the blocking thread pool.
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut S) -> super::Result<T> + Send + 'static,
+    ) -> super::Result<T> {
+        let pool = Arc::clone(&self.pool);
+        spawn_blocking(move || {
+            let mut pooled = PooledStore {
+                store: Some(pool.take()?),
+                pool: &pool,
+            };
+            f(pooled.store.as_mut().unwrap())
+        })
+        .await
+        .map_err(super::Error::new)?
+    }
+}
+
+impl<S> Debug for SpawnBlockingStore<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpawnBlockingStore").finish_non_exhaustive()
+    }
+}
+
+impl<S> Clone for SpawnBlockingStore<S> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: DataStore + 'static> AsyncDataStore for SpawnBlockingStore<S> {
+    async fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let data = data.to_vec();
+        self.run(move |store| store.write_block(key, &data)).await
+    }
+
+    async fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.run(move |store| store.read_block(key)).await
+    }
+
+    async fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.run(move |store| store.remove_block(key)).await
+    }
+
+    async fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.run(move |store| store.list_blocks(kind)).await
+    }
+
+    async fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.run(move |store| store.block_size(key)).await
+    }
+
+    async fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        let expected = expected.map(|expected| expected.to_vec());
+        let data = data.to_vec();
+        self.run(move |store| store.write_block_if(key, expected.as_deref(), &data))
+            .await
+    }
+
+    async fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        let data = data.to_vec();
+        self.run(move |store| store.create_block_exclusive(key, &data))
+            .await
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.pool.conditional_writes
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{canonicalize, create_dir_all, read_dir, remove_file, rename, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::SystemTime;
+
+use once_cell::sync::Lazy;
+use uuid::Uuid;
+use weak_table::WeakValueHashMap;
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::OpenStore;
+
+// The names of directories in the cache directory.
+const BLOCKS_DIRECTORY: &str = "blocks";
+const STAGING_DIRECTORY: &str = "stage";
+
+/// The indices of the cache directories which are currently in use in this process.
+///
+/// Stores which are opened from the same cache directory share an index so that the combined size
+/// of the blocks they cache stays under the maximum size.
+static OPEN_CACHES: Lazy<Mutex<WeakValueHashMap<PathBuf, Weak<Mutex<CacheIndex>>>>> =
+    Lazy::new(|| Mutex::new(WeakValueHashMap::new()));
+
+/// Return the index of the cache directory whose blocks are in `blocks_path`.
+///
+/// If the cache directory is not already in use in this process, the index is read from it.
+fn open_index(blocks_path: &Path) -> io::Result<Arc<Mutex<CacheIndex>>> {
+    let mut caches = OPEN_CACHES.lock().unwrap();
+    let blocks_path = canonicalize(blocks_path)?;
+
+    if let Some(index) = caches.get(&blocks_path) {
+        return Ok(index);
+    }
+
+    // Find the blocks which were cached previously, ordered from least to most recently modified.
+    let mut cached_blocks = Vec::new();
+    for entry in read_dir(&blocks_path)? {
+        let entry = entry?;
+        let id = match entry.file_name().to_str().map(Uuid::parse_str) {
+            Some(Ok(uuid)) => BlockId::from(uuid),
+            // This isn't a cached block, so leave it alone.
+            _ => continue,
+        };
+        let metadata = entry.metadata()?;
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        cached_blocks.push((modified, id, metadata.len()));
+    }
+    cached_blocks.sort_by_key(|(modified, _, _)| *modified);
+
+    let mut index = CacheIndex::default();
+    for (_, id, size) in cached_blocks {
+        index.insert(id, size);
+    }
+
+    let index = Arc::new(Mutex::new(index));
+    caches.insert(blocks_path, Arc::clone(&index));
+    Ok(index)
+}
+
+/// The configuration for opening a [`CachingStore`].
+///
+/// [`CachingStore`]: crate::store::CachingStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CachingConfig<C> {
+    /// The configuration for the data store to cache blocks from.
+    pub inner: C,
+
+    /// The path of the directory to cache blocks in.
+    pub path: PathBuf,
+
+    /// The maximum number of bytes of blocks to store in the cache directory.
+    pub max_size: u64,
+}
+
+impl<C: OpenStore> OpenStore for CachingConfig<C> {
+    type Store = CachingStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let inner = self.inner.open()?;
+
+        let blocks_path = self.path.join(BLOCKS_DIRECTORY);
+        let staging_path = self.path.join(STAGING_DIRECTORY);
+        create_dir_all(&blocks_path)
+            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+        create_dir_all(&staging_path)
+            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+
+        let store = CachingStore {
+            inner,
+            path: self.path.clone(),
+            max_size: self.max_size,
+            index: open_index(&blocks_path)
+                .map_err(|error| crate::Error::Store(super::Error::from(error)))?,
+        };
+
+        // The maximum size may have been reduced since the cache was last used.
+        store
+            .evict(&mut store.index.lock().unwrap(), 0)
+            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+
+        Ok(store)
+    }
+}
+
+/// A block in the cache directory.
+#[derive(Debug)]
+struct CacheEntry {
+    /// The size of the block in bytes.
+    size: u64,
+
+    /// The tick at which this block was last accessed.
+    last_used: u64,
+}
+
+/// The blocks in a cache directory.
+#[derive(Debug, Default)]
+struct CacheIndex {
+    /// The number of bytes of blocks currently stored in the cache directory.
+    size: u64,
+
+    /// The blocks in the cache directory.
+    entries: HashMap<BlockId, CacheEntry>,
+
+    /// The IDs of the blocks in the cache directory, ordered by when they were last accessed.
+    recency: BTreeMap<u64, BlockId>,
+
+    /// A counter which is incremented each time a block is accessed.
+    tick: u64,
+}
+
+impl CacheIndex {
+    /// Add the block with the given `id` and `size` as the most recently used.
+    fn insert(&mut self, id: BlockId, size: u64) {
+        self.tick += 1;
+        self.size += size;
+        self.entries.insert(
+            id,
+            CacheEntry {
+                size,
+                last_used: self.tick,
+            },
+        );
+        self.recency.insert(self.tick, id);
+    }
+
+    /// Mark the block with the given `id` as the most recently used.
+    fn touch(&mut self, id: BlockId) {
+        if let Some(entry) = self.entries.get_mut(&id) {
+            self.tick += 1;
+            self.recency.remove(&entry.last_used);
+            self.recency.insert(self.tick, id);
+            entry.last_used = self.tick;
+        }
+    }
+
+    /// Remove the block with the given `id` from the index.
+    fn remove(&mut self, id: BlockId) {
+        if let Some(entry) = self.entries.remove(&id) {
+            self.recency.remove(&entry.last_used);
+            self.size -= entry.size;
+        }
+    }
+}
+
+/// A `DataStore` which caches data blocks from another data store in a local directory.
+///
+/// This wraps another data store, such as one which stores data on a remote server, and keeps a
+/// copy of recently used data blocks in a directory in the local file system. Reading a block
+/// which is in the cache doesn't require accessing the wrapped data store. Writes are always
+/// written through to the wrapped data store. Once the blocks in the cache directory exceed a
+/// maximum size, the least recently used blocks are removed from the cache.
+///
+/// Only data blocks are cached. Locks, headers, the superblock, and the version block are always
+/// read from and written to the wrapped data store so that locking and committing changes work
+/// correctly when multiple clients access the same data store.
+///
+/// The cache persists between uses, so the same cache directory can be used each time the data
+/// store is opened. However, the cache assumes that data blocks are not modified by other clients
+/// while they are cached. This is always the case for repositories which don't use packing. For
+/// repositories which use packing, each cache directory should only be used with data stores which
+/// are not written to by other clients.
+///
+/// Stores in the same process which use the same cache directory, such as the stores a repository
+/// opens for concurrent reads and writes, keep track of the cached blocks together, so the maximum
+/// size applies to all of them combined. Stores in different processes should not use the same
+/// cache directory at the same time.
+///
+/// You can use [`CachingConfig`] to open a data store of this type.
+///
+/// [`CachingConfig`]: crate::store::CachingConfig
+#[derive(Debug)]
+pub struct CachingStore<S> {
+    /// The wrapped data store.
+    inner: S,
+
+    /// The path of the cache directory.
+    path: PathBuf,
+
+    /// The maximum number of bytes of blocks to store in the cache directory.
+    max_size: u64,
+
+    /// The blocks in the cache directory, which are shared with other stores using it.
+    index: Arc<Mutex<CacheIndex>>,
+}
+
+impl<S: DataStore> CachingStore<S> {
+    /// Consume this value, returning the wrapped store.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Return the path of the cached block with the given `id`.
+    fn block_path(&self, id: BlockId) -> PathBuf {
+        self.path
+            .join(BLOCKS_DIRECTORY)
+            .join(id.as_ref().as_hyphenated().to_string())
+    }
+
+    /// Return a new staging path.
+    fn staging_path(&self) -> PathBuf {
+        let uuid_str = Uuid::new_v4().as_hyphenated().to_string();
+        self.path.join(STAGING_DIRECTORY).join(uuid_str)
+    }
+
+    /// Remove least recently used blocks until there is room for `size` more bytes.
+    fn evict(&self, index: &mut CacheIndex, size: u64) -> io::Result<()> {
+        while index.size + size > self.max_size {
+            let id = match index.recency.values().next() {
+                Some(id) => *id,
+                None => break,
+            };
+            self.uncache_indexed(index, id)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the block with the given `id` from the cache.
+    fn uncache(&self, id: BlockId) -> io::Result<()> {
+        self.uncache_indexed(&mut self.index.lock().unwrap(), id)
+    }
+
+    /// Remove the block with the given `id` from the cache and the given `index`.
+    fn uncache_indexed(&self, index: &mut CacheIndex, id: BlockId) -> io::Result<()> {
+        index.remove(id);
+
+        // The block may have been cached by a store in another process, so we always attempt to
+        // remove it.
+        match remove_file(self.block_path(id)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Read the block with the given `id` from the cache.
+    ///
+    /// This returns `None` if the block is not cached.
+    fn read_cached(&self, id: BlockId) -> io::Result<Option<Vec<u8>>> {
+        if !self.index.lock().unwrap().entries.contains_key(&id) {
+            return Ok(None);
+        }
+
+        let mut file = match File::open(self.block_path(id)) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                // Another store using the same cache directory removed this block.
+                self.uncache(id)?;
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        };
+        let mut buffer = Vec::with_capacity(file.metadata()?.len() as usize);
+        file.read_to_end(&mut buffer)?;
+
+        self.index.lock().unwrap().touch(id);
+        Ok(Some(buffer))
+    }
+
+    /// Write the given `data` to the cache as the block with the given `id`.
+    ///
+    /// If `data` is larger than the maximum size of the cache, it is not cached.
+    fn write_cached(&self, id: BlockId, data: &[u8]) -> io::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        self.uncache_indexed(&mut index, id)?;
+
+        let size = data.len() as u64;
+        if size > self.max_size {
+            return Ok(());
+        }
+        self.evict(&mut index, size)?;
+
+        // Write to a staging file and then atomically move it to its final destination so that
+        // other stores using the same cache directory never read a partially written block.
+        let staging_path = self.staging_path();
+        let result = File::create(&staging_path)
+            .and_then(|mut file| file.write_all(data))
+            .and_then(|_| rename(&staging_path, self.block_path(id)));
+        if let Err(error) = result {
+            remove_file(&staging_path).ok();
+            return Err(error);
+        }
+
+        index.insert(id, size);
+
+        Ok(())
+    }
+}
+
+impl<S: DataStore> DataStore for CachingStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let id = match key {
+            BlockKey::Data(id) => id,
+            _ => return self.inner.write_block(key, data),
+        };
+
+        // Remove the old contents of the block from the cache before writing the new contents so
+        // the cache is never out of date if the write fails.
+        self.uncache(id)?;
+        self.inner.write_block(key, data)?;
+
+        // The block has been written, so failing to cache it is not an error.
+        self.write_cached(id, data).ok();
+
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let id = match key {
+            BlockKey::Data(id) => id,
+            _ => return self.inner.read_block(key),
+        };
+
+        if let Some(data) = self.read_cached(id)? {
+            return Ok(Some(data));
+        }
+
+        let data = self.inner.read_block(key)?;
+        if let Some(data) = &data {
+            // The block has been read, so failing to cache it is not an error.
+            self.write_cached(id, data).ok();
+        }
+
+        Ok(data)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        if let BlockKey::Data(id) = key {
+            self.uncache(id)?;
+        }
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.inner.block_size(key)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        if let BlockKey::Data(id) = key {
+            self.uncache(id)?;
+        }
+        self.inner.write_block_if(key, expected, data)
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        if let BlockKey::Data(id) = key {
+            self.uncache(id)?;
+        }
+        self.inner.create_block_exclusive(key, data)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.inner.conditional_writes()
+    }
+}
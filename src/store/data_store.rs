@@ -31,6 +31,25 @@ pub enum BlockType {
     Header,
 }
 
+/// The level of support a [`DataStore`] has for conditional writes.
+///
+/// This is returned by [`DataStore::conditional_writes`]. Levels are ordered, so a store which
+/// supports `Full` also supports `Create`.
+///
+/// [`DataStore`]: crate::store::DataStore
+/// [`DataStore::conditional_writes`]: crate::store::DataStore::conditional_writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConditionalWrites {
+    /// Conditional writes are emulated and are not atomic.
+    None,
+
+    /// `create_block_exclusive` is atomic, but `write_block_if` is not.
+    Create,
+
+    /// Both `create_block_exclusive` and `write_block_if` are atomic.
+    Full,
+}
+
 /// A persistent store for blocks of data.
 ///
 /// A `DataStore` persistently stores blocks of data uniquely identified by [`BlockKey`] values.
@@ -68,6 +87,62 @@ pub trait DataStore: Send {
 
     /// Return a list of IDs of blocks of the given `kind` in the store.
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>>;
+
+    /// Return the size of the block with the given `key` in bytes.
+    ///
+    /// If there is no block with the given `key`, return `None`.
+    ///
+    /// Repositories use this to estimate how much space cleaning would reclaim. The default
+    /// implementation reads the block. Data stores which can get the size of a block without
+    /// reading it should override this method.
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        Ok(self.read_block(key)?.map(|data| data.len() as u64))
+    }
+
+    /// Write the given `data` to the block with the given `key` only if its current contents are
+    /// `expected`.
+    ///
+    /// If `expected` is `None`, the block is only written if there is no block with the given
+    /// `key`. This returns `true` if the block was written or `false` if its current contents did
+    /// not match `expected`.
+    ///
+    /// Repositories use this to detect when another client has committed changes concurrently.
+    /// The default implementation reads the block and then writes it, which is not atomic; another
+    /// client could write the block in between. Data stores which can perform the comparison and
+    /// the write atomically should override this method.
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        if self.read_block(key)?.as_deref() != expected {
+            return Ok(false);
+        }
+        self.write_block(key, data)?;
+        Ok(true)
+    }
+
+    /// Write the given `data` as a new block with the given `key` only if no such block exists.
+    ///
+    /// This returns `true` if the block was written or `false` if a block with the given `key`
+    /// already exists, in which case it is left unchanged.
+    ///
+    /// Repositories use this to acquire locks. The default implementation calls
+    /// `write_block_if` with an `expected` value of `None`.
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.write_block_if(key, None, data)
+    }
+
+    /// Return which conditional writes this data store can perform atomically.
+    ///
+    /// Repositories use this to decide whether they can rely on `create_block_exclusive` and
+    /// `write_block_if` to coordinate between clients. The default implementation returns
+    /// [`ConditionalWrites::None`]; implementations which override `create_block_exclusive` or
+    /// `write_block_if` with an atomic operation should override this method as well.
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::None
+    }
 }
 
 assert_obj_safe!(DataStore);
@@ -88,6 +163,27 @@ impl DataStore for Box<dyn DataStore> {
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
         self.as_mut().list_blocks(kind)
     }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.as_mut().block_size(key)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        self.as_mut().write_block_if(key, expected, data)
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.as_mut().create_block_exclusive(key, data)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.as_ref().conditional_writes()
+    }
 }
 
 impl Debug for dyn DataStore {
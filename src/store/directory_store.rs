@@ -1,12 +1,12 @@
 #![cfg(feature = "store-directory")]
 
-use std::fs::{create_dir_all, read_dir, remove_file, rename, File};
-use std::io::{Read, Write};
+use std::fs::{create_dir_all, hard_link, metadata, read_dir, remove_file, rename, File};
+use std::io::{self, Read, Write};
 use std::path::PathBuf;
 
 use uuid::Uuid;
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
 use super::open_store::OpenStore;
 
 /// A UUID which acts as the version ID of the directory store format.
@@ -204,4 +204,38 @@ impl DataStore for DirectoryStore {
 
         Ok(block_ids)
     }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let block_path = self.block_path(key);
+
+        if block_path.exists() {
+            Ok(Some(metadata(block_path)?.len()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        let staging_path = self.staging_path();
+        let block_path = self.block_path(key);
+
+        create_dir_all(block_path.parent().unwrap())?;
+
+        // Write to a staging file and then atomically link it to its final destination. Unlike
+        // renaming, linking fails if the destination already exists.
+        let mut staging_file = File::create(&staging_path)?;
+        staging_file.write_all(data)?;
+        let result = hard_link(&staging_path, &block_path);
+        remove_file(&staging_path)?;
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::Create
+    }
 }
@@ -1,5 +1,6 @@
 use std::error::Error as StdError;
 use std::fmt;
+use std::io;
 use std::ops::Deref;
 use std::result;
 
@@ -7,20 +8,48 @@ use std::result;
 ///
 /// This wraps a dynamic error type.
 ///
+/// An error can be either retryable or permanent. A retryable error is one which is likely to be
+/// transient, such as a dropped connection or a timeout, so that retrying the operation which
+/// caused it may succeed. Data stores like [`RetryStore`] use this to decide which operations to
+/// retry. Errors constructed with [`retryable`] or [`retryable_msg`] are retryable. Errors
+/// constructed with [`new`] or `From` are retryable if they wrap an error which is known to be
+/// transient or is caused by one, such as an [`std::io::Error`] caused by a timeout or a connection
+/// being reset, or a server error from an S3 bucket. All other errors are permanent.
+///
 /// [`DataStore`]: crate::store::DataStore
+/// [`RetryStore`]: crate::store::RetryStore
+/// [`retryable`]: crate::store::Error::retryable
+/// [`retryable_msg`]: crate::store::Error::retryable_msg
+/// [`new`]: crate::store::Error::new
 #[derive(Debug)]
 pub struct Error {
     inner: anyhow::Error,
+    retryable: bool,
 }
 
 impl Error {
     /// Construct a new `Error` that wraps the given `error`.
+    ///
+    /// The returned error is retryable if `error` is known to be transient.
     pub fn new<E>(error: E) -> Self
     where
         E: StdError + Send + Sync + 'static,
     {
+        let retryable = is_transient(&error);
         Self {
             inner: anyhow::Error::new(error),
+            retryable,
+        }
+    }
+
+    /// Construct a new retryable `Error` that wraps the given `error`.
+    pub fn retryable<E>(error: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self {
+            inner: anyhow::Error::new(error),
+            retryable: true,
         }
     }
 
@@ -35,8 +64,136 @@ impl Error {
     {
         Self {
             inner: anyhow::Error::msg(message),
+            retryable: false,
+        }
+    }
+
+    /// Construct a new retryable `Error` from a printable error message.
+    pub fn retryable_msg<M>(message: M) -> Self
+    where
+        M: fmt::Display + fmt::Debug + Send + Sync + 'static,
+    {
+        Self {
+            inner: anyhow::Error::msg(message),
+            retryable: true,
+        }
+    }
+
+    /// Return whether retrying the operation which caused this error may succeed.
+    pub fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+}
+
+/// The `LIBSSH2_ERROR_*` codes of SSH session errors which are caused by the connection.
+#[cfg(feature = "store-sftp")]
+const TRANSIENT_SSH_ERRORS: [i32; 6] = [
+    -7,  // LIBSSH2_ERROR_SOCKET_SEND
+    -9,  // LIBSSH2_ERROR_TIMEOUT
+    -13, // LIBSSH2_ERROR_SOCKET_DISCONNECT
+    -30, // LIBSSH2_ERROR_SOCKET_TIMEOUT
+    -37, // LIBSSH2_ERROR_EAGAIN
+    -43, // LIBSSH2_ERROR_SOCKET_RECV
+];
+
+/// The `LIBSSH2_FX_*` codes of SFTP errors which are caused by the connection.
+#[cfg(feature = "store-sftp")]
+const TRANSIENT_SFTP_ERRORS: [i32; 2] = [
+    6, // LIBSSH2_FX_NO_CONNECTION
+    7, // LIBSSH2_FX_CONNECTION_LOST
+];
+
+/// Return whether the given HTTP `status_code` indicates a transient server error.
+#[cfg(feature = "store-s3")]
+fn is_transient_status(status_code: u16) -> bool {
+    status_code >= 500 || status_code == 429
+}
+
+/// Return whether the given `error` or any of its sources is known to be transient.
+fn is_transient(error: &(dyn StdError + 'static)) -> bool {
+    let mut next_error = Some(error);
+    while let Some(error) = next_error {
+        if is_transient_cause(error) {
+            return true;
         }
+        next_error = match error.downcast_ref::<io::Error>() {
+            // An `io::Error` returns the source of the error it wraps rather than the error itself.
+            Some(io_error) => io_error
+                .get_ref()
+                .map(|inner| inner as &(dyn StdError + 'static)),
+            None => error.source(),
+        };
     }
+    false
+}
+
+/// Return whether the given `error`, not including its sources, is known to be transient.
+fn is_transient_cause(error: &(dyn StdError + 'static)) -> bool {
+    if let Some(error) = error.downcast_ref::<io::Error>() {
+        return matches!(
+            error.kind(),
+            io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[cfg(feature = "store-redis")]
+    if let Some(error) = error.downcast_ref::<redis::RedisError>() {
+        return error.is_timeout()
+            || error.is_connection_dropped()
+            || error.is_connection_refusal()
+            || error.is_io_error();
+    }
+
+    #[cfg(feature = "store-postgres")]
+    if let Some(error) = error.downcast_ref::<postgres::Error>() {
+        use postgres::error::SqlState;
+
+        return error.is_closed()
+            || matches!(
+                error.code(),
+                Some(&SqlState::T_R_SERIALIZATION_FAILURE)
+                    | Some(&SqlState::T_R_DEADLOCK_DETECTED)
+                    | Some(&SqlState::CONNECTION_EXCEPTION)
+                    | Some(&SqlState::CONNECTION_FAILURE)
+                    | Some(&SqlState::ADMIN_SHUTDOWN)
+                    | Some(&SqlState::CANNOT_CONNECT_NOW)
+            );
+    }
+
+    #[cfg(feature = "store-s3")]
+    if let Some(error) = error.downcast_ref::<s3::error::S3Error>() {
+        use s3::error::S3Error;
+
+        return match error {
+            S3Error::Http(status_code, _) => is_transient_status(*status_code),
+            // The HTTP client doesn't return the I/O errors it wraps from `Error::source`.
+            S3Error::Atto(error) => match error.kind() {
+                attohttpc::ErrorKind::Io(error) => is_transient(error),
+                attohttpc::ErrorKind::StatusCode(status_code) => {
+                    is_transient_status(status_code.as_u16())
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+    }
+
+    #[cfg(feature = "store-sftp")]
+    if let Some(error) = error.downcast_ref::<ssh2::Error>() {
+        return match error.code() {
+            ssh2::ErrorCode::Session(code) => TRANSIENT_SSH_ERRORS.contains(&code),
+            ssh2::ErrorCode::SFTP(code) => TRANSIENT_SFTP_ERRORS.contains(&code),
+        };
+    }
+
+    false
 }
 
 impl<E> From<E> for Error
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::OpenStore;
+
+/// A fault which a [`FaultStore`] can inject into an operation.
+///
+/// [`FaultStore`]: crate::store::FaultStore
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Fault {
+    /// The operation fails with an error without modifying the data store.
+    Fail,
+
+    /// The operation reports success without modifying the data store.
+    Drop,
+
+    /// The operation writes only the first `len` bytes of the data.
+    ///
+    /// This has no effect on operations which remove blocks.
+    Truncate(usize),
+
+    /// The operation reports success, but it is not performed until after the next operation
+    /// which modifies the data store.
+    ///
+    /// If a read happens first, the operation is performed before the read. If the data store
+    /// crashes or is dropped first, the operation is never performed. This has no effect on
+    /// conditional writes.
+    Reorder,
+
+    /// The data store crashes before the operation.
+    ///
+    /// The operation and every operation after it which would modify the data store fails with an
+    /// error without modifying it. Reads still succeed, so the contents of the data store are
+    /// frozen as they were before the operation.
+    Crash,
+}
+
+/// The shared state of a `FaultInjector`.
+#[derive(Debug, Default)]
+struct InjectorState {
+    /// The faults to inject, keyed by the index of the operation to inject them into.
+    faults: HashMap<u64, Fault>,
+
+    /// The number of operations which modify the data store which have been attempted.
+    operations: u64,
+
+    /// Whether the data store has crashed.
+    crashed: bool,
+}
+
+/// A handle for controlling which faults a [`FaultStore`] injects.
+///
+/// A `FaultInjector` can be cloned, and all clones control the same faults. The operations of
+/// all the data stores which share a `FaultInjector` are numbered together, which makes it
+/// possible to inject faults into a repository which opens several connections to its data store.
+///
+/// Only operations which modify the data store are numbered. These are calls to
+/// [`DataStore::write_block`], [`DataStore::remove_block`], [`DataStore::write_block_if`], and
+/// [`DataStore::create_block_exclusive`]. The first operation has the index `0`.
+///
+/// [`FaultStore`]: crate::store::FaultStore
+/// [`DataStore::write_block`]: crate::store::DataStore::write_block
+/// [`DataStore::remove_block`]: crate::store::DataStore::remove_block
+/// [`DataStore::write_block_if`]: crate::store::DataStore::write_block_if
+/// [`DataStore::create_block_exclusive`]: crate::store::DataStore::create_block_exclusive
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector(Arc<Mutex<InjectorState>>);
+
+impl FaultInjector {
+    /// Create a new `FaultInjector` which doesn't inject any faults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inject the given `fault` into the operation with the given `index`.
+    ///
+    /// This replaces any fault previously injected into that operation.
+    pub fn inject(&self, index: u64, fault: Fault) {
+        self.0.lock().unwrap().faults.insert(index, fault);
+    }
+
+    /// Crash the data store before the operation with the given `index`.
+    ///
+    /// This is the same as injecting [`Fault::Crash`].
+    ///
+    /// [`Fault::Crash`]: crate::store::Fault::Crash
+    pub fn crash_at(&self, index: u64) {
+        self.inject(index, Fault::Crash);
+    }
+
+    /// The number of operations which modify the data store which have been attempted.
+    ///
+    /// This includes operations which failed or had faults injected into them.
+    pub fn operations(&self) -> u64 {
+        self.0.lock().unwrap().operations
+    }
+
+    /// Return whether the data store has crashed.
+    pub fn is_crashed(&self) -> bool {
+        self.0.lock().unwrap().crashed
+    }
+
+    /// Remove all injected faults, recover from any crash, and start numbering operations from
+    /// `0` again.
+    pub fn reset(&self) {
+        *self.0.lock().unwrap() = InjectorState::default();
+    }
+
+    /// Number the next operation, returning the fault to inject into it, if any.
+    ///
+    /// This returns `Some(Fault::Crash)` if the data store has crashed.
+    fn next_fault(&self) -> Option<Fault> {
+        let mut state = self.0.lock().unwrap();
+        let index = state.operations;
+        state.operations += 1;
+        let fault = state.faults.remove(&index);
+        if fault == Some(Fault::Crash) {
+            state.crashed = true;
+        }
+        if state.crashed {
+            Some(Fault::Crash)
+        } else {
+            fault
+        }
+    }
+}
+
+/// The configuration for opening a [`FaultStore`].
+///
+/// Every data store opened from this config shares the same `injector`.
+///
+/// [`FaultStore`]: crate::store::FaultStore
+#[derive(Debug, Clone)]
+pub struct FaultConfig<C> {
+    /// The configuration for the data store to inject faults into.
+    pub inner: C,
+
+    /// The handle which controls which faults are injected.
+    pub injector: FaultInjector,
+}
+
+impl<C: OpenStore> OpenStore for FaultConfig<C> {
+    type Store = FaultStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(FaultStore::new(self.inner.open()?, self.injector.clone()))
+    }
+}
+
+/// An operation which was postponed by `Fault::Reorder`.
+#[derive(Debug)]
+enum Postponed {
+    Write(BlockKey, Vec<u8>),
+    Remove(BlockKey),
+}
+
+/// A `DataStore` which injects faults into the operations of another data store.
+///
+/// This is meant for testing how code which uses a data store, such as a repository, behaves when
+/// the data store fails or the process is interrupted. Faults are injected into operations
+/// according to a [`FaultInjector`], which can simulate failed, lost, truncated, and reordered
+/// writes as well as a crash which leaves the data store frozen at a given operation.
+///
+/// A typical crash test opens a repository using a [`FaultConfig`], crashes the data store at each
+/// operation of some method in turn, and then checks that the repository can be opened again using
+/// the wrapped data store.
+///
+/// [`FaultInjector`]: crate::store::FaultInjector
+/// [`FaultConfig`]: crate::store::FaultConfig
+#[derive(Debug)]
+pub struct FaultStore<S> {
+    inner: S,
+    injector: FaultInjector,
+
+    /// An operation which has been postponed until after the next one.
+    postponed: Option<Postponed>,
+}
+
+impl<S: DataStore> FaultStore<S> {
+    /// Wrap the given `inner` data store, injecting the faults controlled by `injector`.
+    pub fn new(inner: S, injector: FaultInjector) -> Self {
+        Self {
+            inner,
+            injector,
+            postponed: None,
+        }
+    }
+
+    /// Return the handle which controls which faults are injected.
+    pub fn injector(&self) -> &FaultInjector {
+        &self.injector
+    }
+
+    /// Perform any postponed operation.
+    fn perform_postponed(&mut self) -> super::Result<()> {
+        match self.postponed.take() {
+            None => Ok(()),
+            // The data store crashed before the postponed operation could be performed.
+            Some(_) if self.injector.is_crashed() => Ok(()),
+            Some(Postponed::Write(key, data)) => self.inner.write_block(key, &data),
+            Some(Postponed::Remove(key)) => self.inner.remove_block(key),
+        }
+    }
+
+    /// Perform the given `operation`, which modifies the data store, and then any postponed one.
+    fn perform<T>(
+        &mut self,
+        operation: impl FnOnce(&mut S) -> super::Result<T>,
+    ) -> super::Result<T> {
+        let result = operation(&mut self.inner);
+        self.perform_postponed()?;
+        result
+    }
+
+    /// Consume this value, returning the wrapped store.
+    ///
+    /// Any postponed operation is discarded.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: DataStore> DataStore for FaultStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        match self.injector.next_fault() {
+            None => self.perform(|store| store.write_block(key, data)),
+            Some(Fault::Fail) => Err(injected_error()),
+            Some(Fault::Drop) => self.perform(|_| Ok(())),
+            Some(Fault::Truncate(len)) => {
+                self.perform(|store| store.write_block(key, &data[..len.min(data.len())]))
+            }
+            Some(Fault::Reorder) => {
+                self.perform_postponed()?;
+                self.postponed = Some(Postponed::Write(key, data.to_vec()));
+                Ok(())
+            }
+            Some(Fault::Crash) => Err(crashed_error()),
+        }
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.perform_postponed()?;
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        match self.injector.next_fault() {
+            None | Some(Fault::Truncate(_)) => self.perform(|store| store.remove_block(key)),
+            Some(Fault::Fail) => Err(injected_error()),
+            Some(Fault::Drop) => self.perform(|_| Ok(())),
+            Some(Fault::Reorder) => {
+                self.perform_postponed()?;
+                self.postponed = Some(Postponed::Remove(key));
+                Ok(())
+            }
+            Some(Fault::Crash) => Err(crashed_error()),
+        }
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.perform_postponed()?;
+        self.inner.list_blocks(kind)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.perform_postponed()?;
+        self.inner.block_size(key)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        match self.injector.next_fault() {
+            None | Some(Fault::Reorder) => {
+                self.perform(|store| store.write_block_if(key, expected, data))
+            }
+            Some(Fault::Fail) => Err(injected_error()),
+            Some(Fault::Drop) => self.perform(|_| Ok(true)),
+            Some(Fault::Truncate(len)) => self
+                .perform(|store| store.write_block_if(key, expected, &data[..len.min(data.len())])),
+            Some(Fault::Crash) => Err(crashed_error()),
+        }
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        match self.injector.next_fault() {
+            None | Some(Fault::Reorder) => {
+                self.perform(|store| store.create_block_exclusive(key, data))
+            }
+            Some(Fault::Fail) => Err(injected_error()),
+            Some(Fault::Drop) => self.perform(|_| Ok(true)),
+            Some(Fault::Truncate(len)) => self
+                .perform(|store| store.create_block_exclusive(key, &data[..len.min(data.len())])),
+            Some(Fault::Crash) => Err(crashed_error()),
+        }
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.inner.conditional_writes()
+    }
+}
+
+/// Return the error for an operation which failed because of `Fault::Fail`.
+fn injected_error() -> super::Error {
+    super::Error::msg("The operation failed because a fault was injected.")
+}
+
+/// Return the error for an operation which failed because the data store crashed.
+fn crashed_error() -> super::Error {
+    super::Error::msg("The operation failed because the data store crashed.")
+}
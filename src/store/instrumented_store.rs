@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::OpenStore;
+
+/// The number of buckets in a `LatencyHistogram`.
+const LATENCY_BUCKETS: usize = 32;
+
+/// An operation on a [`DataStore`].
+///
+/// [`DataStore`]: crate::store::DataStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StoreOperation {
+    /// A call to [`DataStore::write_block`].
+    ///
+    /// [`DataStore::write_block`]: crate::store::DataStore::write_block
+    WriteBlock,
+
+    /// A call to [`DataStore::read_block`].
+    ///
+    /// [`DataStore::read_block`]: crate::store::DataStore::read_block
+    ReadBlock,
+
+    /// A call to [`DataStore::remove_block`].
+    ///
+    /// [`DataStore::remove_block`]: crate::store::DataStore::remove_block
+    RemoveBlock,
+
+    /// A call to [`DataStore::list_blocks`].
+    ///
+    /// [`DataStore::list_blocks`]: crate::store::DataStore::list_blocks
+    ListBlocks,
+
+    /// A call to [`DataStore::block_size`].
+    ///
+    /// [`DataStore::block_size`]: crate::store::DataStore::block_size
+    BlockSize,
+
+    /// A call to [`DataStore::write_block_if`].
+    ///
+    /// [`DataStore::write_block_if`]: crate::store::DataStore::write_block_if
+    WriteBlockIf,
+
+    /// A call to [`DataStore::create_block_exclusive`].
+    ///
+    /// [`DataStore::create_block_exclusive`]: crate::store::DataStore::create_block_exclusive
+    CreateBlockExclusive,
+}
+
+/// A histogram of the latencies of operations.
+///
+/// Latencies are counted in buckets whose upper bounds start at one microsecond and double with
+/// each bucket. The last bucket counts every latency which is too long for the others.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKETS],
+    total: Duration,
+}
+
+impl LatencyHistogram {
+    /// The upper bound of the bucket with the given `index`.
+    fn upper_bound(index: usize) -> Duration {
+        if index == LATENCY_BUCKETS - 1 {
+            Duration::MAX
+        } else {
+            Duration::from_micros(1 << index)
+        }
+    }
+
+    /// Count the given `latency`.
+    fn record(&mut self, latency: Duration) {
+        let index = (0..LATENCY_BUCKETS)
+            .find(|&index| latency <= Self::upper_bound(index))
+            .unwrap();
+        self.counts[index] += 1;
+        self.total = self.total.saturating_add(latency);
+    }
+
+    /// Add the latencies counted in `other` to this histogram.
+    fn merge(&mut self, other: &Self) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts) {
+            *count += other_count;
+        }
+        self.total = self.total.saturating_add(other.total);
+    }
+
+    /// The number of latencies which were counted.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The sum of all the latencies which were counted.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// The mean latency, or `None` if no latencies were counted.
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count() {
+            0 => None,
+            count => Some(self.total.div_f64(count as f64)),
+        }
+    }
+
+    /// An upper bound for the given `quantile` of the latencies, or `None` if no latencies were
+    /// counted.
+    ///
+    /// For example, a `quantile` of `0.99` returns the upper bound of the bucket which contains the
+    /// 99th percentile latency.
+    ///
+    /// # Panics
+    /// - `quantile` is not between `0.0` and `1.0`.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        assert!(
+            (0.0..=1.0).contains(&quantile),
+            "The quantile must be between 0.0 and 1.0."
+        );
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((quantile * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, bucket_count) in self.counts.iter().enumerate() {
+            seen += bucket_count;
+            if seen >= rank {
+                return Some(Self::upper_bound(index));
+            }
+        }
+        unreachable!()
+    }
+
+    /// Return an iterator over the upper bound of each bucket and the number of latencies in it.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(index, count)| (Self::upper_bound(index), *count))
+    }
+}
+
+/// Statistics about calls to one operation of a data store.
+///
+/// This is returned by [`StoreMetrics`].
+///
+/// [`StoreMetrics`]: crate::store::StoreMetrics
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OperationStats {
+    /// The number of calls, including calls which failed.
+    pub calls: u64,
+
+    /// The number of calls which returned an error.
+    pub errors: u64,
+
+    /// The number of bytes which were read or written.
+    ///
+    /// This only counts the contents of blocks. It is `0` for operations which don't read or write
+    /// blocks.
+    pub bytes: u64,
+
+    /// The latencies of the calls.
+    pub latency: LatencyHistogram,
+}
+
+impl OperationStats {
+    /// Add the statistics in `other` to these ones.
+    fn merge(&mut self, other: &Self) {
+        self.calls += other.calls;
+        self.errors += other.errors;
+        self.bytes += other.bytes;
+        self.latency.merge(&other.latency);
+    }
+}
+
+/// The key which statistics are grouped by in `StoreMetrics`.
+type MetricsKey = (StoreOperation, Option<BlockType>);
+
+/// A handle for reading the metrics collected by an [`InstrumentedStore`].
+///
+/// A `StoreMetrics` can be cloned, and all clones share the same metrics. The metrics of all the
+/// data stores which share a `StoreMetrics` are counted together.
+///
+/// Statistics are grouped by operation and by the type of block the operation was performed on.
+/// The block type is `None` for operations on the superblock and the version block, which don't
+/// have a [`BlockType`].
+///
+/// [`InstrumentedStore`]: crate::store::InstrumentedStore
+/// [`BlockType`]: crate::store::BlockType
+#[derive(Debug, Clone, Default)]
+pub struct StoreMetrics(Arc<Mutex<HashMap<MetricsKey, OperationStats>>>);
+
+impl StoreMetrics {
+    /// Create a new `StoreMetrics` with no calls counted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the statistics for the given `operation` on blocks of the given `block_type`.
+    pub fn get(&self, operation: StoreOperation, block_type: Option<BlockType>) -> OperationStats {
+        self.0
+            .lock()
+            .unwrap()
+            .get(&(operation, block_type))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Return the statistics for the given `operation` on blocks of every type.
+    pub fn total(&self, operation: StoreOperation) -> OperationStats {
+        let mut total = OperationStats::default();
+        for ((current_operation, _), stats) in self.0.lock().unwrap().iter() {
+            if *current_operation == operation {
+                total.merge(stats);
+            }
+        }
+        total
+    }
+
+    /// Return the statistics for each operation and block type which has been called.
+    pub fn all(&self) -> HashMap<(StoreOperation, Option<BlockType>), OperationStats> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Reset all the statistics.
+    pub fn reset(&self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    /// Count a call to `operation` which took `latency`.
+    ///
+    /// `bytes` is the number of bytes the call read or wrote, or `None` if it failed.
+    fn record(
+        &self,
+        operation: StoreOperation,
+        block_type: Option<BlockType>,
+        latency: Duration,
+        bytes: Option<u64>,
+    ) {
+        let mut metrics = self.0.lock().unwrap();
+        let stats = metrics.entry((operation, block_type)).or_default();
+        stats.calls += 1;
+        match bytes {
+            Some(bytes) => stats.bytes += bytes,
+            None => stats.errors += 1,
+        }
+        stats.latency.record(latency);
+    }
+}
+
+/// Return the type of the block with the given `key`.
+fn block_type(key: BlockKey) -> Option<BlockType> {
+    match key {
+        BlockKey::Data(_) => Some(BlockType::Data),
+        BlockKey::Lock(_) => Some(BlockType::Lock),
+        BlockKey::Header(_) => Some(BlockType::Header),
+        BlockKey::Super | BlockKey::Version => None,
+    }
+}
+
+/// The configuration for opening an [`InstrumentedStore`].
+///
+/// Every data store opened from this config shares the same `metrics`.
+///
+/// [`InstrumentedStore`]: crate::store::InstrumentedStore
+#[derive(Debug, Clone)]
+pub struct InstrumentedConfig<C> {
+    /// The configuration for the data store to collect metrics for.
+    pub inner: C,
+
+    /// The handle which metrics are collected in.
+    pub metrics: StoreMetrics,
+}
+
+impl<C: OpenStore> OpenStore for InstrumentedConfig<C> {
+    type Store = InstrumentedStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(InstrumentedStore::new(
+            self.inner.open()?,
+            self.metrics.clone(),
+        ))
+    }
+}
+
+/// A `DataStore` which collects metrics about the operations of another data store.
+///
+/// This wraps another data store and counts the calls, errors, bytes read or written, and
+/// latencies of its operations in a [`StoreMetrics`].
+///
+/// Every repository already collects these metrics for its own data store, which you can access
+/// with [`KeyRepo::store_metrics`], so you only need this to collect metrics for a data store you
+/// use directly or for a data store wrapped by another one.
+///
+/// If the `tracing` feature is enabled, each operation also emits a span at the `TRACE` level.
+///
+/// You can use [`InstrumentedConfig`] to open a data store of this type.
+///
+/// [`StoreMetrics`]: crate::store::StoreMetrics
+/// [`KeyRepo::store_metrics`]: crate::repo::key::KeyRepo::store_metrics
+/// [`InstrumentedConfig`]: crate::store::InstrumentedConfig
+#[derive(Debug)]
+pub struct InstrumentedStore<S> {
+    inner: S,
+    metrics: StoreMetrics,
+}
+
+impl<S: DataStore> InstrumentedStore<S> {
+    /// Wrap the given `inner` data store, collecting metrics in `metrics`.
+    pub fn new(inner: S, metrics: StoreMetrics) -> Self {
+        Self { inner, metrics }
+    }
+
+    /// Return the handle which metrics are collected in.
+    pub fn metrics(&self) -> &StoreMetrics {
+        &self.metrics
+    }
+
+    /// Consume this value, returning the wrapped store.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Perform `operation` on the wrapped store and count it.
+    ///
+    /// `bytes` returns the number of bytes the operation read or wrote given its result.
+    fn instrument<T>(
+        &mut self,
+        kind: StoreOperation,
+        block_type: Option<BlockType>,
+        operation: impl FnOnce(&mut S) -> super::Result<T>,
+        bytes: impl FnOnce(&T) -> u64,
+    ) -> super::Result<T> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::trace_span!("store", operation = ?kind, block_type = ?block_type).entered();
+
+        let start = Instant::now();
+        let result = operation(&mut self.inner);
+        let latency = start.elapsed();
+        self.metrics
+            .record(kind, block_type, latency, result.as_ref().ok().map(bytes));
+        result
+    }
+}
+
+impl<S: DataStore> DataStore for InstrumentedStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.instrument(
+            StoreOperation::WriteBlock,
+            block_type(key),
+            |store| store.write_block(key, data),
+            |_| data.len() as u64,
+        )
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.instrument(
+            StoreOperation::ReadBlock,
+            block_type(key),
+            |store| store.read_block(key),
+            |data| data.as_ref().map_or(0, |data| data.len() as u64),
+        )
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.instrument(
+            StoreOperation::RemoveBlock,
+            block_type(key),
+            |store| store.remove_block(key),
+            |_| 0,
+        )
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.instrument(
+            StoreOperation::ListBlocks,
+            Some(kind),
+            |store| store.list_blocks(kind),
+            |_| 0,
+        )
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.instrument(
+            StoreOperation::BlockSize,
+            block_type(key),
+            |store| store.block_size(key),
+            |_| 0,
+        )
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        self.instrument(
+            StoreOperation::WriteBlockIf,
+            block_type(key),
+            |store| store.write_block_if(key, expected, data),
+            |&written| if written { data.len() as u64 } else { 0 },
+        )
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.instrument(
+            StoreOperation::CreateBlockExclusive,
+            block_type(key),
+            |store| store.create_block_exclusive(key, data),
+            |&written| if written { data.len() as u64 } else { 0 },
+        )
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.inner.conditional_writes()
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
 use super::open_store::OpenStore;
 
 #[derive(Debug, Clone, Default)]
@@ -13,6 +13,40 @@ struct BlockMap {
     version: Option<Vec<u8>>,
 }
 
+impl BlockMap {
+    /// Return a reference to the block with the given `key`.
+    fn get(&self, key: BlockKey) -> Option<&Vec<u8>> {
+        match key {
+            BlockKey::Data(id) => self.data.get(&id),
+            BlockKey::Lock(id) => self.locks.get(&id),
+            BlockKey::Header(id) => self.headers.get(&id),
+            BlockKey::Super => self.superblock.as_ref(),
+            BlockKey::Version => self.version.as_ref(),
+        }
+    }
+
+    /// Write the given `data` to the block with the given `key`.
+    fn insert(&mut self, key: BlockKey, data: &[u8]) {
+        match key {
+            BlockKey::Data(id) => {
+                self.data.insert(id, data.to_owned());
+            }
+            BlockKey::Lock(id) => {
+                self.locks.insert(id, data.to_owned());
+            }
+            BlockKey::Header(id) => {
+                self.headers.insert(id, data.to_owned());
+            }
+            BlockKey::Super => {
+                self.superblock = Some(data.to_owned());
+            }
+            BlockKey::Version => {
+                self.version = Some(data.to_owned());
+            }
+        }
+    }
+}
+
 /// The configuration for opening a [`MemoryStore`].
 ///
 /// [`MemoryStore`]: crate::store::MemoryStore
@@ -53,36 +87,13 @@ pub struct MemoryStore {
 
 impl DataStore for MemoryStore {
     fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
-        let mut block_map = self.blocks.lock().unwrap();
-        match key {
-            BlockKey::Data(id) => {
-                block_map.data.insert(id, data.to_owned());
-            }
-            BlockKey::Lock(id) => {
-                block_map.locks.insert(id, data.to_owned());
-            }
-            BlockKey::Header(id) => {
-                block_map.headers.insert(id, data.to_owned());
-            }
-            BlockKey::Super => {
-                block_map.superblock = Some(data.to_owned());
-            }
-            BlockKey::Version => {
-                block_map.version = Some(data.to_owned());
-            }
-        }
+        self.blocks.lock().unwrap().insert(key, data);
         Ok(())
     }
 
     fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
         let block_map = self.blocks.lock().unwrap();
-        Ok(match key {
-            BlockKey::Data(id) => block_map.data.get(&id).map(|data| data.to_owned()),
-            BlockKey::Lock(id) => block_map.locks.get(&id).map(|data| data.to_owned()),
-            BlockKey::Header(id) => block_map.headers.get(&id).map(|data| data.to_owned()),
-            BlockKey::Super => block_map.superblock.clone(),
-            BlockKey::Version => block_map.version.clone(),
-        })
+        Ok(block_map.get(key).map(|data| data.to_owned()))
     }
 
     fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
@@ -115,4 +126,27 @@ impl DataStore for MemoryStore {
             BlockType::Header => block_map.headers.keys().copied().collect(),
         })
     }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let block_map = self.blocks.lock().unwrap();
+        Ok(block_map.get(key).map(|data| data.len() as u64))
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        let mut block_map = self.blocks.lock().unwrap();
+        if block_map.get(key).map(|data| data.as_slice()) != expected {
+            return Ok(false);
+        }
+        block_map.insert(key, data);
+        Ok(true)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::Full
+    }
 }
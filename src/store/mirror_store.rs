@@ -0,0 +1,396 @@
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::{OpenDynStore, OpenStore};
+
+/// The configuration for opening a [`MirrorStore`].
+///
+/// # Examples
+/// ```
+/// use acid_store::store::{MemoryConfig, MirrorConfig, OpenStore};
+///
+/// let config = MirrorConfig::new()
+///     .replica(MemoryConfig::new())
+///     .replica(MemoryConfig::new())
+///     .write_quorum(1);
+/// let store = config.open().unwrap();
+/// ```
+///
+/// [`MirrorStore`]: crate::store::MirrorStore
+#[derive(Clone, Default)]
+pub struct MirrorConfig {
+    replicas: Vec<Arc<dyn OpenDynStore>>,
+    write_quorum: Option<usize>,
+}
+
+impl Debug for MirrorConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MirrorConfig")
+            .field("replicas", &self.replicas.len())
+            .field("write_quorum", &self.write_quorum)
+            .finish()
+    }
+}
+
+impl MirrorConfig {
+    /// Create a new `MirrorConfig` with no replicas.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a replica which is opened using the given `config`.
+    ///
+    /// The first replica added is the primary replica.
+    pub fn replica<C>(mut self, config: C) -> Self
+    where
+        C: OpenStore + Send + Sync + 'static,
+    {
+        self.replicas.push(Arc::new(config));
+        self
+    }
+
+    /// Set the number of replicas each write must succeed on.
+    ///
+    /// By default, writes must succeed on every replica.
+    pub fn write_quorum(mut self, quorum: usize) -> Self {
+        self.write_quorum = Some(quorum);
+        self
+    }
+}
+
+impl OpenStore for MirrorConfig {
+    type Store = MirrorStore;
+
+    /// # Panics
+    /// - The config has no replicas.
+    /// - The write quorum is `0` or greater than the number of replicas.
+    fn open(&self) -> crate::Result<Self::Store> {
+        let replicas = self
+            .replicas
+            .iter()
+            .map(|config| config.open_dyn())
+            .collect::<crate::Result<Vec<_>>>()?;
+        let write_quorum = self.write_quorum.unwrap_or(replicas.len());
+        Ok(MirrorStore::new(replicas, write_quorum))
+    }
+}
+
+/// A replica in a `MirrorStore`.
+#[derive(Debug)]
+struct Replica {
+    store: Box<dyn DataStore>,
+
+    /// Whether this replica has not failed an operation since it was last in sync.
+    healthy: bool,
+}
+
+/// A `DataStore` which mirrors blocks across several other data stores.
+///
+/// This wraps a list of replicas, which can be data stores of any type. Every block is written to
+/// and removed from every replica, and an operation succeeds if it succeeds on at least a given
+/// number of replicas, called the write quorum. Blocks are read from the first healthy replica.
+///
+/// A replica becomes unhealthy when an operation on it fails, because it may be missing blocks.
+/// Unhealthy replicas are still written to, but they are not read from until they are brought back
+/// in sync with [`resync`]. Whether a replica is healthy is not persisted, so if a replica was
+/// unavailable while the data store was last used, you should call [`resync`] after opening it.
+///
+/// Conditional writes are performed atomically on the first healthy replica, which is the primary,
+/// and then copied to the other replicas. Atomicity is only guaranteed when all clients use the
+/// same primary.
+///
+/// You can use [`MirrorConfig`] to open a data store of this type.
+///
+/// [`resync`]: crate::store::MirrorStore::resync
+/// [`MirrorConfig`]: crate::store::MirrorConfig
+#[derive(Debug)]
+pub struct MirrorStore {
+    replicas: Vec<Replica>,
+    write_quorum: usize,
+}
+
+impl MirrorStore {
+    /// Create a new `MirrorStore` which mirrors blocks across the given `replicas`.
+    ///
+    /// The first replica is the primary replica. Each write must succeed on at least
+    /// `write_quorum` replicas.
+    ///
+    /// # Panics
+    /// - `replicas` is empty.
+    /// - `write_quorum` is `0` or greater than the number of replicas.
+    pub fn new(replicas: Vec<Box<dyn DataStore>>, write_quorum: usize) -> Self {
+        assert!(
+            !replicas.is_empty(),
+            "A mirror store must have at least one replica."
+        );
+        assert!(
+            write_quorum > 0 && write_quorum <= replicas.len(),
+            "The write quorum must be between 1 and the number of replicas."
+        );
+        Self {
+            replicas: replicas
+                .into_iter()
+                .map(|store| Replica {
+                    store,
+                    healthy: true,
+                })
+                .collect(),
+            write_quorum,
+        }
+    }
+
+    /// The number of replicas.
+    pub fn replica_count(&self) -> usize {
+        self.replicas.len()
+    }
+
+    /// Return whether the replica at the given `index` is healthy.
+    ///
+    /// # Panics
+    /// - `index` is out of bounds.
+    pub fn is_healthy(&self, index: usize) -> bool {
+        self.replicas[index].healthy
+    }
+
+    /// Consume this value, returning the replicas.
+    pub fn into_inner(self) -> Vec<Box<dyn DataStore>> {
+        self.replicas
+            .into_iter()
+            .map(|replica| replica.store)
+            .collect()
+    }
+
+    /// Copy blocks which are missing from some replicas to them and mark every replica healthy.
+    ///
+    /// Data blocks and headers which exist in any replica are copied to the replicas which are
+    /// missing them. The superblock and the version block are copied from the primary replica to
+    /// the others. Locks are not copied.
+    ///
+    /// Because blocks are copied from any replica which has them, blocks which were removed while a
+    /// replica was unhealthy, such as by cleaning a repository, are copied back from that replica.
+    /// Those blocks are unreferenced, so they are removed again the next time the repository is
+    /// cleaned.
+    ///
+    /// This returns the number of blocks which were written. This must not be called while a
+    /// repository is using this data store, and it must not be interrupted by another client
+    /// cleaning a repository using one of the replicas.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with one of the replicas.
+    pub fn resync(&mut self) -> super::Result<usize> {
+        let mut blocks_written = 0;
+
+        for kind in [BlockType::Data, BlockType::Header] {
+            let block_lists = self
+                .replicas
+                .iter_mut()
+                .map(|replica| {
+                    replica
+                        .store
+                        .list_blocks(kind)
+                        .map(|ids| ids.into_iter().collect::<HashSet<_>>())
+                })
+                .collect::<super::Result<Vec<_>>>()?;
+
+            let all_blocks = block_lists
+                .iter()
+                .flatten()
+                .copied()
+                .collect::<HashSet<_>>();
+
+            for id in all_blocks {
+                let key = match kind {
+                    BlockType::Data => BlockKey::Data(id),
+                    BlockType::Header => BlockKey::Header(id),
+                    BlockType::Lock => unreachable!(),
+                };
+                let source = block_lists
+                    .iter()
+                    .position(|ids| ids.contains(&id))
+                    .unwrap();
+                let data = match self.replicas[source].store.read_block(key)? {
+                    Some(data) => data,
+                    // The block was removed since it was listed.
+                    None => continue,
+                };
+                for (replica, ids) in self.replicas.iter_mut().zip(&block_lists) {
+                    if !ids.contains(&id) {
+                        replica.store.write_block(key, &data)?;
+                        blocks_written += 1;
+                    }
+                }
+            }
+        }
+
+        for key in [BlockKey::Super, BlockKey::Version] {
+            let (primary, others) = self.replicas.split_first_mut().unwrap();
+            let data = match primary.store.read_block(key)? {
+                Some(data) => data,
+                None => continue,
+            };
+            for replica in others {
+                if replica.store.read_block(key)?.as_deref() != Some(data.as_slice()) {
+                    replica.store.write_block(key, &data)?;
+                    blocks_written += 1;
+                }
+            }
+        }
+
+        for replica in &mut self.replicas {
+            replica.healthy = true;
+        }
+
+        Ok(blocks_written)
+    }
+
+    /// Perform the given `operation` on every replica.
+    ///
+    /// This returns an error if the operation did not succeed on enough replicas.
+    fn write_all(
+        &mut self,
+        mut operation: impl FnMut(&mut dyn DataStore) -> super::Result<()>,
+    ) -> super::Result<()> {
+        let mut succeeded = 0;
+        let mut first_error = None;
+        for replica in &mut self.replicas {
+            match operation(replica.store.as_mut()) {
+                Ok(()) => succeeded += 1,
+                Err(error) => {
+                    replica.healthy = false;
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) if succeeded < self.write_quorum => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Perform the given `operation` on each healthy replica in order until it succeeds.
+    fn read_any<T>(
+        &mut self,
+        mut operation: impl FnMut(&mut dyn DataStore) -> super::Result<T>,
+    ) -> super::Result<T> {
+        let mut last_error = None;
+        for replica in self.replicas.iter_mut().filter(|replica| replica.healthy) {
+            match operation(replica.store.as_mut()) {
+                Ok(value) => return Ok(value),
+                Err(error) => {
+                    replica.healthy = false;
+                    last_error = Some(error);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| super::Error::msg("There are no healthy replicas.")))
+    }
+
+    /// Return the index of the primary replica, which is the first healthy one.
+    fn primary(&self) -> super::Result<usize> {
+        self.replicas
+            .iter()
+            .position(|replica| replica.healthy)
+            .ok_or_else(|| super::Error::msg("There are no healthy replicas."))
+    }
+
+    /// Write `data` to the block with the given `key` on every replica except the primary.
+    ///
+    /// This is called after the block has been written to the `primary` replica.
+    fn write_secondaries(
+        &mut self,
+        primary: usize,
+        key: BlockKey,
+        data: &[u8],
+    ) -> super::Result<()> {
+        let mut succeeded = 1;
+        let mut first_error = None;
+        for (index, replica) in self.replicas.iter_mut().enumerate() {
+            if index == primary {
+                continue;
+            }
+            match replica.store.write_block(key, data) {
+                Ok(()) => succeeded += 1,
+                Err(error) => {
+                    replica.healthy = false;
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) if succeeded < self.write_quorum => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl DataStore for MirrorStore {
+    /// If this fails, the block may have been written to some of the replicas.
+    ///
+    /// The block is not removed from those replicas, because it may have existed before it was
+    /// overwritten. A block which didn't exist is unreferenced, so it is removed the next time the
+    /// repository is cleaned.
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.write_all(|store| store.write_block(key, data))
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.read_any(|store| store.read_block(key))
+    }
+
+    /// If this fails, the block may have been removed from some of the replicas.
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.write_all(|store| store.remove_block(key))
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.read_any(|store| store.list_blocks(kind))
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.read_any(|store| store.block_size(key))
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        let primary = self.primary()?;
+        let written = self.replicas[primary]
+            .store
+            .write_block_if(key, expected, data)
+            .map_err(|error| {
+                self.replicas[primary].healthy = false;
+                error
+            })?;
+        if written {
+            self.write_secondaries(primary, key, data)?;
+        }
+        Ok(written)
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        let primary = self.primary()?;
+        let written = self.replicas[primary]
+            .store
+            .create_block_exclusive(key, data)
+            .map_err(|error| {
+                self.replicas[primary].healthy = false;
+                error
+            })?;
+        if written {
+            self.write_secondaries(primary, key, data)?;
+        }
+        Ok(written)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        match self.primary() {
+            Ok(primary) => self.replicas[primary].store.conditional_writes(),
+            Err(_) => ConditionalWrites::None,
+        }
+    }
+}
@@ -13,34 +13,80 @@
 //! config types with [`OpenOptions`] to open repositories. You'll almost never need to use the
 //! [`OpenStore`] or [`DataStore`] traits directly.
 //!
+//! Some data stores wrap another data store to add functionality to it. For example,
+//! [`CachingStore`] caches blocks from a slow data store in the local file system, and
+//! [`RetryStore`] retries operations which fail with transient errors. [`RateLimitStore`] limits
+//! the bandwidth and request rate of a data store, and [`InstrumentedStore`] collects metrics
+//! about its operations. [`FaultStore`] injects faults into the operations of a data store for
+//! testing. [`MirrorStore`] and [`ShardedStore`] are
+//! different in that they wrap several data stores, possibly of different types.
+//! [`MirrorStore`] mirrors blocks across all of them, and [`ShardedStore`] splits blocks between
+//! them.
+//!
 //! [`DataStore`]: crate::store::DataStore
 //! [`OpenStore`]: crate::store::OpenStore
 //! [`OpenOptions`]: crate::repo::OpenOptions
+//! [`CachingStore`]: crate::store::CachingStore
+//! [`RetryStore`]: crate::store::RetryStore
+//! [`RateLimitStore`]: crate::store::RateLimitStore
+//! [`InstrumentedStore`]: crate::store::InstrumentedStore
+//! [`FaultStore`]: crate::store::FaultStore
+//! [`MirrorStore`]: crate::store::MirrorStore
+//! [`ShardedStore`]: crate::store::ShardedStore
 
-pub use self::data_store::{BlockId, BlockKey, BlockType, DataStore};
+#[cfg(feature = "async")]
+pub use self::async_store::{AsyncDataStore, BlockingStore, SpawnBlockingStore};
+pub use self::caching_store::{CachingConfig, CachingStore};
+pub use self::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
 #[cfg(feature = "store-directory")]
 pub use self::directory_store::{DirectoryConfig, DirectoryStore};
 pub use self::error::{Error, Result};
+pub use self::fault_store::{Fault, FaultConfig, FaultInjector, FaultStore};
+pub use self::instrumented_store::{
+    InstrumentedConfig, InstrumentedStore, LatencyHistogram, OperationStats, StoreMetrics,
+    StoreOperation,
+};
 pub use self::memory_store::{MemoryConfig, MemoryStore};
+pub use self::mirror_store::{MirrorConfig, MirrorStore};
 pub use self::open_store::OpenStore;
+#[cfg(feature = "store-postgres")]
+pub use self::postgres_store::{PostgresConfig, PostgresStore};
+pub use self::rate_limit_store::{RateLimitConfig, RateLimitStore, RateLimiter};
 #[cfg(feature = "store-rclone")]
 pub use self::rclone_store::{RcloneConfig, RcloneStore};
+#[cfg(feature = "store-redb")]
+pub use self::redb_store::{RedbConfig, RedbStore};
 #[cfg(feature = "store-redis")]
 pub use self::redis_store::{RedisAddr, RedisConfig, RedisStore};
+pub use self::retry_store::{RetryConfig, RetryPolicy, RetryStore};
 #[cfg(feature = "store-s3")]
 pub use self::s3_store::{S3Config, S3Credentials, S3Region, S3Store};
 #[cfg(feature = "store-sftp")]
 pub use self::sftp_store::{SftpAuth, SftpConfig, SftpStore};
+pub use self::sharded_store::{ShardedConfig, ShardedStore};
 #[cfg(feature = "store-sqlite")]
 pub use self::sqlite_store::{SqliteConfig, SqliteStore};
+#[cfg(feature = "store-webdav")]
+pub use self::webdav_store::{WebDavConfig, WebDavCredentials, WebDavStore};
 
+mod async_store;
+mod caching_store;
 mod data_store;
 mod directory_store;
 mod error;
+mod fault_store;
+mod instrumented_store;
 mod memory_store;
+mod mirror_store;
 mod open_store;
+mod postgres_store;
+mod rate_limit_store;
 mod rclone_store;
+mod redb_store;
 mod redis_store;
+mod retry_store;
 mod s3_store;
 mod sftp_store;
+mod sharded_store;
 mod sqlite_store;
+mod webdav_store;
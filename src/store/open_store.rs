@@ -17,3 +17,18 @@ pub trait OpenStore {
     /// - `Error::Io`: An I/O error occurred.
     fn open(&self) -> crate::Result<Self::Store>;
 }
+
+/// A value which can open a data store of any type.
+///
+/// This allows configs for data stores which wrap several other data stores to hold configs for
+/// different types of data stores.
+pub(crate) trait OpenDynStore: Send + Sync {
+    /// Open or create a data store, returning it as a trait object.
+    fn open_dyn(&self) -> crate::Result<Box<dyn DataStore>>;
+}
+
+impl<C: OpenStore + Send + Sync> OpenDynStore for C {
+    fn open_dyn(&self) -> crate::Result<Box<dyn DataStore>> {
+        Ok(Box::new(self.open()?))
+    }
+}
@@ -0,0 +1,317 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::OpenStore;
+
+/// A token bucket which limits the rate of some quantity.
+///
+/// The bucket holds up to one second's worth of tokens, so short bursts are allowed. An operation
+/// may take more tokens than the bucket holds, in which case the bucket goes into debt and later
+/// operations wait for it to be paid off.
+#[derive(Debug)]
+struct Bucket {
+    /// The maximum rate per second, or `None` if the rate is unlimited.
+    rate: Option<u64>,
+
+    /// The number of available tokens, which is negative if the bucket is in debt.
+    available: f64,
+
+    /// When the number of available tokens was last updated.
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Option<u64>) -> Self {
+        Self {
+            rate,
+            available: rate.unwrap_or(0) as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Add the tokens which have accumulated since the bucket was last updated.
+    fn refill(&mut self, now: Instant) {
+        if let Some(rate) = self.rate {
+            let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+            self.available = (self.available + elapsed * rate as f64).min(rate as f64);
+        }
+        self.updated = now;
+    }
+
+    /// Change the maximum rate to `rate`.
+    fn set_rate(&mut self, rate: Option<u64>) {
+        self.refill(Instant::now());
+        self.available = match (self.rate, rate) {
+            // The rate was unlimited, so the bucket starts full.
+            (None, Some(rate)) => rate as f64,
+            (_, Some(rate)) => self.available.min(rate as f64),
+            (_, None) => 0.0,
+        };
+        self.rate = rate;
+    }
+
+    /// Take `amount` tokens from the bucket.
+    ///
+    /// If there are not enough tokens available, this returns how long to wait before trying
+    /// again.
+    fn take(&mut self, amount: u64) -> Option<Duration> {
+        let rate = match self.rate {
+            Some(rate) => rate as f64,
+            None => return None,
+        };
+        let needed = (amount as f64).min(rate);
+        if self.available >= needed {
+            self.available -= amount as f64;
+            None
+        } else {
+            Some(Duration::from_secs_f64((needed - self.available) / rate))
+        }
+    }
+}
+
+/// Which limit an amount counts against.
+#[derive(Debug, Clone, Copy)]
+enum Limit {
+    Read,
+    Write,
+    Request,
+}
+
+/// The shared state of a `RateLimiter`.
+#[derive(Debug)]
+struct LimiterState {
+    read: Bucket,
+    write: Bucket,
+    request: Bucket,
+}
+
+impl LimiterState {
+    fn bucket(&mut self, limit: Limit) -> &mut Bucket {
+        match limit {
+            Limit::Read => &mut self.read,
+            Limit::Write => &mut self.write,
+            Limit::Request => &mut self.request,
+        }
+    }
+}
+
+/// The state shared between clones of a `RateLimiter`.
+#[derive(Debug)]
+struct SharedLimits {
+    state: Mutex<LimiterState>,
+
+    /// Notified when the limits are changed.
+    changed: Condvar,
+}
+
+/// A handle for controlling the rate limits of a [`RateLimitStore`].
+///
+/// A `RateLimiter` can be cloned, and all clones control the same limits. All the data stores
+/// which share a `RateLimiter` share its limits, so the limits apply to their combined traffic.
+/// The limits can be changed at any time, including from another thread while an operation is
+/// waiting, in which case the new limits apply immediately.
+///
+/// Each limit allows bursts of up to one second's worth of traffic. A limit of `None` means that
+/// there is no limit.
+///
+/// [`RateLimitStore`]: crate::store::RateLimitStore
+#[derive(Debug, Clone)]
+pub struct RateLimiter(Arc<SharedLimits>);
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self(Arc::new(SharedLimits {
+            state: Mutex::new(LimiterState {
+                read: Bucket::new(None),
+                write: Bucket::new(None),
+                request: Bucket::new(None),
+            }),
+            changed: Condvar::new(),
+        }))
+    }
+}
+
+impl RateLimiter {
+    /// Create a new `RateLimiter` with no limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The maximum number of bytes which can be read per second.
+    pub fn read_limit(&self) -> Option<u64> {
+        self.0.state.lock().unwrap().read.rate
+    }
+
+    /// The maximum number of bytes which can be written per second.
+    pub fn write_limit(&self) -> Option<u64> {
+        self.0.state.lock().unwrap().write.rate
+    }
+
+    /// The maximum number of requests which can be made per second.
+    pub fn request_limit(&self) -> Option<u64> {
+        self.0.state.lock().unwrap().request.rate
+    }
+
+    /// Set the maximum number of bytes which can be read per second.
+    ///
+    /// # Panics
+    /// - `limit` is `Some(0)`.
+    pub fn set_read_limit(&self, limit: Option<u64>) {
+        self.set_limit(Limit::Read, limit);
+    }
+
+    /// Set the maximum number of bytes which can be written per second.
+    ///
+    /// # Panics
+    /// - `limit` is `Some(0)`.
+    pub fn set_write_limit(&self, limit: Option<u64>) {
+        self.set_limit(Limit::Write, limit);
+    }
+
+    /// Set the maximum number of requests which can be made per second.
+    ///
+    /// # Panics
+    /// - `limit` is `Some(0)`.
+    pub fn set_request_limit(&self, limit: Option<u64>) {
+        self.set_limit(Limit::Request, limit);
+    }
+
+    fn set_limit(&self, limit: Limit, rate: Option<u64>) {
+        assert_ne!(rate, Some(0), "A rate limit must be greater than zero.");
+        self.0.state.lock().unwrap().bucket(limit).set_rate(rate);
+        self.0.changed.notify_all();
+    }
+
+    /// Block until `amount` can be counted against the given `limit`, and then count it.
+    fn acquire(&self, limit: Limit, amount: u64) {
+        let mut state = self.0.state.lock().unwrap();
+        loop {
+            let bucket = state.bucket(limit);
+            bucket.refill(Instant::now());
+            match bucket.take(amount) {
+                None => return,
+                // We wake up early if the limits are changed.
+                Some(delay) => state = self.0.changed.wait_timeout(state, delay).unwrap().0,
+            }
+        }
+    }
+}
+
+/// The configuration for opening a [`RateLimitStore`].
+///
+/// Every data store opened from this config shares the same `limiter`.
+///
+/// [`RateLimitStore`]: crate::store::RateLimitStore
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig<C> {
+    /// The configuration for the data store to limit the rate of.
+    pub inner: C,
+
+    /// The handle which controls the rate limits.
+    pub limiter: RateLimiter,
+}
+
+impl<C: OpenStore> OpenStore for RateLimitConfig<C> {
+    type Store = RateLimitStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(RateLimitStore::new(
+            self.inner.open()?,
+            self.limiter.clone(),
+        ))
+    }
+}
+
+/// A `DataStore` which limits the bandwidth and request rate of another data store.
+///
+/// This wraps another data store, such as one which stores data on a remote server, and blocks
+/// operations as necessary to keep the number of bytes read, the number of bytes written, and the
+/// number of requests per second under the limits set by a [`RateLimiter`].
+///
+/// Every operation counts as one request. Bytes are counted against the write limit before a block
+/// is written, and they are counted against the read limit after a block is read, because its
+/// size isn't known until then.
+///
+/// You can use [`RateLimitConfig`] to open a data store of this type.
+///
+/// [`RateLimiter`]: crate::store::RateLimiter
+/// [`RateLimitConfig`]: crate::store::RateLimitConfig
+#[derive(Debug)]
+pub struct RateLimitStore<S> {
+    inner: S,
+    limiter: RateLimiter,
+}
+
+impl<S: DataStore> RateLimitStore<S> {
+    /// Wrap the given `inner` data store, limiting its rate according to `limiter`.
+    pub fn new(inner: S, limiter: RateLimiter) -> Self {
+        Self { inner, limiter }
+    }
+
+    /// Return the handle which controls the rate limits.
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// Consume this value, returning the wrapped store.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Block until a request which writes `data` can be made.
+    fn acquire_write(&self, data: &[u8]) {
+        self.limiter.acquire(Limit::Request, 1);
+        self.limiter.acquire(Limit::Write, data.len() as u64);
+    }
+}
+
+impl<S: DataStore> DataStore for RateLimitStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.acquire_write(data);
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.limiter.acquire(Limit::Request, 1);
+        let data = self.inner.read_block(key)?;
+        if let Some(data) = &data {
+            self.limiter.acquire(Limit::Read, data.len() as u64);
+        }
+        Ok(data)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.limiter.acquire(Limit::Request, 1);
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.limiter.acquire(Limit::Request, 1);
+        self.inner.list_blocks(kind)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.limiter.acquire(Limit::Request, 1);
+        self.inner.block_size(key)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        self.acquire_write(data);
+        self.inner.write_block_if(key, expected, data)
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.acquire_write(data);
+        self.inner.create_block_exclusive(key, data)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.inner.conditional_writes()
+    }
+}
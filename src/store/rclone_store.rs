@@ -149,6 +149,10 @@ impl DataStore for RcloneStore {
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
         self.sftp_store.list_blocks(kind)
     }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.sftp_store.block_size(key)
+    }
 }
 
 impl Drop for RcloneStore {
@@ -0,0 +1,325 @@
+#![cfg(feature = "store-redb")]
+
+use std::fs::{canonicalize, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use once_cell::sync::Lazy;
+use redb::{Database, Durability, ReadableTable, TableDefinition, WriteTransaction};
+use uuid::{uuid, Uuid};
+use weak_table::WeakValueHashMap;
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::OpenStore;
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("0c7b5e36-ca41-11f1-8d52-8f0e6a3d91c7");
+
+// The tables in the database.
+const DATA_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("data");
+const LOCKS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("locks");
+const HEADERS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("headers");
+const BLOCKS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("blocks");
+const METADATA_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("metadata");
+
+/// The databases which are currently open in this process.
+///
+/// A database file can only be opened once at a time, so stores which are opened from the same
+/// path share a `Database`.
+static OPEN_DATABASES: Lazy<Mutex<WeakValueHashMap<PathBuf, Weak<Database>>>> =
+    Lazy::new(|| Mutex::new(WeakValueHashMap::new()));
+
+fn type_table(kind: BlockType) -> TableDefinition<'static, &'static [u8], &'static [u8]> {
+    match kind {
+        BlockType::Data => DATA_TABLE,
+        BlockType::Lock => LOCKS_TABLE,
+        BlockType::Header => HEADERS_TABLE,
+    }
+}
+
+/// Return the table containing the block with the given `key` and the key of its row.
+fn block_row(
+    key: BlockKey,
+) -> (
+    TableDefinition<'static, &'static [u8], &'static [u8]>,
+    Vec<u8>,
+) {
+    match key {
+        BlockKey::Data(id) => (DATA_TABLE, id.as_ref().as_bytes().to_vec()),
+        BlockKey::Lock(id) => (LOCKS_TABLE, id.as_ref().as_bytes().to_vec()),
+        BlockKey::Header(id) => (HEADERS_TABLE, id.as_ref().as_bytes().to_vec()),
+        BlockKey::Super => (BLOCKS_TABLE, b"super".to_vec()),
+        BlockKey::Version => (BLOCKS_TABLE, b"version".to_vec()),
+    }
+}
+
+/// Return how durable a write to the block with the given `key` must be.
+///
+/// Repositories only reference data blocks from headers and the superblock, which are always
+/// written after the data blocks they reference. Writes to data blocks don't need to be flushed to
+/// disk immediately, because they are flushed by the next durable write.
+fn block_durability(key: BlockKey) -> Durability {
+    match key {
+        BlockKey::Data(_) => Durability::Eventual,
+        _ => Durability::Immediate,
+    }
+}
+
+/// Open the database at the given `path`, or return it if it is already open in this process.
+fn open_database(path: &Path) -> super::Result<Arc<Database>> {
+    let mut databases = OPEN_DATABASES.lock().unwrap();
+
+    // The file must exist to get its canonical path. Redb initializes empty files.
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    let path = canonicalize(path)?;
+
+    if let Some(database) = databases.get(&path) {
+        return Ok(database);
+    }
+
+    let database = Arc::new(Database::create(&path)?);
+    databases.insert(path, Arc::clone(&database));
+    Ok(database)
+}
+
+/// The configuration for opening a [`RedbStore`].
+///
+/// [`RedbStore`]: crate::store::RedbStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-redb")))]
+pub struct RedbConfig {
+    /// The path of the database file.
+    pub path: PathBuf,
+}
+
+impl OpenStore for RedbConfig {
+    type Store = RedbStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let database = open_database(&self.path).map_err(crate::Error::Store)?;
+
+        let transaction = database
+            .begin_write()
+            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+        {
+            // Create the tables so that read transactions can always open them.
+            for table in [DATA_TABLE, LOCKS_TABLE, HEADERS_TABLE, BLOCKS_TABLE] {
+                transaction
+                    .open_table(table)
+                    .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+            }
+
+            let mut metadata = transaction
+                .open_table(METADATA_TABLE)
+                .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+            let version_bytes = metadata
+                .get("version")
+                .map_err(|error| crate::Error::Store(super::Error::from(error)))?
+                .map(|guard| guard.value().to_vec());
+
+            match version_bytes {
+                Some(bytes) => {
+                    let version = Uuid::from_slice(bytes.as_slice())
+                        .map_err(|_| crate::Error::UnsupportedStore)?;
+                    if version != CURRENT_VERSION {
+                        return Err(crate::Error::UnsupportedStore);
+                    }
+                }
+                None => {
+                    metadata
+                        .insert("version", &CURRENT_VERSION.as_bytes()[..])
+                        .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+                }
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+
+        Ok(RedbStore { database })
+    }
+}
+
+/// A `DataStore` which stores data in a [redb] database.
+///
+/// This stores all blocks in a single file using an embedded key-value database. Every write is a
+/// transaction, and writing many small blocks is much faster than with a [`DirectoryStore`],
+/// which uses several files and system calls for each block.
+///
+/// A database file can only be used by one process at a time. Stores in the same process which
+/// are opened from the same path share the database, so a repository can be opened more than once.
+///
+/// You can use [`RedbStore::copy_from`] to convert an existing data store, such as a
+/// [`DirectoryStore`], to this type.
+///
+/// You can use [`RedbConfig`] to open a data store of this type.
+///
+/// [redb]: https://docs.rs/redb
+/// [`RedbConfig`]: crate::store::RedbConfig
+/// [`DirectoryStore`]: crate::store::DirectoryStore
+/// [`RedbStore::copy_from`]: crate::store::RedbStore::copy_from
+#[derive(Debug)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-redb")))]
+pub struct RedbStore {
+    /// The database containing the blocks.
+    database: Arc<Database>,
+}
+
+impl RedbStore {
+    /// Copy every block in the `source` data store into this data store.
+    ///
+    /// This copies blocks of every type in a single transaction, so if it fails, this data store
+    /// is left unchanged. Existing blocks with the same keys are overwritten. This returns the
+    /// number of blocks which were copied.
+    ///
+    /// To convert a repository to use this data store, copy its blocks while no clients have it
+    /// open and then open the repository using a [`RedbConfig`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # #[cfg(feature = "store-directory")] {
+    /// use acid_store::store::{DirectoryConfig, OpenStore, RedbConfig};
+    ///
+    /// let mut source = DirectoryConfig { path: "/path/to/store".into() }.open().unwrap();
+    /// let mut destination = RedbConfig { path: "/path/to/store.redb".into() }.open().unwrap();
+    /// destination.copy_from(&mut source).unwrap();
+    /// # }
+    /// ```
+    ///
+    /// [`RedbConfig`]: crate::store::RedbConfig
+    pub fn copy_from(&mut self, source: &mut impl DataStore) -> super::Result<u64> {
+        let mut transaction = self.database.begin_write()?;
+        let mut copied = 0;
+
+        for kind in [BlockType::Data, BlockType::Lock, BlockType::Header] {
+            for id in source.list_blocks(kind)? {
+                let key = match kind {
+                    BlockType::Data => BlockKey::Data(id),
+                    BlockType::Lock => BlockKey::Lock(id),
+                    BlockType::Header => BlockKey::Header(id),
+                };
+                // A block may be removed between listing it and reading it.
+                if let Some(data) = source.read_block(key)? {
+                    insert_block(&mut transaction, key, &data)?;
+                    copied += 1;
+                }
+            }
+        }
+
+        for key in [BlockKey::Version, BlockKey::Super] {
+            if let Some(data) = source.read_block(key)? {
+                insert_block(&mut transaction, key, &data)?;
+                copied += 1;
+            }
+        }
+
+        transaction.commit()?;
+
+        Ok(copied)
+    }
+}
+
+/// Write `data` to the block with the given `key` in the given `transaction`.
+fn insert_block(
+    transaction: &mut WriteTransaction,
+    key: BlockKey,
+    data: &[u8],
+) -> super::Result<()> {
+    let (table, row_key) = block_row(key);
+    transaction
+        .open_table(table)?
+        .insert(row_key.as_slice(), data)?;
+    Ok(())
+}
+
+impl DataStore for RedbStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        let mut transaction = self.database.begin_write()?;
+        transaction.set_durability(block_durability(key));
+        insert_block(&mut transaction, key, data)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let (table, row_key) = block_row(key);
+        let transaction = self.database.begin_read()?;
+        let data = transaction
+            .open_table(table)?
+            .get(row_key.as_slice())?
+            .map(|guard| guard.value().to_vec());
+
+        Ok(data)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        let (table, row_key) = block_row(key);
+        let mut transaction = self.database.begin_write()?;
+        transaction.set_durability(block_durability(key));
+        transaction.open_table(table)?.remove(row_key.as_slice())?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let transaction = self.database.begin_read()?;
+        let table = transaction.open_table(type_table(kind))?;
+
+        let mut block_ids = Vec::new();
+        for entry in table.iter()? {
+            let (id_guard, _) = entry?;
+            let id = Uuid::from_slice(id_guard.value())?;
+            block_ids.push(id.into());
+        }
+
+        Ok(block_ids)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let (table, row_key) = block_row(key);
+        let transaction = self.database.begin_read()?;
+        let size = transaction
+            .open_table(table)?
+            .get(row_key.as_slice())?
+            .map(|guard| guard.value().len() as u64);
+
+        Ok(size)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        // Only one write transaction can be open at a time, so no other store can write the block
+        // between when we read it and when we write it. If the transaction is dropped without
+        // being committed, it is aborted.
+        let (table, row_key) = block_row(key);
+        let mut transaction = self.database.begin_write()?;
+        transaction.set_durability(block_durability(key));
+        {
+            let mut table = transaction.open_table(table)?;
+            let current = table
+                .get(row_key.as_slice())?
+                .map(|guard| guard.value().to_vec());
+            if current.as_deref() != expected {
+                return Ok(false);
+            }
+            table.insert(row_key.as_slice(), data)?;
+        }
+        transaction.commit()?;
+
+        Ok(true)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::Full
+    }
+}
@@ -9,7 +9,7 @@ use redis::{
 };
 use uuid::Uuid;
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
 use super::open_store::OpenStore;
 
 /// A UUID which acts as the version ID of the store format.
@@ -183,4 +183,52 @@ impl DataStore for RedisStore {
 
         Ok(blocks)
     }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let key = block_key(key);
+
+        // `STRLEN` returns 0 for keys which don't exist, so we need to check whether the key
+        // exists in the same transaction to distinguish missing blocks from empty ones.
+        let (exists, size): (bool, u64) = redis::pipe()
+            .atomic()
+            .exists(&key)
+            .cmd("STRLEN")
+            .arg(&key)
+            .query(&mut self.connection)?;
+
+        Ok(exists.then_some(size))
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        let key = block_key(key);
+
+        // This watches the key so that the transaction is aborted and retried if another client
+        // modifies it between when we read it and when we write it.
+        let written = redis::transaction(&mut self.connection, &[&key], |connection, pipe| {
+            let current: Option<Vec<u8>> = connection.get(&key)?;
+            if current.as_deref() != expected {
+                return Ok(Some(false));
+            }
+            Ok(pipe
+                .set(&key, data)
+                .ignore()
+                .query::<Option<()>>(connection)?
+                .map(|_| true))
+        })?;
+
+        Ok(written)
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        Ok(self.connection.set_nx(block_key(key), data)?)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::Full
+    }
 }
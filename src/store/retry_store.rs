@@ -0,0 +1,171 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::OpenStore;
+
+/// How a [`RetryStore`] retries failed operations.
+///
+/// After an operation fails with a retryable error, the store waits before retrying it. The delay
+/// before the first retry is `initial_delay`, and each subsequent delay is `multiplier` times the
+/// previous one, up to `max_delay`. Each delay is then reduced by a random fraction of up to
+/// `jitter` so that many clients which fail at the same time don't all retry at the same time.
+///
+/// [`RetryStore`]: crate::store::RetryStore
+#[derive(Debug, PartialEq, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of times to retry an operation before giving up.
+    pub max_retries: u32,
+
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+
+    /// The maximum delay between retries.
+    pub max_delay: Duration,
+
+    /// The factor to multiply the delay by after each retry.
+    pub multiplier: f64,
+
+    /// The maximum fraction of each delay to subtract at random, between `0.0` and `1.0`.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Return how long to wait before the retry with the given zero-based index.
+    fn delay(&self, retry: u32) -> Duration {
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(retry as i32);
+        let delay = delay.min(self.max_delay.as_secs_f64());
+
+        // We only need a little randomness, so we use a random UUID rather than depending on a
+        // random number generator.
+        let random = Uuid::new_v4().as_u128() as f64 / u128::MAX as f64;
+        let jitter = self.jitter.clamp(0.0, 1.0) * random;
+
+        Duration::from_secs_f64((delay * (1.0 - jitter)).max(0.0))
+    }
+}
+
+/// The configuration for opening a [`RetryStore`].
+///
+/// [`RetryStore`]: crate::store::RetryStore
+#[derive(Debug, PartialEq, Clone)]
+pub struct RetryConfig<C> {
+    /// The configuration for the data store to retry operations on.
+    pub inner: C,
+
+    /// How to retry failed operations.
+    pub policy: RetryPolicy,
+}
+
+impl<C: OpenStore> OpenStore for RetryConfig<C> {
+    type Store = RetryStore<C::Store>;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        Ok(RetryStore::new(self.inner.open()?, self.policy.clone()))
+    }
+}
+
+/// A `DataStore` which retries operations on another data store which fail with transient errors.
+///
+/// This wraps another data store, such as one which stores data on a remote server, and retries
+/// operations which fail with an error for which [`Error::is_retryable`] returns `true`, waiting
+/// between retries according to a [`RetryPolicy`]. Errors which are not retryable are returned
+/// immediately.
+///
+/// Only `write_block`, `read_block`, `remove_block`, and `list_blocks` are retried, because
+/// retrying them has the same effect as performing them once. `write_block_if` and
+/// `create_block_exclusive` are not retried, because if a conditional write succeeds but its
+/// response is lost, retrying it would incorrectly report that its condition was not met.
+///
+/// You can use [`RetryConfig`] to open a data store of this type.
+///
+/// [`Error::is_retryable`]: crate::store::Error::is_retryable
+/// [`RetryPolicy`]: crate::store::RetryPolicy
+/// [`RetryConfig`]: crate::store::RetryConfig
+#[derive(Debug)]
+pub struct RetryStore<S> {
+    inner: S,
+    policy: RetryPolicy,
+}
+
+impl<S: DataStore> RetryStore<S> {
+    /// Wrap the given `inner` data store, retrying operations according to `policy`.
+    pub fn new(inner: S, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Consume this value, returning the wrapped store.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Perform the given `operation` on the wrapped store, retrying it if it fails.
+    fn retry<T>(
+        &mut self,
+        mut operation: impl FnMut(&mut S) -> super::Result<T>,
+    ) -> super::Result<T> {
+        let mut retry = 0;
+        loop {
+            match operation(&mut self.inner) {
+                Err(error) if error.is_retryable() && retry < self.policy.max_retries => {
+                    sleep(self.policy.delay(retry));
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<S: DataStore> DataStore for RetryStore<S> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.retry(|store| store.write_block(key, data))
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.retry(|store| store.read_block(key))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.retry(|store| store.remove_block(key))
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        self.retry(|store| store.list_blocks(kind))
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.retry(|store| store.block_size(key))
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        self.inner.write_block_if(key, expected, data)
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.inner.create_block_exclusive(key, data)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.inner.conditional_writes()
+    }
+}
@@ -7,7 +7,7 @@ use s3::creds::Credentials;
 use s3::region::Region;
 use uuid::{uuid, Uuid};
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
 use super::open_store::OpenStore;
 
 /// The separator to use in S3 object keys.
@@ -43,6 +43,15 @@ const CURRENT_VERSION: Uuid = uuid!("f0511da2-f90d-11eb-be71-13f36b8156e4");
 /// The HTTP status code for an object which does not exist.
 const NOT_FOUND_CODE: u16 = 404;
 
+/// The HTTP status code for a conditional request whose condition was not met.
+const PRECONDITION_FAILED_CODE: u16 = 412;
+
+/// The HTTP status code for a conditional request which conflicts with a concurrent request.
+const CONFLICT_CODE: u16 = 409;
+
+/// The HTTP status code for a request which was rejected because too many requests were sent.
+const TOO_MANY_REQUESTS_CODE: u16 = 429;
+
 /// The environment variable for the AWS access key.
 const ACCESS_KEY_ENV: &str = "AWS_ACCESS_KEY_ID";
 
@@ -126,8 +135,14 @@ pub enum S3Region {
     /// sa-east-1
     SaEast1,
 
-    /// Custom region
-    Custom { name: String, endpoint: String },
+    /// A custom region, such as one provided by an S3-compatible server.
+    Custom {
+        /// The name of the region.
+        name: String,
+
+        /// The URL of the S3 endpoint, such as `http://localhost:9000`.
+        endpoint: String,
+    },
 }
 
 impl S3Region {
@@ -302,6 +317,29 @@ impl S3Credentials {
 
 /// The configuration for opening an [`S3Store`].
 ///
+/// Besides Amazon S3, this can connect to S3-compatible servers like MinIO using a custom region
+/// and path-style addressing.
+///
+/// # Examples
+/// ```no_run
+/// use acid_store::store::{OpenStore, S3Config, S3Credentials, S3Region};
+///
+/// let config = S3Config {
+///     bucket: String::from("backups"),
+///     region: S3Region::Custom {
+///         name: String::from("us-east-1"),
+///         endpoint: String::from("http://localhost:9000"),
+///     },
+///     credentials: S3Credentials::Basic {
+///         access_key: String::from("minioadmin"),
+///         secret_key: String::from("minioadmin"),
+///     },
+///     prefix: String::from("acid-store"),
+///     path_style: true,
+/// };
+/// let store = config.open().unwrap();
+/// ```
+///
 /// [`S3Store`]: crate::store::S3Store
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(docsrs, doc(cfg(feature = "store-s3")))]
@@ -320,11 +358,19 @@ pub struct S3Config {
     /// While keys in S3 are a flat namespace, you can think of this like the directory of the
     /// bucket to create the store in. To create the store in the bucket root, use an empty string.
     pub prefix: String,
+
+    /// Whether to use path-style addressing.
+    ///
+    /// By default, the bucket name is part of the host name, like
+    /// `https://bucket.s3.us-east-1.amazonaws.com/key`. With path-style addressing, it is part of
+    /// the path instead, like `http://localhost:9000/bucket/key`. Most S3-compatible servers
+    /// which are accessed by IP address or through `localhost` require path-style addressing.
+    pub path_style: bool,
 }
 
 impl S3Config {
     fn into_bucket(self) -> Bucket {
-        Bucket::new(
+        let bucket = Bucket::new(
             self.bucket.as_str(),
             Region::Custom {
                 region: self.region.name().to_string(),
@@ -355,7 +401,12 @@ impl S3Config {
                 },
             },
         )
-        .unwrap()
+        .unwrap();
+        if self.path_style {
+            bucket.with_path_style()
+        } else {
+            bucket
+        }
     }
 }
 
@@ -429,16 +480,54 @@ impl S3Store {
     }
 }
 
+/// Return an error if the given HTTP `status_code` is not a success status code.
+///
+/// Status codes in `allowed` are not treated as errors. Server errors and throttling errors are
+/// retryable.
+fn check_status(status_code: u16, allowed: &[u16]) -> super::Result<()> {
+    if (200..300).contains(&status_code) || allowed.contains(&status_code) {
+        return Ok(());
+    }
+    let message = format!("The S3 server responded with status code {}.", status_code);
+    if status_code >= 500 || status_code == TOO_MANY_REQUESTS_CODE {
+        Err(super::Error::retryable_msg(message))
+    } else {
+        Err(super::Error::msg(message))
+    }
+}
+
+impl S3Store {
+    /// Write `data` to the object at `block_path` with the given conditional request header.
+    ///
+    /// This returns `false` if the condition was not met.
+    fn put_object_if(
+        &self,
+        block_path: String,
+        header: &str,
+        value: &str,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        let mut bucket = self.bucket.clone();
+        bucket.add_header(header, value);
+        let response = bucket.put_object(block_path, data)?;
+        match response.status_code() {
+            PRECONDITION_FAILED_CODE | CONFLICT_CODE => Ok(false),
+            status_code => check_status(status_code, &[]).map(|_| true),
+        }
+    }
+}
+
 impl DataStore for S3Store {
     fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
         let block_path = self.block_path(key);
-        self.bucket.put_object(block_path, data)?;
-        Ok(())
+        let response = self.bucket.put_object(block_path, data)?;
+        check_status(response.status_code(), &[])
     }
 
     fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
         let block_path = self.block_path(key);
         let response = self.bucket.get_object(block_path)?;
+        check_status(response.status_code(), &[NOT_FOUND_CODE])?;
         if response.status_code() == NOT_FOUND_CODE {
             Ok(None)
         } else {
@@ -448,8 +537,8 @@ impl DataStore for S3Store {
 
     fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
         let block_path = self.block_path(key);
-        self.bucket.delete_object(block_path)?;
-        Ok(())
+        let response = self.bucket.delete_object(block_path)?;
+        check_status(response.status_code(), &[NOT_FOUND_CODE])
     }
 
     fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
@@ -470,4 +559,56 @@ impl DataStore for S3Store {
             .collect::<Result<Vec<BlockId>, _>>()?;
         Ok(block_ids)
     }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let block_path = self.block_path(key);
+        let (head, status_code) = self.bucket.head_object(&block_path)?;
+        check_status(status_code, &[NOT_FOUND_CODE])?;
+        if status_code == NOT_FOUND_CODE {
+            return Ok(None);
+        }
+        match head.content_length {
+            Some(content_length) => Ok(Some(content_length as u64)),
+            None => Ok(self.read_block(key)?.map(|data| data.len() as u64)),
+        }
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        let expected = match expected {
+            Some(expected) => expected,
+            None => return self.create_block_exclusive(key, data),
+        };
+
+        // Get the ETag of the object before reading it so that the write fails if the object is
+        // modified after we compare its contents.
+        let block_path = self.block_path(key);
+        let (head, status_code) = self.bucket.head_object(&block_path)?;
+        check_status(status_code, &[NOT_FOUND_CODE])?;
+        let e_tag = match head.e_tag {
+            Some(e_tag) if status_code != NOT_FOUND_CODE => e_tag,
+            _ => return Ok(false),
+        };
+
+        if self.read_block(key)?.as_deref() != Some(expected) {
+            return Ok(false);
+        }
+
+        self.put_object_if(block_path, "If-Match", &e_tag, data)
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.put_object_if(self.block_path(key), "If-None-Match", "*", data)
+    }
+
+    /// This relies on the server supporting the `If-Match` and `If-None-Match` headers for
+    /// `PutObject` requests. Some S3-compatible services ignore these headers, in which case writes
+    /// are not conditional.
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::Full
+    }
 }
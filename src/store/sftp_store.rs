@@ -344,6 +344,22 @@ impl DataStore for SftpStore {
 
         Ok(block_ids)
     }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let block_path = self.block_path(key);
+
+        // Like `exists`, this treats a file which can't be stat'd as not existing.
+        let size = match self.sftp.stat(&block_path) {
+            Ok(stat) => stat.size,
+            Err(_) => return Ok(None),
+        };
+
+        // The server isn't required to report the size of the file.
+        match size {
+            Some(size) => Ok(Some(size)),
+            None => Ok(self.read_block(key)?.map(|data| data.len() as u64)),
+        }
+    }
 }
 
 impl Debug for SftpStore {
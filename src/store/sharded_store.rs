@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::{OpenDynStore, OpenStore};
+
+/// The configuration for opening a [`ShardedStore`].
+///
+/// # Examples
+/// ```
+/// use acid_store::store::{MemoryConfig, OpenStore, ShardedConfig};
+///
+/// let config = ShardedConfig::new(MemoryConfig::new())
+///     .shard(MemoryConfig::new())
+///     .shard(MemoryConfig::new());
+/// let store = config.open().unwrap();
+/// ```
+///
+/// [`ShardedStore`]: crate::store::ShardedStore
+#[derive(Clone)]
+pub struct ShardedConfig {
+    primary: Arc<dyn OpenDynStore>,
+    shards: Vec<Arc<dyn OpenDynStore>>,
+}
+
+impl Debug for ShardedConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedConfig")
+            .field("shards", &self.shards.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ShardedConfig {
+    /// Create a new `ShardedConfig` whose primary data store is opened using the given `config`.
+    ///
+    /// The primary data store stores every block other than data blocks.
+    pub fn new<C>(primary: C) -> Self
+    where
+        C: OpenStore + Send + Sync + 'static,
+    {
+        Self {
+            primary: Arc::new(primary),
+            shards: Vec::new(),
+        }
+    }
+
+    /// Add a shard which is opened using the given `config`.
+    ///
+    /// Shards must always be added in the same order, because the shard a block is stored in
+    /// depends on its position.
+    pub fn shard<C>(mut self, config: C) -> Self
+    where
+        C: OpenStore + Send + Sync + 'static,
+    {
+        self.shards.push(Arc::new(config));
+        self
+    }
+}
+
+impl OpenStore for ShardedConfig {
+    type Store = ShardedStore;
+
+    /// # Panics
+    /// - The config has no shards.
+    fn open(&self) -> crate::Result<Self::Store> {
+        let primary = self.primary.open_dyn()?;
+        let shards = self
+            .shards
+            .iter()
+            .map(|config| config.open_dyn())
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(ShardedStore::new(primary, shards))
+    }
+}
+
+/// A `DataStore` which splits data blocks across several other data stores.
+///
+/// This wraps a primary data store and a list of shards, which can be data stores of any type.
+/// Each data block is stored in one of the shards, which is chosen by hashing its ID. All other
+/// blocks, such as locks and headers, are stored in the primary data store. The same data store can
+/// be both the primary and one of the shards.
+///
+/// Blocks are assigned to shards using rendezvous hashing, so when a shard is added, only the
+/// blocks which are assigned to the new shard need to be moved. After adding a shard, you should
+/// call [`rebalance`] to move blocks to the shards they are assigned to. Until then, reading a
+/// block which has not been moved requires checking every shard, but the data store otherwise works
+/// correctly. Removing a block removes it from every shard for the same reason.
+///
+/// You can use [`ShardedConfig`] to open a data store of this type.
+///
+/// [`rebalance`]: crate::store::ShardedStore::rebalance
+/// [`ShardedConfig`]: crate::store::ShardedConfig
+#[derive(Debug)]
+pub struct ShardedStore {
+    primary: Box<dyn DataStore>,
+    shards: Vec<Box<dyn DataStore>>,
+}
+
+impl ShardedStore {
+    /// Create a new `ShardedStore` which stores data blocks in `shards` and all other blocks in
+    /// `primary`.
+    ///
+    /// # Panics
+    /// - `shards` is empty.
+    pub fn new(primary: Box<dyn DataStore>, shards: Vec<Box<dyn DataStore>>) -> Self {
+        assert!(
+            !shards.is_empty(),
+            "A sharded store must have at least one shard."
+        );
+        Self { primary, shards }
+    }
+
+    /// The number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Add the given `shard` after the existing shards.
+    ///
+    /// Existing blocks are not moved to the new shard until [`rebalance`] is called.
+    ///
+    /// [`rebalance`]: crate::store::ShardedStore::rebalance
+    pub fn add_shard(&mut self, shard: Box<dyn DataStore>) {
+        self.shards.push(shard);
+    }
+
+    /// Move each data block to the shard it is assigned to.
+    ///
+    /// Each block is written to its new shard before it is removed from its old one, so no blocks
+    /// are lost if this is interrupted. This returns the number of blocks which were moved.
+    ///
+    /// # Errors
+    /// - `Error::Store`: An error occurred with one of the shards.
+    pub fn rebalance(&mut self) -> super::Result<usize> {
+        let block_lists = self
+            .shards
+            .iter_mut()
+            .map(|shard| {
+                shard
+                    .list_blocks(BlockType::Data)
+                    .map(|ids| ids.into_iter().collect::<HashSet<_>>())
+            })
+            .collect::<super::Result<Vec<_>>>()?;
+
+        let mut blocks_moved = 0;
+        for (source, ids) in block_lists.iter().enumerate() {
+            for &id in ids {
+                let target = self.shard_index(id);
+                if target == source {
+                    continue;
+                }
+                let key = BlockKey::Data(id);
+
+                // Blocks are always written to the shard they're assigned to, so if the block is
+                // already there, the copy in the old shard is out of date.
+                if !block_lists[target].contains(&id) {
+                    if let Some(data) = self.shards[source].read_block(key)? {
+                        self.shards[target].write_block(key, &data)?;
+                        blocks_moved += 1;
+                    }
+                }
+                self.shards[source].remove_block(key)?;
+            }
+        }
+        Ok(blocks_moved)
+    }
+
+    /// Consume this value, returning the primary data store and the shards.
+    pub fn into_inner(self) -> (Box<dyn DataStore>, Vec<Box<dyn DataStore>>) {
+        (self.primary, self.shards)
+    }
+
+    /// Return the index of the shard the data block with the given `id` is assigned to.
+    fn shard_index(&self, id: BlockId) -> usize {
+        // Each shard gets a score for the block, and the block is assigned to the shard with the
+        // highest score. Adding a shard only moves the blocks for which the new shard has the
+        // highest score.
+        (0..self.shards.len())
+            .max_by_key(|index| {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(id.as_ref().as_bytes());
+                hasher.update(&(*index as u64).to_le_bytes());
+                let hash = hasher.finalize();
+                u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap())
+            })
+            .unwrap()
+    }
+
+    /// Return the data store which the block with the given `key` is stored in.
+    fn store_for(&mut self, key: BlockKey) -> &mut dyn DataStore {
+        match key {
+            BlockKey::Data(id) => {
+                let index = self.shard_index(id);
+                self.shards[index].as_mut()
+            }
+            _ => self.primary.as_mut(),
+        }
+    }
+}
+
+impl DataStore for ShardedStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.store_for(key).write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        let id = match key {
+            BlockKey::Data(id) => id,
+            _ => return self.primary.read_block(key),
+        };
+
+        let index = self.shard_index(id);
+        if let Some(data) = self.shards[index].read_block(key)? {
+            return Ok(Some(data));
+        }
+
+        // The block may not have been moved to the shard it is assigned to yet.
+        for (other_index, shard) in self.shards.iter_mut().enumerate() {
+            if other_index == index {
+                continue;
+            }
+            if let Some(data) = shard.read_block(key)? {
+                return Ok(Some(data));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        match key {
+            // The block may not have been moved to the shard it is assigned to yet.
+            BlockKey::Data(_) => {
+                for shard in &mut self.shards {
+                    shard.remove_block(key)?;
+                }
+                Ok(())
+            }
+            _ => self.primary.remove_block(key),
+        }
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        match kind {
+            BlockType::Data => {
+                // A block may be in two shards if rebalancing was interrupted.
+                let mut block_ids = HashSet::new();
+                for shard in &mut self.shards {
+                    block_ids.extend(shard.list_blocks(kind)?);
+                }
+                Ok(block_ids.into_iter().collect())
+            }
+            _ => self.primary.list_blocks(kind),
+        }
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let id = match key {
+            BlockKey::Data(id) => id,
+            _ => return self.primary.block_size(key),
+        };
+
+        let index = self.shard_index(id);
+        if let Some(size) = self.shards[index].block_size(key)? {
+            return Ok(Some(size));
+        }
+
+        // The block may not have been moved to the shard it is assigned to yet.
+        for (other_index, shard) in self.shards.iter_mut().enumerate() {
+            if other_index == index {
+                continue;
+            }
+            if let Some(size) = shard.block_size(key)? {
+                return Ok(Some(size));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        self.store_for(key).write_block_if(key, expected, data)
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        self.store_for(key).create_block_exclusive(key, data)
+    }
+
+    /// Repositories only perform conditional writes on blocks in the primary data store, so this
+    /// returns the level of support of the primary data store.
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.primary.conditional_writes()
+    }
+}
@@ -5,7 +5,7 @@ use std::path::PathBuf;
 use rusqlite::{params, Connection, OptionalExtension, NO_PARAMS};
 use uuid::{uuid, Uuid};
 
-use super::data_store::{BlockId, BlockKey, BlockType, DataStore};
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
 use super::open_store::OpenStore;
 
 /// A UUID which acts as the version ID of the store format.
@@ -289,4 +289,109 @@ impl DataStore for SqliteStore {
 
         Ok(result)
     }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        let size: Option<i64> = match key {
+            BlockKey::Data(id) => self
+                .connection
+                .query_row(
+                    r#"
+                        SELECT length(data) FROM Data
+                        WHERE uuid = ?1;
+                    "#,
+                    params![&id.as_ref().as_bytes()[..]],
+                    |row| row.get(0),
+                )
+                .optional()?,
+            BlockKey::Lock(id) => self
+                .connection
+                .query_row(
+                    r#"
+                        SELECT length(data) FROM Locks
+                        WHERE uuid = ?1;
+                    "#,
+                    params![&id.as_ref().as_bytes()[..]],
+                    |row| row.get(0),
+                )
+                .optional()?,
+            BlockKey::Header(id) => self
+                .connection
+                .query_row(
+                    r#"
+                        SELECT length(data) FROM Headers
+                        WHERE uuid = ?1;
+                    "#,
+                    params![&id.as_ref().as_bytes()[..]],
+                    |row| row.get(0),
+                )
+                .optional()?,
+            BlockKey::Super => self
+                .connection
+                .query_row(
+                    r#"
+                        SELECT length(data) FROM Blocks
+                        WHERE key = 'super';
+                    "#,
+                    NO_PARAMS,
+                    |row| row.get(0),
+                )
+                .optional()?,
+            BlockKey::Version => self
+                .connection
+                .query_row(
+                    r#"
+                        SELECT length(data) FROM Blocks
+                        WHERE key = 'version';
+                    "#,
+                    NO_PARAMS,
+                    |row| row.get(0),
+                )
+                .optional()?,
+        };
+
+        Ok(size.map(|size| size as u64))
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        // An immediate transaction acquires a write lock on the database, so no other connection
+        // can write the block between when we read it and when we write it.
+        self.connection.execute_batch("BEGIN IMMEDIATE;")?;
+        match self.compare_and_write(key, expected, data) {
+            Ok(written) => {
+                self.connection.execute_batch("COMMIT;")?;
+                Ok(written)
+            }
+            Err(error) => {
+                self.connection.execute_batch("ROLLBACK;")?;
+                Err(error)
+            }
+        }
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::Full
+    }
+}
+
+impl SqliteStore {
+    /// Write `data` to the block with the given `key` if its current contents are `expected`.
+    ///
+    /// This must be called within a transaction.
+    fn compare_and_write(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> super::Result<bool> {
+        if self.read_block(key)?.as_deref() != expected {
+            return Ok(false);
+        }
+        self.write_block(key, data)?;
+        Ok(true)
+    }
 }
@@ -0,0 +1,497 @@
+#![cfg(feature = "store-webdav")]
+
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Formatter};
+use std::io::Read;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ureq::{Agent, AgentBuilder, ErrorKind, Response};
+use url::Url;
+use uuid::{uuid, Uuid};
+
+use super::data_store::{BlockId, BlockKey, BlockType, ConditionalWrites, DataStore};
+use super::open_store::OpenStore;
+
+/// A UUID which acts as the version ID of the store format.
+const CURRENT_VERSION: Uuid = uuid!("4d2b7f90-cb0e-11f1-a6f3-3b9c1e07d5a2");
+
+// The names of top-level files and collections in the data store.
+const STORE_COLLECTION: &str = "store";
+const STAGING_COLLECTION: &str = "stage";
+const VERSION_FILE: &str = "version";
+
+/// The HTTP status code for a resource which does not exist.
+const NOT_FOUND_CODE: u16 = 404;
+
+/// The HTTP status code for a `MKCOL` request for a collection which already exists.
+const METHOD_NOT_ALLOWED_CODE: u16 = 405;
+
+/// The HTTP status code for a `MOVE` request whose destination exists and may not be overwritten.
+const PRECONDITION_FAILED_CODE: u16 = 412;
+
+/// The HTTP status code for a request which was rejected because too many requests were sent.
+const TOO_MANY_REQUESTS_CODE: u16 = 429;
+
+/// The body of a `PROPFIND` request which asks only for the type of each resource.
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+
+/// The body of a `PROPFIND` request which asks only for the size of a file.
+const SIZE_PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getcontentlength/></d:prop></d:propfind>"#;
+
+fn type_path(kind: BlockType) -> String {
+    match kind {
+        BlockType::Data => format!("{}/data", STORE_COLLECTION),
+        BlockType::Lock => format!("{}/locks", STORE_COLLECTION),
+        BlockType::Header => format!("{}/headers", STORE_COLLECTION),
+    }
+}
+
+fn block_path(key: BlockKey) -> String {
+    match key {
+        BlockKey::Data(id) => {
+            let uuid_str = id.as_ref().as_hyphenated().to_string();
+            format!(
+                "{}/{}/{}",
+                type_path(BlockType::Data),
+                &uuid_str[..2],
+                uuid_str
+            )
+        }
+        BlockKey::Lock(id) => {
+            let uuid_str = id.as_ref().as_hyphenated().to_string();
+            format!("{}/{}", type_path(BlockType::Lock), uuid_str)
+        }
+        BlockKey::Header(id) => {
+            let uuid_str = id.as_ref().as_hyphenated().to_string();
+            format!("{}/{}", type_path(BlockType::Header), uuid_str)
+        }
+        BlockKey::Super => format!("{}/super", STORE_COLLECTION),
+        BlockKey::Version => format!("{}/version", STORE_COLLECTION),
+    }
+}
+
+/// Return the path of the collection containing the file at `path`.
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+/// Return an error if the given HTTP `status_code` is not a success status code.
+///
+/// Status codes in `allowed` are not treated as errors. Server errors and throttling errors are
+/// retryable.
+fn check_status(status_code: u16, allowed: &[u16]) -> super::Result<()> {
+    if (200..300).contains(&status_code) || allowed.contains(&status_code) {
+        return Ok(());
+    }
+    let message = format!(
+        "The WebDAV server responded with status code {}.",
+        status_code
+    );
+    if status_code >= 500 || status_code == TOO_MANY_REQUESTS_CODE {
+        Err(super::Error::retryable_msg(message))
+    } else {
+        Err(super::Error::msg(message))
+    }
+}
+
+/// Convert an error which occurred while sending a request into a data store error.
+///
+/// Errors caused by the connection are retryable.
+fn transport_error(error: ureq::Error) -> super::Error {
+    match error.kind() {
+        ErrorKind::Dns | ErrorKind::ConnectionFailed | ErrorKind::Io | ErrorKind::ProxyConnect => {
+            super::Error::retryable(error)
+        }
+        _ => super::Error::new(error),
+    }
+}
+
+/// The credentials for connecting to a WebDAV server.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-webdav")))]
+pub enum WebDavCredentials {
+    /// Connect without authenticating.
+    Anonymous,
+
+    /// Authenticate using HTTP basic authentication.
+    Basic {
+        /// The username to authenticate with.
+        username: String,
+
+        /// The password to authenticate with.
+        ///
+        /// Some servers, like Nextcloud, let you create an app password for this purpose.
+        password: String,
+    },
+}
+
+/// The configuration for opening a [`WebDavStore`].
+///
+/// # Examples
+/// ```no_run
+/// use acid_store::store::{OpenStore, WebDavConfig, WebDavCredentials};
+///
+/// let config = WebDavConfig {
+///     url: String::from("https://cloud.example.com/remote.php/dav/files/user/acid-store"),
+///     credentials: WebDavCredentials::Basic {
+///         username: String::from("user"),
+///         password: String::from("app-password"),
+///     },
+/// };
+/// let store = config.open().unwrap();
+/// ```
+///
+/// [`WebDavStore`]: crate::store::WebDavStore
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "store-webdav")))]
+pub struct WebDavConfig {
+    /// The URL of the collection to create the store in.
+    ///
+    /// The collection is created if it doesn't exist, but its parent must already exist.
+    pub url: String,
+
+    /// The credentials to connect with.
+    pub credentials: WebDavCredentials,
+}
+
+impl OpenStore for WebDavConfig {
+    type Store = WebDavStore;
+
+    fn open(&self) -> crate::Result<Self::Store> {
+        let mut base_url = self.url.clone();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let base_url = Url::parse(&base_url)
+            .map_err(|error| crate::Error::Store(super::Error::from(error)))?;
+
+        let mut store = WebDavStore {
+            agent: AgentBuilder::new().build(),
+            base_url,
+            authorization: match &self.credentials {
+                WebDavCredentials::Anonymous => None,
+                WebDavCredentials::Basic { username, password } => Some(format!(
+                    "Basic {}",
+                    BASE64.encode(format!("{}:{}", username, password))
+                )),
+            },
+            collections: HashSet::new(),
+        };
+
+        for collection in [
+            String::new(),
+            STORE_COLLECTION.to_string(),
+            STAGING_COLLECTION.to_string(),
+            type_path(BlockType::Data),
+            type_path(BlockType::Lock),
+            type_path(BlockType::Header),
+        ] {
+            store
+                .create_collection(&collection)
+                .map_err(crate::Error::Store)?;
+        }
+
+        match store.read_file(VERSION_FILE).map_err(crate::Error::Store)? {
+            Some(bytes) => {
+                let version = Uuid::from_slice(bytes.as_slice())
+                    .map_err(|_| crate::Error::UnsupportedStore)?;
+                if version != CURRENT_VERSION {
+                    return Err(crate::Error::UnsupportedStore);
+                }
+            }
+            None => {
+                store
+                    .write_file(VERSION_FILE, CURRENT_VERSION.as_bytes())
+                    .map_err(crate::Error::Store)?;
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// A `DataStore` which stores data on a WebDAV server.
+///
+/// This lays out blocks the same way as a [`DirectoryStore`]. Blocks are written by uploading
+/// them to a staging collection and then moving them to their final location, so that they are
+/// written atomically on servers which implement `MOVE` atomically.
+///
+/// You can use [`WebDavConfig`] to open a data store of this type.
+///
+/// [`DirectoryStore`]: crate::store::DirectoryStore
+/// [`WebDavConfig`]: crate::store::WebDavConfig
+#[cfg_attr(docsrs, doc(cfg(feature = "store-webdav")))]
+pub struct WebDavStore {
+    /// The HTTP client.
+    agent: Agent,
+
+    /// The URL of the store's root collection, ending with a slash.
+    base_url: Url,
+
+    /// The value of the `Authorization` header to send with each request.
+    authorization: Option<String>,
+
+    /// The paths of collections which are known to exist.
+    collections: HashSet<String>,
+}
+
+impl Debug for WebDavStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebDavStore")
+            .field("base_url", &self.base_url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebDavStore {
+    /// Return the URL of the resource at the given `path` relative to the store's root.
+    fn url(&self, path: &str) -> super::Result<Url> {
+        Ok(self.base_url.join(path)?)
+    }
+
+    /// Return the URL of the collection at the given `path` relative to the store's root.
+    fn collection_url(&self, path: &str) -> super::Result<Url> {
+        if path.is_empty() {
+            Ok(self.base_url.clone())
+        } else {
+            self.url(&format!("{}/", path))
+        }
+    }
+
+    /// Send a request with the given `method` to the given `url` and return the response.
+    ///
+    /// Responses with error status codes are returned rather than treated as errors.
+    fn send(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> super::Result<Response> {
+        let mut request = self.agent.request_url(method, url);
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        match request.send_bytes(body) {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => Ok(response),
+            Err(error) => Err(transport_error(error)),
+        }
+    }
+
+    /// Create the collection at the given `path` if it doesn't already exist.
+    fn create_collection(&mut self, path: &str) -> super::Result<()> {
+        if self.collections.contains(path) {
+            return Ok(());
+        }
+        let url = self.collection_url(path)?;
+        let response = self.send("MKCOL", &url, &[], &[])?;
+        check_status(response.status(), &[METHOD_NOT_ALLOWED_CODE])?;
+        self.collections.insert(path.to_string());
+        Ok(())
+    }
+
+    /// Return the contents of the file at the given `path` or `None` if it doesn't exist.
+    fn read_file(&self, path: &str) -> super::Result<Option<Vec<u8>>> {
+        let response = self.send("GET", &self.url(path)?, &[], &[])?;
+        if response.status() == NOT_FOUND_CODE {
+            return Ok(None);
+        }
+        check_status(response.status(), &[])?;
+        let mut buffer = Vec::new();
+        response.into_reader().read_to_end(&mut buffer)?;
+        Ok(Some(buffer))
+    }
+
+    /// Return the size of the file at the given `path` or `None` if it doesn't exist.
+    ///
+    /// This asks the server for the size of the file without downloading it.
+    fn file_size(&self, path: &str) -> super::Result<Option<u64>> {
+        let response = self.send(
+            "PROPFIND",
+            &self.url(path)?,
+            &[
+                ("Depth", "0"),
+                ("Content-Type", "application/xml; charset=utf-8"),
+            ],
+            SIZE_PROPFIND_BODY.as_bytes(),
+        )?;
+        if response.status() == NOT_FOUND_CODE {
+            return Ok(None);
+        }
+        check_status(response.status(), &[])?;
+
+        let mut body = String::new();
+        response.into_reader().read_to_string(&mut body)?;
+        let document = roxmltree::Document::parse(&body)?;
+        let content_length = document
+            .descendants()
+            .find(|node| node.has_tag_name(("DAV:", "getcontentlength")))
+            .and_then(|node| node.text())
+            .map(|text| text.trim().parse::<u64>());
+
+        match content_length {
+            Some(Ok(size)) => Ok(Some(size)),
+            Some(Err(_)) => Err(super::Error::msg("File size is invalid.")),
+            // The server isn't required to report the size of the file.
+            None => Ok(self.read_file(path)?.map(|data| data.len() as u64)),
+        }
+    }
+
+    /// Write `data` to the file at the given `path`, replacing it if it exists.
+    fn write_file(&self, path: &str, data: &[u8]) -> super::Result<()> {
+        let response = self.send("PUT", &self.url(path)?, &[], data)?;
+        check_status(response.status(), &[])
+    }
+
+    /// Upload `data` to a staging file and move it to the file at the given `path`.
+    ///
+    /// If `overwrite` is `false` and the file already exists, this returns `false` and leaves it
+    /// unchanged.
+    fn write_file_atomic(
+        &mut self,
+        path: &str,
+        data: &[u8],
+        overwrite: bool,
+    ) -> super::Result<bool> {
+        self.create_collection(parent_path(path))?;
+
+        let staging_path = format!("{}/{}", STAGING_COLLECTION, Uuid::new_v4().as_hyphenated());
+        self.write_file(&staging_path, data)?;
+
+        let destination = self.url(path)?;
+        let response = self.send(
+            "MOVE",
+            &self.url(&staging_path)?,
+            &[
+                ("Destination", destination.as_str()),
+                ("Overwrite", if overwrite { "T" } else { "F" }),
+            ],
+            &[],
+        )?;
+
+        if response.status() == PRECONDITION_FAILED_CODE {
+            self.remove_file(&staging_path)?;
+            return Ok(false);
+        }
+        check_status(response.status(), &[])?;
+        Ok(true)
+    }
+
+    /// Remove the file at the given `path` if it exists.
+    fn remove_file(&self, path: &str) -> super::Result<()> {
+        let response = self.send("DELETE", &self.url(path)?, &[], &[])?;
+        check_status(response.status(), &[NOT_FOUND_CODE])
+    }
+
+    /// Return the names of the members of the collection at the given `path`.
+    fn list_collection(&self, path: &str) -> super::Result<Vec<String>> {
+        let url = self.collection_url(path)?;
+        let response = self.send(
+            "PROPFIND",
+            &url,
+            &[
+                ("Depth", "1"),
+                ("Content-Type", "application/xml; charset=utf-8"),
+            ],
+            PROPFIND_BODY.as_bytes(),
+        )?;
+        if response.status() == NOT_FOUND_CODE {
+            return Ok(Vec::new());
+        }
+        check_status(response.status(), &[])?;
+
+        let mut body = String::new();
+        response.into_reader().read_to_string(&mut body)?;
+        self.parse_multistatus(&url, &body)
+    }
+
+    /// Return the names of the members of the collection at `url` from a `PROPFIND` `body`.
+    fn parse_multistatus(&self, url: &Url, body: &str) -> super::Result<Vec<String>> {
+        let document = roxmltree::Document::parse(body)?;
+        let collection_path = url.path().trim_end_matches('/');
+
+        let mut names = Vec::new();
+        for href in document
+            .descendants()
+            .filter(|node| node.has_tag_name(("DAV:", "href")))
+        {
+            // The href can be either an absolute URL or an absolute path.
+            let member_url = url.join(href.text().unwrap_or_default().trim())?;
+            let member_path = member_url.path().trim_end_matches('/');
+
+            // The response includes the collection itself.
+            if member_path == collection_path {
+                continue;
+            }
+
+            if let Some((_, name)) = member_path.rsplit_once('/') {
+                names.push(name.to_string());
+            }
+        }
+
+        Ok(names)
+    }
+}
+
+/// Parse the name of a block file as a block ID.
+fn parse_block_id(name: &str) -> super::Result<BlockId> {
+    Uuid::parse_str(name)
+        .map(BlockId::from)
+        .map_err(|_| super::Error::msg("Block file name is invalid."))
+}
+
+impl DataStore for WebDavStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> super::Result<()> {
+        self.write_file_atomic(&block_path(key), data, true)?;
+        Ok(())
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> super::Result<Option<Vec<u8>>> {
+        self.read_file(&block_path(key))
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> super::Result<()> {
+        self.remove_file(&block_path(key))
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> super::Result<Vec<BlockId>> {
+        let mut block_ids = Vec::new();
+
+        match kind {
+            BlockType::Data => {
+                for directory in self.list_collection(&type_path(kind))? {
+                    let directory_path = format!("{}/{}", type_path(kind), directory);
+                    for name in self.list_collection(&directory_path)? {
+                        block_ids.push(parse_block_id(&name)?);
+                    }
+                }
+            }
+            BlockType::Lock | BlockType::Header => {
+                for name in self.list_collection(&type_path(kind))? {
+                    block_ids.push(parse_block_id(&name)?);
+                }
+            }
+        }
+
+        Ok(block_ids)
+    }
+
+    fn block_size(&mut self, key: BlockKey) -> super::Result<Option<u64>> {
+        self.file_size(&block_path(key))
+    }
+
+    fn create_block_exclusive(&mut self, key: BlockKey, data: &[u8]) -> super::Result<bool> {
+        // Unlike a `MOVE` which overwrites its destination, this fails if the destination exists.
+        self.write_file_atomic(&block_path(key), data, false)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        ConditionalWrites::Create
+    }
+}
@@ -0,0 +1,87 @@
+#![cfg(all(feature = "encryption", feature = "compression", feature = "async"))]
+
+use std::io::SeekFrom;
+
+use acid_store::repo::AsyncObject;
+use common::*;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+mod common;
+
+#[rstest]
+#[tokio::test]
+async fn read_written_data(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = AsyncObject::new(repo_object.object);
+    let mut actual_data = Vec::new();
+
+    object.write_all(&buffer).await?;
+    object.commit().await?;
+    object.seek(SeekFrom::Start(0)).await?;
+    object.read_to_end(&mut actual_data).await?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn seek_returns_new_position(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = AsyncObject::new(repo_object.object);
+
+    object.write_all(&buffer).await?;
+    object.commit().await?;
+
+    assert_that!(object.seek(SeekFrom::End(0)).await?).is_equal_to(buffer.len() as u64);
+    assert_that!(object.seek(SeekFrom::Start(10)).await?).is_equal_to(10);
+    assert_that!(object.stream_position().await?).is_equal_to(10);
+
+    let mut actual_data = Vec::new();
+    object.read_to_end(&mut actual_data).await?;
+
+    assert_that!(actual_data.as_slice()).is_equal_to(&buffer[10..]);
+
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn read_into_small_buffers(repo_object: RepoObject, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut object = AsyncObject::new(repo_object.object);
+
+    object.write_all(&buffer).await?;
+    object.commit().await?;
+    object.rewind().await?;
+
+    let mut actual_data = Vec::new();
+    let mut chunk = [0u8; 1000];
+    loop {
+        let bytes_read = object.read(&mut chunk).await?;
+        if bytes_read == 0 {
+            break;
+        }
+        actual_data.extend_from_slice(&chunk[..bytes_read]);
+    }
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn into_inner_preserves_transaction(
+    repo_object: RepoObject,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = AsyncObject::new(repo_object.object);
+
+    object.write_all(&buffer).await?;
+    let mut object = object.into_inner().await?;
+
+    assert_that!(object.size()).is_err_variant(acid_store::Error::TransactionInProgress);
+    object.commit()?;
+    assert_that!(object.size()).is_ok_containing(buffer.len() as u64);
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+#![cfg(all(feature = "encryption", feature = "compression", feature = "async"))]
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    AsyncDataStore, BlockId, BlockKey, BlockType, BlockingStore, ConditionalWrites, DataStore,
+    MemoryConfig, MemoryStore, OpenStore, SpawnBlockingStore,
+};
+use common::*;
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+mod common;
+
+#[rstest]
+#[tokio::test]
+async fn async_store_reads_written_block(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut store = SpawnBlockingStore::new(MemoryConfig::new().open()?);
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer).await).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id)).await).is_ok_containing(Some(buffer));
+    assert_that!(store.list_blocks(BlockType::Data).await).is_ok_containing(vec![id]);
+    assert_that!(store.remove_block(BlockKey::Data(id)).await).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id)).await).is_ok_containing(None);
+
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn async_store_conditionally_writes_block(
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut store = SpawnBlockingStore::new(MemoryConfig::new().open()?);
+
+    assert_that!(
+        store
+            .create_block_exclusive(BlockKey::Super, &first_buffer)
+            .await
+    )
+    .is_ok_containing(true);
+    assert_that!(
+        store
+            .create_block_exclusive(BlockKey::Super, &second_buffer)
+            .await
+    )
+    .is_ok_containing(false);
+    assert_that!(
+        store
+            .write_block_if(BlockKey::Super, Some(&first_buffer), &second_buffer)
+            .await
+    )
+    .is_ok_containing(true);
+    assert_that!(store.read_block(BlockKey::Super).await).is_ok_containing(Some(second_buffer));
+
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn async_store_returns_block_size(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut store = SpawnBlockingStore::new(MemoryConfig::new().open()?);
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer).await).is_ok();
+    assert_that!(store.block_size(BlockKey::Data(id)).await)
+        .is_ok_containing(Some(buffer.len() as u64));
+    assert_that!(
+        store
+            .block_size(BlockKey::Data(Uuid::new_v4().into()))
+            .await
+    )
+    .is_ok_containing(None);
+
+    Ok(())
+}
+
+/// A store whose reads wait until another read is in progress at the same time.
+#[derive(Debug)]
+struct RendezvousStore {
+    store: MemoryStore,
+    readers: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl DataStore for RendezvousStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.store.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        let (readers, arrived) = &*self.readers;
+        let mut readers = readers.lock().unwrap();
+        *readers += 1;
+        arrived.notify_all();
+        let (readers, timeout) = arrived
+            .wait_timeout_while(readers, Duration::from_secs(5), |readers| *readers < 2)
+            .unwrap();
+        drop(readers);
+        if timeout.timed_out() {
+            return Err(acid_store::store::Error::msg(
+                "reads did not run concurrently",
+            ));
+        }
+        self.store.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.store.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.store.list_blocks(kind)
+    }
+}
+
+/// The configuration for opening a `RendezvousStore`.
+#[derive(Debug)]
+struct RendezvousConfig {
+    config: MemoryConfig,
+    readers: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl OpenStore for RendezvousConfig {
+    type Store = RendezvousStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(RendezvousStore {
+            store: self.config.open()?,
+            readers: Arc::clone(&self.readers),
+        })
+    }
+}
+
+#[rstest]
+#[tokio::test(flavor = "multi_thread")]
+async fn async_store_from_config_runs_operations_concurrently() -> anyhow::Result<()> {
+    let config = RendezvousConfig {
+        config: MemoryConfig::new(),
+        readers: Arc::new((Mutex::new(0), Condvar::new())),
+    };
+    let mut first_store = SpawnBlockingStore::from_config(config)?;
+    let mut second_store = first_store.clone();
+
+    let (first_result, second_result) = tokio::join!(
+        first_store.read_block(BlockKey::Super),
+        second_store.read_block(BlockKey::Super),
+    );
+
+    assert_that!(first_result).is_ok_containing(None);
+    assert_that!(second_result).is_ok_containing(None);
+
+    Ok(())
+}
+
+#[rstest]
+fn blocking_store_reads_written_block(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let runtime = Runtime::new()?;
+    let mut store = BlockingStore::new(
+        SpawnBlockingStore::new(MemoryConfig::new().open()?),
+        runtime.handle().clone(),
+    );
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer.clone()));
+    assert_that!(store.block_size(BlockKey::Data(id))).is_ok_containing(Some(buffer.len() as u64));
+    assert_that!(store.conditional_writes()).is_equal_to(ConditionalWrites::Full);
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_can_be_opened_with_blocking_store(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let runtime = Runtime::new()?;
+    let store = BlockingStore::new(
+        SpawnBlockingStore::new(MemoryConfig::new().open()?),
+        runtime.handle().clone(),
+    );
+
+    let mut repo: KeyRepo<String> = OpenOptions::new().mode(OpenMode::CreateNew).open(&store)?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new().open(&store)?;
+    let mut object = repo.object("test").unwrap();
+    let mut actual_data = Vec::new();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(buffer);
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::fs::read_dir;
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{BlockKey, CachingConfig, DataStore, MemoryConfig, OpenStore};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// Return a config for a `CachingStore` which caches blocks from `inner` in `directory`.
+fn cache_config(
+    inner: &MemoryConfig,
+    directory: &TempDir,
+    max_size: u64,
+) -> CachingConfig<MemoryConfig> {
+    CachingConfig {
+        inner: inner.clone(),
+        path: directory.path().join("cache"),
+        max_size,
+    }
+}
+
+/// Return the total size of the blocks in the cache directory.
+fn cache_size(directory: &TempDir) -> anyhow::Result<u64> {
+    let mut size = 0;
+    for entry in read_dir(directory.path().join("cache").join("blocks"))? {
+        size += entry?.metadata()?.len();
+    }
+    Ok(size)
+}
+
+#[rstest]
+fn data_blocks_are_read_from_cache(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = cache_config(&inner, &temp_dir, 1024 * 1024).open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(inner.open()?.remove_block(BlockKey::Data(id))).is_ok();
+
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn other_blocks_bypass_cache(
+    temp_dir: TempDir,
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = cache_config(&inner, &temp_dir, 1024 * 1024).open()?;
+    let id = Uuid::new_v4().into();
+
+    for key in [
+        BlockKey::Lock(id),
+        BlockKey::Header(id),
+        BlockKey::Super,
+        BlockKey::Version,
+    ] {
+        assert_that!(store.write_block(key, &first_buffer)).is_ok();
+        assert_that!(inner.open()?.write_block(key, &second_buffer)).is_ok();
+        assert_that!(store.read_block(key)).is_ok_containing(Some(second_buffer.clone()));
+    }
+
+    assert_that!(cache_size(&temp_dir)).is_ok_containing(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn removed_blocks_are_removed_from_cache(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = cache_config(&inner, &temp_dir, 1024 * 1024).open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.remove_block(BlockKey::Data(id))).is_ok();
+
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(None);
+    assert_that!(cache_size(&temp_dir)).is_ok_containing(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn cache_does_not_exceed_max_size(
+    temp_dir: TempDir,
+    #[with(1024)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let mut store = cache_config(&inner, &temp_dir, 4096).open()?;
+    let ids = (0..8).map(|_| Uuid::new_v4().into()).collect::<Vec<_>>();
+
+    for id in &ids {
+        assert_that!(store.write_block(BlockKey::Data(*id), &fixed_buffer)).is_ok();
+    }
+
+    assert_that!(cache_size(&temp_dir)).is_ok_containing(4096);
+
+    // Every block can still be read from the wrapped data store.
+    for id in &ids {
+        assert_that!(store.read_block(BlockKey::Data(*id)))
+            .is_ok_containing(Some(fixed_buffer.clone()));
+    }
+
+    assert_that!(cache_size(&temp_dir)).is_ok_containing(4096);
+
+    Ok(())
+}
+
+#[rstest]
+fn stores_sharing_cache_do_not_exceed_max_size(
+    temp_dir: TempDir,
+    #[with(1024)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let config = cache_config(&inner, &temp_dir, 4096);
+    let mut stores = [config.open()?, config.open()?];
+
+    for i in 0..8 {
+        let store = &mut stores[i % stores.len()];
+        assert_that!(store.write_block(BlockKey::Data(Uuid::new_v4().into()), &fixed_buffer))
+            .is_ok();
+    }
+
+    assert_that!(cache_size(&temp_dir)).is_ok_containing(4096);
+
+    Ok(())
+}
+
+#[rstest]
+fn cache_persists_after_reopening(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let config = cache_config(&inner, &temp_dir, 1024 * 1024);
+    let id = Uuid::new_v4().into();
+
+    let mut store = config.open()?;
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    drop(store);
+
+    assert_that!(inner.open()?.remove_block(BlockKey::Data(id))).is_ok();
+    let mut store = config.open()?;
+
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn reopening_with_smaller_max_size_evicts_blocks(
+    temp_dir: TempDir,
+    #[with(1024)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+
+    let mut store = cache_config(&inner, &temp_dir, 4096).open()?;
+    for _ in 0..4 {
+        assert_that!(store.write_block(BlockKey::Data(Uuid::new_v4().into()), &fixed_buffer))
+            .is_ok();
+    }
+    drop(store);
+
+    cache_config(&inner, &temp_dir, 2048).open()?;
+
+    assert_that!(cache_size(&temp_dir)).is_ok_containing(2048);
+
+    Ok(())
+}
+
+#[rstest]
+fn open_repo_with_cache(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let inner = MemoryConfig::new();
+    let config = cache_config(&inner, &temp_dir, 1024 * 1024);
+
+    let mut repo: KeyRepo<String> = OpenOptions::new().mode(OpenMode::CreateNew).open(&config)?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new().open(&config)?;
+    let mut object = repo.object("test").unwrap();
+    let mut actual_data = Vec::new();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(buffer);
+
+    Ok(())
+}
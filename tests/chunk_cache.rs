@@ -0,0 +1,166 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::Commit;
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The size of the chunk cache to use for testing.
+const CACHE_SIZE: u64 = 1024 * 1024;
+
+/// Read the full contents of the object with the given `key`.
+fn read_object(repo: &KeyRepo<String>, key: &str) -> anyhow::Result<Vec<u8>> {
+    let mut object = repo.object(key).unwrap();
+    let mut data = Vec::new();
+    object.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[apply(store_config)]
+fn reading_data_twice_hits_cache(
+    #[case] mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.cache_size = CACHE_SIZE;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&buffer);
+    let first_stats = repo.cache_stats();
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&buffer);
+    let second_stats = repo.cache_stats();
+
+    assert_that!(second_stats.hits()).is_greater_than(first_stats.hits());
+    assert_that!(second_stats.misses()).is_equal_to(first_stats.misses());
+
+    Ok(())
+}
+
+#[rstest]
+fn cache_is_shared_between_objects(
+    mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config = fixed_config();
+    repo_store.cache_size = CACHE_SIZE;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    for key in ["first", "second"] {
+        let mut object = repo.insert(String::from(key));
+        object.write_all(&buffer)?;
+        object.commit()?;
+    }
+
+    read_object(&repo, "first")?;
+    let misses = repo.cache_stats().misses();
+    assert_that!(read_object(&repo, "second")?).is_equal_to(&buffer);
+
+    // The second object is made of the same chunks as the first, so they're all cached.
+    assert_that!(repo.cache_stats().misses()).is_equal_to(misses);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn cache_does_not_exceed_capacity(
+    #[case] mut repo_store: RepoStore,
+    #[with(1024 * 16)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.cache_size = 1024;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+    drop(object);
+
+    assert_that!(read_object(&repo, "test")?).is_equal_to(&fixed_buffer);
+
+    let stats = repo.cache_stats();
+    assert_that!(stats.capacity()).is_equal_to(1024);
+    assert_that!(stats.size()).is_greater_than(0);
+    assert_that!(stats.size()).is_less_than_or_equal_to(1024);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn modified_data_is_not_read_from_cache(
+    #[case] mut repo_store: RepoStore,
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.cache_size = CACHE_SIZE;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    let mut object = repo.insert(String::from("first"));
+    object.write_all(&first_buffer)?;
+    object.commit()?;
+    drop(object);
+    read_object(&repo, "first")?;
+
+    // With packing, this writes to the same pack which was just cached.
+    let mut object = repo.insert(String::from("second"));
+    object.write_all(&second_buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let mut object = repo.object("first").unwrap();
+    object.seek(SeekFrom::End(0))?;
+    object.write_all(&second_buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let mut expected_data = first_buffer.clone();
+    expected_data.extend_from_slice(&second_buffer);
+
+    assert_that!(read_object(&repo, "first")?).is_equal_to(&expected_data);
+    assert_that!(read_object(&repo, "second")?).is_equal_to(&second_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn cache_is_disabled_by_default(repo_store: RepoStore, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    read_object(&repo, "test")?;
+    read_object(&repo, "test")?;
+
+    let stats = repo.cache_stats();
+    assert_that!(stats.capacity()).is_equal_to(0);
+    assert_that!(stats.size()).is_equal_to(0);
+    assert_that!(stats.hits()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn verifying_bypasses_cache(mut repo_store: RepoStore, buffer: Vec<u8>) -> anyhow::Result<()> {
+    repo_store.cache_size = CACHE_SIZE;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    read_object(&repo, "test")?;
+    let stats = repo.cache_stats();
+
+    assert_that!(repo.verify()).is_ok();
+    assert_that!(repo.object("test").unwrap().verify()).is_ok_containing(true);
+    assert_that!(repo.cache_stats()).is_equal_to(stats);
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Chunking, RepoConfig};
+use acid_store::store::{BlockKey, BlockType, DataStore, OpenStore};
+use common::*;
+
+mod common;
+
+/// Return the sizes of the chunks in the data blocks in `store` which are not in `existing`.
+fn new_chunk_sizes(store: &mut impl DataStore, existing: &HashSet<BlockKey>) -> Vec<usize> {
+    store
+        .list_blocks(BlockType::Data)
+        .unwrap()
+        .into_iter()
+        .map(BlockKey::Data)
+        .filter(|key| !existing.contains(key))
+        // Each block starts with a one-byte tag identifying how it was compressed.
+        .map(|key| store.read_block(key).unwrap().unwrap().len() - 1)
+        .collect()
+}
+
+#[rstest]
+fn fastcdc_chunks_are_within_bounds(
+    #[with(1024 * 64)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo_store = RepoStore::new(fastcdc_config());
+    repo_store.config.chunking = Chunking::FastCdc {
+        min: 512,
+        avg: 1024,
+        max: 2048,
+    };
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut store = repo_store.store.open()?;
+    let existing = store
+        .list_blocks(BlockType::Data)
+        .unwrap()
+        .into_iter()
+        .map(BlockKey::Data)
+        .collect::<HashSet<_>>();
+
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let sizes = new_chunk_sizes(&mut store, &existing);
+    let undersized = sizes.iter().filter(|size| **size < 512).count();
+
+    assert_that!(sizes.iter().sum::<usize>()).is_equal_to(fixed_buffer.len());
+    assert_that!(sizes.iter().all(|size| *size <= 2048)).is_true();
+    // Only the last chunk can be smaller than the minimum.
+    assert_that!(undersized).is_less_than_or_equal_to(1);
+
+    Ok(())
+}
+
+#[rstest]
+#[case::zpaq_chunking(zpaq_config())]
+#[case::fastcdc_chunking(fastcdc_config())]
+fn inserted_data_is_deduplicated(
+    #[case] config: RepoConfig,
+    #[with(1024 * 64)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = create_repo(config)?;
+
+    let mut modified_buffer = fixed_buffer.clone();
+    modified_buffer.splice(1024 * 32..1024 * 32, b"inserted data".iter().copied());
+
+    for (key, data) in [("original", &fixed_buffer), ("modified", &modified_buffer)] {
+        let mut object = repo.insert(String::from(key));
+        object.write_all(data)?;
+        object.commit()?;
+    }
+
+    // Only the chunks around the inserted data should be stored twice.
+    let repo_size = repo.stats().repo_size();
+    assert_that!(repo_size).is_less_than(fixed_buffer.len() as u64 + 1024 * 8);
+
+    Ok(())
+}
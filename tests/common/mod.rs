@@ -4,23 +4,39 @@
 mod assertions;
 mod config;
 mod data;
+mod http_server;
+mod postgres_database;
 mod repository;
+mod s3_server;
 mod store;
+mod webdav_server;
 
 pub use assertions::ErrorVariantAssertions;
 pub use config::{
-    encoding_config, fixed_config, fixed_packing_large_config, fixed_packing_small_config,
-    zpaq_config, zpaq_packing_config,
+    aes_encoding_config, encoding_config, fastcdc_config, fixed_config, fixed_packing_large_config,
+    fixed_packing_small_config, zpaq_config, zpaq_packing_config,
 };
 pub use data::{buffer, fixed_buffer, larger_buffer, smaller_buffer, temp_dir};
+#[cfg(feature = "store-postgres")]
+pub use postgres_database::PostgresDatabase;
 pub use repository::{create_repo, repo, repo_object, repo_store, RepoObject, RepoStore};
 pub use rstest::*;
+#[cfg(feature = "store-s3")]
+pub use s3_server::S3Server;
 pub use spectral::prelude::*;
+pub use store::{
+    caching_config, caching_store, instrumented_config, instrumented_store, memory_config,
+    memory_store, mirror_config, mirror_store, rate_limit_config, rate_limit_store, retry_config,
+    retry_store, sharded_config, sharded_store,
+};
 #[cfg(feature = "store-directory")]
 pub use store::{directory_config, directory_store};
-pub use store::{memory_config, memory_store};
+#[cfg(feature = "store-postgres")]
+pub use store::{postgres_config, postgres_store};
 #[cfg(feature = "store-rclone")]
 pub use store::{rclone_config, rclone_store};
+#[cfg(feature = "store-redb")]
+pub use store::{redb_config, redb_store};
 #[cfg(feature = "store-redis")]
 pub use store::{redis_config, redis_store};
 #[cfg(feature = "store-s3")]
@@ -29,3 +45,7 @@ pub use store::{s3_config, s3_store};
 pub use store::{sftp_config, sftp_store};
 #[cfg(feature = "store-sqlite")]
 pub use store::{sqlite_config, sqlite_store};
+#[cfg(feature = "store-webdav")]
+pub use store::{webdav_config, webdav_store};
+#[cfg(feature = "store-webdav")]
+pub use webdav_server::WebDavServer;
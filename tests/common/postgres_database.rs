@@ -0,0 +1,58 @@
+#![cfg(feature = "store-postgres")]
+
+use acid_store::store::PostgresConfig;
+use postgres::{Client, NoTls};
+use uuid::Uuid;
+
+/// A PostgreSQL database which is created for a single test and dropped afterwards.
+///
+/// The database is created on the server given by the `POSTGRES_CONFIG` environment variable,
+/// which must be a connection string of key-value pairs. Each test gets its own database so that
+/// tests can run concurrently.
+#[derive(Debug)]
+pub struct PostgresDatabase {
+    server_config: String,
+    name: String,
+}
+
+impl PostgresDatabase {
+    /// Create a new empty database.
+    pub fn create() -> Self {
+        let server_config = dotenv::var("POSTGRES_CONFIG").unwrap();
+        let name = format!("acid_store_{}", Uuid::new_v4().as_simple());
+        let mut client = Client::connect(&server_config, NoTls).unwrap();
+        client
+            .batch_execute(&format!("CREATE DATABASE {};", name))
+            .unwrap();
+        Self {
+            server_config,
+            name,
+        }
+    }
+
+    /// Return a config for a data store in this database.
+    pub fn config(&self) -> PostgresConfig {
+        // Later parameters in the connection string take precedence.
+        PostgresConfig {
+            config: format!("{} dbname={}", self.server_config, self.name),
+        }
+    }
+
+    /// Open a new connection to this database.
+    pub fn connect(&self) -> Client {
+        Client::connect(&self.config().config, NoTls).unwrap()
+    }
+}
+
+impl Drop for PostgresDatabase {
+    fn drop(&mut self) {
+        if let Ok(mut client) = Client::connect(&self.server_config, NoTls) {
+            client
+                .batch_execute(&format!(
+                    "DROP DATABASE IF EXISTS {} WITH (FORCE);",
+                    self.name
+                ))
+                .ok();
+        }
+    }
+}
@@ -38,6 +38,9 @@ pub struct RepoStore {
     pub instance: InstanceId,
     pub context: Vec<u8>,
     pub handler: BoxLockHandler,
+    pub write_concurrency: usize,
+    pub read_ahead: usize,
+    pub cache_size: u64,
 }
 
 impl RepoStore {
@@ -51,6 +54,9 @@ impl RepoStore {
             instance: DEFAULT_INSTANCE,
             context: Vec::new(),
             handler: Box::new(|_| false),
+            write_concurrency: 1,
+            read_ahead: 0,
+            cache_size: 0,
         }
     }
 
@@ -61,6 +67,9 @@ impl RepoStore {
             .password(self.password.as_bytes())
             .instance(self.instance)
             .locking(&self.context, |context| (self.handler)(context))
+            .write_concurrency(self.write_concurrency)
+            .read_ahead(self.read_ahead)
+            .cache_size(self.cache_size)
             .mode(OpenMode::CreateNew)
             .open(&self.store)
     }
@@ -72,9 +81,41 @@ impl RepoStore {
             .password(self.password.as_bytes())
             .instance(self.instance)
             .locking(&self.context, |context| (self.handler)(context))
+            .write_concurrency(self.write_concurrency)
+            .read_ahead(self.read_ahead)
+            .cache_size(self.cache_size)
             .mode(OpenMode::Open)
             .open(&self.store)
     }
+
+    /// Open an existing repository with optimistic concurrency control.
+    pub fn open_optimistic<R: OpenRepo>(&self) -> acid_store::Result<R> {
+        OpenOptions::new()
+            .config(self.config.clone())
+            .password(self.password.as_bytes())
+            .instance(self.instance)
+            .locking(&self.context, |context| (self.handler)(context))
+            .write_concurrency(self.write_concurrency)
+            .read_ahead(self.read_ahead)
+            .cache_size(self.cache_size)
+            .optimistic(true)
+            .mode(OpenMode::Open)
+            .open(&self.store)
+    }
+
+    /// Open an existing repository read-only.
+    pub fn open_read_only<R: OpenRepo>(&self) -> acid_store::Result<R> {
+        OpenOptions::new()
+            .config(self.config.clone())
+            .password(self.password.as_bytes())
+            .instance(self.instance)
+            .locking(&self.context, |context| (self.handler)(context))
+            .write_concurrency(self.write_concurrency)
+            .read_ahead(self.read_ahead)
+            .cache_size(self.cache_size)
+            .mode(OpenMode::ReadOnly)
+            .open(&self.store)
+    }
 }
 
 pub fn create_repo<R: OpenRepo>(config: RepoConfig) -> anyhow::Result<R> {
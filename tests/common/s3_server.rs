@@ -0,0 +1,238 @@
+#![cfg(feature = "store-s3")]
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use acid_store::store::{S3Config, S3Credentials, S3Region};
+
+use super::http_server::{start_http_server, xml_escape, Request, Response};
+
+/// The name of the only bucket the server provides.
+const BUCKET: &str = "test-bucket";
+
+/// An object stored in the server.
+#[derive(Debug, Clone)]
+struct StoredObject {
+    data: Vec<u8>,
+    e_tag: String,
+}
+
+/// The state of an `S3Server`.
+#[derive(Debug)]
+struct ServerState {
+    /// The objects in the bucket, sorted by key.
+    objects: BTreeMap<String, StoredObject>,
+
+    /// The maximum number of keys to return in each page of a listing.
+    page_size: usize,
+
+    /// Status codes to respond to the next requests with instead of handling them.
+    failures: VecDeque<u16>,
+
+    /// The number of requests for listings which have been handled.
+    list_requests: usize,
+
+    /// Used to generate unique ETags.
+    next_e_tag: u64,
+}
+
+/// A minimal S3-compatible server which runs in the current process and stores objects in memory.
+///
+/// This supports just enough of the S3 API for `S3Store`: getting, putting, deleting, and
+/// checking objects in a single bucket using path-style addressing, conditional puts, and
+/// paginated `ListObjectsV2` requests. Requests are not authenticated.
+#[derive(Debug, Clone)]
+pub struct S3Server {
+    state: Arc<Mutex<ServerState>>,
+    endpoint: String,
+}
+
+impl S3Server {
+    /// Start a new server listening on a random local port.
+    pub fn start() -> Self {
+        let state = Arc::new(Mutex::new(ServerState {
+            objects: BTreeMap::new(),
+            page_size: 1000,
+            failures: VecDeque::new(),
+            list_requests: 0,
+            next_e_tag: 0,
+        }));
+
+        let handler_state = Arc::clone(&state);
+        let address =
+            start_http_server(move |request| handler_state.lock().unwrap().handle_request(request));
+
+        Self {
+            state,
+            endpoint: format!("http://{}", address),
+        }
+    }
+
+    /// Return a config for a data store in this server.
+    pub fn config(&self) -> S3Config {
+        S3Config {
+            bucket: BUCKET.to_string(),
+            region: S3Region::Custom {
+                name: String::from("us-east-1"),
+                endpoint: self.endpoint.clone(),
+            },
+            credentials: S3Credentials::Basic {
+                access_key: String::from("access-key"),
+                secret_key: String::from("secret-key"),
+            },
+            prefix: String::from("test"),
+            path_style: true,
+        }
+    }
+
+    /// Set the maximum number of keys to return in each page of a listing.
+    pub fn set_page_size(&self, page_size: usize) {
+        self.state.lock().unwrap().page_size = page_size;
+    }
+
+    /// Respond to the next request with the given `status` code instead of handling it.
+    pub fn fail_next(&self, status: u16) {
+        self.state.lock().unwrap().failures.push_back(status);
+    }
+
+    /// The number of requests for listings which have been handled.
+    pub fn list_requests(&self) -> usize {
+        self.state.lock().unwrap().list_requests
+    }
+
+    /// The number of objects in the bucket.
+    pub fn object_count(&self) -> usize {
+        self.state.lock().unwrap().objects.len()
+    }
+}
+
+impl ServerState {
+    /// Handle an HTTP `request`.
+    fn handle_request(&mut self, request: Request) -> Response {
+        if let Some(status) = self.failures.pop_front() {
+            return Response::empty(status);
+        }
+
+        let path = request.path.trim_start_matches('/');
+        let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+        if bucket != BUCKET {
+            return Response::empty(404);
+        }
+
+        match (request.method.as_str(), key) {
+            ("GET", "") => self.list_objects(&request),
+            ("GET", key) => match self.objects.get(key) {
+                Some(object) => Response {
+                    status: 200,
+                    headers: vec![("ETag", object.e_tag.clone())],
+                    body: object.data.clone(),
+                },
+                None => Response::empty(404),
+            },
+            ("HEAD", key) => match self.objects.get(key) {
+                Some(object) => Response {
+                    status: 200,
+                    headers: vec![
+                        ("ETag", object.e_tag.clone()),
+                        ("Content-Length", object.data.len().to_string()),
+                    ],
+                    body: Vec::new(),
+                },
+                None => Response::empty(404),
+            },
+            ("PUT", key) => {
+                let current = self.objects.get(key);
+                let condition_met =
+                    match (request.header("If-None-Match"), request.header("If-Match")) {
+                        (Some("*"), _) => current.is_none(),
+                        (_, Some(e_tag)) => current.is_some_and(|object| object.e_tag == e_tag),
+                        _ => true,
+                    };
+                if !condition_met {
+                    return Response::empty(412);
+                }
+                self.next_e_tag += 1;
+                let e_tag = format!("\"{}\"", self.next_e_tag);
+                self.objects.insert(
+                    key.to_string(),
+                    StoredObject {
+                        data: request.body,
+                        e_tag: e_tag.clone(),
+                    },
+                );
+                Response {
+                    status: 200,
+                    headers: vec![("ETag", e_tag)],
+                    body: Vec::new(),
+                }
+            }
+            ("DELETE", key) => {
+                self.objects.remove(key);
+                Response::empty(204)
+            }
+            _ => Response::empty(405),
+        }
+    }
+
+    /// Handle a `ListObjectsV2` request.
+    fn list_objects(&mut self, request: &Request) -> Response {
+        self.list_requests += 1;
+
+        let prefix = request.query("prefix").unwrap_or_default();
+        let page_size = request
+            .query("max-keys")
+            .map(|max_keys| max_keys.parse::<usize>().unwrap())
+            .unwrap_or(self.page_size)
+            .min(self.page_size);
+
+        // The continuation token is the last key in the previous page.
+        let matching = self
+            .objects
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .filter(|(key, _)| match request.query("continuation-token") {
+                Some(token) => key.as_str() > token,
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        let page = &matching[..matching.len().min(page_size)];
+        let is_truncated = matching.len() > page.len();
+
+        let mut body = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+            <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">",
+        );
+        body.push_str(&format!(
+            "<Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{}</MaxKeys>\
+            <IsTruncated>{}</IsTruncated>",
+            BUCKET,
+            xml_escape(prefix),
+            page.len(),
+            page_size,
+            is_truncated
+        ));
+        for (key, object) in page {
+            body.push_str(&format!(
+                "<Contents><Key>{}</Key><LastModified>2000-01-01T00:00:00.000Z</LastModified>\
+                <ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                xml_escape(key),
+                xml_escape(&object.e_tag),
+                object.data.len()
+            ));
+        }
+        if is_truncated {
+            let (last_key, _) = page.last().unwrap();
+            body.push_str(&format!(
+                "<NextContinuationToken>{}</NextContinuationToken>",
+                xml_escape(last_key)
+            ));
+        }
+        body.push_str("</ListBucketResult>");
+
+        Response {
+            status: 200,
+            headers: vec![("Content-Type", String::from("application/xml"))],
+            body: body.into_bytes(),
+        }
+    }
+}
@@ -3,17 +3,26 @@
 use rstest_reuse::{self, *};
 use tempfile::TempDir;
 
+#[cfg(feature = "store-postgres")]
+use acid_store::store::PostgresStore;
+#[cfg(feature = "store-s3")]
+use acid_store::store::S3Store;
+#[cfg(feature = "store-webdav")]
+use acid_store::store::WebDavStore;
 use acid_store::store::{
-    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore,
+    BlockId, BlockKey, BlockType, CachingConfig, CachingStore, ConditionalWrites, DataStore,
+    InstrumentedConfig, InstrumentedStore, MemoryConfig, MemoryStore, MirrorConfig, MirrorStore,
+    OpenStore, RateLimitConfig, RateLimitStore, RateLimiter, RetryConfig, RetryPolicy, RetryStore,
+    ShardedConfig, ShardedStore, StoreMetrics,
 };
 #[cfg(feature = "store-directory")]
 use acid_store::store::{DirectoryConfig, DirectoryStore};
 #[cfg(feature = "store-rclone")]
 use acid_store::store::{RcloneConfig, RcloneStore};
+#[cfg(feature = "store-redb")]
+use acid_store::store::{RedbConfig, RedbStore};
 #[cfg(feature = "store-redis")]
 use acid_store::store::{RedisConfig, RedisStore};
-#[cfg(feature = "store-s3")]
-use acid_store::store::{S3Config, S3Credentials, S3Region, S3Store};
 #[cfg(feature = "store-sqlite")]
 use acid_store::store::{SqliteConfig, SqliteStore};
 #[cfg(feature = "store-sftp")]
@@ -22,6 +31,13 @@ use {
     std::path::PathBuf,
 };
 
+#[cfg(feature = "store-postgres")]
+use super::postgres_database::PostgresDatabase;
+#[cfg(feature = "store-s3")]
+use super::s3_server::S3Server;
+#[cfg(feature = "store-webdav")]
+use super::webdav_server::WebDavServer;
+
 /// Remove all blocks in the given `store`.
 fn truncate_store(store: &mut impl DataStore) -> acid_store::store::Result<()> {
     for block_id in store.list_blocks(BlockType::Data)? {
@@ -61,6 +77,27 @@ impl<T: DataStore> DataStore for WithTempDir<T> {
     fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
         self.value.list_blocks(kind)
     }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> acid_store::store::Result<bool> {
+        self.value.write_block_if(key, expected, data)
+    }
+
+    fn create_block_exclusive(
+        &mut self,
+        key: BlockKey,
+        data: &[u8],
+    ) -> acid_store::store::Result<bool> {
+        self.value.create_block_exclusive(key, data)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.value.conditional_writes()
+    }
 }
 
 impl<T: OpenStore> OpenStore for WithTempDir<T> {
@@ -71,6 +108,63 @@ impl<T: OpenStore> OpenStore for WithTempDir<T> {
     }
 }
 
+/// A value which is tied to the lifetime of a temporary PostgreSQL database.
+#[cfg(feature = "store-postgres")]
+struct WithDatabase<T> {
+    // This is dropped first so that its connection is closed before the database is dropped.
+    value: T,
+    database: PostgresDatabase,
+}
+
+#[cfg(feature = "store-postgres")]
+impl<T: DataStore> DataStore for WithDatabase<T> {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.value.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.value.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.value.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.value.list_blocks(kind)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> acid_store::store::Result<bool> {
+        self.value.write_block_if(key, expected, data)
+    }
+
+    fn create_block_exclusive(
+        &mut self,
+        key: BlockKey,
+        data: &[u8],
+    ) -> acid_store::store::Result<bool> {
+        self.value.create_block_exclusive(key, data)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.value.conditional_writes()
+    }
+}
+
+#[cfg(feature = "store-postgres")]
+impl<T: OpenStore> OpenStore for WithDatabase<T> {
+    type Store = T::Store;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        self.value.open()
+    }
+}
+
 pub fn memory_config() -> Box<dyn OpenStore<Store = MemoryStore>> {
     Box::new(MemoryConfig::new())
 }
@@ -79,6 +173,95 @@ pub fn memory_store() -> Box<dyn DataStore> {
     Box::new(memory_config().open().unwrap())
 }
 
+pub fn caching_config() -> Box<dyn OpenStore<Store = CachingStore<MemoryStore>>> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = CachingConfig {
+        inner: MemoryConfig::new(),
+        path: directory.as_ref().join("cache"),
+        max_size: 1024 * 1024,
+    };
+    Box::new(WithTempDir {
+        directory,
+        value: config,
+    })
+}
+
+pub fn caching_store() -> Box<dyn DataStore> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = CachingConfig {
+        inner: MemoryConfig::new(),
+        path: directory.as_ref().join("cache"),
+        max_size: 1024 * 1024,
+    };
+    let store = config.open().unwrap();
+    Box::new(WithTempDir {
+        directory,
+        value: store,
+    })
+}
+
+pub fn retry_config() -> Box<dyn OpenStore<Store = RetryStore<MemoryStore>>> {
+    Box::new(RetryConfig {
+        inner: MemoryConfig::new(),
+        policy: RetryPolicy::default(),
+    })
+}
+
+pub fn retry_store() -> Box<dyn DataStore> {
+    Box::new(retry_config().open().unwrap())
+}
+
+pub fn rate_limit_config() -> Box<dyn OpenStore<Store = RateLimitStore<MemoryStore>>> {
+    // These limits are high enough that tests aren't slowed down.
+    let limiter = RateLimiter::new();
+    limiter.set_read_limit(Some(1024 * 1024 * 1024));
+    limiter.set_write_limit(Some(1024 * 1024 * 1024));
+    limiter.set_request_limit(Some(1_000_000));
+    Box::new(RateLimitConfig {
+        inner: MemoryConfig::new(),
+        limiter,
+    })
+}
+
+pub fn rate_limit_store() -> Box<dyn DataStore> {
+    Box::new(rate_limit_config().open().unwrap())
+}
+
+pub fn instrumented_config() -> Box<dyn OpenStore<Store = InstrumentedStore<MemoryStore>>> {
+    Box::new(InstrumentedConfig {
+        inner: MemoryConfig::new(),
+        metrics: StoreMetrics::new(),
+    })
+}
+
+pub fn instrumented_store() -> Box<dyn DataStore> {
+    Box::new(instrumented_config().open().unwrap())
+}
+
+pub fn mirror_config() -> Box<dyn OpenStore<Store = MirrorStore>> {
+    Box::new(
+        MirrorConfig::new()
+            .replica(MemoryConfig::new())
+            .replica(MemoryConfig::new()),
+    )
+}
+
+pub fn mirror_store() -> Box<dyn DataStore> {
+    Box::new(mirror_config().open().unwrap())
+}
+
+pub fn sharded_config() -> Box<dyn OpenStore<Store = ShardedStore>> {
+    Box::new(
+        ShardedConfig::new(MemoryConfig::new())
+            .shard(MemoryConfig::new())
+            .shard(MemoryConfig::new()),
+    )
+}
+
+pub fn sharded_store() -> Box<dyn DataStore> {
+    Box::new(sharded_config().open().unwrap())
+}
+
 #[cfg(feature = "store-directory")]
 pub fn directory_config() -> Box<dyn OpenStore<Store = DirectoryStore>> {
     let directory = tempfile::tempdir().unwrap();
@@ -131,6 +314,50 @@ pub fn sqlite_store() -> Box<dyn DataStore> {
     })
 }
 
+#[cfg(feature = "store-redb")]
+pub fn redb_config() -> Box<dyn OpenStore<Store = RedbStore>> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = RedbConfig {
+        path: directory.as_ref().join("store.redb"),
+    };
+    Box::new(WithTempDir {
+        directory,
+        value: config,
+    })
+}
+
+#[cfg(feature = "store-redb")]
+pub fn redb_store() -> Box<dyn DataStore> {
+    let directory = tempfile::tempdir().unwrap();
+    let config = RedbConfig {
+        path: directory.as_ref().join("store.redb"),
+    };
+    let store = config.open().unwrap();
+    Box::new(WithTempDir {
+        directory,
+        value: store,
+    })
+}
+
+#[cfg(feature = "store-postgres")]
+pub fn postgres_config() -> Box<dyn OpenStore<Store = PostgresStore>> {
+    let database = PostgresDatabase::create();
+    Box::new(WithDatabase {
+        value: database.config(),
+        database,
+    })
+}
+
+#[cfg(feature = "store-postgres")]
+pub fn postgres_store() -> Box<dyn DataStore> {
+    let database = PostgresDatabase::create();
+    let store = database.config().open().unwrap();
+    Box::new(WithDatabase {
+        value: store,
+        database,
+    })
+}
+
 #[cfg(feature = "store-redis")]
 pub fn redis_config() -> Box<dyn OpenStore<Store = RedisStore>> {
     let url = dotenv::var("REDIS_URL").unwrap();
@@ -147,15 +374,7 @@ pub fn redis_store() -> Box<dyn DataStore> {
 
 #[cfg(feature = "store-s3")]
 pub fn s3_config() -> Box<dyn OpenStore<Store = S3Store>> {
-    Box::new(S3Config {
-        bucket: dotenv::var("S3_BUCKET").unwrap(),
-        region: S3Region::from_name(&dotenv::var("S3_REGION").unwrap()).unwrap(),
-        credentials: S3Credentials::Basic {
-            access_key: dotenv::var("S3_ACCESS_KEY").unwrap(),
-            secret_key: dotenv::var("S3_SECRET_KEY").unwrap(),
-        },
-        prefix: String::from("test"),
-    })
+    Box::new(S3Server::start().config())
 }
 
 #[cfg(feature = "store-s3")]
@@ -166,6 +385,16 @@ pub fn s3_store() -> Box<dyn DataStore> {
     Box::new(store)
 }
 
+#[cfg(feature = "store-webdav")]
+pub fn webdav_config() -> Box<dyn OpenStore<Store = WebDavStore>> {
+    Box::new(WebDavServer::start().config())
+}
+
+#[cfg(feature = "store-webdav")]
+pub fn webdav_store() -> Box<dyn DataStore> {
+    Box::new(webdav_config().open().unwrap())
+}
+
 #[cfg(feature = "store-sftp")]
 pub fn sftp_config() -> Box<dyn OpenStore<Store = SftpStore>> {
     let sftp_server: String = dotenv::var("SFTP_SERVER").unwrap();
@@ -212,10 +441,19 @@ pub fn rclone_store() -> Box<dyn DataStore> {
 #[template]
 #[rstest]
 #[case::store_memory(memory_config())]
+#[case::store_caching(caching_config())]
+#[case::store_retry(retry_config())]
+#[case::store_rate_limit(rate_limit_config())]
+#[case::store_instrumented(instrumented_config())]
+#[case::store_mirror(mirror_config())]
+#[case::store_sharded(sharded_config())]
 #[cfg_attr(feature = "store-directory", case::store_directory(directory_config()))]
 #[cfg_attr(feature = "store-sqlite", case::store_sqlilte(sqlite_config()))]
+#[cfg_attr(feature = "store-redb", case::store_redb(redb_config()))]
 #[cfg_attr(feature = "store-redis", case::store_redis(redis_config()))]
+#[cfg_attr(feature = "store-postgres", case::store_postgres(postgres_config()))]
 #[cfg_attr(feature = "store-s3", case::store_s3(s3_config()))]
+#[cfg_attr(feature = "store-webdav", case::store_webdav(webdav_config()))]
 #[cfg_attr(feature = "store-sftp", case::store_sftp(sftp_config()))]
 #[cfg_attr(feature = "store-rclone", case::store_rclone(rclone_config()))]
 pub fn data_configs(#[case] config: Box<dyn OpenStore>) {}
@@ -227,10 +465,19 @@ pub fn data_configs(#[case] config: Box<dyn OpenStore>) {}
 #[template]
 #[rstest]
 #[case::store_memory(memory_store())]
+#[case::store_caching(caching_store())]
+#[case::store_retry(retry_store())]
+#[case::store_rate_limit(rate_limit_store())]
+#[case::store_instrumented(instrumented_store())]
+#[case::store_mirror(mirror_store())]
+#[case::store_sharded(sharded_store())]
 #[cfg_attr(feature = "store-directory", case::store_directory(directory_store()))]
 #[cfg_attr(feature = "store-sqlite", case::store_sqlilte(sqlite_store()))]
+#[cfg_attr(feature = "store-redb", case::store_redb(redb_store()))]
 #[cfg_attr(feature = "store-redis", case::store_redis(redis_store()))]
+#[cfg_attr(feature = "store-postgres", case::store_postgres(postgres_store()))]
 #[cfg_attr(feature = "store-s3", case::store_s3(s3_store()))]
+#[cfg_attr(feature = "store-webdav", case::store_webdav(webdav_store()))]
 #[cfg_attr(feature = "store-sftp", case::store_sftp(sftp_store()))]
 #[cfg_attr(feature = "store-rclone", case::store_rclone(rclone_store()))]
 pub fn data_stores(#[case] store: Box<dyn DataStore>) {}
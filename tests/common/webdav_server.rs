@@ -0,0 +1,273 @@
+#![cfg(feature = "store-webdav")]
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use acid_store::store::{WebDavConfig, WebDavCredentials};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use super::http_server::{start_http_server, xml_escape, Request, Response};
+
+/// The path of the collection which contains the data store.
+const STORE_PATH: &str = "/dav/store";
+
+// The credentials which the server accepts.
+const USERNAME: &str = "user";
+const PASSWORD: &str = "password";
+
+/// A resource stored in the server.
+#[derive(Debug, Clone)]
+enum Resource {
+    Collection,
+    File(Vec<u8>),
+}
+
+/// The state of a `WebDavServer`.
+#[derive(Debug)]
+struct ServerState {
+    /// The resources in the server by their paths, which don't end with a slash.
+    resources: BTreeMap<String, Resource>,
+
+    /// Status codes to respond to the next requests with instead of handling them.
+    failures: VecDeque<u16>,
+
+    /// The number of requests which have been handled by method.
+    requests: BTreeMap<String, usize>,
+}
+
+/// Return the path of the parent of the resource at `path`.
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+/// A minimal WebDAV server which runs in the current process and stores resources in memory.
+///
+/// This supports just enough of WebDAV for `WebDavStore`: getting, putting, and deleting files,
+/// creating collections, moving files, and `PROPFIND` requests with a depth of `0` or `1`.
+/// Requests must be authenticated using basic authentication.
+#[derive(Debug, Clone)]
+pub struct WebDavServer {
+    state: Arc<Mutex<ServerState>>,
+    endpoint: String,
+}
+
+impl WebDavServer {
+    /// Start a new server listening on a random local port.
+    pub fn start() -> Self {
+        let mut resources = BTreeMap::new();
+        resources.insert(String::new(), Resource::Collection);
+        resources.insert(parent_path(STORE_PATH).to_string(), Resource::Collection);
+        let state = Arc::new(Mutex::new(ServerState {
+            resources,
+            failures: VecDeque::new(),
+            requests: BTreeMap::new(),
+        }));
+
+        let handler_state = Arc::clone(&state);
+        let address =
+            start_http_server(move |request| handler_state.lock().unwrap().handle_request(request));
+
+        Self {
+            state,
+            endpoint: format!("http://{}", address),
+        }
+    }
+
+    /// Return a config for a data store in this server.
+    pub fn config(&self) -> WebDavConfig {
+        WebDavConfig {
+            url: format!("{}{}", self.endpoint, STORE_PATH),
+            credentials: WebDavCredentials::Basic {
+                username: String::from(USERNAME),
+                password: String::from(PASSWORD),
+            },
+        }
+    }
+
+    /// Respond to the next request with the given `status` code instead of handling it.
+    pub fn fail_next(&self, status: u16) {
+        self.state.lock().unwrap().failures.push_back(status);
+    }
+
+    /// The number of requests with the given `method` which have been handled.
+    pub fn requests(&self, method: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state.requests.get(method).copied().unwrap_or(0)
+    }
+
+    /// The paths of the files in the collection at `path` and its descendants.
+    pub fn files(&self, path: &str) -> Vec<String> {
+        let prefix = format!("{}{}/", STORE_PATH, path);
+        let state = self.state.lock().unwrap();
+        state
+            .resources
+            .iter()
+            .filter(|(key, resource)| {
+                key.starts_with(&prefix) && matches!(resource, Resource::File(_))
+            })
+            .map(|(key, _)| key[STORE_PATH.len()..].to_string())
+            .collect()
+    }
+}
+
+impl ServerState {
+    /// Handle an HTTP `request`.
+    fn handle_request(&mut self, request: Request) -> Response {
+        if let Some(status) = self.failures.pop_front() {
+            return Response::empty(status);
+        }
+
+        let expected_authorization = format!(
+            "Basic {}",
+            BASE64.encode(format!("{}:{}", USERNAME, PASSWORD))
+        );
+        if request.header("Authorization") != Some(expected_authorization.as_str()) {
+            return Response::empty(401);
+        }
+
+        *self.requests.entry(request.method.clone()).or_default() += 1;
+
+        let path = request.path.trim_end_matches('/').to_string();
+        match request.method.as_str() {
+            "GET" => match self.resources.get(&path) {
+                Some(Resource::File(data)) => Response {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: data.clone(),
+                },
+                _ => Response::empty(404),
+            },
+            "PUT" => {
+                if !matches!(
+                    self.resources.get(parent_path(&path)),
+                    Some(Resource::Collection)
+                ) {
+                    return Response::empty(409);
+                }
+                match self.resources.insert(path, Resource::File(request.body)) {
+                    Some(_) => Response::empty(204),
+                    None => Response::empty(201),
+                }
+            }
+            "DELETE" => {
+                if self.resources.remove(&path).is_none() {
+                    return Response::empty(404);
+                }
+                let prefix = format!("{}/", path);
+                self.resources.retain(|key, _| !key.starts_with(&prefix));
+                Response::empty(204)
+            }
+            "MKCOL" => {
+                if self.resources.contains_key(&path) {
+                    return Response::empty(405);
+                }
+                if !matches!(
+                    self.resources.get(parent_path(&path)),
+                    Some(Resource::Collection)
+                ) {
+                    return Response::empty(409);
+                }
+                self.resources.insert(path, Resource::Collection);
+                Response::empty(201)
+            }
+            "MOVE" => self.move_resource(&path, &request),
+            "PROPFIND" => self.find_properties(&path, &request),
+            _ => Response::empty(405),
+        }
+    }
+
+    /// Handle a `MOVE` request for the file at `path`.
+    fn move_resource(&mut self, path: &str, request: &Request) -> Response {
+        // The destination is an absolute URL.
+        let destination = match request.header("Destination") {
+            Some(destination) => destination,
+            None => return Response::empty(400),
+        };
+        let destination = destination
+            .splitn(4, '/')
+            .nth(3)
+            .map(|path| format!("/{}", path.trim_end_matches('/')))
+            .unwrap_or_default();
+        let overwrite = request.header("Overwrite") != Some("F");
+
+        if !matches!(self.resources.get(path), Some(Resource::File(_))) {
+            return Response::empty(404);
+        }
+        if !matches!(
+            self.resources.get(parent_path(&destination)),
+            Some(Resource::Collection)
+        ) {
+            return Response::empty(409);
+        }
+        let exists = self.resources.contains_key(&destination);
+        if exists && !overwrite {
+            return Response::empty(412);
+        }
+
+        let resource = self.resources.remove(path).unwrap();
+        self.resources.insert(destination, resource);
+        Response::empty(if exists { 204 } else { 201 })
+    }
+
+    /// Handle a `PROPFIND` request for the resource at `path`.
+    fn find_properties(&self, path: &str, request: &Request) -> Response {
+        let depth = request.header("Depth").unwrap_or("infinity");
+        if depth == "infinity" {
+            return Response::empty(403);
+        }
+
+        let resource = match self.resources.get(path) {
+            Some(resource) => resource,
+            None => return Response::empty(404),
+        };
+
+        let mut members = vec![(path, resource)];
+        if depth == "1" {
+            let prefix = format!("{}/", path);
+            members.extend(
+                self.resources
+                    .iter()
+                    .filter(|(key, _)| {
+                        key.starts_with(&prefix) && !key[prefix.len()..].contains('/')
+                    })
+                    .map(|(key, resource)| (key.as_str(), resource)),
+            );
+        }
+
+        let mut body = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?><d:multistatus xmlns:d=\"DAV:\">",
+        );
+        for (member_path, resource) in members {
+            let (href, properties) = match resource {
+                Resource::Collection => (
+                    format!("{}/", member_path),
+                    String::from("<d:resourcetype><d:collection/></d:resourcetype>"),
+                ),
+                Resource::File(data) => (
+                    member_path.to_string(),
+                    format!(
+                        "<d:resourcetype/><d:getcontentlength>{}</d:getcontentlength>",
+                        data.len()
+                    ),
+                ),
+            };
+            body.push_str(&format!(
+                "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop>\
+                <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+                xml_escape(&href),
+                properties
+            ));
+        }
+        body.push_str("</d:multistatus>");
+
+        Response {
+            status: 207,
+            headers: vec![(
+                "Content-Type",
+                String::from("application/xml; charset=utf-8"),
+            )],
+            body: body.into_bytes(),
+        }
+    }
+}
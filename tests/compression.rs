@@ -0,0 +1,131 @@
+#![cfg(all(
+    feature = "encryption",
+    feature = "compression",
+    feature = "compression-zstd"
+))]
+
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, Compression, RepoConfig, ZstdDictionary};
+use acid_store::store::{BlockKey, BlockType, DataStore, OpenStore};
+use common::*;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+mod common;
+
+/// Return `count` small, similar text records which compress well with a dictionary.
+fn text_records(count: usize) -> Vec<Vec<u8>> {
+    let mut rng = SmallRng::from_entropy();
+    (0..count)
+        .map(|index| {
+            format!(
+                r#"{{"id": {}, "name": "user-{}", "email": "user-{}@example.com", "active": {}, "score": {}}}"#,
+                index,
+                rng.gen::<u32>(),
+                rng.gen::<u32>(),
+                rng.gen::<bool>(),
+                rng.gen_range(0..1000),
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+/// Return a config which compresses data with a dictionary trained on `text_records`.
+fn dictionary_config() -> RepoConfig {
+    let mut config = fixed_config();
+    config.compression = Compression::ZstdDictionary {
+        level: 3,
+        dictionary: ZstdDictionary::train(&text_records(1000), 4096).unwrap(),
+    };
+    config
+}
+
+/// Return a config which compresses data without a dictionary.
+fn zstd_config() -> RepoConfig {
+    let mut config = fixed_config();
+    config.compression = Compression::Zstd { level: 3 };
+    config
+}
+
+/// Return the total size of the data blocks in `store`.
+fn stored_size(store: &mut impl DataStore) -> usize {
+    store
+        .list_blocks(BlockType::Data)
+        .unwrap()
+        .into_iter()
+        .map(|id| store.read_block(BlockKey::Data(id)).unwrap().unwrap().len())
+        .sum()
+}
+
+#[rstest]
+#[case::zstd(zstd_config())]
+#[case::zstd_dictionary(dictionary_config())]
+#[case::zstd_encryption({
+    let mut config = zstd_config();
+    config.encryption = encoding_config().encryption;
+    config
+})]
+fn data_persists_after_reopening(
+    #[case] config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo_store = RepoStore::new(config);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let mut object = repo.object("test").unwrap();
+    let mut actual = Vec::new();
+    object.read_to_end(&mut actual)?;
+
+    assert_that!(actual).is_equal_to(buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn dictionary_is_stored_in_repository() -> anyhow::Result<()> {
+    let config = dictionary_config();
+    let mut repo_store = RepoStore::new(config.clone());
+    let repo: KeyRepo<String> = repo_store.create()?;
+    drop(repo);
+
+    // The dictionary is read from the repository rather than the config used to open it.
+    repo_store.config = fixed_config();
+    let repo: KeyRepo<String> = repo_store.open()?;
+
+    assert_that!(repo.info().config().compression).is_equal_to(config.compression);
+
+    Ok(())
+}
+
+#[rstest]
+fn dictionary_improves_compression_of_small_chunks() -> anyhow::Result<()> {
+    let records = text_records(100).concat();
+    let mut sizes = Vec::new();
+
+    for config in [zstd_config(), dictionary_config()] {
+        let repo_store = RepoStore::new(config);
+        let mut repo: KeyRepo<String> = repo_store.create()?;
+        let mut store = repo_store.store.open()?;
+        let initial_size = stored_size(&mut store);
+
+        let mut object = repo.insert(String::from("test"));
+        object.write_all(&records)?;
+        object.commit()?;
+
+        sizes.push(stored_size(&mut store) - initial_size);
+    }
+
+    assert_that!(sizes[1]).is_less_than(sizes[0]);
+
+    Ok(())
+}
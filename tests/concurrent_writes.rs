@@ -0,0 +1,148 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions, RepoConfig};
+use acid_store::store::MemoryConfig;
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The number of concurrent writes to use for testing.
+const WRITE_CONCURRENCY: usize = 4;
+
+#[apply(store_config)]
+fn read_data_written_concurrently(
+    #[case] mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.write_concurrency = WRITE_CONCURRENCY;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    let mut actual_data = Vec::new();
+
+    object.write_all(&buffer)?;
+    object.commit()?;
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+    assert_that!(&object.verify()).is_ok_containing(true);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn read_data_written_concurrently_after_reopening(
+    #[case] mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.write_concurrency = WRITE_CONCURRENCY;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let mut object = repo.object("test").unwrap();
+    let mut actual_data = Vec::new();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn duplicate_chunks_are_written_once(
+    mut repo_store: RepoStore,
+    #[with(1024 * 64)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.config = fixed_config();
+    repo_store.write_concurrency = WRITE_CONCURRENCY;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+
+    let mut object = repo.insert(String::from("first"));
+    object.write_all(&fixed_buffer)?;
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let mut object = repo.insert(String::from("second"));
+    object.write_all(&fixed_buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let stats = repo.stats();
+
+    assert_that!(stats.apparent_size()).is_equal_to(3 * fixed_buffer.len() as u64);
+    assert_that!(stats.actual_size()).is_equal_to(fixed_buffer.len() as u64);
+    assert_that!(repo.object("first").unwrap().verify()).is_ok_containing(true);
+    assert_that!(repo.object("second").unwrap().verify()).is_ok_containing(true);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn cleaning_preserves_data_written_concurrently(
+    #[case] mut repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    repo_store.write_concurrency = WRITE_CONCURRENCY;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    repo.clean()?;
+
+    let mut object = repo.object("test").unwrap();
+    let mut actual_data = Vec::new();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn uncommitted_concurrent_writes_are_discarded(
+    repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .password(repo_store.password.as_bytes())
+        .write_concurrency(WRITE_CONCURRENCY)
+        .mode(OpenMode::CreateNew)
+        .open(&repo_store.store)?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    drop(object);
+
+    let mut object = repo.object("test").unwrap();
+    assert_that!(object.size()).is_ok_containing(0);
+
+    object.write_all(&buffer)?;
+    object.commit()?;
+
+    assert_that!(object.size()).is_ok_containing(buffer.len() as u64);
+
+    Ok(())
+}
+
+#[rstest]
+#[should_panic]
+fn zero_write_concurrency_panics() {
+    let _: KeyRepo<String> = OpenOptions::new()
+        .config(RepoConfig::default())
+        .write_concurrency(0)
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())
+        .unwrap();
+}
@@ -0,0 +1,330 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+#[cfg(feature = "repo-file")]
+use acid_store::repo::file::{Entry, FileRepo};
+use acid_store::repo::key::KeyRepo;
+#[cfg(feature = "repo-value")]
+use acid_store::repo::value::ValueRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions, OpenRepo, RepoConfig};
+use acid_store::store::{FaultConfig, FaultInjector, MemoryConfig};
+
+use common::*;
+
+mod common;
+
+/// The password to use for encrypted repositories.
+const PASSWORD: &[u8] = b"password";
+
+/// Crash the data store at each operation of `operation` in turn and check the repository after.
+///
+/// For each crash point, this creates a new repository, calls `setup` on it, and then calls
+/// `operation` on it with the data store crashing at that point. It then opens the repository
+/// again from the data store and calls `check` on it, passing whether `operation` completed. This
+/// continues until `operation` completes without the data store crashing.
+fn for_each_crash_point<R, S, O, C>(
+    config: &RepoConfig,
+    setup: S,
+    operation: O,
+    check: C,
+) -> anyhow::Result<()>
+where
+    R: OpenRepo,
+    S: Fn(&mut R) -> anyhow::Result<()>,
+    O: Fn(&mut R) -> acid_store::Result<()>,
+    C: Fn(&R, bool) -> anyhow::Result<()>,
+{
+    for crash_point in 0.. {
+        let store = MemoryConfig::new();
+        let fault_config = FaultConfig {
+            inner: store.clone(),
+            injector: FaultInjector::new(),
+        };
+
+        let mut repo: R = OpenOptions::new()
+            .config(config.clone())
+            .password(PASSWORD)
+            .mode(OpenMode::CreateNew)
+            .open(&fault_config)?;
+        setup(&mut repo)?;
+
+        fault_config.injector.reset();
+        fault_config.injector.crash_at(crash_point);
+        let result = operation(&mut repo);
+        let crashed = fault_config.injector.is_crashed();
+        drop(repo);
+
+        let completed = match result {
+            Ok(()) => true,
+            Err(_) if crashed => false,
+            Err(error) => return Err(error.into()),
+        };
+
+        // The crashed repository never released its lock.
+        let repo: R = OpenOptions::new()
+            .password(PASSWORD)
+            .locking(&[], |_| true)
+            .open(&store)?;
+        check(&repo, completed)?;
+
+        if !crashed {
+            return Ok(());
+        }
+    }
+    unreachable!()
+}
+
+/// Read the contents of the object with the given `key` in a `KeyRepo`.
+fn read_key(repo: &KeyRepo<String>, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut object = match repo.object(key) {
+        Some(object) => object,
+        None => return Ok(None),
+    };
+    let mut contents = Vec::new();
+    object.read_to_end(&mut contents)?;
+    Ok(Some(contents))
+}
+
+/// Write `data` to a new object with the given `key` in a `KeyRepo`.
+fn write_key(repo: &mut KeyRepo<String>, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    let mut object = repo.insert(key.to_string());
+    object.write_all(data)?;
+    object.commit()?;
+    Ok(())
+}
+
+#[rstest]
+#[case::fixed_size_chunking(fixed_config())]
+#[case::encoding(encoding_config())]
+#[case::aes_encoding(aes_encoding_config())]
+#[case::small_pack_size(fixed_packing_small_config())]
+fn key_repo_commit_is_atomic(
+    #[case] config: RepoConfig,
+    #[from(buffer)] old_data: Vec<u8>,
+    #[from(buffer)] new_data: Vec<u8>,
+) -> anyhow::Result<()> {
+    for_each_crash_point(
+        &config,
+        |repo: &mut KeyRepo<String>| {
+            write_key(repo, "changed", &old_data)?;
+            repo.commit()?;
+            write_key(repo, "changed", &new_data)?;
+            write_key(repo, "added", &new_data)?;
+            Ok(())
+        },
+        |repo| repo.commit(),
+        |repo, completed| {
+            assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+            if read_key(repo, "added")?.is_some() {
+                assert_that!(read_key(repo, "changed")?).is_equal_to(Some(new_data.clone()));
+                assert_that!(read_key(repo, "added")?).is_equal_to(Some(new_data.clone()));
+            } else {
+                assert_that!(completed).is_false();
+                assert_that!(read_key(repo, "changed")?).is_equal_to(Some(old_data.clone()));
+            }
+            Ok(())
+        },
+    )
+}
+
+#[rstest]
+#[case::fixed_size_chunking(fixed_config())]
+#[case::encoding(encoding_config())]
+#[case::small_pack_size(fixed_packing_small_config())]
+fn key_repo_clean_does_not_lose_data(
+    #[case] config: RepoConfig,
+    #[from(buffer)] retained_data: Vec<u8>,
+    #[from(buffer)] removed_data: Vec<u8>,
+) -> anyhow::Result<()> {
+    for_each_crash_point(
+        &config,
+        |repo: &mut KeyRepo<String>| {
+            write_key(repo, "retained", &retained_data)?;
+            write_key(repo, "removed", &removed_data)?;
+            repo.commit()?;
+            repo.remove("removed");
+            repo.commit()?;
+            Ok(())
+        },
+        |repo| repo.clean(),
+        |repo, _| {
+            assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+            assert_that!(read_key(repo, "retained")?).is_equal_to(Some(retained_data.clone()));
+            assert_that!(read_key(repo, "removed")?).is_none();
+            Ok(())
+        },
+    )
+}
+
+#[rstest]
+#[case::add_encoding(encoding_config())]
+#[case::change_chunking(fastcdc_config())]
+#[case::add_packing(fixed_packing_small_config())]
+fn key_repo_reencode_is_atomic(
+    #[case] new_config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    for_each_crash_point(
+        &fixed_config(),
+        |repo: &mut KeyRepo<String>| {
+            write_key(repo, "test", &buffer)?;
+            repo.commit()?;
+            Ok(())
+        },
+        |repo| {
+            repo.reencode(new_config.clone(), Some(PASSWORD), |_| {})
+                .map(|_| ())
+        },
+        |repo, completed| {
+            assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+            assert_that!(read_key(repo, "test")?).is_equal_to(Some(buffer.clone()));
+            if completed {
+                assert_that!(repo.info().config().chunking).is_equal_to(&new_config.chunking);
+                assert_that!(repo.info().config().packing).is_equal_to(&new_config.packing);
+                assert_that!(repo.info().config().encryption).is_equal_to(&new_config.encryption);
+            }
+            Ok(())
+        },
+    )
+}
+
+#[cfg(feature = "repo-value")]
+#[rstest]
+#[case::fixed_size_chunking(fixed_config())]
+#[case::small_pack_size(fixed_packing_small_config())]
+fn value_repo_commit_is_atomic(#[case] config: RepoConfig) -> anyhow::Result<()> {
+    for_each_crash_point(
+        &config,
+        |repo: &mut ValueRepo<String>| {
+            repo.insert("changed".into(), &String::from("old"))?;
+            repo.commit()?;
+            repo.insert("changed".into(), &String::from("new"))?;
+            repo.insert("added".into(), &String::from("new"))?;
+            Ok(())
+        },
+        |repo| repo.commit(),
+        |repo, completed| {
+            assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+            let changed: String = repo.get("changed")?;
+            if repo.contains("added") {
+                assert_that!(changed.as_str()).is_equal_to("new");
+                assert_that!(repo.get::<_, String>("added")?.as_str()).is_equal_to("new");
+            } else {
+                assert_that!(completed).is_false();
+                assert_that!(changed.as_str()).is_equal_to("old");
+            }
+            Ok(())
+        },
+    )
+}
+
+#[cfg(feature = "repo-value")]
+#[rstest]
+#[case::fixed_size_chunking(fixed_config())]
+#[case::small_pack_size(fixed_packing_small_config())]
+fn value_repo_clean_does_not_lose_data(#[case] config: RepoConfig) -> anyhow::Result<()> {
+    for_each_crash_point(
+        &config,
+        |repo: &mut ValueRepo<String>| {
+            repo.insert("retained".into(), &String::from("retained"))?;
+            repo.insert("removed".into(), &String::from("removed"))?;
+            repo.commit()?;
+            repo.remove("removed");
+            repo.commit()?;
+            Ok(())
+        },
+        |repo| repo.clean(),
+        |repo, _| {
+            assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+            assert_that!(repo.get::<_, String>("retained")?.as_str()).is_equal_to("retained");
+            assert_that!(repo.contains("removed")).is_false();
+            Ok(())
+        },
+    )
+}
+
+/// Read the contents of the file at the given `path` in a `FileRepo`.
+#[cfg(feature = "repo-file")]
+fn read_file(repo: &FileRepo, path: &str) -> anyhow::Result<Vec<u8>> {
+    let mut object = repo.open(path)?;
+    let mut contents = Vec::new();
+    object.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Write `data` to the file at the given `path` in a `FileRepo`, creating it if necessary.
+#[cfg(feature = "repo-file")]
+fn write_file(repo: &mut FileRepo, path: &str, data: &[u8]) -> anyhow::Result<()> {
+    if !repo.exists(path) {
+        repo.create(path, &Entry::file())?;
+    }
+    let mut object = repo.open(path)?;
+    object.set_len(0)?;
+    object.write_all(data)?;
+    object.commit()?;
+    Ok(())
+}
+
+#[cfg(feature = "repo-file")]
+#[rstest]
+#[case::fixed_size_chunking(fixed_config())]
+#[case::small_pack_size(fixed_packing_small_config())]
+fn file_repo_commit_is_atomic(
+    #[case] config: RepoConfig,
+    #[from(buffer)] old_data: Vec<u8>,
+    #[from(buffer)] new_data: Vec<u8>,
+) -> anyhow::Result<()> {
+    for_each_crash_point(
+        &config,
+        |repo: &mut FileRepo| {
+            write_file(repo, "changed", &old_data)?;
+            repo.commit()?;
+            write_file(repo, "changed", &new_data)?;
+            write_file(repo, "added", &new_data)?;
+            Ok(())
+        },
+        |repo| repo.commit(),
+        |repo, completed| {
+            assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+            if repo.exists("added") {
+                assert_that!(read_file(repo, "changed")?).is_equal_to(new_data.clone());
+                assert_that!(read_file(repo, "added")?).is_equal_to(new_data.clone());
+            } else {
+                assert_that!(completed).is_false();
+                assert_that!(read_file(repo, "changed")?).is_equal_to(old_data.clone());
+            }
+            Ok(())
+        },
+    )
+}
+
+#[cfg(feature = "repo-file")]
+#[rstest]
+#[case::fixed_size_chunking(fixed_config())]
+#[case::small_pack_size(fixed_packing_small_config())]
+fn file_repo_clean_does_not_lose_data(
+    #[case] config: RepoConfig,
+    #[from(buffer)] retained_data: Vec<u8>,
+    #[from(buffer)] removed_data: Vec<u8>,
+) -> anyhow::Result<()> {
+    for_each_crash_point(
+        &config,
+        |repo: &mut FileRepo| {
+            write_file(repo, "retained", &retained_data)?;
+            write_file(repo, "removed", &removed_data)?;
+            repo.commit()?;
+            repo.remove("removed")?;
+            repo.commit()?;
+            Ok(())
+        },
+        |repo| repo.clean(),
+        |repo, _| {
+            assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+            assert_that!(read_file(repo, "retained")?).is_equal_to(retained_data.clone());
+            assert_that!(repo.exists("removed")).is_false();
+            Ok(())
+        },
+    )
+}
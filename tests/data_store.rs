@@ -50,6 +50,17 @@ fn read_header_block(#[case] mut store: Box<dyn DataStore>, buffer: Vec<u8>) {
     assert_that!(store.read_block(BlockKey::Header(id))).is_ok_containing(Some(buffer));
 }
 
+#[apply(data_stores)]
+#[serial(data_store)]
+fn block_size_is_size_of_block(#[case] mut store: Box<dyn DataStore>, buffer: Vec<u8>) {
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.block_size(BlockKey::Data(id))).is_ok_containing(None);
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.block_size(BlockKey::Data(id)))
+        .is_ok_containing(Some(buffer.len() as u64));
+}
+
 #[apply(data_stores)]
 #[serial(data_store)]
 fn read_super_block(#[case] mut store: Box<dyn DataStore>, buffer: Vec<u8>) {
@@ -255,3 +266,56 @@ fn list_header_blocks(#[case] mut store: Box<dyn DataStore>, buffer: Vec<u8>) {
         .is_ok()
         .contains_all_of(&[&id1, &id2, &id3]);
 }
+
+#[apply(data_stores)]
+#[serial(data_store)]
+fn conditionally_write_block_with_expected_contents(
+    #[case] mut store: Box<dyn DataStore>,
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) {
+    assert_that!(store.write_block_if(BlockKey::Super, None, &first_buffer)).is_ok_containing(true);
+    assert_that!(store.write_block_if(BlockKey::Super, Some(&first_buffer), &second_buffer))
+        .is_ok_containing(true);
+    assert_that!(store.read_block(BlockKey::Super)).is_ok_containing(Some(second_buffer));
+}
+
+#[apply(data_stores)]
+#[serial(data_store)]
+fn conditionally_write_block_with_unexpected_contents(
+    #[case] mut store: Box<dyn DataStore>,
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) {
+    assert_that!(store.write_block(BlockKey::Super, &first_buffer)).is_ok();
+    assert_that!(store.write_block_if(BlockKey::Super, None, &second_buffer))
+        .is_ok_containing(false);
+    assert_that!(store.write_block_if(BlockKey::Super, Some(&second_buffer), &second_buffer))
+        .is_ok_containing(false);
+    assert_that!(store.read_block(BlockKey::Super)).is_ok_containing(Some(first_buffer));
+}
+
+#[apply(data_stores)]
+#[serial(data_store)]
+fn create_block_exclusive_when_block_does_not_exist(
+    #[case] mut store: Box<dyn DataStore>,
+    buffer: Vec<u8>,
+) {
+    let id = Uuid::new_v4().into();
+    assert_that!(store.create_block_exclusive(BlockKey::Lock(id), &buffer)).is_ok_containing(true);
+    assert_that!(store.read_block(BlockKey::Lock(id))).is_ok_containing(Some(buffer));
+}
+
+#[apply(data_stores)]
+#[serial(data_store)]
+fn create_block_exclusive_when_block_exists(
+    #[case] mut store: Box<dyn DataStore>,
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) {
+    let id = Uuid::new_v4().into();
+    assert_that!(store.write_block(BlockKey::Data(id), &first_buffer)).is_ok();
+    assert_that!(store.create_block_exclusive(BlockKey::Data(id), &second_buffer))
+        .is_ok_containing(false);
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(first_buffer));
+}
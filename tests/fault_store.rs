@@ -0,0 +1,173 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use acid_store::store::{
+    BlockKey, DataStore, Fault, FaultConfig, FaultInjector, FaultStore, MemoryConfig, MemoryStore,
+    OpenStore,
+};
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// Return a new `FaultStore` along with the config of the data store it wraps.
+fn fault_store() -> (FaultStore<MemoryStore>, MemoryConfig) {
+    let inner = MemoryConfig::new();
+    let store = FaultStore::new(inner.open().unwrap(), FaultInjector::new());
+    (store, inner)
+}
+
+#[rstest]
+fn operations_without_faults_succeed(buffer: Vec<u8>) {
+    let (mut store, inner) = fault_store();
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(inner.open().unwrap().read_block(BlockKey::Data(id)))
+        .is_ok_containing(Some(buffer));
+    assert_that!(store.remove_block(BlockKey::Data(id))).is_ok();
+    assert_that!(inner.open().unwrap().read_block(BlockKey::Data(id))).is_ok_containing(None);
+    assert_that!(store.injector().operations()).is_equal_to(2);
+}
+
+#[rstest]
+fn failed_write_returns_error(buffer: Vec<u8>) {
+    let (mut store, _) = fault_store();
+    let id = Uuid::new_v4().into();
+    store.injector().inject(0, Fault::Fail);
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_err();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(None);
+
+    // Only the given operation fails.
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+}
+
+#[rstest]
+fn dropped_write_is_not_performed(buffer: Vec<u8>) {
+    let (mut store, _) = fault_store();
+    let id = Uuid::new_v4().into();
+    store.injector().inject(0, Fault::Drop);
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(None);
+}
+
+#[rstest]
+fn dropped_remove_is_not_performed(buffer: Vec<u8>) {
+    let (mut store, _) = fault_store();
+    let id = Uuid::new_v4().into();
+    store.injector().inject(1, Fault::Drop);
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.remove_block(BlockKey::Data(id))).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer));
+}
+
+#[rstest]
+fn truncated_write_writes_prefix(buffer: Vec<u8>) {
+    let (mut store, _) = fault_store();
+    let id = Uuid::new_v4().into();
+    store.injector().inject(0, Fault::Truncate(10));
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id)))
+        .is_ok_containing(Some(buffer[..10].to_vec()));
+}
+
+#[rstest]
+fn reordered_write_is_performed_after_next_write(
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) {
+    let (mut store, inner) = fault_store();
+    let first_id = Uuid::new_v4().into();
+    let second_id = Uuid::new_v4().into();
+    store.injector().inject(0, Fault::Reorder);
+
+    assert_that!(store.write_block(BlockKey::Data(first_id), &first_buffer)).is_ok();
+    assert_that!(inner.open().unwrap().read_block(BlockKey::Data(first_id))).is_ok_containing(None);
+
+    assert_that!(store.write_block(BlockKey::Data(second_id), &second_buffer)).is_ok();
+    assert_that!(inner.open().unwrap().read_block(BlockKey::Data(first_id)))
+        .is_ok_containing(Some(first_buffer));
+    assert_that!(inner.open().unwrap().read_block(BlockKey::Data(second_id)))
+        .is_ok_containing(Some(second_buffer));
+}
+
+#[rstest]
+fn reordered_write_is_lost_on_crash(buffer: Vec<u8>) {
+    let (mut store, inner) = fault_store();
+    let first_id = Uuid::new_v4().into();
+    let second_id = Uuid::new_v4().into();
+    store.injector().inject(0, Fault::Reorder);
+    store.injector().crash_at(1);
+
+    assert_that!(store.write_block(BlockKey::Data(first_id), &buffer)).is_ok();
+    assert_that!(store.write_block(BlockKey::Data(second_id), &buffer)).is_err();
+    assert_that!(store.read_block(BlockKey::Data(first_id))).is_ok_containing(None);
+    assert_that!(inner.open().unwrap().read_block(BlockKey::Data(first_id))).is_ok_containing(None);
+}
+
+#[rstest]
+fn reordered_write_is_performed_before_read(buffer: Vec<u8>) {
+    let (mut store, _) = fault_store();
+    let id = Uuid::new_v4().into();
+    store.injector().inject(0, Fault::Reorder);
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer));
+}
+
+#[rstest]
+fn crash_freezes_store(
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) {
+    let (mut store, _) = fault_store();
+    let first_id = Uuid::new_v4().into();
+    let second_id = Uuid::new_v4().into();
+    store.injector().crash_at(1);
+
+    assert_that!(store.write_block(BlockKey::Data(first_id), &first_buffer)).is_ok();
+    assert_that!(store.injector().is_crashed()).is_false();
+    assert_that!(store.write_block(BlockKey::Data(second_id), &second_buffer)).is_err();
+    assert_that!(store.injector().is_crashed()).is_true();
+    assert_that!(store.remove_block(BlockKey::Data(first_id))).is_err();
+    assert_that!(store.create_block_exclusive(BlockKey::Data(second_id), &second_buffer)).is_err();
+
+    assert_that!(store.read_block(BlockKey::Data(first_id))).is_ok_containing(Some(first_buffer));
+    assert_that!(store.read_block(BlockKey::Data(second_id))).is_ok_containing(None);
+}
+
+#[rstest]
+fn reset_recovers_from_crash(buffer: Vec<u8>) {
+    let (mut store, _) = fault_store();
+    let id = Uuid::new_v4().into();
+    store.injector().crash_at(0);
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_err();
+    store.injector().reset();
+
+    assert_that!(store.injector().is_crashed()).is_false();
+    assert_that!(store.injector().operations()).is_equal_to(0);
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+}
+
+#[rstest]
+fn stores_opened_from_config_share_injector(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let config = FaultConfig {
+        inner: MemoryConfig::new(),
+        injector: FaultInjector::new(),
+    };
+    let mut first_store = config.open()?;
+    let mut second_store = config.open()?;
+    config.injector.crash_at(1);
+
+    assert_that!(first_store.write_block(BlockKey::Data(Uuid::new_v4().into()), &buffer)).is_ok();
+    assert_that!(second_store.write_block(BlockKey::Data(Uuid::new_v4().into()), &buffer)).is_err();
+    assert_that!(first_store.write_block(BlockKey::Data(Uuid::new_v4().into()), &buffer)).is_err();
+    assert_that!(config.injector.operations()).is_equal_to(3);
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockKey, BlockType, DataStore, Fault, FaultConfig, FaultInjector, InstrumentedConfig,
+    MemoryConfig, OpenStore, OperationStats, StoreMetrics, StoreOperation,
+};
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// Open an instrumented data store which collects metrics in `metrics`.
+fn store_with_metrics(metrics: &StoreMetrics) -> impl DataStore {
+    InstrumentedConfig {
+        inner: MemoryConfig::new(),
+        metrics: metrics.clone(),
+    }
+    .open()
+    .unwrap()
+}
+
+#[rstest]
+fn calls_and_bytes_are_counted_by_block_type(buffer: Vec<u8>) {
+    let metrics = StoreMetrics::new();
+    let mut store = store_with_metrics(&metrics);
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.write_block(BlockKey::Header(id), &buffer)).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok();
+    assert_that!(store.read_block(BlockKey::Super)).is_ok();
+    assert_that!(store.list_blocks(BlockType::Data)).is_ok();
+
+    let writes = metrics.get(StoreOperation::WriteBlock, Some(BlockType::Data));
+    assert_that!(writes.calls).is_equal_to(1);
+    assert_that!(writes.errors).is_equal_to(0);
+    assert_that!(writes.bytes).is_equal_to(buffer.len() as u64);
+
+    let reads = metrics.get(StoreOperation::ReadBlock, Some(BlockType::Data));
+    assert_that!(reads.calls).is_equal_to(2);
+    assert_that!(reads.bytes).is_equal_to(2 * buffer.len() as u64);
+
+    // The superblock doesn't exist, so no bytes were read.
+    let super_reads = metrics.get(StoreOperation::ReadBlock, None);
+    assert_that!(super_reads.calls).is_equal_to(1);
+    assert_that!(super_reads.bytes).is_equal_to(0);
+
+    assert_that!(metrics.total(StoreOperation::WriteBlock).calls).is_equal_to(2);
+    assert_that!(
+        metrics
+            .get(StoreOperation::ListBlocks, Some(BlockType::Data))
+            .calls
+    )
+    .is_equal_to(1);
+    assert_that!(metrics.get(StoreOperation::RemoveBlock, Some(BlockType::Data)))
+        .is_equal_to(OperationStats::default());
+    assert_that!(metrics.all().len()).is_equal_to(5);
+}
+
+#[rstest]
+fn errors_are_counted(buffer: Vec<u8>) {
+    let injector = FaultInjector::new();
+    let metrics = StoreMetrics::new();
+    let mut store = InstrumentedConfig {
+        inner: FaultConfig {
+            inner: MemoryConfig::new(),
+            injector: injector.clone(),
+        },
+        metrics: metrics.clone(),
+    }
+    .open()
+    .unwrap();
+    injector.inject(0, Fault::Fail);
+
+    let id = Uuid::new_v4().into();
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_err();
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+
+    let writes = metrics.get(StoreOperation::WriteBlock, Some(BlockType::Data));
+    assert_that!(writes.calls).is_equal_to(2);
+    assert_that!(writes.errors).is_equal_to(1);
+    assert_that!(writes.bytes).is_equal_to(buffer.len() as u64);
+}
+
+#[rstest]
+fn latencies_are_recorded(buffer: Vec<u8>) {
+    let metrics = StoreMetrics::new();
+    let mut store = store_with_metrics(&metrics);
+
+    for _ in 0..10 {
+        let id = Uuid::new_v4().into();
+        assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    }
+
+    let latency = metrics.total(StoreOperation::WriteBlock).latency;
+    let bucket_count = latency.buckets().map(|(_, count)| count).sum::<u64>();
+    assert_that!(latency.count()).is_equal_to(10);
+    assert_that!(bucket_count).is_equal_to(10);
+    assert_that!(latency.mean()).is_some();
+    assert_that!(latency.quantile(0.5)).is_some();
+    assert_that!(latency.quantile(0.5).unwrap())
+        .is_less_than_or_equal_to(latency.quantile(1.0).unwrap());
+    assert_that!(latency.quantile(1.0).unwrap()).is_greater_than_or_equal_to(latency.total() / 10);
+}
+
+#[rstest]
+fn empty_histogram_has_no_quantiles() {
+    let latency = StoreMetrics::new().total(StoreOperation::ReadBlock).latency;
+
+    assert_that!(latency.count()).is_equal_to(0);
+    assert_that!(latency.total()).is_equal_to(Duration::ZERO);
+    assert_that!(latency.mean()).is_none();
+    assert_that!(latency.quantile(0.99)).is_none();
+}
+
+#[rstest]
+fn metrics_are_reset(buffer: Vec<u8>) {
+    let metrics = StoreMetrics::new();
+    let mut store = store_with_metrics(&metrics);
+    let id = Uuid::new_v4().into();
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+
+    metrics.reset();
+
+    assert_that!(metrics.all().is_empty()).is_true();
+}
+
+#[rstest]
+fn repo_collects_store_metrics(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())?;
+    let metrics = repo.store_metrics();
+    metrics.reset();
+
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    let data_writes = metrics.get(StoreOperation::WriteBlock, Some(BlockType::Data));
+    assert_that!(data_writes.calls).is_greater_than(0);
+    assert_that!(data_writes.bytes).is_greater_than_or_equal_to(buffer.len() as u64);
+    assert_that!(metrics.total(StoreOperation::WriteBlockIf).calls).is_greater_than(0);
+
+    metrics.reset();
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+
+    let data_reads = metrics.get(StoreOperation::ReadBlock, Some(BlockType::Data));
+    assert_that!(data_reads.calls).is_greater_than(0);
+    assert_that!(metrics.total(StoreOperation::WriteBlock).calls).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_metrics_include_worker_threads(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(fixed_config())
+        .write_concurrency(4)
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())?;
+    let metrics = repo.store_metrics();
+
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    let data_writes = metrics.get(StoreOperation::WriteBlock, Some(BlockType::Data));
+    assert_that!(data_writes.calls).is_greater_than(0);
+    assert_that!(data_writes.bytes).is_greater_than_or_equal_to(buffer.len() as u64);
+
+    Ok(())
+}
+
+#[rstest]
+fn snapshots_share_store_metrics(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .mode(OpenMode::CreateNew)
+        .open(&MemoryConfig::new())?;
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.create_snapshot("snapshot")?;
+    repo.commit()?;
+
+    let snapshot = repo.open_snapshot("snapshot")?;
+    repo.store_metrics().reset();
+    let mut actual = Vec::new();
+    snapshot.object("test").unwrap().read_to_end(&mut actual)?;
+
+    assert_that!(repo.store_metrics().total(StoreOperation::ReadBlock).calls).is_greater_than(0);
+
+    Ok(())
+}
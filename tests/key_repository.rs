@@ -1,13 +1,20 @@
 #![cfg(all(feature = "encryption", feature = "compression"))]
 
 use std::io::{Read, Write};
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
 
 use acid_store::repo::key::KeyRepo;
 use acid_store::repo::{
-    peek_info, Commit, Encryption, ResourceLimit, RestoreSavepoint, SwitchInstance, Unlock,
+    peek_info, Commit, Encryption, OpenMode, OpenOptions, ResourceLimit, RestoreSavepoint,
+    RetentionPolicy, SwitchInstance, Unlock,
+};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore, StoreOperation,
 };
-use acid_store::store::{BlockType, DataStore, OpenStore};
 use common::*;
+use maplit::hashset;
 use rstest_reuse::{self, *};
 use std::collections::HashSet;
 use uuid::Uuid;
@@ -200,8 +207,13 @@ fn committing_commits_all_instances(repo_store: RepoStore) -> anyhow::Result<()>
 }
 
 #[rstest]
-fn change_password(mut repo_store: RepoStore) -> anyhow::Result<()> {
-    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+#[case::xchacha20_poly1305(Encryption::XChaCha20Poly1305)]
+#[case::aes_256_gcm(Encryption::Aes256Gcm)]
+fn change_password(
+    #[case] encryption: Encryption,
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = encryption;
     let mut repo: KeyRepo<String> = repo_store.create()?;
 
     repo.change_password(
@@ -526,6 +538,631 @@ fn clean_before_commit_does_not_prevent_rollback(
     Ok(())
 }
 
+#[apply(store_config)]
+fn snapshot_survives_commit_and_clean(
+    #[case] repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    repo.create_snapshot("snapshot")?;
+    repo.commit()?;
+
+    // Remove the object, commit, and clean so that its data is only referenced by the snapshot.
+    repo.remove("test");
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let snapshot_names = repo
+        .snapshots()
+        .iter()
+        .map(|snapshot| snapshot.name().to_string())
+        .collect::<Vec<_>>();
+
+    assert_that!(snapshot_names).is_equal_to(vec![String::from("snapshot")]);
+    assert_that!(repo.contains("test")).is_false();
+
+    let snapshot = repo.open_snapshot("snapshot")?;
+    let mut actual_data = Vec::new();
+    let mut object = snapshot.object("test").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[apply(repo_config)]
+fn opened_snapshot_can_be_read_after_clean(
+    #[case] mut repo: KeyRepo<String>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.create_snapshot("snapshot")?;
+    repo.commit()?;
+
+    // Once the snapshot is removed, its data is only referenced by the opened snapshot.
+    let snapshot = repo.open_snapshot("snapshot")?;
+    repo.remove("test");
+    repo.remove_snapshot("snapshot");
+    repo.commit()?;
+    repo.clean()?;
+
+    let mut actual_data = Vec::new();
+    let mut object = snapshot.object("test").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn opened_snapshot_is_read_only(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.insert(String::from("test"));
+    repo.create_snapshot("snapshot")?;
+
+    let mut snapshot = repo.open_snapshot("snapshot")?;
+    let mut object = snapshot.object("test").unwrap();
+
+    assert_that!(object.write_all(b"data").map_err(acid_store::Error::from))
+        .is_err_variant(acid_store::Error::ReadOnly);
+    drop(object);
+
+    assert_that!(snapshot.commit()).is_err_variant(acid_store::Error::ReadOnly);
+    assert_that!(snapshot.clean()).is_err_variant(acid_store::Error::ReadOnly);
+    assert_that!(snapshot.is_locked()).is_ok_containing(true);
+
+    Ok(())
+}
+
+#[rstest]
+fn creating_snapshot_with_existing_name_errs(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.create_snapshot("snapshot")?;
+
+    assert_that!(repo.create_snapshot("snapshot")).is_err_variant(acid_store::Error::AlreadyExists);
+
+    Ok(())
+}
+
+#[rstest]
+fn opening_nonexistent_snapshot_errs(repo: KeyRepo<String>) {
+    assert_that!(repo.open_snapshot("snapshot")).is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn snapshot_is_removed_on_rollback(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.create_snapshot("snapshot")?;
+    repo.rollback()?;
+
+    assert_that!(repo.snapshots()).is_empty();
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn removed_snapshot_data_is_reclaimed_on_clean(
+    #[case] repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+
+    repo.create_snapshot("snapshot")?;
+    repo.remove("test");
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let mut store = repo_store.store.open()?;
+    let original_blocks = store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+        .len();
+    drop(store);
+
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.remove_snapshot("snapshot")).is_true();
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let mut store = repo_store.store.open()?;
+    let new_blocks = store
+        .list_blocks(BlockType::Data)
+        .map_err(anyhow::Error::msg)?
+        .len();
+
+    assert_that!(new_blocks).is_less_than(original_blocks);
+
+    Ok(())
+}
+
+#[rstest]
+fn commits_are_added_to_history(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    assert_that!(repo.commits()).is_empty();
+
+    repo.commit()?;
+    repo.commit()?;
+
+    assert_that!(repo.commits()).has_length(2);
+
+    Ok(())
+}
+
+#[rstest]
+fn clean_removes_old_commits_from_history(mut repo: KeyRepo<String>) -> anyhow::Result<()> {
+    repo.commit()?;
+    repo.commit()?;
+    let latest_commit = repo.commits().last().unwrap().id();
+    repo.clean()?;
+
+    let commit_ids = repo
+        .commits()
+        .iter()
+        .map(|commit| commit.id())
+        .collect::<Vec<_>>();
+
+    assert_that!(commit_ids).is_equal_to(vec![latest_commit]);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn retained_commit_survives_clean(
+    #[case] repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    let first_commit = repo.commits()[0].id();
+
+    repo.remove("test");
+    repo.commit()?;
+
+    let mut policy = RetentionPolicy::default();
+    policy.last = 2;
+    repo.clean_retaining(&policy)?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.commits()).has_length(2);
+    assert_that!(repo.contains("test")).is_false();
+
+    let old_repo = repo.open_commit(first_commit)?;
+    let mut actual_data = Vec::new();
+    let mut object = old_repo.object("test").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn clean_dry_run_does_not_modify_repo(
+    #[case] repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    let first_commit = repo.commits()[0].clone();
+
+    repo.remove("test");
+    repo.commit()?;
+
+    repo.store_metrics().reset();
+    let report = repo.clean_dry_run(&RetentionPolicy::default())?;
+
+    assert_that!(report.dropped_commits()).is_equal_to(&[first_commit][..]);
+    assert_that!(report.reclaimed_bytes()).is_greater_than(0);
+    assert_that!(
+        repo.store_metrics()
+            .get(StoreOperation::ReadBlock, Some(BlockType::Data))
+            .calls
+    )
+    .is_equal_to(0);
+    assert_that!(repo.commits()).has_length(2);
+
+    repo.clean()?;
+
+    let report = repo.clean_dry_run(&RetentionPolicy::default())?;
+
+    assert_that!(report.dropped_commits().to_vec()).is_empty();
+
+    Ok(())
+}
+
+#[rstest]
+fn opening_nonexistent_commit_errs(repo: KeyRepo<String>) {
+    assert_that!(repo.open_commit(Uuid::new_v4().into()))
+        .is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn read_only_repo_can_be_opened_while_locked(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert(String::from("test"));
+    repo.commit()?;
+
+    let first_reader: KeyRepo<String> = repo_store.open_read_only()?;
+    let second_reader: KeyRepo<String> = repo_store.open_read_only()?;
+
+    assert_that!(first_reader.contains("test")).is_true();
+    assert_that!(second_reader.contains("test")).is_true();
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Locked);
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_can_be_opened_while_read_only_repo_is_open(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+    let _reader: KeyRepo<String> = repo_store.open_read_only()?;
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_ok();
+    Ok(())
+}
+
+#[rstest]
+fn read_only_repo_can_not_be_modified(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+    let mut reader: KeyRepo<String> = repo_store.open_read_only()?;
+
+    let mut object = reader.insert(String::from("test"));
+    assert_that!(object.write_all(b"data").map_err(acid_store::Error::from))
+        .is_err_variant(acid_store::Error::ReadOnly);
+    drop(object);
+
+    assert_that!(reader.commit()).is_err_variant(acid_store::Error::ReadOnly);
+    assert_that!(reader.clean()).is_err_variant(acid_store::Error::ReadOnly);
+
+    Ok(())
+}
+
+#[rstest]
+fn opening_nonexistent_repo_read_only_errs(repo_store: RepoStore) {
+    assert_that!(repo_store.open_read_only::<KeyRepo<String>>())
+        .is_err_variant(acid_store::Error::NotFound);
+}
+
+#[rstest]
+fn opening_nonexistent_instance_read_only_errs(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    drop(repo_store.create::<KeyRepo<String>>()?);
+    repo_store.instance = Uuid::new_v4().into();
+    assert_that!(repo_store.open_read_only::<KeyRepo<String>>())
+        .is_err_variant(acid_store::Error::NotFound);
+    Ok(())
+}
+
+#[apply(store_config)]
+fn read_only_repo_survives_commit_and_clean(
+    #[case] repo_store: RepoStore,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    let reader: KeyRepo<String> = repo_store.open_read_only()?;
+
+    // Remove the object, commit, and clean so that its data is only referenced by the reader.
+    repo.remove("test");
+    repo.insert(String::from("other"));
+    repo.commit()?;
+    repo.clean()?;
+
+    assert_that!(reader.contains("other")).is_false();
+
+    let mut actual_data = Vec::new();
+    let mut object = reader.object("test").unwrap();
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(actual_data).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+#[case::reclaimed(true, 0)]
+#[case::kept(false, 1)]
+fn lock_handler_can_remove_lock_of_crashed_reader(
+    mut repo_store: RepoStore,
+    #[case] remove: bool,
+    #[case] remaining_locks: usize,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert(String::from("test"));
+    repo.commit()?;
+    drop(repo);
+
+    // Simulate a reader which crashes without releasing its lock.
+    repo_store.context = b"reader".to_vec();
+    let reader: KeyRepo<String> = repo_store.open_read_only()?;
+    std::mem::forget(reader);
+
+    repo_store.context = b"writer".to_vec();
+    repo_store.handler = Box::new(move |context| remove && context == b"reader");
+    drop(repo_store.open::<KeyRepo<String>>()?);
+
+    let mut store = repo_store.store.open()?;
+    let locks = store
+        .list_blocks(BlockType::Lock)
+        .map_err(anyhow::Error::msg)?;
+    assert_that!(locks).has_length(remaining_locks);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn optimistic_commits_to_different_keys_are_merged(
+    #[case] repo_store: RepoStore,
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.insert(String::from("removed"));
+    repo.commit()?;
+    drop(repo);
+
+    let mut first_repo: KeyRepo<String> = repo_store.open_optimistic()?;
+    let mut second_repo: KeyRepo<String> = repo_store.open_optimistic()?;
+
+    let mut object = first_repo.insert(String::from("first"));
+    object.write_all(&first_buffer)?;
+    object.commit()?;
+    drop(object);
+    first_repo.remove("removed");
+    first_repo.commit()?;
+
+    let mut object = second_repo.insert(String::from("second"));
+    object.write_all(&second_buffer)?;
+    object.commit()?;
+    drop(object);
+    second_repo.commit()?;
+
+    drop(first_repo);
+    drop(second_repo);
+
+    // Clean the repository to make sure the merged commit references all the data it needs.
+    let mut repo: KeyRepo<String> = repo_store.open()?;
+    repo.clean()?;
+
+    let keys = repo.keys().cloned().collect::<HashSet<_>>();
+    assert_that!(keys).is_equal_to(hashset![String::from("first"), String::from("second")]);
+
+    let mut actual_data = Vec::new();
+    repo.object("first")
+        .unwrap()
+        .read_to_end(&mut actual_data)?;
+    assert_that!(actual_data).is_equal_to(&first_buffer);
+
+    let mut actual_data = Vec::new();
+    repo.object("second")
+        .unwrap()
+        .read_to_end(&mut actual_data)?;
+    assert_that!(actual_data).is_equal_to(&second_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn optimistic_commits_to_same_key_conflict(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+
+    let mut first_repo: KeyRepo<String> = repo_store.open_optimistic()?;
+    let mut second_repo: KeyRepo<String> = repo_store.open_optimistic()?;
+
+    let mut object = first_repo.insert(String::from("test"));
+    object.write_all(b"first")?;
+    object.commit()?;
+    drop(object);
+    first_repo.commit()?;
+
+    let mut object = second_repo.insert(String::from("test"));
+    object.write_all(b"second")?;
+    object.commit()?;
+    drop(object);
+    assert_that!(second_repo.commit()).is_err_variant(acid_store::Error::Conflict);
+
+    Ok(())
+}
+
+#[rstest]
+fn optimistic_commits_to_different_instances_are_merged(
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+
+    let first_instance = Uuid::new_v4().into();
+    let second_instance = Uuid::new_v4().into();
+
+    repo_store.instance = first_instance;
+    let mut first_repo: KeyRepo<String> = repo_store.open_optimistic()?;
+    repo_store.instance = second_instance;
+    let mut second_repo: KeyRepo<String> = repo_store.open_optimistic()?;
+
+    first_repo.insert(String::from("first"));
+    first_repo.commit()?;
+    second_repo.insert(String::from("second"));
+    second_repo.commit()?;
+
+    drop(first_repo);
+    drop(second_repo);
+
+    repo_store.instance = first_instance;
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.keys().cloned().collect::<Vec<_>>()).is_equal_to(vec![String::from("first")]);
+    drop(repo);
+
+    repo_store.instance = second_instance;
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.keys().cloned().collect::<Vec<_>>())
+        .is_equal_to(vec![String::from("second")]);
+
+    Ok(())
+}
+
+#[rstest]
+fn optimistic_and_exclusive_locks_conflict(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+
+    assert_that!(repo_store.open_optimistic::<KeyRepo<String>>())
+        .is_err_variant(acid_store::Error::Locked);
+    drop(repo);
+
+    let _repo: KeyRepo<String> = repo_store.open_optimistic()?;
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Locked);
+
+    Ok(())
+}
+
+#[rstest]
+fn optimistic_clean_fails_while_other_writers_are_open(
+    repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+
+    let mut first_repo: KeyRepo<String> = repo_store.open_optimistic()?;
+    let second_repo: KeyRepo<String> = repo_store.open_optimistic()?;
+
+    assert_that!(first_repo.clean()).is_err_variant(acid_store::Error::Locked);
+
+    drop(second_repo);
+    first_repo.clean()?;
+
+    // Once the repository is done being cleaned, other clients can open it again.
+    assert_that!(repo_store.open_optimistic::<KeyRepo<String>>()).is_ok();
+
+    Ok(())
+}
+
+/// A data store which can't conditionally write blocks atomically.
+///
+/// Conditional writes to the super block are slow so that they are likely to race.
+#[derive(Debug)]
+struct NonAtomicStore(MemoryStore);
+
+impl DataStore for NonAtomicStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.0.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.0.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.0.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.0.list_blocks(kind)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> acid_store::store::Result<bool> {
+        let current = self.0.read_block(key)?;
+        if key == BlockKey::Super {
+            thread::sleep(Duration::from_millis(200));
+        }
+        if current.as_deref() != expected {
+            return Ok(false);
+        }
+        self.0.write_block(key, data)?;
+        Ok(true)
+    }
+}
+
+/// The configuration for opening a `NonAtomicStore`.
+#[derive(Debug)]
+struct NonAtomicConfig(MemoryConfig);
+
+impl OpenStore for NonAtomicConfig {
+    type Store = NonAtomicStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(NonAtomicStore(self.0.open()?))
+    }
+}
+
+#[rstest]
+fn concurrent_optimistic_commits_without_atomic_writes_are_not_lost() -> anyhow::Result<()> {
+    let config = NonAtomicConfig(MemoryConfig::new());
+    let mut repo: KeyRepo<String> = OpenOptions::new().mode(OpenMode::Create).open(&config)?;
+    repo.commit()?;
+    drop(repo);
+
+    let keys = ["first", "second"];
+    let barrier = Barrier::new(keys.len());
+    let committed = thread::scope(|scope| {
+        let handles = keys
+            .iter()
+            .map(|key| {
+                let mut repo: KeyRepo<String> = OpenOptions::new()
+                    .optimistic(true)
+                    .open(&config)
+                    .unwrap();
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    repo.insert(key.to_string());
+                    barrier.wait();
+                    repo.commit().is_ok().then_some(*key)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    // A commit may fail, but one which succeeds must not be overwritten by the other.
+    let repo: KeyRepo<String> = OpenOptions::new().open(&config)?;
+    for key in committed {
+        assert_that!(repo.contains(key)).is_true();
+    }
+
+    Ok(())
+}
+
 #[rstest]
 fn clear_instance_deletes_objects(repo_object: RepoObject) -> anyhow::Result<()> {
     let RepoObject {
@@ -721,3 +1358,43 @@ fn update_lock_context(mut repo_store: RepoStore) -> anyhow::Result<()> {
     assert_that!(repo_store.open::<KeyRepo<String>>()).is_ok();
     Ok(())
 }
+
+#[rstest]
+fn exclusive_lock_can_be_broken_by_lock_handler(mut repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Locked);
+
+    repo_store.context = b"second".to_vec();
+    repo_store.handler = Box::new(|_| true);
+    let second_repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(second_repo.context()).is_ok_containing(b"second".to_vec());
+
+    repo_store.handler = Box::new(|_| false);
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Locked);
+
+    Ok(())
+}
+
+#[rstest]
+fn unlocking_broken_exclusive_lock_does_not_release_new_lock(
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+
+    repo_store.handler = Box::new(|_| true);
+    let second_repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.is_locked()).is_ok_containing(false);
+
+    // The first client releases the lock it no longer holds.
+    repo.unlock()?;
+    drop(repo);
+
+    assert_that!(second_repo.is_locked()).is_ok_containing(true);
+    repo_store.handler = Box::new(|_| false);
+    assert_that!(repo_store.open::<KeyRepo<String>>()).is_err_variant(acid_store::Error::Locked);
+
+    Ok(())
+}
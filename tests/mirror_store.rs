@@ -0,0 +1,222 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockKey, BlockType, CachingConfig, DataStore, Fault, FaultConfig, FaultInjector, MemoryConfig,
+    MirrorConfig, OpenStore,
+};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// Return a config for a replica which fails according to `injector`.
+fn faulty_replica(inner: &MemoryConfig, injector: &FaultInjector) -> FaultConfig<MemoryConfig> {
+    FaultConfig {
+        inner: inner.clone(),
+        injector: injector.clone(),
+    }
+}
+
+#[rstest]
+fn blocks_are_written_to_every_replica(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let first = MemoryConfig::new();
+    let second = MemoryConfig::new();
+    let mut store = MirrorConfig::new()
+        .replica(first.clone())
+        .replica(second.clone())
+        .open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+
+    assert_that!(first.open()?.read_block(BlockKey::Data(id)))
+        .is_ok_containing(Some(buffer.clone()));
+    assert_that!(second.open()?.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer));
+
+    assert_that!(store.remove_block(BlockKey::Data(id))).is_ok();
+
+    assert_that!(first.open()?.read_block(BlockKey::Data(id))).is_ok_containing(None);
+    assert_that!(second.open()?.read_block(BlockKey::Data(id))).is_ok_containing(None);
+
+    Ok(())
+}
+
+#[rstest]
+fn write_succeeds_with_quorum(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let injector = FaultInjector::new();
+    let second = MemoryConfig::new();
+    let mut store = MirrorConfig::new()
+        .replica(faulty_replica(&MemoryConfig::new(), &injector))
+        .replica(second.clone())
+        .write_quorum(1)
+        .open()?;
+    let id = Uuid::new_v4().into();
+    injector.inject(0, Fault::Fail);
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.is_healthy(0)).is_false();
+    assert_that!(store.is_healthy(1)).is_true();
+    assert_that!(second.open()?.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn write_fails_without_quorum(
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let injector = FaultInjector::new();
+    let second = MemoryConfig::new();
+    let mut store = MirrorConfig::new()
+        .replica(faulty_replica(&MemoryConfig::new(), &injector))
+        .replica(second.clone())
+        .open()?;
+    let id = Uuid::new_v4().into();
+    assert_that!(store.write_block(BlockKey::Header(id), &first_buffer)).is_ok();
+    injector.inject(1, Fault::Fail);
+
+    assert_that!(store.write_block(BlockKey::Header(id), &second_buffer)).is_err();
+
+    // The block is not removed from the replicas it was overwritten on.
+    assert_that!(second.open()?.read_block(BlockKey::Header(id)))
+        .is_ok_containing(Some(second_buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn unhealthy_replicas_are_not_read_from(
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let injector = FaultInjector::new();
+    let mut store = MirrorConfig::new()
+        .replica(faulty_replica(&MemoryConfig::new(), &injector))
+        .replica(MemoryConfig::new())
+        .write_quorum(1)
+        .open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &first_buffer)).is_ok();
+
+    // The first replica misses this write, so it has the old contents of the block.
+    injector.inject(1, Fault::Fail);
+    assert_that!(store.write_block(BlockKey::Data(id), &second_buffer)).is_ok();
+
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(second_buffer));
+
+    Ok(())
+}
+
+#[rstest]
+fn resync_copies_missing_blocks(
+    #[from(buffer)] first_buffer: Vec<u8>,
+    #[from(buffer)] second_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let first = MemoryConfig::new();
+    let second = MemoryConfig::new();
+    let first_id = Uuid::new_v4().into();
+    let second_id = Uuid::new_v4().into();
+    assert_that!(first
+        .open()?
+        .write_block(BlockKey::Data(first_id), &first_buffer))
+    .is_ok();
+    assert_that!(second
+        .open()?
+        .write_block(BlockKey::Header(second_id), &second_buffer))
+    .is_ok();
+    assert_that!(first.open()?.write_block(BlockKey::Super, &second_buffer)).is_ok();
+
+    let mut store = MirrorConfig::new()
+        .replica(first.clone())
+        .replica(second.clone())
+        .open()?;
+
+    assert_that!(store.resync()).is_ok_containing(3);
+    assert_that!(store.resync()).is_ok_containing(0);
+
+    for replica in [&first, &second] {
+        let mut replica = replica.open()?;
+        assert_that!(replica.read_block(BlockKey::Data(first_id)))
+            .is_ok_containing(Some(first_buffer.clone()));
+        assert_that!(replica.read_block(BlockKey::Header(second_id)))
+            .is_ok_containing(Some(second_buffer.clone()));
+        assert_that!(replica.read_block(BlockKey::Super))
+            .is_ok_containing(Some(second_buffer.clone()));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn resync_restores_health(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let injector = FaultInjector::new();
+    let mut store = MirrorConfig::new()
+        .replica(faulty_replica(&MemoryConfig::new(), &injector))
+        .replica(MemoryConfig::new())
+        .write_quorum(1)
+        .open()?;
+    let id = Uuid::new_v4().into();
+    injector.inject(0, Fault::Fail);
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.is_healthy(0)).is_false();
+
+    assert_that!(store.resync()).is_ok_containing(1);
+
+    assert_that!(store.is_healthy(0)).is_true();
+    let mut replica = store.into_inner().remove(0);
+    assert_that!(replica.list_blocks(BlockType::Data)).is_ok_containing(vec![id]);
+
+    Ok(())
+}
+
+#[test]
+#[should_panic]
+fn write_quorum_larger_than_replicas_panics() {
+    MirrorConfig::new()
+        .replica(MemoryConfig::new())
+        .write_quorum(2)
+        .open()
+        .ok();
+}
+
+#[rstest]
+fn repo_can_be_mirrored_to_different_stores(
+    temp_dir: TempDir,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let memory = MemoryConfig::new();
+    let config = MirrorConfig::new()
+        .replica(memory.clone())
+        .replica(CachingConfig {
+            inner: MemoryConfig::new(),
+            path: temp_dir.path().join("cache"),
+            max_size: 1024 * 1024,
+        });
+    let mut repo: KeyRepo<String> = OpenOptions::new().mode(OpenMode::CreateNew).open(&config)?;
+
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    // Each replica contains a complete copy of the repository.
+    let repo: KeyRepo<String> = OpenOptions::new().open(&memory)?;
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+    assert_that!(actual).is_equal_to(buffer);
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+
+    Ok(())
+}
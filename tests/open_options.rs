@@ -9,17 +9,23 @@ use acid_store::repo::value::ValueRepo;
 use acid_store::repo::{
     Chunking, Commit, Compression, Encryption, OpenMode, OpenOptions, RepoConfig, ResourceLimit,
 };
-use acid_store::store::MemoryConfig;
+use acid_store::store::{BlockKey, DataStore, MemoryConfig, OpenStore};
 use common::*;
+use rmpv::Value;
 
 mod common;
 
 #[rstest]
-fn set_existing_config_and_create_new_repo(mut repo_store: RepoStore) -> anyhow::Result<()> {
+#[case::xchacha20_poly1305(Encryption::XChaCha20Poly1305)]
+#[case::aes_256_gcm(Encryption::Aes256Gcm)]
+fn set_existing_config_and_create_new_repo(
+    #[case] encryption: Encryption,
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
     // These are random config values for testing. This should not be used as an example config.
     repo_store.config.chunking = Chunking::Fixed { size: 1024 * 16 };
     repo_store.config.compression = Compression::Lz4 { level: 2 };
-    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+    repo_store.config.encryption = encryption;
     repo_store.config.memory_limit = ResourceLimit::Moderate;
     repo_store.config.operations_limit = ResourceLimit::Moderate;
 
@@ -31,12 +37,14 @@ fn set_existing_config_and_create_new_repo(mut repo_store: RepoStore) -> anyhow:
 }
 
 #[rstest]
-fn configure_and_create_new_repo() -> anyhow::Result<()> {
+#[case::xchacha20_poly1305(Encryption::XChaCha20Poly1305)]
+#[case::aes_256_gcm(Encryption::Aes256Gcm)]
+fn configure_and_create_new_repo(#[case] encryption: Encryption) -> anyhow::Result<()> {
     // These are random config values for testing. This should not be used as an example config.
     let mut expected_config = RepoConfig::default();
     expected_config.chunking = Chunking::Fixed { size: 1024 * 16 };
     expected_config.compression = Compression::Lz4 { level: 2 };
-    expected_config.encryption = Encryption::XChaCha20Poly1305;
+    expected_config.encryption = encryption.clone();
     expected_config.memory_limit = ResourceLimit::Moderate;
     expected_config.operations_limit = ResourceLimit::Moderate;
 
@@ -44,7 +52,7 @@ fn configure_and_create_new_repo() -> anyhow::Result<()> {
     let repo: KeyRepo<String> = OpenOptions::new()
         .chunking(Chunking::Fixed { size: 1024 * 16 })
         .compression(Compression::Lz4 { level: 2 })
-        .encryption(Encryption::XChaCha20Poly1305)
+        .encryption(encryption)
         .memory_limit(ResourceLimit::Moderate)
         .operations_limit(ResourceLimit::Moderate)
         .password(b"password")
@@ -56,6 +64,59 @@ fn configure_and_create_new_repo() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[rstest]
+fn creating_with_invalid_compression_threshold_errs() {
+    let config = MemoryConfig::new();
+    assert_that!(OpenOptions::new()
+        .compression_threshold(101)
+        .mode(OpenMode::CreateNew)
+        .open::<KeyRepo<String>, _>(&config))
+    .is_err_variant(acid_store::Error::InvalidConfig);
+}
+
+#[rstest]
+#[case::min_above_avg(Chunking::FastCdc { min: 4096, avg: 1024, max: 8192 })]
+#[case::avg_above_max(Chunking::FastCdc { min: 1024, avg: 8192, max: 4096 })]
+#[case::zero_sizes(Chunking::FastCdc { min: 0, avg: 0, max: 0 })]
+fn creating_with_invalid_fastcdc_sizes_errs(#[case] chunking: Chunking) {
+    let config = MemoryConfig::new();
+    assert_that!(OpenOptions::new()
+        .chunking(chunking)
+        .mode(OpenMode::CreateNew)
+        .open::<KeyRepo<String>, _>(&config))
+    .is_err_variant(acid_store::Error::InvalidConfig);
+}
+
+#[rstest]
+fn opening_repo_with_config_serialized_without_compression_threshold_succeeds(
+    repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    drop(repo);
+
+    // Remove the trailing `compression_threshold` from the serialized config to get the layout
+    // used before that option existed.
+    let mut store = repo_store.store.open()?;
+    let serialized_metadata = store.read_block(BlockKey::Super).unwrap().unwrap();
+    let mut metadata = rmpv::decode::read_value(&mut serialized_metadata.as_slice())?;
+    match &mut metadata {
+        Value::Array(fields) => match &mut fields[1] {
+            Value::Array(config_fields) => config_fields.pop(),
+            _ => panic!("The config is not serialized as an array."),
+        },
+        _ => panic!("The metadata is not serialized as an array."),
+    };
+    let mut old_metadata = Vec::new();
+    rmpv::encode::write_value(&mut old_metadata, &metadata)?;
+    store.write_block(BlockKey::Super, &old_metadata).unwrap();
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.info().config().compression_threshold).is_equal_to(10);
+
+    Ok(())
+}
+
 #[rstest]
 fn creating_new_existing_repo_errs(repo_store: RepoStore) -> anyhow::Result<()> {
     repo_store.create::<KeyRepo<String>>()?;
@@ -93,8 +154,13 @@ fn opening_nonexistent_repo_errs(repo_store: RepoStore) {
 }
 
 #[rstest]
-fn opening_with_invalid_password_errs(mut repo_store: RepoStore) -> anyhow::Result<()> {
-    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+#[case::xchacha20_poly1305(Encryption::XChaCha20Poly1305)]
+#[case::aes_256_gcm(Encryption::Aes256Gcm)]
+fn opening_with_invalid_password_errs(
+    #[case] encryption: Encryption,
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = encryption;
     repo_store.create::<KeyRepo<String>>()?;
     repo_store.password = String::from("Not the password");
 
@@ -104,18 +170,25 @@ fn opening_with_invalid_password_errs(mut repo_store: RepoStore) -> anyhow::Resu
 }
 
 #[rstest]
-fn creating_without_password_errs() {
+#[case::xchacha20_poly1305(Encryption::XChaCha20Poly1305)]
+#[case::aes_256_gcm(Encryption::Aes256Gcm)]
+fn creating_without_password_errs(#[case] encryption: Encryption) {
     let config = MemoryConfig::new();
     assert_that!(OpenOptions::new()
-        .encryption(Encryption::XChaCha20Poly1305)
+        .encryption(encryption)
         .mode(OpenMode::CreateNew)
         .open::<KeyRepo<String>, _>(&config))
     .is_err_variant(acid_store::Error::Password);
 }
 
 #[rstest]
-fn opening_without_password_errs(mut repo_store: RepoStore) -> anyhow::Result<()> {
-    repo_store.config.encryption = Encryption::XChaCha20Poly1305;
+#[case::xchacha20_poly1305(Encryption::XChaCha20Poly1305)]
+#[case::aes_256_gcm(Encryption::Aes256Gcm)]
+fn opening_without_password_errs(
+    #[case] encryption: Encryption,
+    mut repo_store: RepoStore,
+) -> anyhow::Result<()> {
+    repo_store.config.encryption = encryption;
     repo_store.create::<KeyRepo<String>>()?;
     assert_that!(OpenOptions::new().open::<KeyRepo<String>, _>(&repo_store.store))
         .is_err_variant(acid_store::Error::Password);
@@ -0,0 +1,170 @@
+#![cfg(all(
+    feature = "encryption",
+    feature = "compression",
+    feature = "store-postgres"
+))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::thread;
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{BlockKey, BlockType, DataStore, OpenStore, PostgresConfig};
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+#[rstest]
+fn blocks_persist_between_connections() -> anyhow::Result<()> {
+    let database = PostgresDatabase::create();
+    let id = Uuid::new_v4().into();
+
+    let mut store = database.config().open()?;
+    assert_that!(store.write_block(BlockKey::Data(id), b"data")).is_ok();
+    assert_that!(store.write_block(BlockKey::Super, b"super")).is_ok();
+    drop(store);
+
+    let mut store = database.config().open()?;
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(b"data".to_vec()));
+    assert_that!(store.read_block(BlockKey::Super)).is_ok_containing(Some(b"super".to_vec()));
+    assert_that!(store.list_blocks(BlockType::Data)).is_ok_containing(vec![id]);
+
+    Ok(())
+}
+
+#[rstest]
+fn unsupported_version_errs() -> anyhow::Result<()> {
+    let database = PostgresDatabase::create();
+    database.config().open()?;
+
+    database.connect().execute(
+        "UPDATE Metadata SET value = $1 WHERE key = 'version';",
+        &[&&Uuid::new_v4().as_bytes()[..]],
+    )?;
+
+    assert_that!(database.config().open()).is_err_variant(acid_store::Error::UnsupportedStore);
+
+    Ok(())
+}
+
+#[rstest]
+fn stores_can_be_opened_concurrently() {
+    let database = PostgresDatabase::create();
+
+    let handles = (0..8)
+        .map(|_| {
+            let config = database.config();
+            thread::spawn(move || config.open().map(drop))
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        assert_that!(handle.join().unwrap()).is_ok();
+    }
+}
+
+#[rstest]
+fn exactly_one_exclusive_create_succeeds() {
+    let database = PostgresDatabase::create();
+    let id = Uuid::new_v4().into();
+
+    let handles = (0..8)
+        .map(|_| {
+            let mut store = database.config().open().unwrap();
+            thread::spawn(move || {
+                store
+                    .create_block_exclusive(BlockKey::Lock(id), b"lock")
+                    .unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let created = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .filter(|created| *created)
+        .count();
+
+    assert_that!(created).is_equal_to(1);
+}
+
+#[rstest]
+fn concurrent_conditional_writes_are_not_lost() {
+    let database = PostgresDatabase::create();
+    let mut store = database.config().open().unwrap();
+    assert_that!(store.write_block(BlockKey::Super, &0u32.to_be_bytes())).is_ok();
+
+    // Each thread increments a counter using compare-and-swap until it succeeds.
+    let handles = (0..4)
+        .map(|_| {
+            let mut store = database.config().open().unwrap();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    loop {
+                        let current = store.read_block(BlockKey::Super).unwrap().unwrap();
+                        let count = u32::from_be_bytes(current.as_slice().try_into().unwrap());
+                        let next = (count + 1).to_be_bytes();
+                        if store
+                            .write_block_if(BlockKey::Super, Some(&current), &next)
+                            .unwrap()
+                        {
+                            break;
+                        }
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_that!(store.read_block(BlockKey::Super))
+        .is_ok_containing(Some(40u32.to_be_bytes().to_vec()));
+}
+
+#[rstest]
+fn repo_lock_is_shared_between_clients(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let database = PostgresDatabase::create();
+    let config = database.config();
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(fixed_packing_small_config())
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+
+    assert_that!(OpenOptions::new().open::<KeyRepo<String>, _>(&config))
+        .is_err_variant(acid_store::Error::Locked);
+
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new().open(&config)?;
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+    assert_that!(actual).is_equal_to(buffer);
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+
+    Ok(())
+}
+
+#[rstest]
+fn connection_errors_are_retryable() {
+    // Nothing listens on this port, so the connection is refused.
+    let config = PostgresConfig {
+        config: String::from("host=127.0.0.1 port=1 user=postgres"),
+    };
+
+    match config.open() {
+        Err(acid_store::Error::Store(error)) => assert_that!(error.is_retryable()).is_true(),
+        other => panic!("Expected a data store error, got {:?}.", other.err()),
+    }
+}
@@ -0,0 +1,134 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use acid_store::store::{
+    BlockKey, BlockType, DataStore, MemoryConfig, OpenStore, RateLimitConfig, RateLimiter,
+};
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// The size of the blocks to write in these tests.
+const BLOCK_SIZE: usize = 10_000;
+
+/// Open a data store whose rate is limited by `limiter`.
+fn limited_store(limiter: &RateLimiter) -> impl DataStore {
+    RateLimitConfig {
+        inner: MemoryConfig::new(),
+        limiter: limiter.clone(),
+    }
+    .open()
+    .unwrap()
+}
+
+/// Write `count` blocks of `BLOCK_SIZE` bytes to `store` and return how long it took.
+fn time_writes(store: &mut impl DataStore, count: usize) -> Duration {
+    let data = vec![0u8; BLOCK_SIZE];
+    let start = Instant::now();
+    for _ in 0..count {
+        store
+            .write_block(BlockKey::Data(Uuid::new_v4().into()), &data)
+            .unwrap();
+    }
+    start.elapsed()
+}
+
+#[rstest]
+fn limits_can_be_read() {
+    let limiter = RateLimiter::new();
+    limiter.set_read_limit(Some(1000));
+    limiter.set_write_limit(Some(2000));
+
+    assert_that!(limiter.read_limit()).is_equal_to(Some(1000));
+    assert_that!(limiter.write_limit()).is_equal_to(Some(2000));
+    assert_that!(limiter.request_limit()).is_none();
+}
+
+#[rstest]
+fn unlimited_store_does_not_wait() {
+    let mut store = limited_store(&RateLimiter::new());
+
+    assert_that!(time_writes(&mut store, 20)).is_less_than(Duration::from_millis(500));
+}
+
+#[rstest]
+fn writes_are_limited() {
+    let limiter = RateLimiter::new();
+    limiter.set_write_limit(Some(10 * BLOCK_SIZE as u64));
+    let mut store = limited_store(&limiter);
+
+    // The first second's worth of writes is allowed as a burst.
+    assert_that!(time_writes(&mut store, 15))
+        .is_greater_than_or_equal_to(Duration::from_millis(400));
+}
+
+#[rstest]
+fn reads_are_limited() {
+    let limiter = RateLimiter::new();
+    let mut store = limited_store(&limiter);
+    let id = Uuid::new_v4().into();
+    assert_that!(store.write_block(BlockKey::Data(id), &vec![0u8; BLOCK_SIZE])).is_ok();
+    limiter.set_read_limit(Some(10 * BLOCK_SIZE as u64));
+
+    let start = Instant::now();
+    for _ in 0..15 {
+        assert_that!(store.read_block(BlockKey::Data(id))).is_ok();
+    }
+
+    assert_that!(start.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(400));
+}
+
+#[rstest]
+fn requests_are_limited() {
+    let limiter = RateLimiter::new();
+    limiter.set_request_limit(Some(10));
+    let mut store = limited_store(&limiter);
+
+    let start = Instant::now();
+    for _ in 0..15 {
+        assert_that!(store.list_blocks(BlockType::Data)).is_ok();
+    }
+
+    assert_that!(start.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(400));
+}
+
+#[rstest]
+fn limits_are_shared_between_stores() {
+    let limiter = RateLimiter::new();
+    limiter.set_write_limit(Some(10 * BLOCK_SIZE as u64));
+    let mut first = limited_store(&limiter);
+    let mut second = limited_store(&limiter);
+
+    let elapsed = time_writes(&mut first, 8) + time_writes(&mut second, 8);
+
+    assert_that!(elapsed).is_greater_than_or_equal_to(Duration::from_millis(500));
+}
+
+#[rstest]
+fn limits_can_be_changed_from_another_thread() {
+    let limiter = RateLimiter::new();
+    limiter.set_write_limit(Some(BLOCK_SIZE as u64));
+    let mut store = limited_store(&limiter);
+
+    let handle = {
+        let limiter = limiter.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            limiter.set_write_limit(None);
+        })
+    };
+
+    // Without changing the limit, this would take about 10 seconds.
+    assert_that!(time_writes(&mut store, 11)).is_less_than(Duration::from_secs(5));
+    handle.join().unwrap();
+}
+
+#[test]
+#[should_panic]
+fn zero_limit_panics() {
+    RateLimiter::new().set_request_limit(Some(0));
+}
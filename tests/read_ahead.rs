@@ -0,0 +1,155 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, ReadOnlyObject};
+use common::*;
+use rstest_reuse::{self, *};
+
+mod common;
+
+/// The number of chunks to read ahead for testing.
+const READ_AHEAD: usize = 4;
+
+/// The size of the test data, which spans many chunks.
+const DATA_SIZE: usize = 1024 * 16;
+
+/// Create a repository which reads ahead and write the given `data` to a new object in it.
+fn write_object(repo_store: &mut RepoStore, data: &[u8]) -> anyhow::Result<KeyRepo<String>> {
+    repo_store.read_ahead = READ_AHEAD;
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut object = repo.insert(String::from("test"));
+    object.write_all(data)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    Ok(repo)
+}
+
+#[apply(store_config)]
+fn read_data_sequentially(
+    #[case] mut repo_store: RepoStore,
+    #[with(DATA_SIZE)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo = write_object(&mut repo_store, &fixed_buffer)?;
+    let mut object = repo.object("test").unwrap();
+    let mut actual_data = Vec::new();
+
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn read_data_in_small_reads(
+    #[case] mut repo_store: RepoStore,
+    #[with(DATA_SIZE)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo = write_object(&mut repo_store, &fixed_buffer)?;
+    let mut object = repo.object("test").unwrap();
+    let mut actual_data = Vec::new();
+    let mut buffer = [0u8; 100];
+
+    loop {
+        let bytes_read = object.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        actual_data.extend_from_slice(&buffer[..bytes_read]);
+    }
+
+    assert_that!(&actual_data).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn seek_and_read_data(
+    #[case] mut repo_store: RepoStore,
+    #[with(DATA_SIZE)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo = write_object(&mut repo_store, &fixed_buffer)?;
+    let mut object = repo.object("test").unwrap();
+    let mut buffer = vec![0u8; 1024];
+
+    // Start reading sequentially so that chunks are read ahead, then seek elsewhere.
+    object.read_exact(&mut buffer)?;
+    assert_that!(&buffer.as_slice()).is_equal_to(&fixed_buffer[..1024]);
+
+    object.seek(SeekFrom::Start(8192))?;
+    object.read_exact(&mut buffer)?;
+    assert_that!(&buffer.as_slice()).is_equal_to(&fixed_buffer[8192..9216]);
+
+    object.seek(SeekFrom::Start(2048))?;
+    object.read_exact(&mut buffer)?;
+    assert_that!(&buffer.as_slice()).is_equal_to(&fixed_buffer[2048..3072]);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn read_data_from_read_only_object(
+    #[case] mut repo_store: RepoStore,
+    #[with(DATA_SIZE)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo = write_object(&mut repo_store, &fixed_buffer)?;
+    let mut object = ReadOnlyObject::try_from(repo.object("test").unwrap())?;
+    let mut actual_data = Vec::new();
+
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn read_data_after_overwriting_it(
+    #[case] mut repo_store: RepoStore,
+    #[with(DATA_SIZE)] fixed_buffer: Vec<u8>,
+    #[from(fixed_buffer)]
+    #[with(1024)]
+    new_data: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo = write_object(&mut repo_store, &fixed_buffer)?;
+    let mut object = repo.object("test").unwrap();
+    let mut actual_data = Vec::new();
+
+    // Read part of the object so that chunks are read ahead before the object is modified.
+    object.read_exact(&mut vec![0u8; 1024])?;
+    object.seek(SeekFrom::Start(4096))?;
+    object.write_all(&new_data)?;
+    object.commit()?;
+    object.seek(SeekFrom::Start(0))?;
+    object.read_to_end(&mut actual_data)?;
+
+    let mut expected_data = fixed_buffer.clone();
+    expected_data[4096..5120].copy_from_slice(&new_data);
+
+    assert_that!(&actual_data).is_equal_to(&expected_data);
+
+    Ok(())
+}
+
+#[apply(store_config)]
+fn read_data_from_reopened_repo(
+    #[case] mut repo_store: RepoStore,
+    #[with(DATA_SIZE)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo = write_object(&mut repo_store, &fixed_buffer)?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open_read_only()?;
+    let mut object = repo.object("test").unwrap();
+    let mut actual_data = Vec::new();
+
+    object.read_to_end(&mut actual_data)?;
+
+    assert_that!(&actual_data).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
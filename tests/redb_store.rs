@@ -0,0 +1,214 @@
+#![cfg(all(
+    feature = "encryption",
+    feature = "compression",
+    feature = "store-redb"
+))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, MemoryStore, OpenStore, RedbConfig,
+};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// Return a config for a data store in the given temporary `directory`.
+fn redb_config_in(directory: &TempDir) -> RedbConfig {
+    RedbConfig {
+        path: directory.path().join("store.redb"),
+    }
+}
+
+/// A data store which fails to read blocks after a number of successful reads.
+#[derive(Debug)]
+struct FailingReads {
+    inner: MemoryStore,
+    successful_reads: usize,
+}
+
+impl DataStore for FailingReads {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        if self.successful_reads == 0 {
+            return Err(acid_store::store::Error::msg("Injected read failure."));
+        }
+        self.successful_reads -= 1;
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.inner.list_blocks(kind)
+    }
+}
+
+#[rstest]
+fn blocks_persist_after_reopening(temp_dir: TempDir) -> anyhow::Result<()> {
+    let config = redb_config_in(&temp_dir);
+    let id = Uuid::new_v4().into();
+
+    let mut store = config.open()?;
+    assert_that!(store.write_block(BlockKey::Data(id), b"data")).is_ok();
+    assert_that!(store.write_block(BlockKey::Super, b"super")).is_ok();
+    drop(store);
+
+    let mut store = config.open()?;
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(b"data".to_vec()));
+    assert_that!(store.read_block(BlockKey::Super)).is_ok_containing(Some(b"super".to_vec()));
+    assert_that!(store.list_blocks(BlockType::Data)).is_ok_containing(vec![id]);
+
+    Ok(())
+}
+
+#[rstest]
+fn stores_opened_from_same_path_share_blocks(temp_dir: TempDir) -> anyhow::Result<()> {
+    let config = redb_config_in(&temp_dir);
+    let mut first = config.open()?;
+    let mut second = config.open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(first.create_block_exclusive(BlockKey::Lock(id), b"first")).is_ok_containing(true);
+    assert_that!(second.create_block_exclusive(BlockKey::Lock(id), b"second"))
+        .is_ok_containing(false);
+    assert_that!(second.read_block(BlockKey::Lock(id))).is_ok_containing(Some(b"first".to_vec()));
+
+    Ok(())
+}
+
+#[rstest]
+fn other_files_are_unsupported(temp_dir: TempDir) -> anyhow::Result<()> {
+    let path = temp_dir.path().join("store.redb");
+    std::fs::write(&path, b"not a database")?;
+
+    assert_that!(RedbConfig { path }.open()).is_err();
+
+    Ok(())
+}
+
+#[rstest]
+fn copy_from_copies_every_block(temp_dir: TempDir) -> anyhow::Result<()> {
+    let mut source = MemoryConfig::new().open()?;
+    let data_ids = (0..10)
+        .map(|_| Uuid::new_v4().into())
+        .collect::<HashSet<_>>();
+    for id in &data_ids {
+        assert_that!(source.write_block(BlockKey::Data(*id), b"data")).is_ok();
+    }
+    let lock_id = Uuid::new_v4().into();
+    let header_id = Uuid::new_v4().into();
+    assert_that!(source.write_block(BlockKey::Lock(lock_id), b"lock")).is_ok();
+    assert_that!(source.write_block(BlockKey::Header(header_id), b"header")).is_ok();
+    assert_that!(source.write_block(BlockKey::Super, b"super")).is_ok();
+    assert_that!(source.write_block(BlockKey::Version, b"version")).is_ok();
+
+    let mut destination = redb_config_in(&temp_dir).open()?;
+
+    assert_that!(destination.copy_from(&mut source)).is_ok_containing(14);
+    let actual_ids = destination
+        .list_blocks(BlockType::Data)
+        .unwrap()
+        .into_iter()
+        .collect::<HashSet<_>>();
+    assert_that!(actual_ids).is_equal_to(&data_ids);
+    assert_that!(destination.read_block(BlockKey::Lock(lock_id)))
+        .is_ok_containing(Some(b"lock".to_vec()));
+    assert_that!(destination.read_block(BlockKey::Header(header_id)))
+        .is_ok_containing(Some(b"header".to_vec()));
+    assert_that!(destination.read_block(BlockKey::Super)).is_ok_containing(Some(b"super".to_vec()));
+    assert_that!(destination.read_block(BlockKey::Version))
+        .is_ok_containing(Some(b"version".to_vec()));
+
+    Ok(())
+}
+
+#[rstest]
+fn failed_copy_leaves_store_unchanged(temp_dir: TempDir) -> anyhow::Result<()> {
+    let mut source = FailingReads {
+        inner: MemoryConfig::new().open()?,
+        successful_reads: 3,
+    };
+    for _ in 0..5 {
+        let id = Uuid::new_v4().into();
+        assert_that!(source.write_block(BlockKey::Data(id), b"data")).is_ok();
+    }
+
+    let mut destination = redb_config_in(&temp_dir).open()?;
+
+    assert_that!(destination.copy_from(&mut source)).is_err();
+    assert_that!(destination.list_blocks(BlockType::Data)).is_ok_containing(Vec::new());
+
+    Ok(())
+}
+
+#[cfg(feature = "store-directory")]
+#[rstest]
+fn repo_can_be_converted_from_directory_store(
+    temp_dir: TempDir,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    use acid_store::store::DirectoryConfig;
+
+    let directory_config = DirectoryConfig {
+        path: temp_dir.path().join("store"),
+    };
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(fixed_packing_small_config())
+        .mode(OpenMode::CreateNew)
+        .open(&directory_config)?;
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let redb_config = redb_config_in(&temp_dir);
+    let mut destination = redb_config.open()?;
+    assert_that!(destination.copy_from(&mut directory_config.open()?)).is_ok();
+    drop(destination);
+
+    let repo: KeyRepo<String> = OpenOptions::new().open(&redb_config)?;
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+    assert_that!(actual).is_equal_to(buffer);
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_can_use_worker_threads(temp_dir: TempDir, buffer: Vec<u8>) -> anyhow::Result<()> {
+    let config = redb_config_in(&temp_dir);
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(fixed_config())
+        .write_concurrency(4)
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new().open(&config)?;
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+    assert_that!(actual).is_equal_to(buffer);
+
+    Ok(())
+}
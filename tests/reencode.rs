@@ -0,0 +1,331 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{
+    Chunking, Commit, OpenMode, OpenOptions, RepoConfig, SwitchInstance, DEFAULT_INSTANCE,
+};
+use acid_store::store::{FaultConfig, FaultInjector, MemoryConfig};
+use acid_store::uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// Read the contents of the object with the given `key`.
+fn read_key(repo: &KeyRepo<String>, key: &str) -> anyhow::Result<Vec<u8>> {
+    let mut object = repo.object(key).unwrap();
+    let mut contents = Vec::new();
+    object.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Write `data` to a new object with the given `key`.
+fn write_key(repo: &mut KeyRepo<String>, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    let mut object = repo.insert(key.to_string());
+    object.write_all(data)?;
+    object.commit()?;
+    Ok(())
+}
+
+/// Return a config which encrypts and compresses data and uses FastCDC chunking.
+fn fastcdc_encoding_config() -> RepoConfig {
+    let mut config = encoding_config();
+    config.chunking = fastcdc_config().chunking;
+    config
+}
+
+#[rstest]
+#[case::add_encoding(fixed_config(), encoding_config())]
+#[case::remove_encoding(encoding_config(), fixed_config())]
+#[case::change_encryption(encoding_config(), aes_encoding_config())]
+#[case::change_chunking(fixed_config(), fastcdc_config())]
+#[case::add_packing(fixed_config(), fixed_packing_small_config())]
+#[case::remove_packing(fixed_packing_large_config(), fixed_config())]
+#[case::change_everything(fixed_packing_small_config(), fastcdc_encoding_config())]
+fn data_persists_after_reencode(
+    #[case] old_config: RepoConfig,
+    #[case] new_config: RepoConfig,
+    #[from(buffer)] committed_data: Vec<u8>,
+    #[from(buffer)] uncommitted_data: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo_store = RepoStore::new(old_config);
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_key(&mut repo, "committed", &committed_data)?;
+    repo.commit()?;
+    write_key(&mut repo, "uncommitted", &uncommitted_data)?;
+
+    repo.reencode(
+        new_config.clone(),
+        Some(repo_store.password.as_bytes()),
+        |_| {},
+    )?;
+
+    assert_that!(read_key(&repo, "committed")?).is_equal_to(&committed_data);
+    assert_that!(read_key(&repo, "uncommitted")?).is_equal_to(&uncommitted_data);
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let info = repo.info();
+    assert_that!(info.config().chunking).is_equal_to(new_config.chunking);
+    assert_that!(info.config().packing).is_equal_to(new_config.packing);
+    assert_that!(info.config().compression).is_equal_to(new_config.compression);
+    assert_that!(info.config().encryption).is_equal_to(new_config.encryption);
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+    assert_that!(read_key(&repo, "committed")?).is_equal_to(&committed_data);
+    assert_that!(read_key(&repo, "uncommitted")?).is_equal_to(&uncommitted_data);
+
+    Ok(())
+}
+
+#[rstest]
+#[case::same_chunking(encoding_config())]
+#[case::change_chunking(fastcdc_encoding_config())]
+fn other_instances_are_reencoded(
+    #[case] new_config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo_store = RepoStore::new(fixed_config());
+    let repo: KeyRepo<String> = repo_store.create()?;
+    let instance_id = Uuid::new_v4().into();
+    let mut repo: KeyRepo<String> = repo.switch_instance(instance_id)?;
+    write_key(&mut repo, "test", &buffer)?;
+    let mut repo: KeyRepo<String> = repo.switch_instance(DEFAULT_INSTANCE)?;
+    repo.commit()?;
+
+    repo.reencode(new_config, Some(repo_store.password.as_bytes()), |_| {})?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    let repo: KeyRepo<String> = repo.switch_instance(instance_id)?;
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+    assert_that!(read_key(&repo, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+#[case::same_chunking(encoding_config())]
+#[case::change_chunking(fastcdc_encoding_config())]
+fn snapshots_and_commits_survive_reencode(
+    #[case] new_config: RepoConfig,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let repo_store = RepoStore::new(fixed_config());
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_key(&mut repo, "test", &buffer)?;
+    repo.create_snapshot("snapshot")?;
+    repo.commit()?;
+    let first_commit = repo.commits()[0].id();
+    repo.remove("test");
+    repo.commit()?;
+
+    repo.reencode(new_config, Some(repo_store.password.as_bytes()), |_| {})?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = repo_store.open()?;
+    assert_that!(repo.contains("test")).is_false();
+    assert_that!(repo.commits()).has_length(3);
+
+    let snapshot = repo.open_snapshot("snapshot")?;
+    assert_that!(read_key(&snapshot, "test")?).is_equal_to(&buffer);
+
+    let old_repo = repo.open_commit(first_commit)?;
+    assert_that!(read_key(&old_repo, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn old_data_is_reclaimed_on_clean(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let repo_store = RepoStore::new(fixed_config());
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_key(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+
+    repo.reencode(
+        fastcdc_encoding_config(),
+        Some(repo_store.password.as_bytes()),
+        |_| {},
+    )?;
+    repo.clean()?;
+
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+    assert_that!(read_key(&repo, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn progress_is_reported(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let repo_store = RepoStore::new(fixed_config());
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_key(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+
+    let mut reported = Vec::new();
+    let report = repo.reencode(
+        encoding_config(),
+        Some(repo_store.password.as_bytes()),
+        |progress| reported.push(progress),
+    )?;
+
+    let last_progress = reported.last().unwrap();
+    assert_that!(last_progress.total_bytes()).is_greater_than_or_equal_to(buffer.len() as u64);
+    assert_that!(last_progress.processed_bytes()).is_equal_to(last_progress.total_bytes());
+    assert_that!(report.before().apparent_size()).is_equal_to(buffer.len() as u64);
+    assert_that!(report.after().apparent_size()).is_equal_to(buffer.len() as u64);
+    assert_that!(report.resumed_bytes()).is_equal_to(0);
+
+    Ok(())
+}
+
+#[rstest]
+fn changing_encryption_requires_password(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let repo_store = RepoStore::new(encoding_config());
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    write_key(&mut repo, "test", &buffer)?;
+    repo.commit()?;
+
+    assert_that!(repo.reencode(fixed_config(), None, |_| {}))
+        .is_err_variant(acid_store::Error::Password);
+    assert_that!(repo.reencode(fixed_config(), Some(b"wrong password"), |_| {}))
+        .is_err_variant(acid_store::Error::Password);
+    assert_that!(read_key(&repo, "test")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn reencode_with_invalid_compression_threshold_errs(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut config = encoding_config();
+    config.compression_threshold = 101;
+
+    assert_that!(repo.reencode(config, Some(repo_store.password.as_bytes()), |_| {}))
+        .is_err_variant(acid_store::Error::InvalidConfig);
+
+    Ok(())
+}
+
+#[rstest]
+fn reencode_with_invalid_fastcdc_sizes_errs(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    let mut config = encoding_config();
+    config.chunking = Chunking::FastCdc {
+        min: 4096,
+        avg: 1024,
+        max: 8192,
+    };
+
+    assert_that!(repo.reencode(config, Some(repo_store.password.as_bytes()), |_| {}))
+        .is_err_variant(acid_store::Error::InvalidConfig);
+
+    Ok(())
+}
+
+#[rstest]
+fn reencode_errs_while_other_client_has_repo_open(repo_store: RepoStore) -> anyhow::Result<()> {
+    let mut repo: KeyRepo<String> = repo_store.create()?;
+    repo.commit()?;
+    let _read_only_repo: KeyRepo<String> = repo_store.open_read_only()?;
+
+    assert_that!(repo.reencode(fastcdc_config(), None, |_| {}))
+        .is_err_variant(acid_store::Error::Locked);
+
+    Ok(())
+}
+
+/// Write `data` to a new repository and interrupt re-encoding it, returning its data store.
+fn interrupt_reencode(data: &[u8]) -> anyhow::Result<MemoryConfig> {
+    let store = MemoryConfig::new();
+    let fault_config = FaultConfig {
+        inner: store.clone(),
+        injector: FaultInjector::new(),
+    };
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(fixed_config())
+        .mode(OpenMode::CreateNew)
+        .open(&fault_config)?;
+    write_key(&mut repo, "test", data)?;
+    repo.commit()?;
+
+    // Crash the data store once most of the data has been re-encoded.
+    let result = repo.reencode(encoding_config(), Some(b"password"), |progress| {
+        if progress.processed_bytes() * 4 >= progress.total_bytes() * 3 {
+            let injector = &fault_config.injector;
+            injector.crash_at(injector.operations());
+        }
+    });
+    assert_that!(result.is_err()).is_true();
+
+    Ok(store)
+}
+
+#[rstest]
+fn interrupted_reencode_is_resumed(
+    #[with(256 * 2048)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store = interrupt_reencode(&fixed_buffer)?;
+
+    // The crashed repository never released its lock.
+    let mut repo: KeyRepo<String> = OpenOptions::new().locking(&[], |_| true).open(&store)?;
+    let report = repo.reencode(encoding_config(), Some(b"password"), |_| {})?;
+    drop(repo);
+
+    assert_that!(report.resumed_bytes()).is_greater_than(0);
+
+    let repo: KeyRepo<String> = OpenOptions::new().password(b"password").open(&store)?;
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+    assert_that!(read_key(&repo, "test")?).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn interrupted_reencode_is_resumed_after_commit(
+    #[with(256 * 2048)] fixed_buffer: Vec<u8>,
+    buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store = interrupt_reencode(&fixed_buffer)?;
+
+    let mut repo: KeyRepo<String> = OpenOptions::new().locking(&[], |_| true).open(&store)?;
+    write_key(&mut repo, "other", &buffer)?;
+    repo.commit()?;
+    drop(repo);
+
+    let mut repo: KeyRepo<String> = OpenOptions::new().open(&store)?;
+    let report = repo.reencode(encoding_config(), Some(b"password"), |_| {})?;
+    drop(repo);
+
+    assert_that!(report.resumed_bytes()).is_greater_than(0);
+
+    let repo: KeyRepo<String> = OpenOptions::new().password(b"password").open(&store)?;
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+    assert_that!(read_key(&repo, "test")?).is_equal_to(&fixed_buffer);
+    assert_that!(read_key(&repo, "other")?).is_equal_to(&buffer);
+
+    Ok(())
+}
+
+#[rstest]
+fn interrupted_reencode_is_discarded_on_clean(
+    #[with(256 * 2048)] fixed_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let store = interrupt_reencode(&fixed_buffer)?;
+
+    let mut repo: KeyRepo<String> = OpenOptions::new().locking(&[], |_| true).open(&store)?;
+    repo.clean()?;
+    let report = repo.reencode(encoding_config(), Some(b"password"), |_| {})?;
+    drop(repo);
+
+    assert_that!(report.resumed_bytes()).is_equal_to(0);
+
+    let repo: KeyRepo<String> = OpenOptions::new().password(b"password").open(&store)?;
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+    assert_that!(read_key(&repo, "test")?).is_equal_to(&fixed_buffer);
+
+    Ok(())
+}
@@ -0,0 +1,286 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, ConditionalWrites, DataStore, Error, MemoryConfig, MemoryStore,
+    OpenStore, RetryConfig, RetryPolicy, RetryStore,
+};
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// A data store which fails a number of times before each operation succeeds.
+#[derive(Debug)]
+struct FlakyStore {
+    inner: MemoryStore,
+
+    /// The number of times to fail before each operation succeeds.
+    failures: u32,
+
+    /// Whether the errors returned by this store are retryable.
+    retryable: bool,
+
+    /// Whether conditional writes can fail.
+    flaky_conditional_writes: bool,
+
+    /// The number of times an operation has failed since one last succeeded.
+    failed: u32,
+
+    /// The total number of operations which were attempted.
+    attempts: u32,
+}
+
+impl FlakyStore {
+    fn new(failures: u32, retryable: bool) -> Self {
+        Self {
+            inner: MemoryConfig::new().open().unwrap(),
+            failures,
+            retryable,
+            flaky_conditional_writes: true,
+            failed: 0,
+            attempts: 0,
+        }
+    }
+
+    /// Return an error if this operation should fail.
+    fn attempt(&mut self) -> acid_store::store::Result<()> {
+        self.attempts += 1;
+        if self.failed < self.failures {
+            self.failed += 1;
+            return Err(if self.retryable {
+                Error::retryable_msg("The connection was interrupted.")
+            } else {
+                Error::msg("Access was denied.")
+            });
+        }
+        self.failed = 0;
+        Ok(())
+    }
+}
+
+impl DataStore for FlakyStore {
+    fn write_block(&mut self, key: BlockKey, data: &[u8]) -> acid_store::store::Result<()> {
+        self.attempt()?;
+        self.inner.write_block(key, data)
+    }
+
+    fn read_block(&mut self, key: BlockKey) -> acid_store::store::Result<Option<Vec<u8>>> {
+        self.attempt()?;
+        self.inner.read_block(key)
+    }
+
+    fn remove_block(&mut self, key: BlockKey) -> acid_store::store::Result<()> {
+        self.attempt()?;
+        self.inner.remove_block(key)
+    }
+
+    fn list_blocks(&mut self, kind: BlockType) -> acid_store::store::Result<Vec<BlockId>> {
+        self.attempt()?;
+        self.inner.list_blocks(kind)
+    }
+
+    fn write_block_if(
+        &mut self,
+        key: BlockKey,
+        expected: Option<&[u8]>,
+        data: &[u8],
+    ) -> acid_store::store::Result<bool> {
+        if self.flaky_conditional_writes {
+            self.attempt()?;
+        }
+        self.inner.write_block_if(key, expected, data)
+    }
+
+    fn create_block_exclusive(
+        &mut self,
+        key: BlockKey,
+        data: &[u8],
+    ) -> acid_store::store::Result<bool> {
+        if self.flaky_conditional_writes {
+            self.attempt()?;
+        }
+        self.inner.create_block_exclusive(key, data)
+    }
+
+    fn conditional_writes(&self) -> ConditionalWrites {
+        self.inner.conditional_writes()
+    }
+}
+
+/// The configuration for opening a `FlakyStore` whose conditional writes never fail.
+#[derive(Debug)]
+struct FlakyConfig {
+    inner: MemoryConfig,
+    failures: u32,
+}
+
+impl OpenStore for FlakyConfig {
+    type Store = FlakyStore;
+
+    fn open(&self) -> acid_store::Result<Self::Store> {
+        Ok(FlakyStore {
+            inner: self.inner.open()?,
+            flaky_conditional_writes: false,
+            ..FlakyStore::new(self.failures, true)
+        })
+    }
+}
+
+/// Return a retry policy which retries `max_retries` times without waiting.
+fn policy(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        initial_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+        ..RetryPolicy::default()
+    }
+}
+
+#[rstest]
+fn retryable_errors_are_retried(buffer: Vec<u8>) {
+    let mut store = RetryStore::new(FlakyStore::new(3, true), policy(3));
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(buffer));
+    assert_that!(store.list_blocks(BlockType::Data)).is_ok_containing(vec![id]);
+    assert_that!(store.remove_block(BlockKey::Data(id))).is_ok();
+    assert_that!(store.into_inner().attempts).is_equal_to(16);
+}
+
+#[rstest]
+fn permanent_errors_are_not_retried(buffer: Vec<u8>) {
+    let mut store = RetryStore::new(FlakyStore::new(1, false), policy(3));
+    let id = Uuid::new_v4().into();
+
+    let result = store.write_block(BlockKey::Data(id), &buffer);
+
+    assert_that!(result.map_err(|error| error.is_retryable())).is_err_containing(false);
+    assert_that!(store.into_inner().attempts).is_equal_to(1);
+}
+
+#[rstest]
+fn retrying_gives_up_after_max_retries(buffer: Vec<u8>) {
+    let mut store = RetryStore::new(FlakyStore::new(4, true), policy(3));
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_err();
+    assert_that!(store.into_inner().attempts).is_equal_to(4);
+}
+
+#[rstest]
+fn conditional_writes_are_not_retried(buffer: Vec<u8>) {
+    let mut store = RetryStore::new(FlakyStore::new(1, true), policy(3));
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.create_block_exclusive(BlockKey::Data(id), &buffer)).is_err();
+    assert_that!(store.write_block_if(BlockKey::Data(id), None, &buffer)).is_ok_containing(true);
+    assert_that!(store.into_inner().attempts).is_equal_to(2);
+}
+
+#[rstest]
+fn delays_grow_exponentially_up_to_max_delay(buffer: Vec<u8>) {
+    let policy = RetryPolicy {
+        max_retries: 3,
+        initial_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(20),
+        multiplier: 2.0,
+        jitter: 0.0,
+    };
+    let mut store = RetryStore::new(FlakyStore::new(3, true), policy);
+    let id = Uuid::new_v4().into();
+
+    let start = Instant::now();
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+
+    // The delays are 10ms, 20ms, and 20ms.
+    assert_that!(start.elapsed()).is_greater_than_or_equal_to(Duration::from_millis(50));
+}
+
+#[test]
+fn transient_io_errors_are_retryable() {
+    let timed_out = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+    let not_found = std::io::Error::new(std::io::ErrorKind::NotFound, "not found");
+
+    assert_that!(Error::from(timed_out).is_retryable()).is_true();
+    assert_that!(Error::from(not_found).is_retryable()).is_false();
+    assert_that!(Error::msg("message").is_retryable()).is_false();
+    assert_that!(Error::retryable_msg("message").is_retryable()).is_true();
+}
+
+/// An error which wraps another error as its source.
+#[derive(Debug)]
+struct WrappingError(std::io::Error);
+
+impl std::fmt::Display for WrappingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "wrapped: {}", self.0)
+    }
+}
+
+impl std::error::Error for WrappingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+#[test]
+fn errors_caused_by_transient_errors_are_retryable() {
+    let timed_out = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+    let not_found = std::io::Error::new(std::io::ErrorKind::NotFound, "not found");
+    let wrapped_reset = std::io::Error::new(
+        std::io::ErrorKind::Other,
+        std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset"),
+    );
+
+    assert_that!(Error::new(WrappingError(timed_out)).is_retryable()).is_true();
+    assert_that!(Error::new(WrappingError(not_found)).is_retryable()).is_false();
+    assert_that!(Error::new(wrapped_reset).is_retryable()).is_true();
+}
+
+#[cfg(feature = "store-sftp")]
+#[test]
+fn ssh_connection_errors_are_retryable() {
+    use ssh2::ErrorCode;
+
+    // LIBSSH2_ERROR_SOCKET_RECV
+    let socket_error = ssh2::Error::new(ErrorCode::Session(-43), "socket error");
+    // LIBSSH2_FX_CONNECTION_LOST
+    let connection_lost = ssh2::Error::new(ErrorCode::SFTP(7), "connection lost");
+    // LIBSSH2_FX_PERMISSION_DENIED
+    let permission_denied = ssh2::Error::new(ErrorCode::SFTP(3), "permission denied");
+
+    assert_that!(Error::new(socket_error).is_retryable()).is_true();
+    assert_that!(Error::new(connection_lost).is_retryable()).is_true();
+    assert_that!(Error::new(permission_denied).is_retryable()).is_false();
+}
+
+#[rstest]
+fn repo_can_be_used_with_flaky_store(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let config = RetryConfig {
+        inner: FlakyConfig {
+            inner: MemoryConfig::new(),
+            failures: 2,
+        },
+        policy: policy(2),
+    };
+    let mut repo: KeyRepo<String> = OpenOptions::new().mode(OpenMode::CreateNew).open(&config)?;
+
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+    assert_that!(actual).is_equal_to(buffer);
+
+    Ok(())
+}
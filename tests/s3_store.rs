@@ -0,0 +1,198 @@
+#![cfg(all(feature = "encryption", feature = "compression", feature = "store-s3"))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockKey, BlockType, DataStore, Error, OpenStore, RetryConfig, RetryPolicy, S3Region,
+};
+use s3::error::S3Error;
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+#[rstest]
+fn listing_follows_pagination() -> anyhow::Result<()> {
+    let server = S3Server::start();
+    server.set_page_size(3);
+    let mut store = server.config().open()?;
+
+    let mut expected = HashSet::new();
+    for _ in 0..10 {
+        let id = Uuid::new_v4().into();
+        assert_that!(store.write_block(BlockKey::Data(id), b"data")).is_ok();
+        expected.insert(id);
+    }
+    let id = Uuid::new_v4().into();
+    assert_that!(store.write_block(BlockKey::Lock(id), b"lock")).is_ok();
+
+    let actual = store
+        .list_blocks(BlockType::Data)
+        .unwrap()
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    assert_that!(actual).is_equal_to(expected);
+    assert_that!(server.list_requests()).is_equal_to(4);
+
+    Ok(())
+}
+
+#[rstest]
+fn missing_blocks_are_not_found() -> anyhow::Result<()> {
+    let server = S3Server::start();
+    let mut store = server.config().open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(None);
+    assert_that!(store.remove_block(BlockKey::Data(id))).is_ok();
+    assert_that!(store.write_block_if(BlockKey::Data(id), Some(b"expected"), b"data"))
+        .is_ok_containing(false);
+
+    Ok(())
+}
+
+#[rstest]
+fn large_blocks_are_stored() -> anyhow::Result<()> {
+    let server = S3Server::start();
+    let mut store = server.config().open()?;
+    let id = Uuid::new_v4().into();
+    let data = (0..16 * 1024 * 1024)
+        .map(|index| (index % 251) as u8)
+        .collect::<Vec<_>>();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &data)).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(data));
+
+    Ok(())
+}
+
+#[rstest]
+fn conditional_writes_are_atomic() -> anyhow::Result<()> {
+    let server = S3Server::start();
+    let mut store = server.config().open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.create_block_exclusive(BlockKey::Lock(id), b"first")).is_ok_containing(true);
+    assert_that!(store.create_block_exclusive(BlockKey::Lock(id), b"second"))
+        .is_ok_containing(false);
+    assert_that!(store.write_block_if(BlockKey::Lock(id), Some(b"first"), b"third"))
+        .is_ok_containing(true);
+    assert_that!(store.write_block_if(BlockKey::Lock(id), Some(b"first"), b"fourth"))
+        .is_ok_containing(false);
+    assert_that!(store.read_block(BlockKey::Lock(id))).is_ok_containing(Some(b"third".to_vec()));
+
+    Ok(())
+}
+
+#[rstest]
+fn server_errors_are_retryable() -> anyhow::Result<()> {
+    let server = S3Server::start();
+    let mut store = server.config().open()?;
+    let id = Uuid::new_v4().into();
+
+    server.fail_next(503);
+    let error = store.write_block(BlockKey::Data(id), b"data").unwrap_err();
+    assert_that!(error.is_retryable()).is_true();
+
+    server.fail_next(403);
+    let error = store.read_block(BlockKey::Data(id)).unwrap_err();
+    assert_that!(error.is_retryable()).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn connection_errors_are_retryable() {
+    let server = S3Server::start();
+    let mut config = server.config();
+    // Nothing listens on this port, so the connection is refused.
+    config.region = S3Region::Custom {
+        name: String::from("us-east-1"),
+        endpoint: String::from("http://127.0.0.1:1"),
+    };
+
+    match config.open() {
+        Err(acid_store::Error::Store(error)) => assert_that!(error.is_retryable()).is_true(),
+        other => panic!("Expected a data store error, got {:?}.", other.err()),
+    }
+}
+
+#[rstest]
+fn client_http_errors_are_mapped_by_status_code() {
+    let server_error = S3Error::Http(503, String::from("Slow Down"));
+    let client_error = S3Error::Http(404, String::from("Not Found"));
+
+    assert_that!(Error::new(server_error).is_retryable()).is_true();
+    assert_that!(Error::new(client_error).is_retryable()).is_false();
+}
+
+#[rstest]
+fn throttled_requests_are_retried() -> anyhow::Result<()> {
+    let server = S3Server::start();
+    let mut store = RetryConfig {
+        inner: server.config(),
+        policy: RetryPolicy {
+            initial_delay: Default::default(),
+            ..Default::default()
+        },
+    }
+    .open()?;
+    let id = Uuid::new_v4().into();
+
+    server.fail_next(429);
+    server.fail_next(500);
+    assert_that!(store.write_block(BlockKey::Data(id), b"data")).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(b"data".to_vec()));
+
+    Ok(())
+}
+
+#[rstest]
+fn stores_with_different_prefixes_are_separate() -> anyhow::Result<()> {
+    let server = S3Server::start();
+    let mut first = server.config().open()?;
+    let mut config = server.config();
+    config.prefix = String::from("other");
+    let mut second = config.open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(first.write_block(BlockKey::Data(id), b"data")).is_ok();
+
+    assert_that!(second.read_block(BlockKey::Data(id))).is_ok_containing(None);
+    assert_that!(second.list_blocks(BlockType::Data)).is_ok_containing(Vec::new());
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_can_be_stored_in_s3(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let server = S3Server::start();
+    server.set_page_size(2);
+    let config = server.config();
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(fixed_packing_small_config())
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new().open(&config)?;
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+    assert_that!(actual).is_equal_to(buffer);
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+    assert_that!(server.object_count()).is_greater_than(2);
+
+    Ok(())
+}
@@ -0,0 +1,224 @@
+#![cfg(all(feature = "encryption", feature = "compression"))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockId, BlockKey, BlockType, DataStore, MemoryConfig, OpenStore, ShardedConfig, ShardedStore,
+};
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+/// The number of blocks to write when testing how blocks are distributed.
+const NUM_BLOCKS: usize = 64;
+
+/// Write `NUM_BLOCKS` small data blocks to `store` and return their IDs.
+fn write_blocks(store: &mut impl DataStore) -> Vec<BlockId> {
+    (0..NUM_BLOCKS)
+        .map(|index| {
+            let id = Uuid::new_v4().into();
+            store
+                .write_block(BlockKey::Data(id), &index.to_le_bytes())
+                .unwrap();
+            id
+        })
+        .collect()
+}
+
+/// Return the number of data blocks in the given `config`.
+fn count_blocks(config: &MemoryConfig) -> usize {
+    config
+        .open()
+        .unwrap()
+        .list_blocks(BlockType::Data)
+        .unwrap()
+        .len()
+}
+
+#[rstest]
+fn data_blocks_are_split_between_shards() -> anyhow::Result<()> {
+    let primary = MemoryConfig::new();
+    let first = MemoryConfig::new();
+    let second = MemoryConfig::new();
+    let mut store = ShardedConfig::new(primary.clone())
+        .shard(first.clone())
+        .shard(second.clone())
+        .open()?;
+
+    write_blocks(&mut store);
+
+    assert_that!(count_blocks(&primary)).is_equal_to(0);
+    assert_that!(count_blocks(&first)).is_greater_than(0);
+    assert_that!(count_blocks(&second)).is_greater_than(0);
+    assert_that!(count_blocks(&first) + count_blocks(&second)).is_equal_to(NUM_BLOCKS);
+
+    Ok(())
+}
+
+#[rstest]
+fn other_blocks_are_stored_in_primary(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let primary = MemoryConfig::new();
+    let shard = MemoryConfig::new();
+    let mut store = ShardedConfig::new(primary.clone())
+        .shard(shard.clone())
+        .open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Header(id), &buffer)).is_ok();
+    assert_that!(store.write_block(BlockKey::Super, &buffer)).is_ok();
+
+    let mut primary = primary.open()?;
+    let mut shard = shard.open()?;
+    assert_that!(primary.read_block(BlockKey::Header(id))).is_ok_containing(Some(buffer.clone()));
+    assert_that!(primary.read_block(BlockKey::Super)).is_ok_containing(Some(buffer));
+    assert_that!(shard.read_block(BlockKey::Header(id))).is_ok_containing(None);
+    assert_that!(shard.read_block(BlockKey::Super)).is_ok_containing(None);
+
+    Ok(())
+}
+
+#[rstest]
+fn list_blocks_merges_shards() -> anyhow::Result<()> {
+    let mut store = ShardedConfig::new(MemoryConfig::new())
+        .shard(MemoryConfig::new())
+        .shard(MemoryConfig::new())
+        .shard(MemoryConfig::new())
+        .open()?;
+
+    let expected = write_blocks(&mut store).into_iter().collect::<HashSet<_>>();
+    let actual = store
+        .list_blocks(BlockType::Data)
+        .unwrap()
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    assert_that!(actual).is_equal_to(expected);
+
+    Ok(())
+}
+
+#[rstest]
+fn blocks_can_be_read_before_rebalancing() -> anyhow::Result<()> {
+    let mut store = ShardedConfig::new(MemoryConfig::new())
+        .shard(MemoryConfig::new())
+        .open()?;
+    let block_ids = write_blocks(&mut store);
+
+    store.add_shard(Box::new(MemoryConfig::new().open()?));
+
+    for (index, id) in block_ids.into_iter().enumerate() {
+        assert_that!(store.read_block(BlockKey::Data(id)))
+            .is_ok_containing(Some(index.to_le_bytes().to_vec()));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn rebalancing_moves_blocks_to_new_shard() -> anyhow::Result<()> {
+    let first = MemoryConfig::new();
+    let second = MemoryConfig::new();
+    let mut store = ShardedConfig::new(MemoryConfig::new())
+        .shard(first.clone())
+        .open()?;
+    let block_ids = write_blocks(&mut store);
+
+    store.add_shard(Box::new(second.open()?));
+    let blocks_moved = store.rebalance().unwrap();
+
+    assert_that!(blocks_moved).is_greater_than(0);
+    assert_that!(blocks_moved).is_less_than(NUM_BLOCKS);
+    assert_that!(count_blocks(&second)).is_equal_to(blocks_moved);
+    assert_that!(count_blocks(&first) + count_blocks(&second)).is_equal_to(NUM_BLOCKS);
+    assert_that!(store.rebalance()).is_ok_containing(0);
+
+    for (index, id) in block_ids.into_iter().enumerate() {
+        assert_that!(store.read_block(BlockKey::Data(id)))
+            .is_ok_containing(Some(index.to_le_bytes().to_vec()));
+    }
+
+    Ok(())
+}
+
+#[rstest]
+fn rebalancing_keeps_newer_copies(
+    #[from(buffer)] old_buffer: Vec<u8>,
+    #[from(buffer)] new_buffer: Vec<u8>,
+) -> anyhow::Result<()> {
+    let mut store = ShardedStore::new(
+        Box::new(MemoryConfig::new().open()?),
+        vec![Box::new(MemoryConfig::new().open()?)],
+    );
+    store.add_shard(Box::new(MemoryConfig::new().open()?));
+
+    // Find a block which is assigned to the new shard, and write an old copy of it to the old
+    // shard like it was written before the new shard was added.
+    let id = loop {
+        let id = Uuid::new_v4().into();
+        assert_that!(store.write_block(BlockKey::Data(id), &new_buffer)).is_ok();
+        let (_, mut shards) = store.into_inner();
+        let assigned_to_new_shard = shards[1].read_block(BlockKey::Data(id)).unwrap().is_some();
+        if assigned_to_new_shard {
+            assert_that!(shards[0].write_block(BlockKey::Data(id), &old_buffer)).is_ok();
+        }
+        let primary = Box::new(MemoryConfig::new().open()?);
+        store = ShardedStore::new(primary, shards);
+        if assigned_to_new_shard {
+            break id;
+        }
+    };
+
+    assert_that!(store.rebalance()).is_ok_containing(0);
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(new_buffer));
+    assert_that!(store
+        .list_blocks(BlockType::Data)
+        .map(|ids| ids.contains(&id)))
+    .is_ok_containing(true);
+
+    Ok(())
+}
+
+#[test]
+#[should_panic]
+fn store_without_shards_panics() {
+    ShardedConfig::new(MemoryConfig::new()).open().ok();
+}
+
+#[rstest]
+fn repo_can_be_sharded_and_rebalanced(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let primary = MemoryConfig::new();
+    let first = MemoryConfig::new();
+    let second = MemoryConfig::new();
+    let config = ShardedConfig::new(primary.clone()).shard(first.clone());
+
+    // Use small chunks so that there are enough blocks for some to move to the new shard.
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(fixed_config())
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    drop(repo);
+
+    // Add a shard and rebalance the existing blocks.
+    let config = config.shard(second.clone());
+    let mut store = config.open()?;
+    assert_that!(store.rebalance()).is_ok();
+    assert_that!(count_blocks(&second)).is_greater_than(0);
+
+    let repo: KeyRepo<String> = OpenOptions::new().open(&config)?;
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+    assert_that!(actual).is_equal_to(buffer);
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+
+    Ok(())
+}
@@ -129,3 +129,20 @@ fn verify_valid_repository_is_valid(mut repo: ValueRepo<String>) -> anyhow::Resu
 
     Ok(())
 }
+
+#[rstest]
+fn snapshot_contains_values_from_when_it_was_created(
+    mut repo: ValueRepo<String>,
+) -> anyhow::Result<()> {
+    repo.insert("test".into(), &TEST_VALUE)?;
+    repo.create_snapshot("snapshot")?;
+    repo.remove("test");
+    repo.commit()?;
+
+    let snapshot = repo.open_snapshot("snapshot")?;
+
+    assert_that!(repo.contains("test")).is_false();
+    assert_that!(snapshot.get("test")).is_ok_containing(TEST_VALUE);
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+#![cfg(all(
+    feature = "encryption",
+    feature = "compression",
+    feature = "store-webdav"
+))]
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+use acid_store::repo::key::KeyRepo;
+use acid_store::repo::{Commit, OpenMode, OpenOptions};
+use acid_store::store::{
+    BlockKey, BlockType, DataStore, OpenStore, RetryConfig, RetryPolicy, WebDavCredentials,
+};
+use uuid::Uuid;
+
+use common::*;
+
+mod common;
+
+#[rstest]
+fn blocks_are_stored_like_directory_store() -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let mut store = server.config().open()?;
+    let uuid = Uuid::new_v4();
+    let uuid_str = uuid.as_hyphenated().to_string();
+
+    assert_that!(store.write_block(BlockKey::Data(uuid.into()), b"data")).is_ok();
+    assert_that!(store.write_block(BlockKey::Super, b"super")).is_ok();
+
+    assert_that!(server.files("/store")).is_equal_to(vec![
+        format!("/store/data/{}/{}", &uuid_str[..2], uuid_str),
+        String::from("/store/super"),
+    ]);
+    assert_that!(server.files("/stage")).is_equal_to(Vec::<String>::new());
+
+    Ok(())
+}
+
+#[rstest]
+fn blocks_persist_after_reopening() -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let config = server.config();
+    let id = Uuid::new_v4().into();
+
+    let mut store = config.open()?;
+    assert_that!(store.write_block(BlockKey::Data(id), b"data")).is_ok();
+    drop(store);
+
+    let mut store = config.open()?;
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(b"data".to_vec()));
+    assert_that!(store.list_blocks(BlockType::Data)).is_ok_containing(vec![id]);
+
+    Ok(())
+}
+
+#[rstest]
+fn missing_blocks_are_not_found() -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let mut store = server.config().open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(None);
+    assert_that!(store.remove_block(BlockKey::Data(id))).is_ok();
+    assert_that!(store.list_blocks(BlockType::Lock)).is_ok_containing(Vec::new());
+
+    Ok(())
+}
+
+#[rstest]
+fn block_size_does_not_download_block(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let mut store = server.config().open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(store.write_block(BlockKey::Data(id), &buffer)).is_ok();
+    let downloads = server.requests("GET");
+    assert_that!(store.block_size(BlockKey::Data(id))).is_ok_containing(Some(buffer.len() as u64));
+    assert_that!(store.block_size(BlockKey::Lock(id))).is_ok_containing(None);
+    assert_that!(server.requests("GET")).is_equal_to(downloads);
+
+    Ok(())
+}
+
+#[rstest]
+fn blocks_are_listed_by_type() -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let mut store = server.config().open()?;
+
+    let mut expected = HashSet::new();
+    for _ in 0..10 {
+        let id = Uuid::new_v4().into();
+        assert_that!(store.write_block(BlockKey::Data(id), b"data")).is_ok();
+        expected.insert(id);
+    }
+    let lock_id = Uuid::new_v4().into();
+    assert_that!(store.write_block(BlockKey::Lock(lock_id), b"lock")).is_ok();
+
+    let actual = store
+        .list_blocks(BlockType::Data)
+        .unwrap()
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    assert_that!(actual).is_equal_to(expected);
+    assert_that!(store.list_blocks(BlockType::Lock)).is_ok_containing(vec![lock_id]);
+    assert_that!(store.list_blocks(BlockType::Header)).is_ok_containing(Vec::new());
+
+    Ok(())
+}
+
+#[rstest]
+fn exclusive_creates_do_not_overwrite() -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let mut first = server.config().open()?;
+    let mut second = server.config().open()?;
+    let id = Uuid::new_v4().into();
+
+    assert_that!(first.create_block_exclusive(BlockKey::Lock(id), b"first")).is_ok_containing(true);
+    assert_that!(second.create_block_exclusive(BlockKey::Lock(id), b"second"))
+        .is_ok_containing(false);
+    assert_that!(second.read_block(BlockKey::Lock(id))).is_ok_containing(Some(b"first".to_vec()));
+    assert_that!(server.files("/stage")).is_equal_to(Vec::<String>::new());
+
+    Ok(())
+}
+
+#[rstest]
+fn wrong_credentials_are_rejected() -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let mut config = server.config();
+    config.credentials = WebDavCredentials::Basic {
+        username: String::from("user"),
+        password: String::from("wrong"),
+    };
+
+    assert_that!(config.open()).is_err();
+
+    config.credentials = WebDavCredentials::Anonymous;
+
+    assert_that!(config.open()).is_err();
+
+    Ok(())
+}
+
+#[rstest]
+fn server_errors_are_retryable() -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let mut store = server.config().open()?;
+    let id = Uuid::new_v4().into();
+
+    server.fail_next(503);
+    let error = store.write_block(BlockKey::Data(id), b"data").unwrap_err();
+    assert_that!(error.is_retryable()).is_true();
+
+    server.fail_next(403);
+    let error = store.read_block(BlockKey::Data(id)).unwrap_err();
+    assert_that!(error.is_retryable()).is_false();
+
+    Ok(())
+}
+
+#[rstest]
+fn throttled_requests_are_retried() -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let mut store = RetryConfig {
+        inner: server.config(),
+        policy: RetryPolicy {
+            initial_delay: Default::default(),
+            ..Default::default()
+        },
+    }
+    .open()?;
+    let id = Uuid::new_v4().into();
+
+    server.fail_next(429);
+    server.fail_next(500);
+    assert_that!(store.write_block(BlockKey::Data(id), b"data")).is_ok();
+    assert_that!(store.read_block(BlockKey::Data(id))).is_ok_containing(Some(b"data".to_vec()));
+
+    Ok(())
+}
+
+#[rstest]
+fn repo_can_be_stored_in_webdav(buffer: Vec<u8>) -> anyhow::Result<()> {
+    let server = WebDavServer::start();
+    let config = server.config();
+
+    let mut repo: KeyRepo<String> = OpenOptions::new()
+        .config(fixed_packing_small_config())
+        .mode(OpenMode::CreateNew)
+        .open(&config)?;
+    let mut object = repo.insert("test".into());
+    object.write_all(&buffer)?;
+    object.commit()?;
+    drop(object);
+    repo.commit()?;
+    repo.clean()?;
+    drop(repo);
+
+    let repo: KeyRepo<String> = OpenOptions::new().open(&config)?;
+    let mut actual = Vec::new();
+    repo.object("test").unwrap().read_to_end(&mut actual)?;
+    assert_that!(actual).is_equal_to(buffer);
+    assert_that!(repo.verify()).is_ok_containing(HashSet::new());
+    assert_that!(server.requests("MOVE")).is_greater_than(2);
+
+    Ok(())
+}